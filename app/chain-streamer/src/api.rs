@@ -69,6 +69,44 @@ struct CreateStreamParams {
     description: String,
 }
 
+#[derive(Deserialize)]
+struct ClipStreamParams {
+    identifier: StreamIdentifier,
+    //seconds before the live edge, the last `duration` seconds by default
+    start: Option<u64>,
+    duration: u64,
+    name: String,
+    description: String,
+}
+
+#[derive(Deserialize)]
+struct TrimVodParams {
+    stream_index: u64,
+    //the segment range, the end is excluded
+    start: usize,
+    end: usize,
+}
+
+#[derive(Deserialize)]
+struct ConcatVodParams {
+    stream_indexes: Vec<u64>,
+    name: String,
+    description: String,
+}
+
+#[derive(Deserialize)]
+struct UpdateVodInfoParams {
+    stream_index: u64,
+    name: String,
+    description: String,
+}
+
+#[derive(Deserialize)]
+struct SetVodListedParams {
+    stream_index: u64,
+    listed: bool,
+}
+
 #[derive(Clone)]
 struct ApiService {
     channel_event_producer: StreamHubEventSender,
@@ -80,7 +118,12 @@ impl ApiService {
             "Usage of chain-streamer http api:
                 ./api/query_whole_streams(get) query whole streams' information or top streams' information.
                 ./api/query_stream(post) query stream information by identifier and uuid.
-                ./api/kick_off_client(post) kick off client by publish/subscribe id.\n",
+                ./api/kick_off_client(post) kick off client by publish/subscribe id.
+                ./api/start_relay_stream(post) ./api/stop_relay_stream(post) start or stop a relay by id, a pull dials host[:port] and a push is sent to a rtmp url.
+                ./api/query_relays(get) query the status, the bytes and the errors of the relays.
+                ./api/clip(post) register the recent segments of a live stream as a VOD.
+                ./api/trim_vod(post) ./api/concat_vod(post) ./api/update_vod_info(post) edit the VODs as new versions.
+                ./api/set_vod_listed(post) ./api/delete_vod(post) unlist or delete a VOD, the deleted one is hidden but its blobs are kept.\n",
         )
     }
 
@@ -271,6 +314,33 @@ impl ApiService {
         }
     }
 
+    async fn query_relays(&self) -> Json<ApiResponse<Value>> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        let hub_event = define::StreamHubEvent::ApiQueryRelays { result_sender };
+        if let Err(err) = self.channel_event_producer.send(hub_event) {
+            log::error!("send api query_relays event error: {}", err);
+        }
+
+        match result_receiver.await {
+            Ok(dat_val) => {
+                let api_response = ApiResponse {
+                    error_code: 0,
+                    desp: String::from("succ"),
+                    data: dat_val,
+                };
+                Json(api_response)
+            }
+            Err(err) => {
+                let api_response = ApiResponse {
+                    error_code: -1,
+                    desp: String::from("failed"),
+                    data: serde_json::json!(err.to_string()),
+                };
+                Json(api_response)
+            }
+        }
+    }
+
     async fn create_live_stream(&self, stream_info: CreateStreamParams) -> Json<ApiResponse<Value>> {
         let (result_sender, result_receiver) = oneshot::channel();
         let hub_event = define::StreamHubEvent::ApiCreateStream {
@@ -303,6 +373,140 @@ impl ApiService {
         }
     }
 
+    async fn clip_stream(&self, clip_info: ClipStreamParams) -> Json<ApiResponse<Value>> {
+        let (result_sender, result_receiver) = oneshot::channel();
+
+        let hub_event = define::StreamHubEvent::ApiClipStream {
+            identifier: clip_info.identifier,
+            start: clip_info.start.unwrap_or(clip_info.duration),
+            duration: clip_info.duration,
+            name: clip_info.name,
+            description: clip_info.description,
+            result_sender,
+        };
+
+        if let Err(err) = self.channel_event_producer.send(hub_event) {
+            log::error!("send api clip_stream event error: {}", err);
+        }
+
+        match result_receiver.await {
+            Ok(Ok(())) => {
+                let api_response = ApiResponse {
+                    error_code: 0,
+                    desp: String::from("succ"),
+                    data: Value::Null,
+                };
+                Json(api_response)
+            }
+            Ok(Err(err)) => {
+                let api_response = ApiResponse {
+                    error_code: -1,
+                    desp: String::from("failed"),
+                    data: serde_json::json!(err.to_string()),
+                };
+                Json(api_response)
+            }
+            Err(err) => {
+                let api_response = ApiResponse {
+                    error_code: -1,
+                    desp: String::from("failed"),
+                    data: serde_json::json!(err.to_string()),
+                };
+                Json(api_response)
+            }
+        }
+    }
+
+    async fn trim_vod(&self, params: TrimVodParams) -> Json<ApiResponse<Value>> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        let hub_event = define::StreamHubEvent::ApiTrimVod {
+            index: params.stream_index,
+            start: params.start,
+            end: params.end,
+            result_sender,
+        };
+        self.edit_vod(hub_event, result_receiver).await
+    }
+
+    async fn concat_vod(&self, params: ConcatVodParams) -> Json<ApiResponse<Value>> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        let hub_event = define::StreamHubEvent::ApiConcatVod {
+            indexes: params.stream_indexes,
+            name: params.name,
+            description: params.description,
+            result_sender,
+        };
+        self.edit_vod(hub_event, result_receiver).await
+    }
+
+    async fn update_vod_info(&self, params: UpdateVodInfoParams) -> Json<ApiResponse<Value>> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        let hub_event = define::StreamHubEvent::ApiUpdateVodInfo {
+            index: params.stream_index,
+            name: params.name,
+            description: params.description,
+            result_sender,
+        };
+        self.edit_vod(hub_event, result_receiver).await
+    }
+
+    async fn set_vod_listed(&self, params: SetVodListedParams) -> Json<ApiResponse<Value>> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        let hub_event = define::StreamHubEvent::ApiSetVodListed {
+            index: params.stream_index,
+            listed: params.listed,
+            result_sender,
+        };
+        self.edit_vod(hub_event, result_receiver).await
+    }
+
+    async fn delete_vod(&self, params: StreamIndexParam) -> Json<ApiResponse<Value>> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        let hub_event = define::StreamHubEvent::ApiDeleteVod {
+            index: params.stream_index,
+            result_sender,
+        };
+        self.edit_vod(hub_event, result_receiver).await
+    }
+
+    //the data is the index of the new or the changed VOD stream
+    async fn edit_vod(
+        &self,
+        hub_event: define::StreamHubEvent,
+        result_receiver: oneshot::Receiver<Value>,
+    ) -> Json<ApiResponse<Value>> {
+        if let Err(err) = self.channel_event_producer.send(hub_event) {
+            log::error!("send api edit_vod event error: {}", err);
+        }
+
+        match result_receiver.await {
+            Ok(val) if val.is_u64() => {
+                let api_response = ApiResponse {
+                    error_code: 0,
+                    desp: String::from("succ"),
+                    data: val,
+                };
+                Json(api_response)
+            }
+            Ok(val) => {
+                let api_response = ApiResponse {
+                    error_code: -1,
+                    desp: String::from("failed"),
+                    data: val,
+                };
+                Json(api_response)
+            }
+            Err(err) => {
+                let api_response = ApiResponse {
+                    error_code: -1,
+                    desp: String::from("failed"),
+                    data: serde_json::json!(err.to_string()),
+                };
+                Json(api_response)
+            }
+        }
+    }
+
     async fn stop_relay_stream(&self, relay_info: RelayStreamParams) -> Json<ApiResponse<Value>> {
         let (result_sender, result_receiver) = oneshot::channel();
 
@@ -398,6 +602,39 @@ pub async fn run(producer: StreamHubEventSender, port: usize) {
         api_stop_relay_stream.stop_relay_stream(params).await
     };
 
+    let api_query_relays = api.clone();
+    let query_relays = move || async move { api_query_relays.query_relays().await };
+
+    let api_clip_stream = api.clone();
+    let clip_stream = move |Json(params): Json<ClipStreamParams>| async move {
+        api_clip_stream.clip_stream(params).await
+    };
+
+    let api_trim_vod = api.clone();
+    let trim_vod = move |Json(params): Json<TrimVodParams>| async move {
+        api_trim_vod.trim_vod(params).await
+    };
+
+    let api_concat_vod = api.clone();
+    let concat_vod = move |Json(params): Json<ConcatVodParams>| async move {
+        api_concat_vod.concat_vod(params).await
+    };
+
+    let api_update_vod_info = api.clone();
+    let update_vod_info = move |Json(params): Json<UpdateVodInfoParams>| async move {
+        api_update_vod_info.update_vod_info(params).await
+    };
+
+    let api_set_vod_listed = api.clone();
+    let set_vod_listed = move |Json(params): Json<SetVodListedParams>| async move {
+        api_set_vod_listed.set_vod_listed(params).await
+    };
+
+    let api_delete_vod = api.clone();
+    let delete_vod = move |Json(params): Json<StreamIndexParam>| async move {
+        api_delete_vod.delete_vod(params).await
+    };
+
     let app = Router::new()
         .route("/", get(root))
         .route("/api/query_whole_streams", get(query_streams))
@@ -408,7 +645,14 @@ pub async fn run(producer: StreamHubEventSender, port: usize) {
         .route("/api/query_stream", post(query_stream))
         .route("/api/kick_off_client", post(kick_off))
         .route("/api/start_relay_stream", post(start_relay_stream))
-        .route("/api/stop_relay_stream", post(stop_relay_stream));
+        .route("/api/stop_relay_stream", post(stop_relay_stream))
+        .route("/api/query_relays", get(query_relays))
+        .route("/api/clip", post(clip_stream))
+        .route("/api/trim_vod", post(trim_vod))
+        .route("/api/concat_vod", post(concat_vod))
+        .route("/api/update_vod_info", post(update_vod_info))
+        .route("/api/set_vod_listed", post(set_vod_listed))
+        .route("/api/delete_vod", post(delete_vod));
 
     log::info!("Http api server listening on http://0.0.0.0:{}", port);
 
@@ -3,8 +3,10 @@ pub mod errors;
 use commonlib::auth::AuthAlgorithm;
 use errors::ConfigError;
 use serde_derive::Deserialize;
+use std::collections::HashMap;
 use std::fs;
 use std::vec::Vec;
+use streamhub::define::PublishConflictPolicy;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
@@ -12,6 +14,7 @@ pub struct Config {
     pub hls: Option<HlsConfig>,
     pub httpapi: Option<HttpApiConfig>,
     pub httpnotify: Option<HttpNotifierConfig>,
+    pub blobcleanup: Option<BlobCleanupConfig>,
     pub authsecret: AuthSecretConfig,
     pub log: Option<LogConfig>,
 }
@@ -31,6 +34,11 @@ impl Config {
                 pull: None,
                 push: None,
                 auth: None,
+                rtmps: None,
+                relay_reconnect: None,
+                publish_conflict: None,
+                failover_stall_timeout: None,
+                session_timeout: None,
             });
         }
 
@@ -41,6 +49,17 @@ impl Config {
                 port: hls_port,
                 need_record: true,
                 auth: None,
+                rendition_groups: None,
+                target_duration: None,
+                max_duration: None,
+                keyframe_only: None,
+                live_window: None,
+                dvr_window: None,
+                storage: None,
+                playlist_type: None,
+                republish_grace_period: None,
+                iframe_playlist: None,
+                overrides: None,
             });
         }
 
@@ -54,6 +73,7 @@ impl Config {
             hls: hls_config,
             httpapi: None,
             httpnotify: None,
+            blobcleanup: None,
             authsecret: AuthSecretConfig::default(),
             log: log_config,
         }
@@ -68,18 +88,70 @@ pub struct RtmpConfig {
     pub pull: Option<RtmpPullConfig>,
     pub push: Option<Vec<RtmpPushConfig>>,
     pub auth: Option<AuthConfig>,
+    //a second listener of RTMP over TLS
+    pub rtmps: Option<RtmpsConfig>,
+    //how the push and pull relays reconnect after they drop
+    pub relay_reconnect: Option<RelayReconnectConfig>,
+    //what to do with a second publisher of a stream
+    pub publish_conflict: Option<PublishConflictConfig>,
+    //milliseconds without media before a standby(backup) publisher takes over
+    pub failover_stall_timeout: Option<u64>,
+    //when the stalled publishing sessions are closed
+    pub session_timeout: Option<SessionTimeoutConfig>,
+}
+#[derive(Debug, Deserialize, Clone)]
+pub struct SessionTimeoutConfig {
+    //milliseconds without receiving anything, 2000 if not set
+    pub idle: Option<u64>,
+    //milliseconds without receiving anything before the client is pinged
+    pub ping_interval: Option<u64>,
+    //milliseconds without audio or video from the publisher
+    pub no_media: Option<u64>,
+    //milliseconds without a video keyframe from the publisher
+    pub no_keyframe: Option<u64>,
+}
+#[derive(Debug, Deserialize, Clone)]
+pub struct PublishConflictConfig {
+    //reject/replace/queue, reject if not set
+    pub policy: Option<PublishConflictPolicy>,
+    //app name to the policy of the app
+    pub apps: Option<HashMap<String, PublishConflictPolicy>>,
+}
+#[derive(Debug, Deserialize, Clone)]
+pub struct RelayReconnectConfig {
+    //milliseconds before the first retry, doubled on each retry until max_delay
+    pub initial_delay: u64,
+    pub max_delay: u64,
+    //the relay fails after the retries in a row, it retries forever if not set
+    pub max_retries: Option<u32>,
+    //from 0 to 1, how much the delays are randomized
+    pub jitter: f64,
+}
+#[derive(Debug, Deserialize, Clone)]
+pub struct RtmpsConfig {
+    pub enabled: bool,
+    pub port: usize,
+    //PEM files of the certificate chain and the private key
+    pub cert_path: String,
+    pub key_path: String,
 }
 #[derive(Debug, Deserialize, Clone)]
 pub struct RtmpPullConfig {
     pub enabled: bool,
     pub address: String,
     pub port: u16,
+    //app name to the origin address(host:port), the address above is used for the other apps
+    pub routes: Option<HashMap<String, String>>,
+    //seconds an on demand pull lasts without players
+    pub idle_timeout: Option<u64>,
 }
 #[derive(Debug, Deserialize, Clone)]
 pub struct RtmpPushConfig {
     pub enabled: bool,
     pub address: String,
     pub port: usize,
+    //milliseconds of the media batched into one aggregate message
+    pub aggregate_duration: Option<u32>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -89,6 +161,70 @@ pub struct HlsConfig {
     //record or not
     pub need_record: bool,
     pub auth: Option<AuthConfig>,
+    //streams of different resolutions grouped by a master playlist
+    pub rendition_groups: Option<Vec<HlsRenditionGroupConfig>>,
+    //segment duration in seconds
+    pub target_duration: Option<i64>,
+    //a video segment is cut on a non keyframe beyond it unless keyframe_only is set,
+    //twice the target duration by default
+    pub max_duration: Option<i64>,
+    pub keyframe_only: Option<bool>,
+    //how many segments are listed in the live playlist
+    pub live_window: Option<usize>,
+    //seconds of the time-shift window, the viewers can rewind within it, the live
+    //playlist keeps the segments by duration instead of live_window
+    pub dvr_window: Option<u64>,
+    pub storage: Option<HlsStorageConfig>,
+    pub playlist_type: Option<HlsPlaylistTypeConfig>,
+    //seconds to wait for a stream to be republished, the playlist is continued
+    //with a discontinuity instead of being turned into a vod
+    pub republish_grace_period: Option<u64>,
+    //write the I-frame only playlists (EXT-X-I-FRAMES-ONLY) for fast scrubbing, false if not set
+    pub iframe_playlist: Option<bool>,
+    //settings of the apps or streams which differ from the defaults above,
+    //applied in order, the later ones win
+    pub overrides: Option<Vec<HlsOverrideConfig>>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub enum HlsStorageConfig {
+    #[serde(rename = "walrus")]
+    Walrus,
+    #[serde(rename = "local")]
+    Local,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub enum HlsPlaylistTypeConfig {
+    #[serde(rename = "live")]
+    Live,
+    #[serde(rename = "event")]
+    Event,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct HlsOverrideConfig {
+    pub app_name: Option<String>,
+    //regular expression matched against the stream name
+    pub stream_pattern: Option<String>,
+    pub target_duration: Option<i64>,
+    pub max_duration: Option<i64>,
+    pub keyframe_only: Option<bool>,
+    pub live_window: Option<usize>,
+    pub dvr_window: Option<u64>,
+    pub need_record: Option<bool>,
+    pub storage: Option<HlsStorageConfig>,
+    pub playlist_type: Option<HlsPlaylistTypeConfig>,
+    pub republish_grace_period: Option<u64>,
+    pub iframe_playlist: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct HlsRenditionGroupConfig {
+    pub app_name: String,
+    //the master playlist is named after the group
+    pub name: String,
+    pub streams: Vec<String>,
 }
 
 pub enum LogLevel {
@@ -112,6 +248,19 @@ pub struct LogFile {
     pub path: String,
 }
 
+//the segments which are not recorded are uploaded as deletable blobs and released by
+//the walrus client once they leave the live playlist
+#[derive(Debug, Deserialize, Clone)]
+pub struct BlobCleanupConfig {
+    pub enabled: bool,
+    //the address which owns the deletable blobs, the walrus client uses its wallet
+    pub owner: String,
+    //path of the walrus client, "walrus" by default
+    pub walrus_bin: Option<String>,
+    //seconds to keep a blob after it is released, the players may be still fetching it
+    pub delay: Option<u64>,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct HttpApiConfig {
     pub port: usize,
@@ -124,6 +273,12 @@ pub struct HttpNotifierConfig {
     pub on_unpublish: Option<String>,
     pub on_play: Option<String>,
     pub on_stop: Option<String>,
+    //relay state changes, connecting/active/backing off/stopped/failed
+    pub on_relay: Option<String>,
+    //the second publishers of a stream, rejected/replaced/queued/promoted
+    pub on_publish_conflict: Option<String>,
+    //the stalled sessions closed by the server, idle/no media/no keyframe
+    pub on_session_timeout: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Clone, Default)]
@@ -0,0 +1,286 @@
+pub mod writer;
+
+use {
+    self::writer::{ExportFormat, ExportWriter},
+    anyhow::{anyhow, Result},
+    bytes::Bytes,
+    commonlib::move_call::get_vod_playlist,
+    futures::stream::{self, StreamExt},
+    reqwest::Client,
+    std::{path::Path, time::Duration},
+    xmpegts::define::TS_PACKET_SIZE,
+};
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(60);
+/*the interval grows with the retries*/
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct ExportOptions {
+    /*vod_<index>.<extension> if not set*/
+    pub output: Option<String>,
+    /*by the extension of the output, TS if unknown*/
+    pub format: Option<ExportFormat>,
+    /*the segments downloaded at the same time*/
+    pub concurrency: usize,
+    pub retries: usize,
+}
+
+pub struct PlaylistSegment {
+    pub url: String,
+    /*seconds*/
+    pub duration: f64,
+    pub discontinuity: bool,
+}
+
+/*The media segments of a VOD playlist, the URIs are the aggregator URLs. The
+durations make the timeline of the file, so a playlist which is cut short or has
+a bad duration is an error.*/
+pub fn parse_segments(m3u8_content: &str) -> Result<Vec<PlaylistSegment>> {
+    let mut segments = Vec::new();
+    let mut duration = None;
+    let mut discontinuity = false;
+
+    for line in m3u8_content.lines().map(str::trim) {
+        if line.starts_with("#EXT-X-STREAM-INF") {
+            return Err(anyhow!("the master playlists cannot be exported"));
+        } else if let Some(value) = line.strip_prefix("#EXTINF:") {
+            let value = value.split(',').next().unwrap_or_default().trim();
+            match value.parse::<f64>() {
+                Ok(value) if value.is_finite() && value >= 0.0 => duration = Some(value),
+                _ => return Err(anyhow!("invalid segment duration: {}", line)),
+            }
+        } else if line == "#EXT-X-DISCONTINUITY" {
+            discontinuity = true;
+        } else if !line.is_empty() && !line.starts_with('#') {
+            let Some(duration) = duration.take() else {
+                return Err(anyhow!("the segment {} has no EXTINF", line));
+            };
+            segments.push(PlaylistSegment {
+                url: line.to_string(),
+                duration,
+                discontinuity,
+            });
+            discontinuity = false;
+        }
+    }
+
+    if duration.is_some() {
+        return Err(anyhow!(
+            "the playlist ends without the URI of the last segment"
+        ));
+    }
+    Ok(segments)
+}
+
+/*a segment blob is a whole number of TS packets*/
+fn check_segment(data: &[u8]) -> Result<()> {
+    if data.is_empty() {
+        return Err(anyhow!("the blob is empty"));
+    }
+    if !data.len().is_multiple_of(TS_PACKET_SIZE) {
+        return Err(anyhow!(
+            "the blob size {} is not a multiple of the TS packet size",
+            data.len()
+        ));
+    }
+    if let Some(index) = data
+        .chunks(TS_PACKET_SIZE)
+        .position(|packet| packet[0] != 0x47)
+    {
+        return Err(anyhow!("the TS packet {} has no sync byte", index));
+    }
+
+    Ok(())
+}
+
+async fn download_segment(client: &Client, url: &str) -> Result<Bytes> {
+    let response = client.get(url).send().await?.error_for_status()?;
+    let data = response.bytes().await?;
+    check_segment(&data)?;
+    Ok(data)
+}
+
+async fn fetch_segment(client: &Client, url: &str, retries: usize) -> Result<Bytes> {
+    let mut retry_count = 0;
+    loop {
+        match download_segment(client, url).await {
+            Ok(data) => return Ok(data),
+            Err(err) if retry_count < retries => {
+                retry_count += 1;
+                log::warn!(
+                    "fetch {} error: {}, retry {}/{}",
+                    url,
+                    err,
+                    retry_count,
+                    retries
+                );
+                tokio::time::sleep(RETRY_INTERVAL * retry_count as u32).await;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/*Downloads the segments of a VOD stream into one file. The segments which cannot
+be fetched are skipped and reported, the file keeps the timeline of the playlist.*/
+pub async fn export_vod(index: u64, options: ExportOptions) -> Result<()> {
+    let ExportOptions {
+        output,
+        format,
+        concurrency,
+        retries,
+    } = options;
+    let format = format
+        .or_else(|| {
+            let extension = Path::new(output.as_ref()?).extension()?.to_str()?;
+            ExportFormat::from_name(extension)
+        })
+        .unwrap_or(ExportFormat::Ts);
+    let output = output.unwrap_or_else(|| format!("vod_{}.{}", index, format.extension()));
+
+    let m3u8_content = get_vod_playlist(index)
+        .await
+        .map_err(|err| anyhow!("get the playlist of VOD {} error: {}", index, err))?;
+    let segments = parse_segments(&m3u8_content)?;
+    if segments.is_empty() {
+        return Err(anyhow!("VOD {} has no segments", index));
+    }
+    log::info!(
+        "export {} segments of VOD {} to {} as {:?}",
+        segments.len(),
+        index,
+        output,
+        format
+    );
+
+    let client = Client::builder().timeout(FETCH_TIMEOUT).build()?;
+    let mut writer = ExportWriter::new(format, &output)?;
+
+    /*the segments are downloaded at the same time and written in order*/
+    let mut results = stream::iter(&segments)
+        .map(|segment| fetch_segment(&client, &segment.url, retries))
+        .buffered(std::cmp::max(concurrency, 1));
+
+    let mut missing_segments = Vec::new();
+    let mut start_time = 0.0;
+    let mut previous_missing = false;
+    for (segment_index, segment) in segments.iter().enumerate() {
+        let Some(result) = results.next().await else {
+            break;
+        };
+
+        match result {
+            Ok(data) => {
+                let rebase = segment.discontinuity || previous_missing;
+                writer.write_segment(&data, rebase.then_some((start_time * 1000.0) as u32))?;
+                log::info!(
+                    "segment {}/{} is exported",
+                    segment_index + 1,
+                    segments.len()
+                );
+                previous_missing = false;
+            }
+            Err(err) => {
+                missing_segments.push((segment_index, segment, err));
+                previous_missing = true;
+            }
+        }
+        start_time += segment.duration;
+    }
+    drop(results);
+    writer.finish()?;
+
+    if missing_segments.is_empty() {
+        log::info!("VOD {} is exported to {}", index, output);
+        return Ok(());
+    }
+
+    for (segment_index, segment, err) in &missing_segments {
+        log::error!(
+            "segment {} ({:.3}s) is missing: {}: {}",
+            segment_index,
+            segment.duration,
+            segment.url,
+            err
+        );
+    }
+    Err(anyhow!(
+        "{} of {} segments are missing in {}",
+        missing_segments.len(),
+        segments.len(),
+        output
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{check_segment, parse_segments},
+        xmpegts::define::TS_PACKET_SIZE,
+    };
+
+    #[test]
+    fn test_parse_segments() {
+        let segments = parse_segments(
+            "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:10\n#EXTINF:9.5,\nhttps://a/0\n\
+             #EXT-X-DISCONTINUITY\n#EXTINF:10.000,title\nhttps://a/1\n#EXTINF:4,\nhttps://a/2\n\
+             #EXT-X-ENDLIST\n",
+        )
+        .unwrap();
+
+        let segments: Vec<(&str, f64, bool)> = segments
+            .iter()
+            .map(|segment| {
+                (
+                    segment.url.as_str(),
+                    segment.duration,
+                    segment.discontinuity,
+                )
+            })
+            .collect();
+        assert_eq!(
+            segments,
+            vec![
+                ("https://a/0", 9.5, false),
+                ("https://a/1", 10.0, true),
+                ("https://a/2", 4.0, false),
+            ]
+        );
+
+        assert!(parse_segments("#EXTM3U\n#EXT-X-ENDLIST\n")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_parse_malformed_playlists() {
+        /*a master playlist*/
+        assert!(parse_segments("#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=1000\nlow.m3u8\n").is_err());
+        /*bad durations*/
+        assert!(parse_segments("#EXTM3U\n#EXTINF:abc,\nhttps://a/0\n").is_err());
+        assert!(parse_segments("#EXTM3U\n#EXTINF:-1,\nhttps://a/0\n").is_err());
+        assert!(parse_segments("#EXTM3U\n#EXTINF:,\nhttps://a/0\n").is_err());
+        /*a segment without EXTINF*/
+        assert!(parse_segments("#EXTM3U\n#EXTINF:1,\nhttps://a/0\nhttps://a/1\n").is_err());
+        /*cut short after the last EXTINF*/
+        assert!(parse_segments("#EXTM3U\n#EXTINF:1,\nhttps://a/0\n#EXTINF:1,\n").is_err());
+    }
+
+    #[test]
+    fn test_check_segment() {
+        let mut data = vec![0; TS_PACKET_SIZE * 2];
+        data[0] = 0x47;
+        data[TS_PACKET_SIZE] = 0x47;
+        assert!(check_segment(&data).is_ok());
+
+        /*the aggregator returns an empty body or an error page for a missing blob*/
+        assert!(check_segment(&[]).is_err());
+        assert!(check_segment(b"<html>blob not found</html>").is_err());
+        /*a truncated download*/
+        assert!(check_segment(&data[..TS_PACKET_SIZE + 10]).is_err());
+
+        data[TS_PACKET_SIZE] = 0;
+        let err = check_segment(&data).unwrap_err();
+        assert_eq!(err.to_string(), "the TS packet 1 has no sync byte");
+    }
+}
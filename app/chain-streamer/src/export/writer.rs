@@ -0,0 +1,297 @@
+use {
+    crate::import::tags::TsTagConverter,
+    anyhow::{anyhow, Result},
+    bytes::BytesMut,
+    bytesio::bytes_reader::BytesReader,
+    std::{
+        fs::{self, File},
+        io::{self, Seek, SeekFrom, Write},
+    },
+    xflv::{
+        define::{tag_type, FlvData},
+        mpeg4_avc::Mpeg4AvcProcessor,
+        muxer::{FlvMuxer, HEADER_LENGTH},
+    },
+    xmp4::{boxes::Mp4Codec, muxer::Mp4Muxer},
+    xmpegts::demuxer::TsDemuxer,
+};
+
+/*the offset of the audio and video flags in the FLV header*/
+const FLV_FLAGS_OFFSET: u64 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Ts,
+    Flv,
+    Mp4,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "ts" => Some(ExportFormat::Ts),
+            "flv" => Some(ExportFormat::Flv),
+            "mp4" => Some(ExportFormat::Mp4),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Ts => "ts",
+            ExportFormat::Flv => "flv",
+            ExportFormat::Mp4 => "mp4",
+        }
+    }
+}
+
+pub struct FlvFileWriter {
+    file: File,
+    muxer: FlvMuxer,
+    has_audio: bool,
+    has_video: bool,
+}
+
+impl FlvFileWriter {
+    /*the flags of the header are rewritten once the tracks are known*/
+    fn new(path: &str) -> Result<Self> {
+        let mut muxer = FlvMuxer::new();
+        muxer
+            .write_flv_header(true, true)
+            .and_then(|_| muxer.write_previous_tag_size(0))
+            .map_err(|err| anyhow!("write flv header error: {}", err))?;
+
+        let mut file = File::create(path)?;
+        file.write_all(&muxer.writer.extract_current_bytes())?;
+
+        Ok(Self {
+            file,
+            muxer,
+            has_audio: false,
+            has_video: false,
+        })
+    }
+
+    fn write_tag(&mut self, tag: FlvData) -> Result<()> {
+        let (tag_type, timestamp, data) = match tag {
+            FlvData::Video { timestamp, data } => {
+                self.has_video = true;
+                (tag_type::VIDEO, timestamp, data)
+            }
+            FlvData::Audio { timestamp, data } => {
+                self.has_audio = true;
+                (tag_type::AUDIO, timestamp, data)
+            }
+            FlvData::MetaData { .. } => return Ok(()),
+        };
+
+        let data_size = data.len() as u32;
+        self.muxer
+            .write_flv_tag_header(tag_type, data_size, timestamp)
+            .and_then(|_| self.muxer.write_flv_tag_body(data))
+            .and_then(|_| {
+                self.muxer
+                    .write_previous_tag_size(data_size + HEADER_LENGTH)
+            })
+            .map_err(|err| anyhow!("write flv tag error: {}", err))?;
+        self.file
+            .write_all(&self.muxer.writer.extract_current_bytes())?;
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let flags = ((self.has_audio as u8) << 2) | self.has_video as u8;
+        self.file.seek(SeekFrom::Start(FLV_FLAGS_OFFSET))?;
+        self.file.write_all(&[flags])?;
+        Ok(())
+    }
+}
+
+/*The first sequence header of each codec makes its track, the sample description
+of a track cannot change. The moov box goes before the samples but is only known
+at the end, so the samples are kept in a temporary file until then.*/
+pub struct Mp4FileWriter {
+    path: String,
+    sample_path: String,
+    sample_file: File,
+    muxer: Mp4Muxer,
+    video_track: Option<usize>,
+    audio_track: Option<usize>,
+}
+
+impl Mp4FileWriter {
+    fn new(path: &str) -> Result<Self> {
+        let sample_path = format!("{}.mdat", path);
+        let sample_file = File::create(&sample_path)?;
+
+        Ok(Self {
+            path: path.to_string(),
+            sample_path,
+            sample_file,
+            muxer: Mp4Muxer::new(),
+            video_track: None,
+            audio_track: None,
+        })
+    }
+
+    fn add_video_track(&mut self, avc_config: &[u8]) -> usize {
+        let mut processor = Mpeg4AvcProcessor::new();
+        let mut reader = BytesReader::new(BytesMut::from(avc_config));
+        if let Err(err) = processor.decoder_configuration_record_load(&mut reader) {
+            log::warn!("read the resolution of the video error: {}", err);
+        }
+
+        self.muxer.add_track(
+            Mp4Codec::H264 {
+                avc_config: BytesMut::from(avc_config),
+            },
+            processor.mpeg4_avc.width as u16,
+            processor.mpeg4_avc.height as u16,
+        )
+    }
+
+    fn write_tag(&mut self, tag: FlvData) -> Result<()> {
+        let result = match tag {
+            FlvData::Video { timestamp, data } if data.len() > 5 => {
+                if data[1] == 0 {
+                    if self.video_track.is_none() {
+                        self.video_track = Some(self.add_video_track(&data[5..]));
+                    }
+                    return Ok(());
+                }
+                let Some(track_index) = self.video_track else {
+                    return Ok(());
+                };
+
+                let composition_time = i32::from_be_bytes([0, data[2], data[3], data[4]]) << 8 >> 8;
+                self.muxer.write_sample(
+                    track_index,
+                    timestamp as i64,
+                    timestamp as i64 + composition_time as i64,
+                    data[0] >> 4 == 1,
+                    &data[5..],
+                )
+            }
+            FlvData::Audio { timestamp, data } if data.len() > 2 => {
+                if data[1] == 0 {
+                    if self.audio_track.is_none() {
+                        self.audio_track = Some(self.muxer.add_track(
+                            Mp4Codec::Aac {
+                                audio_specific_config: BytesMut::from(&data[2..]),
+                            },
+                            0,
+                            0,
+                        ));
+                    }
+                    return Ok(());
+                }
+                let Some(track_index) = self.audio_track else {
+                    return Ok(());
+                };
+
+                self.muxer.write_sample(
+                    track_index,
+                    timestamp as i64,
+                    timestamp as i64,
+                    true,
+                    &data[2..],
+                )
+            }
+            _ => return Ok(()),
+        };
+
+        result.map_err(|err| anyhow!("write mp4 sample error: {}", err))?;
+        self.sample_file.write_all(&self.muxer.take_sample_data())?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let header = self
+            .muxer
+            .finish_header()
+            .map_err(|err| anyhow!("write mp4 error: {}", err))?;
+
+        let mut file = File::create(&self.path)?;
+        file.write_all(&header)?;
+        io::copy(&mut File::open(&self.sample_path)?, &mut file)?;
+        fs::remove_file(&self.sample_path)?;
+        Ok(())
+    }
+}
+
+/*The TS segments are concatenated as they are, they are demuxed and converted to
+FLV tags for the other formats.*/
+pub enum ExportWriter {
+    Ts(File),
+    Flv(TsTagConverter, FlvFileWriter),
+    Mp4(TsTagConverter, Mp4FileWriter),
+}
+
+impl ExportWriter {
+    pub fn new(format: ExportFormat, path: &str) -> Result<Self> {
+        let writer = match format {
+            ExportFormat::Ts => ExportWriter::Ts(File::create(path)?),
+            ExportFormat::Flv => {
+                ExportWriter::Flv(TsTagConverter::new(), FlvFileWriter::new(path)?)
+            }
+            ExportFormat::Mp4 => {
+                ExportWriter::Mp4(TsTagConverter::new(), Mp4FileWriter::new(path)?)
+            }
+        };
+
+        Ok(writer)
+    }
+
+    /*the timestamps start from the start time if set, after a discontinuity or a
+    missing segment*/
+    pub fn write_segment(&mut self, data: &[u8], start_time: Option<u32>) -> Result<()> {
+        match self {
+            ExportWriter::Ts(file) => file.write_all(data)?,
+            ExportWriter::Flv(converter, writer) => {
+                for tag in segment_tags(converter, data, start_time)? {
+                    writer.write_tag(tag)?;
+                }
+            }
+            ExportWriter::Mp4(converter, writer) => {
+                for tag in segment_tags(converter, data, start_time)? {
+                    writer.write_tag(tag)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> Result<()> {
+        match self {
+            ExportWriter::Ts(file) => file.flush()?,
+            ExportWriter::Flv(_, writer) => writer.finish()?,
+            ExportWriter::Mp4(_, writer) => writer.finish()?,
+        }
+
+        Ok(())
+    }
+}
+
+/*every segment starts with its own PAT and PMT*/
+fn segment_tags(
+    converter: &mut TsTagConverter,
+    data: &[u8],
+    start_time: Option<u32>,
+) -> Result<Vec<FlvData>> {
+    if let Some(start_time) = start_time {
+        converter.start_segment(start_time);
+    }
+
+    let mut demuxer = TsDemuxer::new();
+    let mut frames = demuxer
+        .demux(data)
+        .map_err(|err| anyhow!("read ts error: {}", err))?;
+    frames.extend(demuxer.flush());
+
+    Ok(frames
+        .into_iter()
+        .flat_map(|frame| converter.convert(frame))
+        .collect())
+}
@@ -0,0 +1,281 @@
+pub mod tags;
+
+use {
+    self::tags::{aac_frame, aac_sequence_header, avc_frame, avc_sequence_header, TsTagConverter},
+    anyhow::{anyhow, Result},
+    bytes::BytesMut,
+    hls::{
+        flv2hls::Flv2HlsRemuxer,
+        settings::{HlsSettings, StorageBackend},
+    },
+    std::{fs::File, io::Read, path::Path},
+    xflv::{define::FlvData, demuxer::FlvDemuxer},
+    xmp4::{boxes::Mp4Codec, demuxer::Mp4Demuxer},
+    xmpegts::{define::TS_PACKET_SIZE, demuxer::TsDemuxer},
+};
+
+/*the local files of the imported streams are written under ./import*/
+const IMPORT_APP_NAME: &str = "import";
+/*the transport stream is demuxed in chunks of packets*/
+const TS_CHUNK_SIZE: usize = TS_PACKET_SIZE * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaFormat {
+    Flv,
+    Mp4,
+    Ts,
+}
+
+impl MediaFormat {
+    /*by the signature of the data, then by the extension of the file*/
+    pub fn detect(path: &Path, data: &[u8]) -> Option<Self> {
+        if data.starts_with(b"FLV") {
+            return Some(MediaFormat::Flv);
+        }
+        if data.get(4..8) == Some(b"ftyp") {
+            return Some(MediaFormat::Mp4);
+        }
+        if data.first() == Some(&0x47) && data.get(TS_PACKET_SIZE) == Some(&0x47) {
+            return Some(MediaFormat::Ts);
+        }
+
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "flv" => Some(MediaFormat::Flv),
+            "mp4" | "m4v" | "mov" => Some(MediaFormat::Mp4),
+            "ts" => Some(MediaFormat::Ts),
+            _ => None,
+        }
+    }
+}
+
+pub struct ImportOptions {
+    /*the file name without the extension if not set*/
+    pub name: Option<String>,
+    pub description: String,
+    /*seconds, the default segment duration if not set*/
+    pub target_duration: Option<i64>,
+}
+
+struct Mp4TagReader {
+    demuxer: Mp4Demuxer,
+    /*the sequence header of each track is sent before its first sample*/
+    header_sent: Vec<bool>,
+}
+
+impl Mp4TagReader {
+    fn new(data: BytesMut) -> Result<Self> {
+        let demuxer = Mp4Demuxer::new(data).map_err(|err| anyhow!("read mp4 error: {}", err))?;
+        for track in &demuxer.tracks {
+            if let Mp4Codec::Unsupported(codec) = &track.codec {
+                log::warn!("skip the track of the unsupported codec: {}", codec);
+            }
+        }
+        let header_sent = vec![false; demuxer.tracks.len()];
+
+        Ok(Self {
+            demuxer,
+            header_sent,
+        })
+    }
+
+    fn read_tags(&mut self) -> Result<Option<Vec<FlvData>>> {
+        let Some(frame) = self
+            .demuxer
+            .read_frame()
+            .map_err(|err| anyhow!("read mp4 sample error: {}", err))?
+        else {
+            return Ok(None);
+        };
+
+        let timestamp = std::cmp::max(frame.dts, 0) as u32;
+        let header_sent = &mut self.header_sent[frame.track_index];
+        let mut tags = Vec::new();
+
+        match &self.demuxer.tracks[frame.track_index].codec {
+            Mp4Codec::H264 { avc_config } => {
+                if !*header_sent {
+                    tags.push(FlvData::Video {
+                        timestamp,
+                        data: avc_sequence_header(avc_config),
+                    });
+                    *header_sent = true;
+                }
+                tags.push(FlvData::Video {
+                    timestamp,
+                    data: avc_frame(frame.is_sync, frame.pts - frame.dts, &frame.data),
+                });
+            }
+            Mp4Codec::Aac {
+                audio_specific_config,
+            } => {
+                if !*header_sent {
+                    tags.push(FlvData::Audio {
+                        timestamp,
+                        data: aac_sequence_header(audio_specific_config),
+                    });
+                    *header_sent = true;
+                }
+                tags.push(FlvData::Audio {
+                    timestamp,
+                    data: aac_frame(&frame.data),
+                });
+            }
+            Mp4Codec::Unsupported(_) => {}
+        }
+
+        Ok(Some(tags))
+    }
+}
+
+struct TsTagReader {
+    data: BytesMut,
+    demuxer: TsDemuxer,
+    converter: TsTagConverter,
+    finished: bool,
+}
+
+impl TsTagReader {
+    fn new(data: BytesMut) -> Self {
+        Self {
+            data,
+            demuxer: TsDemuxer::new(),
+            converter: TsTagConverter::new(),
+            finished: false,
+        }
+    }
+
+    fn read_tags(&mut self) -> Result<Option<Vec<FlvData>>> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        let frames = if self.data.is_empty() {
+            self.finished = true;
+            self.demuxer.flush()
+        } else {
+            let chunk = self
+                .data
+                .split_to(std::cmp::min(TS_CHUNK_SIZE, self.data.len()));
+            self.demuxer
+                .demux(&chunk)
+                .map_err(|err| anyhow!("read ts error: {}", err))?
+        };
+
+        Ok(Some(
+            frames
+                .into_iter()
+                .flat_map(|frame| self.converter.convert(frame))
+                .collect(),
+        ))
+    }
+}
+
+/*Every format is read as FLV tags, the input of the HLS remuxer.*/
+enum MediaTagReader {
+    Flv(FlvDemuxer),
+    Mp4(Mp4TagReader),
+    Ts(TsTagReader),
+}
+
+impl MediaTagReader {
+    fn new(format: MediaFormat, data: BytesMut) -> Result<Self> {
+        let reader = match format {
+            MediaFormat::Flv => {
+                let mut demuxer = FlvDemuxer::new(data);
+                demuxer
+                    .read_flv_header()
+                    .map_err(|err| anyhow!("read flv header error: {}", err))?;
+                MediaTagReader::Flv(demuxer)
+            }
+            MediaFormat::Mp4 => MediaTagReader::Mp4(Mp4TagReader::new(data)?),
+            MediaFormat::Ts => MediaTagReader::Ts(TsTagReader::new(data)),
+        };
+
+        Ok(reader)
+    }
+
+    /*None at the end of the file, the script tags of FLV are skipped*/
+    fn read_tags(&mut self) -> Result<Option<Vec<FlvData>>> {
+        match self {
+            MediaTagReader::Flv(demuxer) => {
+                if demuxer.is_finished() {
+                    return Ok(None);
+                }
+                let tag = demuxer
+                    .read_flv_tag()
+                    .map_err(|err| anyhow!("read flv tag error: {}", err))?;
+                Ok(Some(tag.into_iter().collect()))
+            }
+            MediaTagReader::Mp4(reader) => reader.read_tags(),
+            MediaTagReader::Ts(reader) => reader.read_tags(),
+        }
+    }
+}
+
+/*Segments a media file and uploads the segments to walrus as a recorded stream, then
+adds the record as a VOD stream. No live playlist is committed on chain.*/
+pub async fn import_file(path: &str, options: ImportOptions) -> Result<()> {
+    let file_path = Path::new(path);
+    /*read into the buffer of the demuxers directly, a long recording is held only once*/
+    let mut file = File::open(file_path)?;
+    let mut data = BytesMut::zeroed(file.metadata()?.len() as usize);
+    file.read_exact(&mut data)?;
+    let format = MediaFormat::detect(file_path, &data)
+        .ok_or_else(|| anyhow!("unknown media format of {}", path))?;
+    let stream_name = file_path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| anyhow!("invalid file name: {}", path))?
+        .to_string();
+    log::info!("import {} as {:?}", path, format);
+
+    let mut settings = HlsSettings {
+        need_record: true,
+        storage: StorageBackend::Walrus,
+        iframe_playlist: false,
+        commit_live: false,
+        ..Default::default()
+    };
+    if let Some(target_duration) = options.target_duration {
+        settings.target_duration = target_duration;
+    }
+
+    let mut reader = MediaTagReader::new(format, data)?;
+    let mut remuxer = Flv2HlsRemuxer::new(
+        IMPORT_APP_NAME.to_string(),
+        stream_name.clone(),
+        &settings,
+        None,
+    );
+
+    let mut tag_count = 0;
+    while let Some(tags) = reader.read_tags()? {
+        for tag in tags {
+            remuxer
+                .process_flv_data(tag)
+                .await
+                .map_err(|err| anyhow!("segment {} error: {}", path, err))?;
+            tag_count += 1;
+        }
+    }
+    if tag_count == 0 {
+        return Err(anyhow!("no media data is found in {}", path));
+    }
+
+    remuxer
+        .flush_remaining_data()
+        .await
+        .map_err(|err| anyhow!("segment {} error: {}", path, err))?;
+    remuxer
+        .publish_vod(options.name.unwrap_or(stream_name), options.description)
+        .await
+        .map_err(|err| anyhow!("publish {} error: {}", path, err))?;
+    remuxer
+        .clear_files()
+        .await
+        .map_err(|err| anyhow!("clear the live files of {} error: {}", path, err))?;
+
+    log::info!("{} is imported, {} tags", path, tag_count);
+    Ok(())
+}
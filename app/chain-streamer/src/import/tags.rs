@@ -0,0 +1,329 @@
+use {
+    bytes::{BufMut, BytesMut},
+    h264_decoder::sei::split_annexb_nalus,
+    xflv::define::FlvData,
+    xmpegts::{
+        define::{epsi_stream_type, PTS_NO_VALUE},
+        demuxer::TsFrame,
+    },
+};
+
+const NALU_TYPE_IDR: u8 = 5;
+const NALU_TYPE_SPS: u8 = 7;
+const NALU_TYPE_PPS: u8 = 8;
+const NALU_TYPE_AUD: u8 = 9;
+
+const ADTS_SAMPLING_FREQUENCIES: [i64; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+/*the samples of an AAC frame*/
+const AAC_FRAME_SAMPLES: i64 = 1024;
+
+/*The bodies of the FLV tags, the H.264 frames are in the AVCC format.*/
+pub fn avc_sequence_header(avc_config: &[u8]) -> BytesMut {
+    let mut body = BytesMut::from(&[0x17, 0x00, 0x00, 0x00, 0x00][..]);
+    body.extend_from_slice(avc_config);
+    body
+}
+
+pub fn avc_frame(is_key_frame: bool, composition_time: i64, avc_data: &[u8]) -> BytesMut {
+    let frame_type = if is_key_frame { 0x17 } else { 0x27 };
+    let mut body = BytesMut::from(&[frame_type, 0x01][..]);
+    body.extend_from_slice(&(composition_time as i32).to_be_bytes()[1..]);
+    body.extend_from_slice(avc_data);
+    body
+}
+
+pub fn aac_sequence_header(audio_specific_config: &[u8]) -> BytesMut {
+    let mut body = BytesMut::from(&[0xAF, 0x00][..]);
+    body.extend_from_slice(audio_specific_config);
+    body
+}
+
+pub fn aac_frame(raw_data: &[u8]) -> BytesMut {
+    let mut body = BytesMut::from(&[0xAF, 0x01][..]);
+    body.extend_from_slice(raw_data);
+    body
+}
+
+/*Converts the H.264 and AAC frames of a transport stream to FLV tags. The Annex-B
+frames carry their parameter sets and the ADTS frames their audio config, the
+sequence headers are sent once they are known and again when they change.*/
+pub struct TsTagConverter {
+    /*the dts of the first frame in 90kHz, the tags start from 0*/
+    base_dts: Option<i64>,
+    /*milliseconds, the timestamp of the first frame since the base dts*/
+    start_time: u32,
+    sps: Option<BytesMut>,
+    pps: Option<BytesMut>,
+    avc_config: Option<BytesMut>,
+    audio_specific_config: Option<[u8; 2]>,
+}
+
+impl Default for TsTagConverter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TsTagConverter {
+    pub fn new() -> Self {
+        Self {
+            base_dts: None,
+            start_time: 0,
+            sps: None,
+            pps: None,
+            avc_config: None,
+            audio_specific_config: None,
+        }
+    }
+
+    /*the timestamps of the frames after a discontinuity start again from the given
+    time, in milliseconds*/
+    pub fn start_segment(&mut self, start_time: u32) {
+        self.base_dts = None;
+        self.start_time = start_time;
+    }
+
+    /*the timestamps wrapping around at 33 bits are not handled*/
+    fn tag_timestamp(&mut self, timestamp: i64) -> u32 {
+        let base_dts = *self.base_dts.get_or_insert(timestamp);
+        self.start_time + (std::cmp::max(timestamp - base_dts, 0) / 90) as u32
+    }
+
+    pub fn convert(&mut self, frame: TsFrame) -> Vec<FlvData> {
+        if frame.pts == PTS_NO_VALUE {
+            return Vec::new();
+        }
+        match frame.stream_type {
+            epsi_stream_type::PSI_STREAM_H264 => self.convert_h264(frame),
+            epsi_stream_type::PSI_STREAM_AAC | epsi_stream_type::PSI_STREAM_MPEG4_AAC => {
+                self.convert_aac(frame)
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn convert_h264(&mut self, frame: TsFrame) -> Vec<FlvData> {
+        let dts = if frame.dts == PTS_NO_VALUE {
+            frame.pts
+        } else {
+            frame.dts
+        };
+        let timestamp = self.tag_timestamp(dts);
+        let composition_time = (frame.pts - dts) / 90;
+
+        let mut is_key_frame = false;
+        let mut avc_data = BytesMut::new();
+        for nalu in split_annexb_nalus(&frame.data) {
+            if nalu.is_empty() {
+                continue;
+            }
+            match nalu[0] & 0x1F {
+                NALU_TYPE_SPS => self.sps = Some(BytesMut::from(nalu)),
+                NALU_TYPE_PPS => self.pps = Some(BytesMut::from(nalu)),
+                NALU_TYPE_AUD => {}
+                nalu_type => {
+                    is_key_frame |= nalu_type == NALU_TYPE_IDR;
+                    avc_data.put_u32(nalu.len() as u32);
+                    avc_data.extend_from_slice(nalu);
+                }
+            }
+        }
+
+        let mut tags = Vec::new();
+        if let Some(avc_config) = self.update_avc_config() {
+            tags.push(FlvData::Video {
+                timestamp,
+                data: avc_sequence_header(&avc_config),
+            });
+        }
+        /*the frames before the first parameter sets cannot be decoded*/
+        if self.avc_config.is_some() && !avc_data.is_empty() {
+            tags.push(FlvData::Video {
+                timestamp,
+                data: avc_frame(is_key_frame, composition_time, &avc_data),
+            });
+        }
+
+        tags
+    }
+
+    /*the AVCDecoderConfigurationRecord of the latest parameter sets if it changes*/
+    fn update_avc_config(&mut self) -> Option<BytesMut> {
+        let (sps, pps) = (self.sps.as_ref()?, self.pps.as_ref()?);
+        if sps.len() < 4 {
+            return None;
+        }
+
+        let mut avc_config = BytesMut::from(&[0x01, sps[1], sps[2], sps[3], 0xFF, 0xE1][..]);
+        avc_config.put_u16(sps.len() as u16);
+        avc_config.extend_from_slice(sps);
+        avc_config.put_u8(0x01);
+        avc_config.put_u16(pps.len() as u16);
+        avc_config.extend_from_slice(pps);
+
+        if self.avc_config.as_ref() == Some(&avc_config) {
+            return None;
+        }
+        self.avc_config = Some(avc_config.clone());
+        Some(avc_config)
+    }
+
+    /*a PES packet may carry several ADTS frames*/
+    fn convert_aac(&mut self, frame: TsFrame) -> Vec<FlvData> {
+        let mut tags = Vec::new();
+        let mut data = &frame.data[..];
+        let mut frame_index = 0;
+
+        while data.len() >= 7 && data[0] == 0xFF && data[1] & 0xF0 == 0xF0 {
+            let header_size = if data[1] & 0x01 > 0 { 7 } else { 9 };
+            let frame_size = ((data[3] as usize & 0x03) << 11)
+                | (data[4] as usize) << 3
+                | (data[5] as usize) >> 5;
+            if frame_size <= header_size || frame_size > data.len() {
+                break;
+            }
+
+            let object_type = (data[2] >> 6) + 1;
+            let sampling_frequency_index = (data[2] >> 2) & 0x0F;
+            let channel_configuration = ((data[2] & 0x01) << 2) | (data[3] >> 6);
+            let Some(sampling_frequency) =
+                ADTS_SAMPLING_FREQUENCIES.get(sampling_frequency_index as usize)
+            else {
+                break;
+            };
+
+            let pts = frame.pts + frame_index * AAC_FRAME_SAMPLES * 90000 / sampling_frequency;
+            let timestamp = self.tag_timestamp(pts);
+
+            let audio_specific_config = [
+                (object_type << 3) | (sampling_frequency_index >> 1),
+                ((sampling_frequency_index & 0x01) << 7) | (channel_configuration << 3),
+            ];
+            if self.audio_specific_config != Some(audio_specific_config) {
+                self.audio_specific_config = Some(audio_specific_config);
+                tags.push(FlvData::Audio {
+                    timestamp,
+                    data: aac_sequence_header(&audio_specific_config),
+                });
+            }
+            tags.push(FlvData::Audio {
+                timestamp,
+                data: aac_frame(&data[header_size..frame_size]),
+            });
+
+            data = &data[frame_size..];
+            frame_index += 1;
+        }
+
+        tags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{aac_frame, aac_sequence_header, avc_frame, avc_sequence_header, TsTagConverter},
+        bytes::BytesMut,
+        xflv::{
+            define::FlvData,
+            demuxer::{FlvAudioTagDemuxer, FlvVideoTagDemuxer},
+        },
+        xmpegts::{define::epsi_stream_type, demuxer::TsFrame},
+    };
+
+    fn tag_bodies(tags: Vec<FlvData>) -> Vec<(u32, BytesMut)> {
+        tags.into_iter()
+            .map(|tag| match tag {
+                FlvData::Audio { timestamp, data } | FlvData::Video { timestamp, data } => {
+                    (timestamp, data)
+                }
+                _ => panic!("unexpected tag"),
+            })
+            .collect()
+    }
+
+    /*the FLV tags are muxed into ADTS frames as the HLS remuxer does, then converted back*/
+    #[test]
+    fn test_adts_round_trip() {
+        /*AAC LC, 44100Hz, stereo*/
+        let audio_specific_config = [0x12, 0x10];
+        let raw_data = [0x21, 0x10, 0x05, 0x00];
+        let mut demuxer = FlvAudioTagDemuxer::new();
+        let mut converter = TsTagConverter::new();
+
+        demuxer
+            .demux(0, aac_sequence_header(&audio_specific_config))
+            .unwrap();
+        let mut tags = Vec::new();
+        for timestamp in [1000, 1023] {
+            let audio_data = demuxer.demux(timestamp, aac_frame(&raw_data)).unwrap();
+            tags.extend(converter.convert(TsFrame {
+                stream_type: epsi_stream_type::PSI_STREAM_AAC,
+                pts: audio_data.pts * 90,
+                dts: audio_data.dts * 90,
+                data: audio_data.data,
+            }));
+        }
+
+        assert_eq!(
+            tag_bodies(tags),
+            vec![
+                (0, aac_sequence_header(&audio_specific_config)),
+                (0, aac_frame(&raw_data)),
+                (23, aac_frame(&raw_data)),
+            ]
+        );
+    }
+
+    /*the avcC parameter sets are sent in band before the IDR frames in Annex-B*/
+    #[test]
+    fn test_avcc_round_trip() {
+        /*High profile, 1280x720*/
+        let sps = [
+            0x67, 0x64, 0x00, 0x1F, 0xAC, 0xD9, 0x40, 0x50, 0x05, 0xBB, 0x01, 0x10, 0x00, 0x00,
+            0x03, 0x00, 0x10, 0x00, 0x00, 0x03, 0x03, 0xC0, 0xF1, 0x83, 0x19, 0x60,
+        ];
+        let pps = [0x68, 0xEB, 0xE3, 0xCB];
+        let mut avc_config = vec![0x01, 0x64, 0x00, 0x1F, 0xFF, 0xE1, 0x00, sps.len() as u8];
+        avc_config.extend_from_slice(&sps);
+        avc_config.extend_from_slice(&[0x01, 0x00, pps.len() as u8]);
+        avc_config.extend_from_slice(&pps);
+
+        let idr_frame = [0x00, 0x00, 0x00, 0x04, 0x65, 0x88, 0x84, 0x00];
+        let inter_frame = [0x00, 0x00, 0x00, 0x03, 0x41, 0x9A, 0x02];
+        let mut demuxer = FlvVideoTagDemuxer::new();
+        let mut converter = TsTagConverter::new();
+
+        assert!(demuxer
+            .demux(0, avc_sequence_header(&avc_config))
+            .unwrap()
+            .is_none());
+        let mut tags = Vec::new();
+        for (timestamp, is_key_frame, avc_data) in [
+            (2000, true, &idr_frame[..]),
+            (2040, false, &inter_frame[..]),
+        ] {
+            let video_data = demuxer
+                .demux(timestamp, avc_frame(is_key_frame, 80, avc_data))
+                .unwrap()
+                .unwrap();
+            tags.extend(converter.convert(TsFrame {
+                stream_type: epsi_stream_type::PSI_STREAM_H264,
+                pts: video_data.pts * 90,
+                dts: video_data.dts * 90,
+                data: video_data.data,
+            }));
+        }
+
+        assert_eq!(
+            tag_bodies(tags),
+            vec![
+                (0, avc_sequence_header(&avc_config)),
+                (0, avc_frame(true, 80, &idr_frame)),
+                (40, avc_frame(false, 80, &inter_frame)),
+            ]
+        );
+    }
+}
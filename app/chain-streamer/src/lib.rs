@@ -2,4 +2,6 @@ extern crate rtmp;
 extern crate serde_derive;
 pub mod api;
 pub mod config;
+pub mod export;
+pub mod import;
 pub mod service;
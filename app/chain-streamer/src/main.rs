@@ -4,7 +4,13 @@ use {
     env_logger_extend::logger::{Logger, Rotate},
     std::{env, str::FromStr},
     tokio::signal,
-    chain_streamer::{config, config::Config, service::Service},
+    chain_streamer::{
+        config,
+        config::Config,
+        export::{export_vod, writer::ExportFormat, ExportOptions},
+        import::{import_file, ImportOptions},
+        service::Service,
+    },
 };
 
 // #[tokio::main(flavor = "current_thread")]
@@ -49,6 +55,85 @@ async fn main() -> Result<()> {
                 .help("Specify the log level.")
                 .value_parser(log_levels)
                 .conflicts_with("config_file_path"),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Segment a FLV, MP4 or TS file, upload it to walrus and add it as a VOD stream.")
+                .arg(
+                    Arg::new("file")
+                        .value_name("file")
+                        .help("Specify the media file path.")
+                        .value_parser(value_parser!(String))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("name")
+                        .long("name")
+                        .short('n')
+                        .value_name("name")
+                        .help("Specify the name of the VOD stream, the file name if not set.")
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("description")
+                        .long("description")
+                        .short('d')
+                        .value_name("text")
+                        .help("Specify the description of the VOD stream.")
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("target_duration")
+                        .long("target-duration")
+                        .short('t')
+                        .value_name("seconds")
+                        .help("Specify the segment duration.")
+                        .value_parser(value_parser!(i64)),
+                ),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Download the segments of a VOD stream into one TS, FLV or MP4 file.")
+                .arg(
+                    Arg::new("vod")
+                        .long("vod")
+                        .value_name("index")
+                        .help("Specify the index of the VOD stream.")
+                        .value_parser(value_parser!(u64))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .value_name("path")
+                        .help("Specify the output file path, vod_<index>.<format> if not set.")
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .value_name("format")
+                        .help("Specify the output format, by the output extension if not set.")
+                        .value_parser(["ts", "flv", "mp4"]),
+                )
+                .arg(
+                    Arg::new("concurrency")
+                        .long("concurrency")
+                        .value_name("count")
+                        .help("Specify the number of the segments downloaded at the same time.")
+                        .value_parser(value_parser!(usize))
+                        .default_value("4"),
+                )
+                .arg(
+                    Arg::new("retries")
+                        .long("retries")
+                        .value_name("count")
+                        .help("Specify the retries of a segment download.")
+                        .value_parser(value_parser!(usize))
+                        .default_value("3"),
+                ),
         );
 
     let args: Vec<String> = env::args().collect();
@@ -59,6 +144,42 @@ async fn main() -> Result<()> {
 
     let matches = cmd.clone().get_matches();
 
+    if let Some(("import", import_matches)) = matches.subcommand() {
+        let logger = Logger::new(&String::from("info"), None, None)?;
+
+        let file = import_matches.get_one::<String>("file").unwrap();
+        let options = ImportOptions {
+            name: import_matches.get_one::<String>("name").cloned(),
+            description: import_matches
+                .get_one::<String>("description")
+                .cloned()
+                .unwrap_or_default(),
+            target_duration: import_matches.get_one::<i64>("target_duration").copied(),
+        };
+
+        let result = import_file(file, options).await;
+        logger.stop();
+        return result;
+    }
+
+    if let Some(("export", export_matches)) = matches.subcommand() {
+        let logger = Logger::new(&String::from("info"), None, None)?;
+
+        let index = *export_matches.get_one::<u64>("vod").unwrap();
+        let options = ExportOptions {
+            output: export_matches.get_one::<String>("output").cloned(),
+            format: export_matches
+                .get_one::<String>("format")
+                .and_then(|format| ExportFormat::from_name(format)),
+            concurrency: *export_matches.get_one::<usize>("concurrency").unwrap(),
+            retries: *export_matches.get_one::<usize>("retries").unwrap(),
+        };
+
+        let result = export_vod(index, options).await;
+        logger.stop();
+        return result;
+    }
+
     let config = if let Some(path) = matches.get_one::<String>("config_file_path") {
         let config = config::load(path);
         match config {
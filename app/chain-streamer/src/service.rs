@@ -1,18 +1,32 @@
-use crate::config::{AuthConfig, AuthSecretConfig};
+use crate::config::{AuthConfig, AuthSecretConfig, HlsPlaylistTypeConfig, HlsStorageConfig};
 use commonlib::auth::AuthType;
+use commonlib::blob_cleanup::BlobCleanup;
 use std::sync::Arc;
+use std::time::Duration;
+
+const DEFAULT_BLOB_RELEASE_DELAY: u64 = 300;
 
 use {
     super::api,
     super::config::Config,
     //https://rustcc.cn/article?id=6dcbf032-0483-4980-8bfe-c64a7dfb33c7
-    anyhow::Result,
+    anyhow::{anyhow, Result},
     commonlib::auth::Auth,
-    hls::remuxer::HlsRemuxer,
     hls::server as hls_server,
+    hls::{
+        master_playlist::RenditionGroup,
+        remuxer::HlsRemuxer,
+        settings::{
+            HlsSettings, HlsSettingsOverride, HlsSettingsResolver, PlaylistType, StorageBackend,
+        },
+    },
     rtmp::{
-        relay::{pull_client::PullClient, push_client::PushClient},
+        relay::{
+            pull_client::PullClient, push_client::PushClient, push_relay::PushRelayClient,
+            supervisor::ReconnectPolicy,
+        },
         rtmp::RtmpServer,
+        session::watchdog::SessionTimeouts,
     },
     streamhub::{notify::http::HttpNotifier, notify::Notifier, StreamsHub},
     tokio,
@@ -67,6 +81,9 @@ impl Service {
                     httpnotifier.on_unpublish.clone(),
                     httpnotifier.on_play.clone(),
                     httpnotifier.on_stop.clone(),
+                    httpnotifier.on_relay.clone(),
+                    httpnotifier.on_publish_conflict.clone(),
+                    httpnotifier.on_session_timeout.clone(),
                 )))
             }
         } else {
@@ -75,6 +92,7 @@ impl Service {
 
         let mut stream_hub = StreamsHub::new(notifier);
 
+        self.start_blob_cleanup();
         self.start_hls(&mut stream_hub).await?;
         self.start_rtmp(&mut stream_hub).await?;
         self.start_http_api_server(&mut stream_hub).await?;
@@ -117,6 +135,30 @@ impl Service {
 
             let producer = stream_hub.get_hub_event_sender();
 
+            if let Some(conflict_cfg) = &rtmp_cfg_value.publish_conflict {
+                if let Some(policy) = conflict_cfg.policy {
+                    stream_hub.set_publish_conflict_policy(policy);
+                }
+                if let Some(apps) = &conflict_cfg.apps {
+                    for (app_name, policy) in apps {
+                        stream_hub.set_app_publish_conflict_policy(app_name.clone(), *policy);
+                    }
+                }
+            }
+            if let Some(stall_timeout) = rtmp_cfg_value.failover_stall_timeout {
+                stream_hub.set_stall_timeout(Duration::from_millis(stall_timeout));
+            }
+
+            let reconnect_policy = match &rtmp_cfg_value.relay_reconnect {
+                Some(reconnect_cfg) => ReconnectPolicy {
+                    initial_delay: Duration::from_millis(reconnect_cfg.initial_delay),
+                    max_delay: Duration::from_millis(reconnect_cfg.max_delay),
+                    max_retries: reconnect_cfg.max_retries,
+                    jitter: reconnect_cfg.jitter,
+                },
+                None => ReconnectPolicy::default(),
+            };
+
             /*static push */
             if let Some(push_cfg_values) = &rtmp_cfg_value.push {
                 for push_value in push_cfg_values {
@@ -135,6 +177,10 @@ impl Service {
                         stream_hub.get_client_event_consumer(),
                         producer.clone(),
                     );
+                    if let Some(duration) = push_value.aggregate_duration {
+                        push_client.set_aggregate_duration(duration);
+                    }
+                    push_client.set_reconnect_policy(reconnect_policy.clone());
                     tokio::spawn(async move {
                         if let Err(err) = push_client.run().await {
                             log::error!("push client error {}", err);
@@ -144,7 +190,9 @@ impl Service {
                     stream_hub.set_rtmp_push_enabled(true);
                 }
             }
-            /*static pull*/
+            /*on demand pull from the origin, the api pulls are served by the same client*/
+            let mut pull_address = None;
+            let mut pull_routes = None;
             if let Some(pull_cfg_value) = &rtmp_cfg_value.pull {
                 if pull_cfg_value.enabled {
                     let address = format!(
@@ -153,37 +201,120 @@ impl Service {
                         port = pull_cfg_value.port
                     );
                     log::info!("start rtmp pull client from address: {}", address);
-                    let mut pull_client = PullClient::new(
-                        address,
-                        stream_hub.get_client_event_consumer(),
-                        producer.clone(),
-                    );
-
-                    tokio::spawn(async move {
-                        if let Err(err) = pull_client.run().await {
-                            log::error!("pull client error {}", err);
-                        }
-                    });
+                    pull_address = Some(address);
+                    pull_routes = pull_cfg_value.routes.clone();
 
+                    if let Some(idle_timeout) = pull_cfg_value.idle_timeout {
+                        stream_hub.set_edge_idle_timeout(Duration::from_secs(idle_timeout));
+                    }
                     stream_hub.set_rtmp_pull_enabled(true);
                 }
             }
+            let mut pull_client = PullClient::new(
+                pull_address,
+                stream_hub.get_client_event_consumer(),
+                producer.clone(),
+            );
+            if let Some(routes) = pull_routes {
+                pull_client.set_routes(routes);
+            }
+            pull_client.set_reconnect_policy(reconnect_policy.clone());
+            tokio::spawn(async move {
+                if let Err(err) = pull_client.run().await {
+                    log::error!("pull client error {}", err);
+                }
+            });
+            /*dynamic push started by the api*/
+            let mut push_relay_client =
+                PushRelayClient::new(stream_hub.get_client_event_consumer(), producer.clone());
+            push_relay_client.set_reconnect_policy(reconnect_policy);
+            tokio::spawn(async move {
+                if let Err(err) = push_relay_client.run().await {
+                    log::error!("push relay client error {}", err);
+                }
+            });
 
             let listen_port = rtmp_cfg_value.port;
             let address = format!("0.0.0.0:{listen_port}");
 
+            let mut session_timeouts = SessionTimeouts::default();
+            if let Some(timeout_cfg) = &rtmp_cfg_value.session_timeout {
+                if let Some(idle) = timeout_cfg.idle {
+                    session_timeouts.idle = Duration::from_millis(idle);
+                }
+                session_timeouts.ping_interval =
+                    timeout_cfg.ping_interval.map(Duration::from_millis);
+                session_timeouts.no_media = timeout_cfg.no_media.map(Duration::from_millis);
+                session_timeouts.no_keyframe = timeout_cfg.no_keyframe.map(Duration::from_millis);
+            }
+
             let auth = Self::gen_auth(&rtmp_cfg_value.auth, &self.cfg.authsecret);
-            let mut rtmp_server = RtmpServer::new(address, producer, gop_num, auth);
+            let mut rtmp_server =
+                RtmpServer::new(address, producer.clone(), gop_num, auth.clone());
+            rtmp_server.set_session_timeouts(session_timeouts.clone());
             tokio::spawn(async move {
                 if let Err(err) = rtmp_server.run().await {
                     log::error!("rtmp server error: {}", err);
                 }
             });
+
+            if let Some(rtmps_cfg_value) = &rtmp_cfg_value.rtmps {
+                if rtmps_cfg_value.enabled {
+                    let listen_port = rtmps_cfg_value.port;
+                    let address = format!("0.0.0.0:{listen_port}");
+
+                    let mut rtmps_server = RtmpServer::new(address, producer, gop_num, auth);
+                    rtmps_server.set_session_timeouts(session_timeouts);
+                    rtmps_server
+                        .enable_tls(&rtmps_cfg_value.cert_path, &rtmps_cfg_value.key_path)?;
+                    tokio::spawn(async move {
+                        if let Err(err) = rtmps_server.run().await {
+                            log::error!("rtmps server error: {}", err);
+                        }
+                    });
+                }
+            }
         }
 
         Ok(())
     }
 
+    fn gen_storage_backend(storage: HlsStorageConfig) -> StorageBackend {
+        match storage {
+            HlsStorageConfig::Walrus => StorageBackend::Walrus,
+            HlsStorageConfig::Local => StorageBackend::Local,
+        }
+    }
+
+    fn gen_playlist_type(playlist_type: HlsPlaylistTypeConfig) -> PlaylistType {
+        match playlist_type {
+            HlsPlaylistTypeConfig::Live => PlaylistType::Live,
+            HlsPlaylistTypeConfig::Event => PlaylistType::Event,
+        }
+    }
+
+    fn start_blob_cleanup(&self) {
+        let Some(cleanup_cfg) = &self.cfg.blobcleanup else {
+            return;
+        };
+        if !cleanup_cfg.enabled {
+            return;
+        }
+
+        let walrus_bin = cleanup_cfg
+            .walrus_bin
+            .clone()
+            .unwrap_or_else(|| String::from("walrus"));
+        let delay = cleanup_cfg.delay.unwrap_or(DEFAULT_BLOB_RELEASE_DELAY);
+        if let Some(mut blob_cleanup) =
+            BlobCleanup::new(cleanup_cfg.owner.clone(), walrus_bin, delay)
+        {
+            tokio::spawn(async move {
+                blob_cleanup.run().await;
+            });
+        }
+    }
+
     async fn start_hls(&mut self, stream_hub: &mut StreamsHub) -> Result<()> {
         let hls_cfg = &self.cfg.hls;
 
@@ -192,12 +323,79 @@ impl Service {
                 return Ok(());
             }
 
+            let rendition_groups = hls_cfg_value
+                .rendition_groups
+                .iter()
+                .flatten()
+                .map(|group_cfg| RenditionGroup {
+                    app_name: group_cfg.app_name.clone(),
+                    group_name: group_cfg.name.clone(),
+                    stream_names: group_cfg.streams.clone(),
+                })
+                .collect();
+
+            let mut default_settings = HlsSettings {
+                need_record: hls_cfg_value.need_record,
+                ..Default::default()
+            };
+            if let Some(target_duration) = hls_cfg_value.target_duration {
+                default_settings.target_duration = target_duration;
+            }
+            if let Some(max_duration) = hls_cfg_value.max_duration {
+                default_settings.max_duration = Some(max_duration);
+            }
+            if let Some(keyframe_only) = hls_cfg_value.keyframe_only {
+                default_settings.keyframe_only = keyframe_only;
+            }
+            if let Some(live_window) = hls_cfg_value.live_window {
+                default_settings.live_window = live_window;
+            }
+            if let Some(dvr_window) = hls_cfg_value.dvr_window {
+                default_settings.dvr_window = Some(dvr_window);
+            }
+            if let Some(storage) = hls_cfg_value.storage {
+                default_settings.storage = Self::gen_storage_backend(storage);
+            }
+            if let Some(playlist_type) = hls_cfg_value.playlist_type {
+                default_settings.playlist_type = Self::gen_playlist_type(playlist_type);
+            }
+            if let Some(grace_period) = hls_cfg_value.republish_grace_period {
+                default_settings.republish_grace_period = grace_period;
+            }
+            if let Some(iframe_playlist) = hls_cfg_value.iframe_playlist {
+                default_settings.iframe_playlist = iframe_playlist;
+            }
+
+            let settings_overrides = hls_cfg_value
+                .overrides
+                .iter()
+                .flatten()
+                .map(|override_cfg| HlsSettingsOverride {
+                    app_name: override_cfg.app_name.clone(),
+                    stream_pattern: override_cfg.stream_pattern.clone(),
+                    target_duration: override_cfg.target_duration,
+                    max_duration: override_cfg.max_duration,
+                    keyframe_only: override_cfg.keyframe_only,
+                    live_window: override_cfg.live_window,
+                    dvr_window: override_cfg.dvr_window,
+                    need_record: override_cfg.need_record,
+                    storage: override_cfg.storage.map(Self::gen_storage_backend),
+                    playlist_type: override_cfg.playlist_type.map(Self::gen_playlist_type),
+                    republish_grace_period: override_cfg.republish_grace_period,
+                    iframe_playlist: override_cfg.iframe_playlist,
+                })
+                .collect();
+
+            let settings_resolver = HlsSettingsResolver::new(default_settings, settings_overrides)
+                .map_err(|err| anyhow!("{}", err))?;
+
             let event_producer = stream_hub.get_hub_event_sender();
             let cient_event_consumer = stream_hub.get_client_event_consumer();
             let mut hls_remuxer = HlsRemuxer::new(
                 cient_event_consumer,
                 event_producer,
-                hls_cfg_value.need_record,
+                settings_resolver,
+                rendition_groups,
             );
 
             tokio::spawn(async move {
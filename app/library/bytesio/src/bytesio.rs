@@ -9,6 +9,7 @@ use futures::SinkExt;
 use futures::StreamExt;
 use tokio::net::TcpStream;
 use tokio::net::UdpSocket;
+use tokio_rustls::server::TlsStream;
 use tokio_util::codec::BytesCodec;
 use tokio_util::codec::Framed;
 
@@ -214,6 +215,53 @@ impl TNetIO for TcpIO {
     }
 }
 
+/*the server side of a TLS connection, e.g. RTMPS*/
+pub struct TlsIO {
+    stream: Framed<TlsStream<TcpStream>, BytesCodec>,
+}
+
+impl TlsIO {
+    pub fn new(stream: TlsStream<TcpStream>) -> Self {
+        Self {
+            stream: Framed::new(stream, BytesCodec::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl TNetIO for TlsIO {
+    fn get_net_type(&self) -> NetType {
+        NetType::TCP
+    }
+
+    async fn write(&mut self, bytes: Bytes) -> Result<(), BytesIOError> {
+        self.stream.send(bytes).await?;
+
+        Ok(())
+    }
+
+    async fn read_timeout(&mut self, duration: Duration) -> Result<BytesMut, BytesIOError> {
+        match tokio::time::timeout(duration, self.read()).await {
+            Ok(data) => data,
+            Err(err) => Err(BytesIOError {
+                value: BytesIOErrorValue::TimeoutError(err),
+            }),
+        }
+    }
+
+    async fn read(&mut self) -> Result<BytesMut, BytesIOError> {
+        match self.stream.next().await {
+            Some(Ok(bytes)) => Ok(bytes),
+            Some(Err(err)) => Err(BytesIOError {
+                value: BytesIOErrorValue::IOError(err),
+            }),
+            None => Err(BytesIOError {
+                value: BytesIOErrorValue::NoneReturn,
+            }),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -1,4 +1,5 @@
 use bytesio::bits_errors::BitError;
+use bytesio::bytes_errors::BytesReadError;
 use failure::{Backtrace, Fail};
 use std::fmt;
 
@@ -6,6 +7,8 @@ use std::fmt;
 pub enum H264ErrorValue {
     #[fail(display = "bit error")]
     BitError(BitError),
+    #[fail(display = "bytes read error")]
+    BytesReadError(BytesReadError),
 }
 #[derive(Debug)]
 pub struct H264Error {
@@ -20,6 +23,14 @@ impl From<BitError> for H264Error {
     }
 }
 
+impl From<BytesReadError> for H264Error {
+    fn from(error: BytesReadError) -> Self {
+        H264Error {
+            value: H264ErrorValue::BytesReadError(error),
+        }
+    }
+}
+
 impl fmt::Display for H264Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Display::fmt(&self.value, f)
@@ -1,4 +1,5 @@
 pub mod errors;
 pub mod pps;
+pub mod sei;
 pub mod sps;
 pub mod utils;
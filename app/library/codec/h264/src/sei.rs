@@ -0,0 +1,212 @@
+use {
+    super::errors::H264Error, byteorder::BigEndian, bytes::BytesMut,
+    bytesio::bytes_reader::BytesReader,
+};
+
+pub const NALU_TYPE_SEI: u8 = 6;
+
+// 7.3.2.3.1 Supplemental enhancement information message syntax
+pub const SEI_TYPE_USER_DATA_REGISTERED_ITU_T_T35: u32 = 4;
+
+// ATSC A/53 Part 4, the closed captions are carried as
+// itu_t_t35_country_code(0xB5) itu_t_t35_provider_code(0x0031) "GA94" 0x03 cc_data()
+const ITU_T_T35_COUNTRY_CODE_US: u8 = 0xB5;
+const ITU_T_T35_PROVIDER_CODE_ATSC: u16 = 0x0031;
+const ATSC_USER_IDENTIFIER: [u8; 4] = *b"GA94";
+const ATSC_USER_DATA_TYPE_CC: u8 = 0x03;
+
+#[derive(Debug, Clone)]
+pub struct SeiMessage {
+    pub payload_type: u32,
+    pub payload: BytesMut,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CcData {
+    pub cc_valid: bool,
+    /*
+        0: NTSC field 1 (CEA-608 CC1/CC2)
+        1: NTSC field 2 (CEA-608 CC3/CC4)
+        2: DTVCC packet data
+        3: DTVCC packet start
+    */
+    pub cc_type: u8,
+    pub cc_data_1: u8,
+    pub cc_data_2: u8,
+}
+
+// split the nalus of an annexb frame, the start codes are removed
+pub fn split_annexb_nalus(data: &[u8]) -> Vec<&[u8]> {
+    let mut nalus = Vec::new();
+    let mut nalu_start: Option<usize> = None;
+    let mut i = 0;
+
+    while i + 3 <= data.len() {
+        if data[i] == 0x00 && data[i + 1] == 0x00 && data[i + 2] == 0x01 {
+            if let Some(start) = nalu_start {
+                /*a 4 bytes start code begins with one more zero byte*/
+                let mut end = i;
+                if end > start && data[end - 1] == 0x00 {
+                    end -= 1;
+                }
+                nalus.push(&data[start..end]);
+            }
+            i += 3;
+            nalu_start = Some(i);
+        } else {
+            i += 1;
+        }
+    }
+
+    if let Some(start) = nalu_start {
+        if start < data.len() {
+            nalus.push(&data[start..]);
+        }
+    }
+
+    nalus
+}
+
+// remove the emulation_prevention_three_byte(0x03) of 0x000003
+pub fn ebsp_to_rbsp(data: &[u8]) -> BytesMut {
+    let mut rbsp = BytesMut::with_capacity(data.len());
+    let mut zero_count = 0;
+
+    for &byte in data {
+        if zero_count >= 2 && byte == 0x03 {
+            zero_count = 0;
+            continue;
+        }
+        if byte == 0x00 {
+            zero_count += 1;
+        } else {
+            zero_count = 0;
+        }
+        rbsp.extend_from_slice(&[byte]);
+    }
+
+    rbsp
+}
+
+// parse the sei messages of a sei nalu which starts with the nalu header
+pub fn parse_sei_nalu(nalu: &[u8]) -> Result<Vec<SeiMessage>, H264Error> {
+    let mut messages = Vec::new();
+    if nalu.is_empty() || nalu[0] & 0x1F != NALU_TYPE_SEI {
+        return Ok(messages);
+    }
+
+    let mut reader = BytesReader::new(ebsp_to_rbsp(&nalu[1..]));
+
+    /*stop at the rbsp_trailing_bits*/
+    while reader.len() > 1 || (reader.len() == 1 && reader.get(0)? != 0x80) {
+        let payload_type = read_ff_coded_number(&mut reader)?;
+        let payload_size = read_ff_coded_number(&mut reader)? as usize;
+        let payload = reader.read_bytes(payload_size)?;
+
+        messages.push(SeiMessage {
+            payload_type,
+            payload,
+        });
+    }
+
+    Ok(messages)
+}
+
+// the payload type and size are the sum of the 0xFF bytes and the last byte
+fn read_ff_coded_number(reader: &mut BytesReader) -> Result<u32, H264Error> {
+    let mut value: u32 = 0;
+
+    loop {
+        let byte = reader.read_u8()?;
+        value += byte as u32;
+        if byte != 0xFF {
+            break;
+        }
+    }
+
+    Ok(value)
+}
+
+// get the closed caption data of an ATSC A/53 user data sei, None if it is other user data
+pub fn parse_a53_cc_data(message: &SeiMessage) -> Result<Option<Vec<CcData>>, H264Error> {
+    if message.payload_type != SEI_TYPE_USER_DATA_REGISTERED_ITU_T_T35 {
+        return Ok(None);
+    }
+
+    let mut reader = BytesReader::new(message.payload.clone());
+    if reader.read_u8()? != ITU_T_T35_COUNTRY_CODE_US
+        || reader.read_u16::<BigEndian>()? != ITU_T_T35_PROVIDER_CODE_ATSC
+        || reader.read_bytes(4)?[..] != ATSC_USER_IDENTIFIER
+        || reader.read_u8()? != ATSC_USER_DATA_TYPE_CC
+    {
+        return Ok(None);
+    }
+
+    /*process_em_data_flag(1) process_cc_data_flag(1) additional_data_flag(1) cc_count(5)*/
+    let flags = reader.read_u8()?;
+    if flags & 0x40 == 0 {
+        return Ok(None);
+    }
+    let cc_count = flags & 0x1F;
+    /*em_data*/
+    reader.read_u8()?;
+
+    let mut cc_datas = Vec::with_capacity(cc_count as usize);
+    for _ in 0..cc_count {
+        /*marker_bits(5) cc_valid(1) cc_type(2)*/
+        let cc_flags = reader.read_u8()?;
+        let cc_data_1 = reader.read_u8()?;
+        let cc_data_2 = reader.read_u8()?;
+
+        cc_datas.push(CcData {
+            cc_valid: cc_flags & 0x04 != 0,
+            cc_type: cc_flags & 0x03,
+            cc_data_1,
+            cc_data_2,
+        });
+    }
+
+    Ok(Some(cc_datas))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ebsp_to_rbsp, parse_a53_cc_data, parse_sei_nalu, split_annexb_nalus};
+
+    #[test]
+    fn test_parse_cc_sei() {
+        let frame: Vec<u8> = [
+            &[0x00, 0x00, 0x00, 0x01, 0x09, 0xF0][..],
+            /*sei nalu with one user data registered message*/
+            &[0x00, 0x00, 0x00, 0x01, 0x06, 0x04, 0x0E],
+            &[0xB5, 0x00, 0x31, b'G', b'A', b'9', b'4', 0x03],
+            &[0x41, 0xFF, 0xFC, 0x94, 0x20, 0xFF],
+            &[0x80],
+            &[0x00, 0x00, 0x01, 0x65, 0x88],
+        ]
+        .concat();
+
+        let nalus = split_annexb_nalus(&frame);
+        assert_eq!(nalus.len(), 3);
+        assert_eq!(nalus[0], &[0x09, 0xF0]);
+        assert_eq!(nalus[2], &[0x65, 0x88]);
+
+        let messages = parse_sei_nalu(nalus[1]).unwrap();
+        assert_eq!(messages.len(), 1);
+
+        let cc_datas = parse_a53_cc_data(&messages[0]).unwrap().unwrap();
+        assert_eq!(cc_datas.len(), 1);
+        assert!(cc_datas[0].cc_valid);
+        assert_eq!(cc_datas[0].cc_type, 0);
+        assert_eq!(cc_datas[0].cc_data_1, 0x94);
+        assert_eq!(cc_datas[0].cc_data_2, 0x20);
+    }
+
+    #[test]
+    fn test_ebsp_to_rbsp() {
+        assert_eq!(
+            &ebsp_to_rbsp(&[0x00, 0x00, 0x03, 0x01, 0x00, 0x00, 0x03])[..],
+            &[0x00, 0x00, 0x01, 0x00, 0x00]
+        );
+    }
+}
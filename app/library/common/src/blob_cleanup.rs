@@ -0,0 +1,117 @@
+use {
+    std::{
+        collections::VecDeque,
+        sync::OnceLock,
+        time::{Duration, Instant},
+    },
+    tokio::{process::Command, sync::mpsc, time::interval},
+};
+
+/*The segments which are not recorded are uploaded as deletable blobs and sent to the
+owner address. The walrus client, set up with the wallet of the owner, deletes them
+after a delay once no playlist refers them. The recorded segments are permanent blobs.*/
+
+const CLEANUP_INTERVAL: u64 = 10;
+
+struct BlobCleanupContext {
+    owner: String,
+    sender: mpsc::UnboundedSender<String>,
+}
+
+static BLOB_CLEANUP: OnceLock<BlobCleanupContext> = OnceLock::new();
+
+/*the blobs are uploaded as deletable only if they can be released*/
+pub fn deletable_blob_owner() -> Option<&'static str> {
+    BLOB_CLEANUP.get().map(|context| context.owner.as_str())
+}
+
+pub fn release_blobs(blob_ids: Vec<String>) {
+    let Some(context) = BLOB_CLEANUP.get() else {
+        return;
+    };
+
+    for blob_id in blob_ids {
+        if context.sender.send(blob_id).is_err() {
+            log::error!("release blobs error: the blob cleanup job is stopped");
+            return;
+        }
+    }
+}
+
+pub struct BlobCleanup {
+    walrus_bin: String,
+    delay: Duration,
+    receiver: mpsc::UnboundedReceiver<String>,
+    pending_blobs: VecDeque<(Instant, String)>,
+}
+
+impl BlobCleanup {
+    /*There is one cleanup job in the process, returns None if it has been set up.*/
+    pub fn new(owner: String, walrus_bin: String, delay: u64) -> Option<Self> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        BLOB_CLEANUP
+            .set(BlobCleanupContext { owner, sender })
+            .ok()?;
+
+        Some(Self {
+            walrus_bin,
+            delay: Duration::from_secs(delay),
+            receiver,
+            pending_blobs: VecDeque::new(),
+        })
+    }
+
+    pub async fn run(&mut self) {
+        let mut ticker = interval(Duration::from_secs(CLEANUP_INTERVAL));
+
+        loop {
+            tokio::select! {
+                blob_id = self.receiver.recv() => {
+                    let Some(blob_id) = blob_id else {
+                        break;
+                    };
+                    self.pending_blobs
+                        .push_back((Instant::now() + self.delay, blob_id));
+                }
+                _ = ticker.tick() => {
+                    self.release_due_blobs().await;
+                }
+            }
+        }
+    }
+
+    async fn release_due_blobs(&mut self) {
+        let now = Instant::now();
+        while let Some((due_time, _)) = self.pending_blobs.front() {
+            if *due_time > now {
+                break;
+            }
+            let (_, blob_id) = self.pending_blobs.pop_front().unwrap();
+            self.delete_blob(&blob_id).await;
+        }
+    }
+
+    /*a permanent blob or one of another owner cannot be deleted, it is only logged*/
+    async fn delete_blob(&self, blob_id: &str) {
+        let output = Command::new(&self.walrus_bin)
+            .args(["delete", "--blob-id", blob_id, "--yes"])
+            .output()
+            .await;
+
+        match output {
+            Ok(output) if output.status.success() => {
+                log::info!("blob {} is deleted", blob_id);
+            }
+            Ok(output) => {
+                log::warn!(
+                    "delete blob {} error: {}",
+                    blob_id,
+                    String::from_utf8_lossy(&output.stderr)
+                );
+            }
+            Err(err) => {
+                log::error!("run {} error: {}", self.walrus_bin, err);
+            }
+        }
+    }
+}
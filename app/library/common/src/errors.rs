@@ -63,6 +63,10 @@ pub enum SuiErrorValue {
     PTBObjError,
     #[fail(display = "json parse error")]
     JsonParseError,
+    #[fail(display = "playlist archive fetch error")]
+    ArchiveFetchError,
+    #[fail(display = "invalid vod edit")]
+    VodEditError,
 }
 
 impl fmt::Display for SuiError {
@@ -1,6 +1,9 @@
 pub mod auth;
+pub mod blob_cleanup;
 pub mod define;
 pub mod errors;
 pub mod utils;
 pub mod move_call;
-pub mod sui_utils;
\ No newline at end of file
+pub mod playlist_archive;
+pub mod sui_utils;
+pub mod vod_edit;
\ No newline at end of file
@@ -1,5 +1,5 @@
 use {
-    crate::{errors::{SuiError, SuiErrorValue}, sui_utils::setup_for_write}, serde::Deserialize, shared_crypto::intent::Intent, std::time::{Duration, SystemTime}, sui_config::{sui_config_dir, SUI_KEYSTORE_FILENAME}, sui_keys::keystore::{AccountKeystore, FileBasedKeystore}, sui_sdk::{
+    crate::{errors::{SuiError, SuiErrorValue}, playlist_archive::expand_archives, sui_utils::setup_for_write, vod_edit::{concat_playlists, trim_playlist}}, serde::{de::DeserializeOwned, Deserialize, Serialize}, shared_crypto::intent::Intent, std::time::{Duration, SystemTime}, sui_config::{sui_config_dir, SUI_KEYSTORE_FILENAME}, sui_keys::keystore::{AccountKeystore, FileBasedKeystore}, sui_sdk::{
         rpc_types::{SuiObjectDataOptions, SuiObjectResponseQuery, SuiTransactionBlockResponseOptions, SuiTransactionBlockEffects, SuiExecutionStatus},
         types::{
             base_types::{ObjectID, SequenceNumber}, programmable_transaction_builder::ProgrammableTransactionBuilder, quorum_driver_types::ExecuteTransactionRequestType, transaction::{
@@ -9,6 +9,8 @@ use {
     }
 };
 
+// the latest published id of the package(latest-published-id in Move.lock), the package is
+// upgraded in place, so the shared objects and their initial shared version stay the same
 const CONTRACT_PACKAGE: &str = "0x631274a289104633260905535e8a26903fd44026fe313ea1c96e55ff83cef5fc";
 const ADMIN_CAP: &str = "0xeeff0b099111189d01fd5307548a8467488fdb68c7f30b0d4774e5f2d9f6eb7b";
 const STREAMER_ADDR: &str = "0xfac88744d3c6b359d21fad3aa20f0aa81cca9fdaee25b10d2ffac62a989f8785";
@@ -42,8 +44,48 @@ pub struct VodM3u8Info {
     pub m3u8_content: String,
 }
 
+#[derive(Deserialize, Debug)]
+pub struct VodEditedResult {
+    pub index: String,
+}
+
+/*the uri which a master playlist on the contract refers its playlists with, see
+hls::master_playlist*/
+pub const LIVE_PLAYLIST_QUERY: &str = "query_live_m3u8?stream_name=";
+
+// the segments are stored as walrus blob ids, the playlists referred by a master
+// playlist are queried from the contract and kept as they are
+fn concat_aggregator_url(m3u8_content: &str) -> String {
+    let mut ret: String = String::new();
+    for line in m3u8_content.split("\n") {
+        if line.starts_with("#") {
+            ret.push_str(&concat_aggregator_url_attribute(line));
+        } else {
+            if !line.is_empty() && !line.starts_with(LIVE_PLAYLIST_QUERY) {
+                ret.push_str(AGGREGATOR_URL_PREFIX);
+            }
+            ret.push_str(line);
+        }
+        ret.push('\n');
+    }
+    ret
+}
 
-pub async fn upload_playlist_to_contract(url_path: String, m3u8_content: &String) -> Result<(), SuiError> {
+// the uri of a tag, e.g. the media initialization section of the I-frames
+fn concat_aggregator_url_attribute(line: &str) -> String {
+    let Some(start) = line.find("URI=\"").map(|index| index + "URI=\"".len()) else {
+        return line.to_owned();
+    };
+    if line[start..].starts_with(LIVE_PLAYLIST_QUERY) || line[start..].starts_with("\"") {
+        return line.to_owned();
+    }
+
+    format!("{}{}{}", &line[..start], AGGREGATOR_URL_PREFIX, &line[start..])
+}
+
+
+/*returns the digest of the transaction*/
+pub async fn upload_playlist_to_contract(url_path: String, m3u8_content: &String) -> Result<String, SuiError> {
     let now = SystemTime::now();
 
     // 1) get the Sui client, the sender and recipient that we will use
@@ -134,11 +176,12 @@ pub async fn upload_playlist_to_contract(url_path: String, m3u8_content: &String
         )
         .await.map_err(|_| SuiError{value: SuiErrorValue::TransactionBlockExecuteError})?;
     log::info!("{}", transaction_response);
+    let digest = transaction_response.digest.to_string();
 
     let res = match transaction_response.effects.unwrap() {
         SuiTransactionBlockEffects::V1(t) => {
             match t.status {
-                SuiExecutionStatus::Success => Ok(()),
+                SuiExecutionStatus::Success => Ok(digest),
                 SuiExecutionStatus::Failure {error: e} => {
                     log::error!("contract error: {}", e);
                     return Err(SuiError{value: SuiErrorValue::TransactionBlockExecuteError});
@@ -230,16 +273,12 @@ pub async fn get_live_playlist(path_url: String) -> Result<String, SuiError> {
                     let res: LiveM3u8Result = serde_json::from_str(v[0].parsed_json.to_string().as_str())
                             .map_err(|_| SuiError{value: SuiErrorValue::SetupSuiClientError})?;
                     log::info!("{}", res.data.m3u8_content);
-                
+
+                    // the long playlists keep their older segments in archive blobs
+                    let m3u8_content = expand_archives(&res.data.m3u8_content, AGGREGATOR_URL_PREFIX).await?;
+
                     // concat with aggregator url
-                    let mut ret: String = String::new();
-                    for line in res.data.m3u8_content.split("\n") {
-                        if !line.starts_with("#") && line.len() > 40 {
-                            ret.push_str(AGGREGATOR_URL_PREFIX);
-                        }
-                        ret.push_str(line);
-                        ret.push('\n');
-                    }
+                    let ret = concat_aggregator_url(&m3u8_content);
                     Ok(ret)
                 },
                 SuiExecutionStatus::Failure {error: e} => {
@@ -475,65 +514,97 @@ pub async fn create_live_stream(url: String, name: String, description: String)
     res
 }
 
-pub async fn get_vod_playlist(index: u64) -> Result<String, SuiError> {
+fn pure_arg<T: Serialize>(value: &T) -> Result<CallArg, SuiError> {
+    bcs::to_bytes(value).map(CallArg::Pure).map_err(|_| SuiError{value: SuiErrorValue::PTBInputError})
+}
+
+// calls a function of the streamer module with the admin cap if with_admin_cap, the streams
+// and the clock if with_clock before the args, returns the events of the transaction
+async fn execute_streamer_call(function: &str, with_admin_cap: bool, with_clock: bool, args: Vec<CallArg>) -> Result<Vec<serde_json::Value>, SuiError> {
+    let now = SystemTime::now();
+
+    // 1) get the Sui client and the sender that we will use
+    // for the transaction, and find the coin we use as gas
+    let (sui, sender, _recipient) = setup_for_write().await
+                                .map_err(|_| SuiError{value: SuiErrorValue::SetupSuiClientError})?;
+
+    let coins = sui
+        .coin_read_api()
+        .get_coins(sender, None, None, None).await
+        .map_err(|_| SuiError{value: SuiErrorValue::GetSuiCoinError})?;
+    let coin = coins.data.into_iter().next().ok_or(SuiError{value: SuiErrorValue::GetSuiCoinError})?;
+
+    // 2) create a programmable transaction builder to add commands and create a PTB
     let mut ptb = ProgrammableTransactionBuilder::new();
-    
+    let mut arguments = Vec::new();
+
+    if with_admin_cap {
+        let admin_cap_id: ObjectID = ADMIN_CAP.parse().map_err(|_| SuiError{value: SuiErrorValue::ParseError})?;
+        let admin_cap_obj = sui.read_api().get_object_with_options(admin_cap_id, SuiObjectDataOptions::bcs_lossless()).await
+                                .map_err(|_| SuiError{value: SuiErrorValue::SuiRPCError})?
+                                .data
+                                .ok_or(SuiError{value: SuiErrorValue::SuiRPCError})?;
+        let admin_cap_input = ptb.input(CallArg::Object(ObjectArg::ImmOrOwnedObject((admin_cap_obj.object_id, admin_cap_obj.version, admin_cap_obj.digest))))
+                                .map_err(|_| SuiError{value: SuiErrorValue::PTBInputError})?;
+        arguments.push(admin_cap_input);
+    }
+
     let streamer_id: ObjectID = STREAMER_ADDR.parse().map_err(|_| SuiError{value: SuiErrorValue::ParseError})?;
-    let arg0 = CallArg::Object(ObjectArg::SharedObject {
+    let streamer_input = ptb.obj(ObjectArg::SharedObject {
         id: streamer_id,
         initial_shared_version: SequenceNumber::from(206208636),
         mutable: true,
-    });
-    ptb.input(arg0).map_err(|_| SuiError{value: SuiErrorValue::PTBInputError})?;
-
-    let arg1 = CallArg::Pure(bcs::to_bytes(&index).unwrap());
-    ptb.input(arg1).map_err(|_| SuiError{value: SuiErrorValue::PTBInputError})?;
+    }).map_err(|_| SuiError{value: SuiErrorValue::PTBObjError})?;
+    arguments.push(streamer_input);
+
+    if with_clock {
+        let clock_id: ObjectID = CLOCK_OBJ_ID.parse().map_err(|_| SuiError{value: SuiErrorValue::ParseError})?;
+        let clock_input = ptb.obj(ObjectArg::SharedObject {
+            id: clock_id,
+            initial_shared_version: SequenceNumber::from(1),
+            mutable: false,
+        }).map_err(|_| SuiError{value: SuiErrorValue::PTBObjError})?;
+        arguments.push(clock_input);
+    }
+    for arg in args {
+        arguments.push(ptb.input(arg).map_err(|_| SuiError{value: SuiErrorValue::PTBInputError})?);
+    }
 
-    // add a move call to the PTB
+    // 3) add a move call to the PTB
     let package = ObjectID::from_hex_literal(CONTRACT_PACKAGE).map_err(|_| SuiError{value: SuiErrorValue::IdentifierFormatError})?;
     let module = Identifier::new("streamer").map_err(|_| SuiError{value: SuiErrorValue::IdentifierFormatError})?;
-    let function = Identifier::new("get_vod_stream").map_err(|_| SuiError{value: SuiErrorValue::IdentifierFormatError})?;
+    let move_function = Identifier::new(function).map_err(|_| SuiError{value: SuiErrorValue::IdentifierFormatError})?;
     ptb.command(Command::move_call(
         package,
         module,
-        function,
+        move_function,
         vec![],
-        vec![Argument::Input(0), Argument::Input(1)],
+        arguments,
     ));
 
     // build the transaction block by calling finish on the ptb
     let builder = ptb.finish();
 
-    // get the Sui client, the sender and recipient that we will use
-    // for the transaction, and find the coin we use as gas       
-    let (sui, sender, recipient) = setup_for_write().await
-                                .map_err(|_| SuiError{value: SuiErrorValue::SetupSuiClientError})?;
-
-    // we need to find the coin we will use as gas
-    let coins = sui
-        .coin_read_api()
-        .get_coins(recipient, None, None, None).await
-        .map_err(|_| SuiError{value: SuiErrorValue::GetSuiCoinError})?;
-    let coin = coins.data.into_iter().next().unwrap();
-
     let gas_budget = 10_000_000;
     let gas_price = sui.read_api().get_reference_gas_price().await.map_err(|_| SuiError{value: SuiErrorValue::SuiRPCError})?;
 
     // create the transaction data that will be sent to the network
     let tx_data = TransactionData::new_programmable(
-        recipient.clone(),
+        sender,
         vec![coin.object_ref()],
         builder,
         gas_budget,
         gas_price,
     );
 
-    // sign transaction
-    let keystore = FileBasedKeystore::new(&sui_config_dir().map_err(|_| SuiError{value: SuiErrorValue::SuiRPCError})?
-                                .join(SUI_KEYSTORE_FILENAME)).map_err(|_| SuiError{value: SuiErrorValue::FileKeyStoreError})?;
-    let signature = keystore.sign_secure(&recipient, &tx_data, Intent::sui_transaction())
-                                .map_err(|_| SuiError{value: SuiErrorValue::TransactionSignError})?;
-    // execute the transaction
+    // 4) sign transaction
+    let keystore = FileBasedKeystore::new(
+        &sui_config_dir().map_err(|_| SuiError{value: SuiErrorValue::SuiConfigError})?.join(SUI_KEYSTORE_FILENAME))
+            .map_err(|_| SuiError{value: SuiErrorValue::FileKeyStoreError})?;
+    let signature = keystore.sign_secure(&sender, &tx_data, Intent::sui_transaction())
+                            .map_err(|_| SuiError{value: SuiErrorValue::TransactionSignError})?;
+
+    // 5) execute the transaction
     let transaction_response = sui
         .quorum_driver_api()
         .execute_transaction_block(
@@ -541,31 +612,18 @@ pub async fn get_vod_playlist(index: u64) -> Result<String, SuiError> {
             SuiTransactionBlockResponseOptions::full_content(),
             Some(ExecuteTransactionRequestType::WaitForLocalExecution),
         )
-        .await.map_err(|e| {
-            log::error!("{}", e);
+        .await.map_err(|err| {
+            log::error!("{} error: {}", function, err);
             SuiError{value: SuiErrorValue::TransactionBlockExecuteError}
         })?;
-    
-    let res = match transaction_response.effects.unwrap() {
+
+    let effects = transaction_response.effects.ok_or(SuiError{value: SuiErrorValue::TransactionBlockExecuteError})?;
+    let res = match effects {
         SuiTransactionBlockEffects::V1(t) => {
             match t.status {
                 SuiExecutionStatus::Success => {
-                    let v = &transaction_response.events.unwrap().data;    
-                    let res: VodM3u8Result = serde_json::from_str(v[0].parsed_json.to_string().as_str())
-                            .map_err(|_| SuiError{value: SuiErrorValue::SetupSuiClientError})?;
-                    log::info!("{}", res.data.m3u8_content);
-
-                    // concat with aggregator url
-                    let mut ret: String = String::new();
-                    for line in res.data.m3u8_content.split("\n") {
-                        if !line.starts_with("#") && line.len() > 40 {
-                            ret.push_str(AGGREGATOR_URL_PREFIX);
-                        }
-                        ret.push_str(line);
-                        ret.push('\n');
-                    }
-
-                    Ok(ret)                    
+                    let events = transaction_response.events.map(|events| events.data).unwrap_or_default();
+                    Ok(events.into_iter().map(|event| event.parsed_json).collect())
                 },
                 SuiExecutionStatus::Failure {error: e} => {
                     log::error!("contract error: {}", e);
@@ -575,5 +633,98 @@ pub async fn get_vod_playlist(index: u64) -> Result<String, SuiError> {
         }
     };
 
+    let span = SystemTime::now().duration_since(now).unwrap_or_default().as_secs();
+    log::info!("{} seconds: {}", function, span);
+
     res
-}
\ No newline at end of file
+}
+
+// the first event of the call, e.g. the result of a query
+fn first_event<T: DeserializeOwned>(events: Vec<serde_json::Value>) -> Result<T, SuiError> {
+    let event = events.into_iter().next().ok_or(SuiError{value: SuiErrorValue::JsonParseError})?;
+    serde_json::from_value(event).map_err(|_| SuiError{value: SuiErrorValue::JsonParseError})
+}
+
+// the edits are added as new VOD streams which refer their sources,
+// returns the index of the new one
+async fn execute_vod_edit(function: &str, args: Vec<CallArg>) -> Result<u64, SuiError> {
+    let events = execute_streamer_call(function, true, true, args).await?;
+    let res: VodEditedResult = first_event(events)?;
+
+    res.index.parse().map_err(|_| SuiError{value: SuiErrorValue::ParseError})
+}
+
+pub async fn trim_vod_stream(index: u64, start: usize, end: usize) -> Result<u64, SuiError> {
+    let info = get_vod_stream(index).await?;
+    let m3u8_content = trim_playlist(&info.m3u8_content, start, end).ok_or(SuiError{value: SuiErrorValue::VodEditError})?;
+
+    execute_vod_edit("trim_vod_stream", vec![
+        pure_arg(&index)?,
+        pure_arg(&m3u8_content)?,
+    ]).await
+}
+
+pub async fn concat_vod_streams(indexes: Vec<u64>, name: String, description: String) -> Result<u64, SuiError> {
+    let mut m3u8_contents = Vec::new();
+    for index in &indexes {
+        m3u8_contents.push(get_vod_stream(*index).await?.m3u8_content);
+    }
+    let m3u8_content = concat_playlists(&m3u8_contents).ok_or(SuiError{value: SuiErrorValue::VodEditError})?;
+
+    execute_vod_edit("concat_vod_streams", vec![
+        pure_arg(&indexes)?,
+        pure_arg(&name)?,
+        pure_arg(&description)?,
+        pure_arg(&m3u8_content)?,
+    ]).await
+}
+
+pub async fn update_vod_stream_info(index: u64, name: String, description: String) -> Result<u64, SuiError> {
+    execute_vod_edit("update_vod_stream_info", vec![
+        pure_arg(&index)?,
+        pure_arg(&name)?,
+        pure_arg(&description)?,
+    ]).await
+}
+
+pub async fn set_vod_stream_listed(index: u64, listed: bool) -> Result<(), SuiError> {
+    execute_streamer_call("set_vod_stream_listed", true, false, vec![
+        pure_arg(&index)?,
+        pure_arg(&listed)?,
+    ]).await?;
+
+    Ok(())
+}
+
+pub async fn delete_vod_stream(index: u64) -> Result<(), SuiError> {
+    execute_streamer_call("delete_vod_stream", true, false, vec![
+        pure_arg(&index)?,
+    ]).await?;
+
+    Ok(())
+}
+
+// a VOD made of the blobs which are already on walrus, e.g. a clip of a live stream
+pub async fn add_vod_stream(name: String, description: String, m3u8_content: &String) -> Result<(), SuiError> {
+    execute_streamer_call("add_vod_stream", true, true, vec![
+        pure_arg(&name)?,
+        pure_arg(&description)?,
+        pure_arg(m3u8_content)?,
+    ]).await?;
+
+    Ok(())
+}
+
+pub async fn get_vod_playlist(index: u64) -> Result<String, SuiError> {
+    let info = get_vod_stream(index).await?;
+
+    // concat with aggregator url
+    Ok(concat_aggregator_url(&info.m3u8_content))
+}
+
+async fn get_vod_stream(index: u64) -> Result<VodM3u8Info, SuiError> {
+    let events = execute_streamer_call("get_vod_stream", false, false, vec![pure_arg(&index)?]).await?;
+    let res: VodM3u8Result = first_event(events)?;
+
+    Ok(res.data)
+}
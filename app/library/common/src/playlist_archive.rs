@@ -0,0 +1,105 @@
+use {
+    crate::errors::{SuiError, SuiErrorValue},
+    futures::future::join_all,
+    std::{
+        collections::HashMap,
+        sync::{Mutex, OnceLock},
+    },
+};
+
+/*The long live playlists (DVR windows, events) keep their older segments in archive
+blobs on walrus, so the live entry on chain stays small. An archive line refers a
+blob which holds the playlist lines of the archived segments, the archives of an
+event may be merged into a blob of the archive lines of those blobs.*/
+pub const ARCHIVE_TAG: &str = "#WALRUS-ARCHIVE:";
+
+/*a merged archive only refers the blobs of the playlist lines, stop on a malformed chain*/
+const MAX_ARCHIVE_DEPTH: usize = 2;
+
+/*The blobs are immutable, so the fetched archives are kept instead of being fetched
+again on each request of the live playlist. Once full the cache is simply emptied.*/
+const MAX_CACHED_ARCHIVES: usize = 1024;
+static ARCHIVE_CACHE: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+
+fn archive_cache() -> &'static Mutex<HashMap<String, String>> {
+    ARCHIVE_CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub fn archive_line(blob_id: &str) -> String {
+    format!("{ARCHIVE_TAG}{blob_id}\n")
+}
+
+async fn fetch_archive(aggregator_url_prefix: &str, blob_id: &str) -> Result<String, SuiError> {
+    if let Some(archive) = archive_cache().lock().unwrap().get(blob_id) {
+        return Ok(archive.clone());
+    }
+
+    let url = format!("{aggregator_url_prefix}{blob_id}");
+    let response = reqwest::get(url.as_str()).await.map_err(|err| {
+        log::error!("fetch archive {} error: {}", url, err);
+        SuiError {
+            value: SuiErrorValue::ArchiveFetchError,
+        }
+    })?;
+
+    /*an error page must not be cached as the archive*/
+    if !response.status().is_success() {
+        log::error!("fetch archive {} status: {}", url, response.status());
+        return Err(SuiError {
+            value: SuiErrorValue::ArchiveFetchError,
+        });
+    }
+    let archive = response.text().await.map_err(|_| SuiError {
+        value: SuiErrorValue::ArchiveFetchError,
+    })?;
+
+    let mut cache = archive_cache().lock().unwrap();
+    if cache.len() >= MAX_CACHED_ARCHIVES {
+        cache.clear();
+    }
+    cache.insert(blob_id.to_string(), archive.clone());
+    Ok(archive)
+}
+
+/*Replace the archive lines with the lines of the archived segments.*/
+pub async fn expand_archives(
+    m3u8_content: &str,
+    aggregator_url_prefix: &str,
+) -> Result<String, SuiError> {
+    let mut content = m3u8_content.to_string();
+
+    /*one more round finds no archive lines*/
+    for _ in 0..=MAX_ARCHIVE_DEPTH {
+        let blob_ids: Vec<&str> = content
+            .lines()
+            .filter_map(|line| line.strip_prefix(ARCHIVE_TAG))
+            .collect();
+        if blob_ids.is_empty() {
+            return Ok(content);
+        }
+
+        let archives = join_all(
+            blob_ids
+                .iter()
+                .map(|blob_id| fetch_archive(aggregator_url_prefix, blob_id)),
+        )
+        .await;
+        let mut archives = archives.into_iter();
+
+        let mut expanded = String::new();
+        for line in content.lines() {
+            if line.starts_with(ARCHIVE_TAG) {
+                /*one archive was fetched for each archive line*/
+                expanded += archives.next().unwrap()?.as_str();
+            } else {
+                expanded += line;
+                expanded += "\n";
+            }
+        }
+        content = expanded;
+    }
+
+    Err(SuiError {
+        value: SuiErrorValue::ArchiveFetchError,
+    })
+}
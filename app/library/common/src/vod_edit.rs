@@ -0,0 +1,168 @@
+/*The edits of the VOD playlists on chain. A VOD playlist is split into its segments,
+each one is its uri with the tags before it, the edited playlist gets a new header.*/
+
+const DEFAULT_VERSION: u16 = 3;
+
+struct VodPlaylist {
+    version: u16,
+    segments: Vec<String>,
+}
+
+fn is_header_tag(line: &str) -> bool {
+    [
+        "#EXTM3U",
+        "#EXT-X-VERSION:",
+        "#EXT-X-TARGETDURATION:",
+        "#EXT-X-MEDIA-SEQUENCE:",
+        "#EXT-X-PLAYLIST-TYPE:",
+        "#EXT-X-ALLOW-CACHE:",
+        "#EXT-X-ENDLIST",
+    ]
+    .iter()
+    .any(|tag| line.starts_with(tag))
+}
+
+fn parse_playlist(m3u8_content: &str) -> VodPlaylist {
+    let mut version = DEFAULT_VERSION;
+    let mut segments = Vec::new();
+    let mut segment = String::new();
+
+    for line in m3u8_content.lines().map(str::trim) {
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(value) = line.strip_prefix("#EXT-X-VERSION:") {
+            version = value.parse().unwrap_or(DEFAULT_VERSION);
+        }
+        if is_header_tag(line) {
+            continue;
+        }
+
+        segment += line;
+        segment += "\n";
+        if !line.starts_with('#') {
+            segments.push(std::mem::take(&mut segment));
+        }
+    }
+
+    VodPlaylist { version, segments }
+}
+
+fn segment_duration(segment: &str) -> f64 {
+    segment
+        .lines()
+        .filter_map(|line| line.strip_prefix("#EXTINF:"))
+        .filter_map(|value| value.split(',').next()?.parse::<f64>().ok())
+        .next()
+        .unwrap_or_default()
+}
+
+fn generate_playlist(version: u16, segments: &[String]) -> String {
+    let target_duration = segments
+        .iter()
+        .map(|segment| segment_duration(segment).ceil() as u64)
+        .max()
+        .unwrap_or_default();
+
+    let mut m3u8_content = "#EXTM3U\n".to_string();
+    m3u8_content += format!("#EXT-X-VERSION:{version}\n").as_str();
+    m3u8_content += format!("#EXT-X-TARGETDURATION:{target_duration}\n").as_str();
+    m3u8_content += "#EXT-X-MEDIA-SEQUENCE:0\n";
+    m3u8_content += "#EXT-X-PLAYLIST-TYPE:VOD\n";
+    m3u8_content += "#EXT-X-ALLOW-CACHE:YES\n";
+    for segment in segments {
+        m3u8_content += segment.as_str();
+    }
+    m3u8_content += "#EXT-X-ENDLIST\n";
+
+    m3u8_content
+}
+
+fn strip_discontinuity(segment: &str) -> String {
+    segment
+        .lines()
+        .filter(|line| *line != "#EXT-X-DISCONTINUITY")
+        .map(|line| format!("{line}\n"))
+        .collect()
+}
+
+/*Keep the segments from start to end(excluded).*/
+pub fn trim_playlist(m3u8_content: &str, start: usize, end: usize) -> Option<String> {
+    let playlist = parse_playlist(m3u8_content);
+    if start >= end || end > playlist.segments.len() {
+        return None;
+    }
+
+    let mut segments = playlist.segments[start..end].to_vec();
+    segments[0] = strip_discontinuity(&segments[0]);
+
+    Some(generate_playlist(playlist.version, &segments))
+}
+
+/*Join the playlists in order, each one begins with a discontinuity.*/
+pub fn concat_playlists(m3u8_contents: &[String]) -> Option<String> {
+    let mut version = DEFAULT_VERSION;
+    let mut segments = Vec::new();
+
+    for m3u8_content in m3u8_contents {
+        let playlist = parse_playlist(m3u8_content);
+        version = std::cmp::max(version, playlist.version);
+
+        for (index, segment) in playlist.segments.into_iter().enumerate() {
+            let segment = if index > 0 {
+                segment
+            } else if segments.is_empty() {
+                strip_discontinuity(&segment)
+            } else {
+                format!("#EXT-X-DISCONTINUITY\n{}", strip_discontinuity(&segment))
+            };
+            segments.push(segment);
+        }
+    }
+
+    if segments.is_empty() {
+        return None;
+    }
+
+    Some(generate_playlist(version, &segments))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{concat_playlists, trim_playlist};
+
+    const PLAYLIST: &str = "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:10\n\
+        #EXT-X-MEDIA-SEQUENCE:0\n#EXT-X-PLAYLIST-TYPE:VOD\n#EXT-X-ALLOW-CACHE:YES\n\
+        #EXTINF:4.000\nblob_0\n#EXT-X-DISCONTINUITY\n#EXTINF:6.000\nblob_1\n\
+        #EXTINF:2.500\nblob_2\n#EXT-X-ENDLIST\n";
+
+    const HEADER: &str = "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:{}\n\
+        #EXT-X-MEDIA-SEQUENCE:0\n#EXT-X-PLAYLIST-TYPE:VOD\n#EXT-X-ALLOW-CACHE:YES\n";
+
+    fn header(target_duration: u64) -> String {
+        HEADER.replace("{}", &target_duration.to_string())
+    }
+
+    #[test]
+    fn test_trim_playlist() {
+        assert_eq!(
+            trim_playlist(PLAYLIST, 1, 3).unwrap(),
+            header(6) + "#EXTINF:6.000\nblob_1\n#EXTINF:2.500\nblob_2\n#EXT-X-ENDLIST\n"
+        );
+        assert!(trim_playlist(PLAYLIST, 2, 2).is_none());
+        assert!(trim_playlist(PLAYLIST, 0, 4).is_none());
+    }
+
+    #[test]
+    fn test_concat_playlists() {
+        let first = trim_playlist(PLAYLIST, 0, 1).unwrap();
+        let second = trim_playlist(PLAYLIST, 2, 3).unwrap();
+
+        assert_eq!(
+            concat_playlists(&[first, second]).unwrap(),
+            header(4)
+                + "#EXTINF:4.000\nblob_0\n#EXT-X-DISCONTINUITY\n#EXTINF:2.500\nblob_2\n#EXT-X-ENDLIST\n"
+        );
+        assert!(concat_playlists(&[]).is_none());
+    }
+}
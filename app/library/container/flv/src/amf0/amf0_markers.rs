@@ -6,3 +6,5 @@ pub const NULL: u8 = 0x05;
 pub const ECMA_ARRAY: u8 = 0x08;
 pub const OBJECT_END: u8 = 0x09;
 pub const LONG_STRING: u8 = 0x0c;
+/*the value after it is in AMF3*/
+pub const AVMPLUS_OBJECT: u8 = 0x11;
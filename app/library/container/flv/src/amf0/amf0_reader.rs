@@ -1,9 +1,11 @@
 use {
     super::{amf0_markers, errors::Amf0ReadErrorValue, Amf0ReadError, Amf0ValueType},
+    crate::amf3::{amf3_markers, amf3_reader::Amf3Reader},
     byteorder::BigEndian,
-    // bytes::BytesMut,
+    bytes::BytesMut,
     bytesio::bytes_reader::BytesReader,
     indexmap::IndexMap,
+    std::mem,
 };
 
 pub struct Amf0Reader {
@@ -48,6 +50,7 @@ impl Amf0Reader {
             amf0_markers::NULL => self.read_null(),
             amf0_markers::ECMA_ARRAY => self.read_ecma_array(),
             amf0_markers::LONG_STRING => self.read_long_string(),
+            amf0_markers::AVMPLUS_OBJECT => self.read_avmplus_object(),
             _ => Err(Amf0ReadError {
                 value: Amf0ReadErrorValue::UnknownMarker { marker: markers },
             }),
@@ -56,6 +59,10 @@ impl Amf0Reader {
     pub fn read_with_type(&mut self, specified_marker: u8) -> Result<Amf0ValueType, Amf0ReadError> {
         let marker = self.reader.advance_u8()?;
 
+        if marker == amf0_markers::AVMPLUS_OBJECT && self.is_amf3_type(specified_marker)? {
+            return self.read_any();
+        }
+
         if marker != specified_marker {
             return Err(Amf0ReadError {
                 value: Amf0ReadErrorValue::WrongType,
@@ -65,6 +72,35 @@ impl Amf0Reader {
         self.read_any()
     }
 
+    /*the AMF3 value after the AVMPLUS marker has the type of the AMF0 marker*/
+    fn is_amf3_type(&self, specified_marker: u8) -> Result<bool, Amf0ReadError> {
+        let amf3_marker = self.reader.get(1)?;
+
+        let is_matched = match specified_marker {
+            amf0_markers::NUMBER => {
+                amf3_marker == amf3_markers::INTEGER || amf3_marker == amf3_markers::DOUBLE
+            }
+            amf0_markers::STRING => amf3_marker == amf3_markers::STRING,
+            amf0_markers::OBJECT => amf3_marker == amf3_markers::OBJECT,
+            amf0_markers::NULL => {
+                amf3_marker == amf3_markers::NULL || amf3_marker == amf3_markers::UNDEFINED
+            }
+            _ => false,
+        };
+        Ok(is_matched)
+    }
+
+    /*Every AVMPLUS marker starts a new AMF3 context, the reference tables are not
+    shared with the other AMF3 values of the message.*/
+    pub fn read_avmplus_object(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
+        let reader = mem::replace(&mut self.reader, BytesReader::new(BytesMut::new()));
+        let mut amf3_reader = Amf3Reader::new(reader);
+        let result = amf3_reader.read_any();
+        self.reader = amf3_reader.into_reader();
+
+        Ok(Amf0ValueType::from(result?))
+    }
+
     pub fn read_number(&mut self) -> Result<Amf0ValueType, Amf0ReadError> {
         let number = self.reader.read_f64::<BigEndian>()?;
         let value = Amf0ValueType::Number(number);
@@ -177,6 +213,7 @@ mod tests {
     use super::amf0_markers;
     use super::Amf0Reader;
     use super::Amf0ValueType;
+    use crate::amf0::amf0_writer::Amf0Writer;
 
     use bytes::BytesMut;
     use bytesio::bytes_reader::BytesReader;
@@ -368,4 +405,54 @@ mod tests {
 
         assert_eq!(command_obj_raw.unwrap(), Amf0ValueType::Object(properties));
     }
+
+    #[test]
+    fn test_avmplus_object() {
+        let data: [u8; 31] = [
+            0x02, 0x00, 0x07, 0x63, 0x6f, 0x6e, 0x6e, 0x65, 0x63, 0x74, //connect
+            0x11, 0x04, 0x01, //amf3 integer 1
+            0x11, 0x0a, 0x0b, 0x01, //anonymous dynamic object
+            0x07, 0x61, 0x70, 0x70, 0x06, 0x09, 0x6c, 0x69, 0x76, 0x65, //app: live
+            0x01, //end of the dynamic members
+            0x11, 0x01, //amf3 null
+            0x05,
+        ];
+
+        let mut bytes_reader = BytesReader::new(BytesMut::new());
+        bytes_reader.extend_from_slice(&data);
+        let mut amf_reader = Amf0Reader::new(bytes_reader);
+
+        let command_name = amf_reader.read_with_type(amf0_markers::STRING).unwrap();
+        assert_eq!(
+            command_name,
+            Amf0ValueType::UTF8String(String::from("connect"))
+        );
+
+        let transaction_id = amf_reader.read_with_type(amf0_markers::NUMBER).unwrap();
+        assert_eq!(transaction_id, Amf0ValueType::Number(1.0));
+
+        let mut properties = IndexMap::new();
+        properties.insert(
+            String::from("app"),
+            Amf0ValueType::UTF8String(String::from("live")),
+        );
+        let command_obj = amf_reader.read_with_type(amf0_markers::OBJECT).unwrap();
+        assert_eq!(command_obj, Amf0ValueType::Object(properties.clone()));
+
+        assert_eq!(amf_reader.read_all().unwrap().len(), 2);
+
+        let mut amf_writer = Amf0Writer::new();
+        amf_writer.set_amf3_objects(true);
+        amf_writer
+            .write_any(&Amf0ValueType::Object(properties.clone()))
+            .unwrap();
+        let data = amf_writer.extract_current_bytes();
+        assert_eq!(data[0], amf0_markers::AVMPLUS_OBJECT);
+
+        let mut amf_reader = Amf0Reader::new(BytesReader::new(data));
+        assert_eq!(
+            amf_reader.read_any().unwrap(),
+            Amf0ValueType::Object(properties)
+        );
+    }
 }
@@ -1,5 +1,6 @@
 use {
     super::{amf0_markers, errors::Amf0WriteErrorValue, Amf0ValueType, Amf0WriteError},
+    crate::amf3::{amf3_writer::Amf3Writer, Amf3ValueType},
     byteorder::BigEndian,
     bytes::BytesMut,
     bytesio::bytes_writer::BytesWriter,
@@ -9,14 +10,21 @@ use {
 #[derive(Default)]
 pub struct Amf0Writer {
     writer: BytesWriter,
+    /*the objects and the ECMA arrays are written in AMF3 after the AVMPLUS marker*/
+    amf3_objects: bool,
 }
 
 impl Amf0Writer {
     pub fn new() -> Self {
         Self {
             writer: BytesWriter::new(),
+            amf3_objects: false,
         }
     }
+
+    pub fn set_amf3_objects(&mut self, amf3_objects: bool) {
+        self.amf3_objects = amf3_objects;
+    }
     pub fn write_anys(&mut self, values: &Vec<Amf0ValueType>) -> Result<(), Amf0WriteError> {
         for val in values {
             self.write_any(val)?;
@@ -77,6 +85,10 @@ impl Amf0Writer {
         &mut self,
         properties: &IndexMap<String, Amf0ValueType>,
     ) -> Result<(), Amf0WriteError> {
+        if self.amf3_objects {
+            return self.write_avmplus_object(&Amf0ValueType::Object(properties.clone()));
+        }
+
         self.writer.write_u8(amf0_markers::OBJECT)?;
 
         for (key, value) in properties {
@@ -93,6 +105,10 @@ impl Amf0Writer {
         &mut self,
         properties: &IndexMap<String, Amf0ValueType>,
     ) -> Result<(), Amf0WriteError> {
+        if self.amf3_objects {
+            return self.write_avmplus_object(&Amf0ValueType::EcmaArray(properties.clone()));
+        }
+
         self.writer.write_u8(amf0_markers::ECMA_ARRAY)?;
         self.writer
             .write_u32::<BigEndian>(properties.len() as u32)?;
@@ -107,6 +123,16 @@ impl Amf0Writer {
         Ok(())
     }
 
+    /*every AVMPLUS marker starts a new AMF3 context*/
+    pub fn write_avmplus_object(&mut self, value: &Amf0ValueType) -> Result<(), Amf0WriteError> {
+        let mut amf3_writer = Amf3Writer::new();
+        amf3_writer.write_any(&Amf3ValueType::from(value))?;
+
+        self.writer.write_u8(amf0_markers::AVMPLUS_OBJECT)?;
+        self.writer.write(&amf3_writer.extract_current_bytes())?;
+        Ok(())
+    }
+
     // pub async fn flush(&mut self) -> Result<(), Amf0WriteError> {
     //     self.writer.flush()?;
     // }
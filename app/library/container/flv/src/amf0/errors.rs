@@ -1,4 +1,5 @@
 use {
+    crate::amf3::{Amf3ReadError, Amf3WriteError},
     bytesio::bytes_errors::{BytesReadError, BytesWriteError},
     failure::{Backtrace, Fail},
     std::{
@@ -16,6 +17,8 @@ pub enum Amf0ReadErrorValue {
     BytesReadError(BytesReadError),
     #[fail(display = "wrong type")]
     WrongType,
+    #[fail(display = "amf3 read error: {}", _0)]
+    Amf3ReadError(#[cause] Amf3ReadError),
 }
 
 #[derive(Debug)]
@@ -39,6 +42,14 @@ impl From<BytesReadError> for Amf0ReadError {
     }
 }
 
+impl From<Amf3ReadError> for Amf0ReadError {
+    fn from(error: Amf3ReadError) -> Self {
+        Amf0ReadError {
+            value: Amf0ReadErrorValue::Amf3ReadError(error),
+        }
+    }
+}
+
 #[derive(Debug, Fail)]
 pub enum Amf0WriteErrorValue {
     #[fail(display = "normal string too long")]
@@ -47,6 +58,8 @@ pub enum Amf0WriteErrorValue {
     BufferWriteError(io::Error),
     #[fail(display = "bytes write error")]
     BytesWriteError(BytesWriteError),
+    #[fail(display = "amf3 write error: {}", _0)]
+    Amf3WriteError(#[cause] Amf3WriteError),
 }
 
 #[derive(Debug)]
@@ -70,6 +83,14 @@ impl From<BytesWriteError> for Amf0WriteError {
     }
 }
 
+impl From<Amf3WriteError> for Amf0WriteError {
+    fn from(error: Amf3WriteError) -> Self {
+        Amf0WriteError {
+            value: Amf0WriteErrorValue::Amf3WriteError(error),
+        }
+    }
+}
+
 impl fmt::Display for Amf0ReadError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Display::fmt(&self.value, f)
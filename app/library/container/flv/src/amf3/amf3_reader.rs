@@ -0,0 +1,629 @@
+use {
+    super::{amf3_markers, errors::Amf3ReadErrorValue, Amf3ReadError, Amf3Trait, Amf3ValueType},
+    byteorder::BigEndian,
+    bytesio::bytes_reader::BytesReader,
+    indexmap::IndexMap,
+};
+
+const MAX_DEPTH: usize = 64;
+/*A reference is resolved by a copy of the value, so a few bytes referring to the
+former values again and again would expand exponentially. Every value counts as a
+node, so does every byte of a string and every item of a vector or byte array.*/
+const MAX_NODES: usize = 1 << 20;
+
+/*The strings, the complex values and the traits which have been read are kept in
+the reference tables, a value can refer to the former ones by the index.*/
+pub struct Amf3Reader {
+    reader: BytesReader,
+    strings: Vec<String>,
+    /*the values with their numbers of nodes*/
+    objects: Vec<(Amf3ValueType, usize)>,
+    traits: Vec<Amf3Trait>,
+    depth: usize,
+    nodes: usize,
+}
+
+impl Amf3Reader {
+    pub fn new(reader: BytesReader) -> Self {
+        Self {
+            reader,
+            strings: Vec::new(),
+            objects: Vec::new(),
+            traits: Vec::new(),
+            depth: 0,
+            nodes: 0,
+        }
+    }
+
+    pub fn into_reader(self) -> BytesReader {
+        self.reader
+    }
+
+    pub fn read_all(&mut self) -> Result<Vec<Amf3ValueType>, Amf3ReadError> {
+        let mut results = vec![];
+
+        while !self.reader.is_empty() {
+            results.push(self.read_any()?);
+        }
+        Ok(results)
+    }
+
+    pub fn read_any(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(Amf3ReadError {
+                value: Amf3ReadErrorValue::TooDeep {
+                    max_depth: MAX_DEPTH,
+                },
+            });
+        }
+        self.add_nodes(1)?;
+
+        self.depth += 1;
+        let value = self.read_value();
+        self.depth -= 1;
+        value
+    }
+
+    fn add_nodes(&mut self, count: usize) -> Result<(), Amf3ReadError> {
+        self.nodes += count;
+        if self.nodes > MAX_NODES {
+            return Err(Amf3ReadError {
+                value: Amf3ReadErrorValue::TooManyNodes {
+                    max_nodes: MAX_NODES,
+                },
+            });
+        }
+        Ok(())
+    }
+
+    fn read_value(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        let marker = self.reader.read_u8()?;
+
+        match marker {
+            amf3_markers::UNDEFINED => Ok(Amf3ValueType::Undefined),
+            amf3_markers::NULL => Ok(Amf3ValueType::Null),
+            amf3_markers::FALSE => Ok(Amf3ValueType::Boolean(false)),
+            amf3_markers::TRUE => Ok(Amf3ValueType::Boolean(true)),
+            amf3_markers::INTEGER => self.read_integer(),
+            amf3_markers::DOUBLE => self.read_double(),
+            amf3_markers::STRING => Ok(Amf3ValueType::UTF8String(self.read_raw_string()?)),
+            amf3_markers::XML_DOC => self.read_xml(true),
+            amf3_markers::DATE => self.read_date(),
+            amf3_markers::ARRAY => self.read_array(),
+            amf3_markers::OBJECT => self.read_object(),
+            amf3_markers::XML => self.read_xml(false),
+            amf3_markers::BYTE_ARRAY => self.read_byte_array(),
+            amf3_markers::VECTOR_INT | amf3_markers::VECTOR_UINT | amf3_markers::VECTOR_DOUBLE => {
+                self.read_number_vector(marker)
+            }
+            amf3_markers::VECTOR_OBJECT => self.read_object_vector(),
+            amf3_markers::DICTIONARY => self.read_dictionary(),
+            _ => Err(Amf3ReadError {
+                value: Amf3ReadErrorValue::UnknownMarker { marker },
+            }),
+        }
+    }
+
+    /*the first three bytes carry 7 bits with a continuation flag, the fourth
+    byte carries 8 bits*/
+    pub fn read_u29(&mut self) -> Result<u32, Amf3ReadError> {
+        let mut value: u32 = 0;
+
+        for _ in 0..3 {
+            let byte = self.reader.read_u8()?;
+            value = (value << 7) | (byte & 0x7f) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        let byte = self.reader.read_u8()?;
+        Ok((value << 8) | byte as u32)
+    }
+
+    pub fn read_integer(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        let value = self.read_u29()?;
+        /*sign extend the 29 bits*/
+        let value = ((value << 3) as i32) >> 3;
+        Ok(Amf3ValueType::Integer(value))
+    }
+
+    pub fn read_double(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        let value = self.reader.read_f64::<BigEndian>()?;
+        Ok(Amf3ValueType::Double(value))
+    }
+
+    /*the empty strings are never sent by reference*/
+    pub fn read_raw_string(&mut self) -> Result<String, Amf3ReadError> {
+        let header = self.read_u29()?;
+        let index = header >> 1;
+
+        if header & 0x01 == 0 {
+            let length =
+                self.strings
+                    .get(index as usize)
+                    .map(String::len)
+                    .ok_or(Amf3ReadError {
+                        value: Amf3ReadErrorValue::ReferenceNotFound { index },
+                    })?;
+            self.add_nodes(length)?;
+            return Ok(self.strings[index as usize].clone());
+        }
+
+        self.add_nodes(index as usize)?;
+        let value = self.read_utf8(index as usize)?;
+        if !value.is_empty() {
+            self.strings.push(value.clone());
+        }
+        Ok(value)
+    }
+
+    fn read_utf8(&mut self, length: usize) -> Result<String, Amf3ReadError> {
+        let bytes = self.reader.read_bytes(length)?;
+        Ok(String::from_utf8(bytes.to_vec())?)
+    }
+
+    /*returns the referenced value or the length of an inline value*/
+    fn read_object_header(&mut self) -> Result<Result<Amf3ValueType, u32>, Amf3ReadError> {
+        let header = self.read_u29()?;
+        let index = header >> 1;
+
+        if header & 0x01 == 0 {
+            let nodes = self
+                .objects
+                .get(index as usize)
+                .map(|(_, nodes)| *nodes)
+                .ok_or(Amf3ReadError {
+                    value: Amf3ReadErrorValue::ReferenceNotFound { index },
+                })?;
+            /*the value itself is counted by read_any*/
+            self.add_nodes(nodes - 1)?;
+            return Ok(Ok(self.objects[index as usize].0.clone()));
+        }
+
+        Ok(Err(index))
+    }
+
+    /*the sized values are read at once, the length is the number of their nodes*/
+    fn push_object(&mut self, value: Amf3ValueType, length: u32) -> Result<(), Amf3ReadError> {
+        self.add_nodes(length as usize)?;
+        self.objects.push((value, length as usize + 1));
+        Ok(())
+    }
+
+    /*the index of a complex value is taken before its members are read, the
+    members referring to the value itself get a null*/
+    fn reserve_object(&mut self) -> (usize, usize) {
+        self.objects.push((Amf3ValueType::Null, 1));
+        (self.objects.len() - 1, self.nodes)
+    }
+
+    fn save_object(&mut self, reserved: (usize, usize), value: Amf3ValueType) -> Amf3ValueType {
+        let (index, nodes) = reserved;
+        self.objects[index] = (value.clone(), self.nodes - nodes + 1);
+        value
+    }
+
+    pub fn read_xml(&mut self, is_document: bool) -> Result<Amf3ValueType, Amf3ReadError> {
+        let length = match self.read_object_header()? {
+            Ok(value) => return Ok(value),
+            Err(length) => length,
+        };
+
+        let value = self.read_utf8(length as usize)?;
+        let value = if is_document {
+            Amf3ValueType::XmlDocument(value)
+        } else {
+            Amf3ValueType::Xml(value)
+        };
+        self.push_object(value.clone(), length)?;
+        Ok(value)
+    }
+
+    pub fn read_date(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        if let Ok(value) = self.read_object_header()? {
+            return Ok(value);
+        }
+
+        let value = Amf3ValueType::Date(self.reader.read_f64::<BigEndian>()?);
+        self.push_object(value.clone(), 0)?;
+        Ok(value)
+    }
+
+    pub fn read_array(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        let length = match self.read_object_header()? {
+            Ok(value) => return Ok(value),
+            Err(length) => length,
+        };
+        let reserved = self.reserve_object();
+
+        let mut associative = IndexMap::new();
+        loop {
+            let key = self.read_raw_string()?;
+            if key.is_empty() {
+                break;
+            }
+            let value = self.read_any()?;
+            associative.insert(key, value);
+        }
+
+        let mut dense = Vec::new();
+        for _ in 0..length {
+            dense.push(self.read_any()?);
+        }
+
+        Ok(self.save_object(reserved, Amf3ValueType::Array { associative, dense }))
+    }
+
+    fn read_trait(&mut self, header: u32) -> Result<Amf3Trait, Amf3ReadError> {
+        if header & 0x01 == 0 {
+            let index = header >> 1;
+            return self
+                .traits
+                .get(index as usize)
+                .cloned()
+                .ok_or(Amf3ReadError {
+                    value: Amf3ReadErrorValue::ReferenceNotFound { index },
+                });
+        }
+
+        let is_externalizable = header & 0x02 != 0;
+        let is_dynamic = header & 0x04 != 0;
+        let class_name = self.read_raw_string()?;
+        /*the data of an externalizable class is in the format of the class*/
+        if is_externalizable {
+            return Err(Amf3ReadError {
+                value: Amf3ReadErrorValue::ExternalizableNotSupported { class_name },
+            });
+        }
+
+        let mut sealed_names = Vec::new();
+        for _ in 0..header >> 3 {
+            sealed_names.push(self.read_raw_string()?);
+        }
+
+        let amf3_trait = Amf3Trait {
+            class_name,
+            is_dynamic,
+            is_externalizable,
+            sealed_names,
+        };
+        self.traits.push(amf3_trait.clone());
+        Ok(amf3_trait)
+    }
+
+    pub fn read_object(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        let header = match self.read_object_header()? {
+            Ok(value) => return Ok(value),
+            Err(header) => header,
+        };
+        let amf3_trait = self.read_trait(header)?;
+        let reserved = self.reserve_object();
+
+        let mut sealed = IndexMap::new();
+        for name in amf3_trait.sealed_names {
+            let value = self.read_any()?;
+            sealed.insert(name, value);
+        }
+
+        let dynamic = if amf3_trait.is_dynamic {
+            let mut dynamic = IndexMap::new();
+            loop {
+                let key = self.read_raw_string()?;
+                if key.is_empty() {
+                    break;
+                }
+                let value = self.read_any()?;
+                dynamic.insert(key, value);
+            }
+            Some(dynamic)
+        } else {
+            None
+        };
+
+        Ok(self.save_object(
+            reserved,
+            Amf3ValueType::Object {
+                class_name: amf3_trait.class_name,
+                sealed,
+                dynamic,
+            },
+        ))
+    }
+
+    pub fn read_byte_array(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        let length = match self.read_object_header()? {
+            Ok(value) => return Ok(value),
+            Err(length) => length,
+        };
+
+        let bytes = self.reader.read_bytes(length as usize)?;
+        let value = Amf3ValueType::ByteArray(bytes.to_vec());
+        self.push_object(value.clone(), length)?;
+        Ok(value)
+    }
+
+    pub fn read_number_vector(&mut self, marker: u8) -> Result<Amf3ValueType, Amf3ReadError> {
+        let length = match self.read_object_header()? {
+            Ok(value) => return Ok(value),
+            Err(length) => length,
+        };
+        let fixed = self.reader.read_u8()? != 0;
+
+        let value = match marker {
+            amf3_markers::VECTOR_INT => {
+                let mut items = Vec::new();
+                for _ in 0..length {
+                    items.push(self.reader.read_u32::<BigEndian>()? as i32);
+                }
+                Amf3ValueType::VectorInt { fixed, items }
+            }
+            amf3_markers::VECTOR_UINT => {
+                let mut items = Vec::new();
+                for _ in 0..length {
+                    items.push(self.reader.read_u32::<BigEndian>()?);
+                }
+                Amf3ValueType::VectorUint { fixed, items }
+            }
+            _ => {
+                let mut items = Vec::new();
+                for _ in 0..length {
+                    items.push(self.reader.read_f64::<BigEndian>()?);
+                }
+                Amf3ValueType::VectorDouble { fixed, items }
+            }
+        };
+        self.push_object(value.clone(), length)?;
+        Ok(value)
+    }
+
+    pub fn read_object_vector(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        let length = match self.read_object_header()? {
+            Ok(value) => return Ok(value),
+            Err(length) => length,
+        };
+        let fixed = self.reader.read_u8()? != 0;
+        let type_name = self.read_raw_string()?;
+        let reserved = self.reserve_object();
+
+        let mut items = Vec::new();
+        for _ in 0..length {
+            items.push(self.read_any()?);
+        }
+
+        Ok(self.save_object(
+            reserved,
+            Amf3ValueType::VectorObject {
+                fixed,
+                type_name,
+                items,
+            },
+        ))
+    }
+
+    pub fn read_dictionary(&mut self) -> Result<Amf3ValueType, Amf3ReadError> {
+        let length = match self.read_object_header()? {
+            Ok(value) => return Ok(value),
+            Err(length) => length,
+        };
+        let weak_keys = self.reader.read_u8()? != 0;
+        let reserved = self.reserve_object();
+
+        let mut entries = Vec::new();
+        for _ in 0..length {
+            let key = self.read_any()?;
+            let value = self.read_any()?;
+            entries.push((key, value));
+        }
+
+        Ok(self.save_object(reserved, Amf3ValueType::Dictionary { weak_keys, entries }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::super::amf3_writer::Amf3Writer;
+    use super::Amf3ReadErrorValue;
+    use super::Amf3Reader;
+    use super::Amf3ValueType;
+
+    use bytes::BytesMut;
+    use bytesio::bytes_reader::BytesReader;
+
+    use indexmap::IndexMap;
+
+    fn read_all(data: &[u8]) -> Vec<Amf3ValueType> {
+        let mut amf_reader = Amf3Reader::new(BytesReader::new(BytesMut::from(data)));
+        amf_reader.read_all().unwrap()
+    }
+
+    #[test]
+    fn test_amf3_integer() {
+        let data: [u8; 14] = [
+            0x04, 0x00, //0
+            0x04, 0x7f, //127
+            0x04, 0x81, 0x00, //128
+            0x04, 0xbf, 0xff, 0xff, 0xff, //0x0fffffff
+            0x04, 0x00,
+        ];
+
+        let values = read_all(&data);
+        assert_eq!(
+            values,
+            vec![
+                Amf3ValueType::Integer(0),
+                Amf3ValueType::Integer(127),
+                Amf3ValueType::Integer(128),
+                Amf3ValueType::Integer(0x0fffffff),
+                Amf3ValueType::Integer(0),
+            ]
+        );
+
+        let values = read_all(&[0x04, 0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(values, vec![Amf3ValueType::Integer(-1)]);
+    }
+
+    #[test]
+    fn test_amf3_references() {
+        let data: [u8; 30] = [
+            //"app"
+            0x06, 0x07, 0x61, 0x70, 0x70, //
+            //the reference of "app"
+            0x06, 0x00, //
+            //an anonymous dynamic object with the sealed member "app"
+            0x0a, 0x1b, 0x01, 0x00, 0x06, 0x09, 0x6c, 0x69, 0x76, 0x65, 0x01, //
+            //the same trait
+            0x0a, 0x01, 0x06, 0x02, 0x03, 0x61, 0x06, 0x04, 0x01, //
+            //the reference of the first object
+            0x0a, 0x00, //
+            0x01,
+        ];
+
+        let values = read_all(&data);
+
+        let mut sealed = IndexMap::new();
+        sealed.insert(
+            String::from("app"),
+            Amf3ValueType::UTF8String(String::from("live")),
+        );
+        let first = Amf3ValueType::Object {
+            class_name: String::new(),
+            sealed,
+            dynamic: Some(IndexMap::new()),
+        };
+
+        let mut sealed = IndexMap::new();
+        sealed.insert(
+            String::from("app"),
+            Amf3ValueType::UTF8String(String::from("live")),
+        );
+        let mut dynamic = IndexMap::new();
+        dynamic.insert(
+            String::from("a"),
+            Amf3ValueType::UTF8String(String::from("a")),
+        );
+        let second = Amf3ValueType::Object {
+            class_name: String::new(),
+            sealed,
+            dynamic: Some(dynamic),
+        };
+
+        assert_eq!(
+            values,
+            vec![
+                Amf3ValueType::UTF8String(String::from("app")),
+                Amf3ValueType::UTF8String(String::from("app")),
+                first.clone(),
+                second,
+                first,
+                Amf3ValueType::Null,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_amf3_write_and_read() {
+        let mut properties = IndexMap::new();
+        properties.insert(
+            String::from("code"),
+            Amf3ValueType::UTF8String(String::from("NetStream.Play.Start")),
+        );
+        properties.insert(String::from("level"), Amf3ValueType::Integer(-100));
+        properties.insert(String::from("time"), Amf3ValueType::Double(0.5));
+        properties.insert(String::from("big"), Amf3ValueType::Integer(1 << 28));
+        let object = Amf3ValueType::Object {
+            class_name: String::new(),
+            sealed: IndexMap::new(),
+            dynamic: Some(properties),
+        };
+
+        let mut associative = IndexMap::new();
+        associative.insert(
+            String::from("code"),
+            Amf3ValueType::UTF8String(String::from("code")),
+        );
+        let values = vec![
+            Amf3ValueType::UTF8String(String::from("onStatus")),
+            Amf3ValueType::UTF8String(String::new()),
+            object.clone(),
+            object,
+            Amf3ValueType::Array {
+                associative,
+                dense: vec![Amf3ValueType::Boolean(true), Amf3ValueType::Undefined],
+            },
+            Amf3ValueType::Date(1.0e12),
+            Amf3ValueType::ByteArray(vec![1, 2, 3]),
+            Amf3ValueType::VectorInt {
+                fixed: false,
+                items: vec![-1, 2],
+            },
+            Amf3ValueType::VectorObject {
+                fixed: true,
+                type_name: String::from("code"),
+                items: vec![Amf3ValueType::Null],
+            },
+            Amf3ValueType::Dictionary {
+                weak_keys: false,
+                entries: vec![(
+                    Amf3ValueType::Integer(1),
+                    Amf3ValueType::XmlDocument(String::from("<a/>")),
+                )],
+            },
+        ];
+
+        let mut amf_writer = Amf3Writer::new();
+        amf_writer.write_anys(&values).unwrap();
+        let data = amf_writer.extract_current_bytes();
+
+        let mut expected = values;
+        /*the integers out of 29 bits are written as doubles*/
+        if let Amf3ValueType::Object {
+            dynamic: Some(properties),
+            ..
+        } = &mut expected[2]
+        {
+            properties.insert(String::from("big"), Amf3ValueType::Double((1 << 28) as f64));
+        }
+        expected[3] = expected[2].clone();
+
+        assert_eq!(read_all(&data), expected);
+    }
+
+    #[test]
+    fn test_amf3_reference_expansion() {
+        /*every array holds the former one twice, the copies would double with
+        every array*/
+        let mut data = vec![0x09, 0x05, 0x01, 0x01, 0x01];
+        for index in 0..40u8 {
+            data.extend_from_slice(&[0x09, 0x05, 0x01, 0x09, index << 1, 0x09, index << 1]);
+        }
+
+        let mut amf_reader = Amf3Reader::new(BytesReader::new(BytesMut::from(&data[..])));
+        let err = amf_reader.read_all().unwrap_err();
+        assert!(matches!(err.value, Amf3ReadErrorValue::TooManyNodes { .. }));
+
+        /*an array holding itself gets a null*/
+        let values = read_all(&[0x09, 0x03, 0x01, 0x09, 0x00]);
+        assert_eq!(
+            values,
+            vec![Amf3ValueType::Array {
+                associative: IndexMap::new(),
+                dense: vec![Amf3ValueType::Null],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_amf3_nesting_depth() {
+        let mut data = Vec::new();
+        for _ in 0..100 {
+            data.extend_from_slice(&[0x09, 0x03, 0x01]);
+        }
+        data.push(0x01);
+
+        let mut amf_reader = Amf3Reader::new(BytesReader::new(BytesMut::from(&data[..])));
+        let err = amf_reader.read_all().unwrap_err();
+        assert!(matches!(err.value, Amf3ReadErrorValue::TooDeep { .. }));
+    }
+}
@@ -0,0 +1,294 @@
+use {
+    super::{
+        amf3_markers,
+        define::{INTEGER_MAX, INTEGER_MIN, LENGTH_MAX},
+        errors::Amf3WriteErrorValue,
+        Amf3Trait, Amf3ValueType, Amf3WriteError,
+    },
+    byteorder::BigEndian,
+    bytes::BytesMut,
+    bytesio::bytes_writer::BytesWriter,
+    indexmap::IndexMap,
+    std::collections::HashMap,
+};
+
+/*The strings and the traits are written by reference after the first time, the
+complex values are always written inline, they are not compared with the former
+ones.*/
+#[derive(Default)]
+pub struct Amf3Writer {
+    writer: BytesWriter,
+    strings: HashMap<String, usize>,
+    traits: HashMap<Amf3Trait, usize>,
+}
+
+impl Amf3Writer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write_anys(&mut self, values: &[Amf3ValueType]) -> Result<(), Amf3WriteError> {
+        for val in values {
+            self.write_any(val)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_any(&mut self, value: &Amf3ValueType) -> Result<(), Amf3WriteError> {
+        match value {
+            Amf3ValueType::Undefined => self.write_marker(amf3_markers::UNDEFINED),
+            Amf3ValueType::Null => self.write_marker(amf3_markers::NULL),
+            Amf3ValueType::Boolean(false) => self.write_marker(amf3_markers::FALSE),
+            Amf3ValueType::Boolean(true) => self.write_marker(amf3_markers::TRUE),
+            Amf3ValueType::Integer(val) => self.write_integer(*val),
+            Amf3ValueType::Double(val) => self.write_double(*val),
+            Amf3ValueType::UTF8String(val) => {
+                self.write_marker(amf3_markers::STRING)?;
+                self.write_raw_string(val)
+            }
+            Amf3ValueType::XmlDocument(val) => self.write_xml(amf3_markers::XML_DOC, val),
+            Amf3ValueType::Date(val) => self.write_date(*val),
+            Amf3ValueType::Array { associative, dense } => self.write_array(associative, dense),
+            Amf3ValueType::Object {
+                class_name,
+                sealed,
+                dynamic,
+            } => self.write_object(class_name, sealed, dynamic.as_ref()),
+            Amf3ValueType::Xml(val) => self.write_xml(amf3_markers::XML, val),
+            Amf3ValueType::ByteArray(val) => self.write_byte_array(val),
+            Amf3ValueType::VectorInt { fixed, items } => {
+                self.write_vector_header(amf3_markers::VECTOR_INT, items.len(), *fixed)?;
+                for item in items {
+                    self.writer.write_u32::<BigEndian>(*item as u32)?;
+                }
+                Ok(())
+            }
+            Amf3ValueType::VectorUint { fixed, items } => {
+                self.write_vector_header(amf3_markers::VECTOR_UINT, items.len(), *fixed)?;
+                for item in items {
+                    self.writer.write_u32::<BigEndian>(*item)?;
+                }
+                Ok(())
+            }
+            Amf3ValueType::VectorDouble { fixed, items } => {
+                self.write_vector_header(amf3_markers::VECTOR_DOUBLE, items.len(), *fixed)?;
+                for item in items {
+                    self.writer.write_f64::<BigEndian>(*item)?;
+                }
+                Ok(())
+            }
+            Amf3ValueType::VectorObject {
+                fixed,
+                type_name,
+                items,
+            } => {
+                self.write_vector_header(amf3_markers::VECTOR_OBJECT, items.len(), *fixed)?;
+                self.write_raw_string(type_name)?;
+                for item in items {
+                    self.write_any(item)?;
+                }
+                Ok(())
+            }
+            Amf3ValueType::Dictionary { weak_keys, entries } => {
+                self.write_vector_header(amf3_markers::DICTIONARY, entries.len(), *weak_keys)?;
+                for (key, value) in entries {
+                    self.write_any(key)?;
+                    self.write_any(value)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn write_marker(&mut self, marker: u8) -> Result<(), Amf3WriteError> {
+        self.writer.write_u8(marker)?;
+        Ok(())
+    }
+
+    pub fn write_u29(&mut self, value: u32) -> Result<(), Amf3WriteError> {
+        if value < 0x80 {
+            self.writer.write_u8(value as u8)?;
+        } else if value < 0x4000 {
+            self.writer.write_u8(((value >> 7) | 0x80) as u8)?;
+            self.writer.write_u8((value & 0x7f) as u8)?;
+        } else if value < 0x200000 {
+            self.writer.write_u8(((value >> 14) | 0x80) as u8)?;
+            self.writer.write_u8((((value >> 7) & 0x7f) | 0x80) as u8)?;
+            self.writer.write_u8((value & 0x7f) as u8)?;
+        } else if value < 0x20000000 {
+            self.writer.write_u8(((value >> 22) | 0x80) as u8)?;
+            self.writer
+                .write_u8((((value >> 15) & 0x7f) | 0x80) as u8)?;
+            self.writer.write_u8((((value >> 8) & 0x7f) | 0x80) as u8)?;
+            self.writer.write_u8((value & 0xff) as u8)?;
+        } else {
+            return Err(Amf3WriteError {
+                value: Amf3WriteErrorValue::ValueTooLong,
+            });
+        }
+
+        Ok(())
+    }
+
+    /*the length of an inline value with the flag bit*/
+    fn write_inline_length(&mut self, length: usize) -> Result<(), Amf3WriteError> {
+        if length > LENGTH_MAX {
+            return Err(Amf3WriteError {
+                value: Amf3WriteErrorValue::ValueTooLong,
+            });
+        }
+        self.write_u29(((length as u32) << 1) | 0x01)
+    }
+
+    /*the integers out of 29 bits are written as doubles*/
+    pub fn write_integer(&mut self, value: i32) -> Result<(), Amf3WriteError> {
+        if !(INTEGER_MIN..=INTEGER_MAX).contains(&value) {
+            return self.write_double(value as f64);
+        }
+
+        self.writer.write_u8(amf3_markers::INTEGER)?;
+        self.write_u29(value as u32 & 0x1fffffff)
+    }
+
+    pub fn write_double(&mut self, value: f64) -> Result<(), Amf3WriteError> {
+        self.writer.write_u8(amf3_markers::DOUBLE)?;
+        self.writer.write_f64::<BigEndian>(value)?;
+        Ok(())
+    }
+
+    /*the empty strings are never sent by reference*/
+    pub fn write_raw_string(&mut self, value: &str) -> Result<(), Amf3WriteError> {
+        if let Some(index) = self.strings.get(value) {
+            return self.write_u29((*index as u32) << 1);
+        }
+
+        self.write_inline_length(value.len())?;
+        self.writer.write(value.as_bytes())?;
+
+        if !value.is_empty() {
+            let index = self.strings.len();
+            self.strings.insert(value.to_string(), index);
+        }
+        Ok(())
+    }
+
+    pub fn write_xml(&mut self, marker: u8, value: &str) -> Result<(), Amf3WriteError> {
+        self.writer.write_u8(marker)?;
+        self.write_inline_length(value.len())?;
+        self.writer.write(value.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn write_date(&mut self, value: f64) -> Result<(), Amf3WriteError> {
+        self.writer.write_u8(amf3_markers::DATE)?;
+        self.write_u29(0x01)?;
+        self.writer.write_f64::<BigEndian>(value)?;
+        Ok(())
+    }
+
+    pub fn write_array(
+        &mut self,
+        associative: &IndexMap<String, Amf3ValueType>,
+        dense: &[Amf3ValueType],
+    ) -> Result<(), Amf3WriteError> {
+        self.writer.write_u8(amf3_markers::ARRAY)?;
+        self.write_inline_length(dense.len())?;
+
+        self.write_properties(associative)?;
+        for value in dense {
+            self.write_any(value)?;
+        }
+        Ok(())
+    }
+
+    /*the dynamic members and the associative members end with an empty key*/
+    fn write_properties(
+        &mut self,
+        properties: &IndexMap<String, Amf3ValueType>,
+    ) -> Result<(), Amf3WriteError> {
+        for (key, value) in properties {
+            self.write_raw_string(key)?;
+            self.write_any(value)?;
+        }
+        self.write_raw_string("")
+    }
+
+    pub fn write_object(
+        &mut self,
+        class_name: &str,
+        sealed: &IndexMap<String, Amf3ValueType>,
+        dynamic: Option<&IndexMap<String, Amf3ValueType>>,
+    ) -> Result<(), Amf3WriteError> {
+        self.writer.write_u8(amf3_markers::OBJECT)?;
+
+        let amf3_trait = Amf3Trait {
+            class_name: class_name.to_string(),
+            is_dynamic: dynamic.is_some(),
+            is_externalizable: false,
+            sealed_names: sealed.keys().cloned().collect(),
+        };
+
+        if let Some(index) = self.traits.get(&amf3_trait) {
+            self.write_u29(((*index as u32) << 2) | 0x01)?;
+        } else {
+            if sealed.len() > LENGTH_MAX >> 3 {
+                return Err(Amf3WriteError {
+                    value: Amf3WriteErrorValue::ValueTooLong,
+                });
+            }
+            let header = ((sealed.len() as u32) << 4) | ((dynamic.is_some() as u32) << 3) | 0x03;
+            self.write_u29(header)?;
+            self.write_raw_string(class_name)?;
+            for name in sealed.keys() {
+                self.write_raw_string(name)?;
+            }
+
+            let index = self.traits.len();
+            self.traits.insert(amf3_trait, index);
+        }
+
+        for value in sealed.values() {
+            self.write_any(value)?;
+        }
+        if let Some(dynamic) = dynamic {
+            self.write_properties(dynamic)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_byte_array(&mut self, value: &[u8]) -> Result<(), Amf3WriteError> {
+        self.writer.write_u8(amf3_markers::BYTE_ARRAY)?;
+        self.write_inline_length(value.len())?;
+        self.writer.write(value)?;
+        Ok(())
+    }
+
+    /*the vectors and the dictionaries have a flag byte after the length*/
+    fn write_vector_header(
+        &mut self,
+        marker: u8,
+        length: usize,
+        flag: bool,
+    ) -> Result<(), Amf3WriteError> {
+        self.writer.write_u8(marker)?;
+        self.write_inline_length(length)?;
+        self.writer.write_u8(flag as u8)?;
+        Ok(())
+    }
+
+    pub fn extract_current_bytes(&mut self) -> BytesMut {
+        self.writer.extract_current_bytes()
+    }
+
+    pub fn get_current_bytes(&mut self) -> BytesMut {
+        self.writer.get_current_bytes()
+    }
+
+    pub fn len(&self) -> usize {
+        self.writer.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -0,0 +1,187 @@
+use {crate::amf0::define::Amf0ValueType, indexmap::IndexMap};
+
+/*the integers are 29 bits signed, the others are written as doubles*/
+pub const INTEGER_MIN: i32 = -(1 << 28);
+pub const INTEGER_MAX: i32 = (1 << 28) - 1;
+/*the lengths of the inline values share the U29 with a flag bit*/
+pub const LENGTH_MAX: usize = (1 << 28) - 1;
+
+/*The class of an object, an anonymous object has an empty class name.*/
+#[derive(PartialEq, Eq, Hash, Clone, Debug, Default)]
+pub struct Amf3Trait {
+    pub class_name: String,
+    pub is_dynamic: bool,
+    pub is_externalizable: bool,
+    pub sealed_names: Vec<String>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum Amf3ValueType {
+    Undefined,
+    Null,
+    Boolean(bool),
+    Integer(i32),
+    Double(f64),
+    UTF8String(String),
+    XmlDocument(String),
+    /*milliseconds since the epoch in UTC*/
+    Date(f64),
+    Array {
+        associative: IndexMap<String, Amf3ValueType>,
+        dense: Vec<Amf3ValueType>,
+    },
+    /*the sealed members are in the order of the trait, the dynamic members are
+    None if the class is not dynamic*/
+    Object {
+        class_name: String,
+        sealed: IndexMap<String, Amf3ValueType>,
+        dynamic: Option<IndexMap<String, Amf3ValueType>>,
+    },
+    Xml(String),
+    ByteArray(Vec<u8>),
+    VectorInt {
+        fixed: bool,
+        items: Vec<i32>,
+    },
+    VectorUint {
+        fixed: bool,
+        items: Vec<u32>,
+    },
+    VectorDouble {
+        fixed: bool,
+        items: Vec<f64>,
+    },
+    VectorObject {
+        fixed: bool,
+        type_name: String,
+        items: Vec<Amf3ValueType>,
+    },
+    Dictionary {
+        weak_keys: bool,
+        entries: Vec<(Amf3ValueType, Amf3ValueType)>,
+    },
+}
+
+fn indexed_array(
+    items: impl IntoIterator<Item = Amf0ValueType>,
+) -> IndexMap<String, Amf0ValueType> {
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(index, item)| (index.to_string(), item))
+        .collect()
+}
+
+fn amf0_properties(properties: IndexMap<String, Amf3ValueType>) -> IndexMap<String, Amf0ValueType> {
+    properties
+        .into_iter()
+        .map(|(key, value)| (key, Amf0ValueType::from(value)))
+        .collect()
+}
+
+/*The AMF0 values have no binaries, vectors and dates, the arrays, vectors and byte
+arrays become ECMA arrays keyed by the indexes and the dates become numbers.*/
+impl From<Amf3ValueType> for Amf0ValueType {
+    fn from(value: Amf3ValueType) -> Self {
+        match value {
+            Amf3ValueType::Undefined | Amf3ValueType::Null => Amf0ValueType::Null,
+            Amf3ValueType::Boolean(value) => Amf0ValueType::Boolean(value),
+            Amf3ValueType::Integer(value) => Amf0ValueType::Number(value as f64),
+            Amf3ValueType::Double(value) | Amf3ValueType::Date(value) => {
+                Amf0ValueType::Number(value)
+            }
+            Amf3ValueType::UTF8String(value)
+            | Amf3ValueType::XmlDocument(value)
+            | Amf3ValueType::Xml(value) => Amf0ValueType::UTF8String(value),
+            Amf3ValueType::Array { associative, dense } => {
+                let mut properties = indexed_array(dense.into_iter().map(Amf0ValueType::from));
+                properties.extend(amf0_properties(associative));
+                Amf0ValueType::EcmaArray(properties)
+            }
+            Amf3ValueType::Object {
+                sealed, dynamic, ..
+            } => {
+                let mut properties = amf0_properties(sealed);
+                properties.extend(amf0_properties(dynamic.unwrap_or_default()));
+                Amf0ValueType::Object(properties)
+            }
+            Amf3ValueType::ByteArray(items) => Amf0ValueType::EcmaArray(indexed_array(
+                items
+                    .into_iter()
+                    .map(|item| Amf0ValueType::Number(item as f64)),
+            )),
+            Amf3ValueType::VectorInt { items, .. } => Amf0ValueType::EcmaArray(indexed_array(
+                items
+                    .into_iter()
+                    .map(|item| Amf0ValueType::Number(item as f64)),
+            )),
+            Amf3ValueType::VectorUint { items, .. } => Amf0ValueType::EcmaArray(indexed_array(
+                items
+                    .into_iter()
+                    .map(|item| Amf0ValueType::Number(item as f64)),
+            )),
+            Amf3ValueType::VectorDouble { items, .. } => Amf0ValueType::EcmaArray(indexed_array(
+                items.into_iter().map(Amf0ValueType::Number),
+            )),
+            Amf3ValueType::VectorObject { items, .. } => {
+                Amf0ValueType::EcmaArray(indexed_array(items.into_iter().map(Amf0ValueType::from)))
+            }
+            Amf3ValueType::Dictionary { entries, .. } => Amf0ValueType::EcmaArray(
+                entries
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, (key, value))| {
+                        let key = match key {
+                            Amf3ValueType::UTF8String(key) => key,
+                            Amf3ValueType::Integer(key) => key.to_string(),
+                            Amf3ValueType::Double(key) => key.to_string(),
+                            _ => index.to_string(),
+                        };
+                        (key, Amf0ValueType::from(value))
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/*The objects become anonymous dynamic objects, the numbers become integers if
+they fit.*/
+impl From<&Amf0ValueType> for Amf3ValueType {
+    fn from(value: &Amf0ValueType) -> Self {
+        let properties = |properties: &IndexMap<String, Amf0ValueType>| {
+            properties
+                .iter()
+                .map(|(key, value)| (key.clone(), Amf3ValueType::from(value)))
+                .collect()
+        };
+
+        match value {
+            Amf0ValueType::Number(value) => {
+                if value.fract() == 0.0
+                    && *value >= INTEGER_MIN as f64
+                    && *value <= INTEGER_MAX as f64
+                {
+                    Amf3ValueType::Integer(*value as i32)
+                } else {
+                    Amf3ValueType::Double(*value)
+                }
+            }
+            Amf0ValueType::Boolean(value) => Amf3ValueType::Boolean(*value),
+            Amf0ValueType::UTF8String(value) | Amf0ValueType::LongUTF8String(value) => {
+                Amf3ValueType::UTF8String(value.clone())
+            }
+            Amf0ValueType::Object(value) => Amf3ValueType::Object {
+                class_name: String::new(),
+                sealed: IndexMap::new(),
+                dynamic: Some(properties(value)),
+            },
+            Amf0ValueType::EcmaArray(value) => Amf3ValueType::Array {
+                associative: properties(value),
+                dense: Vec::new(),
+            },
+            Amf0ValueType::Null => Amf3ValueType::Null,
+            Amf0ValueType::END => Amf3ValueType::Undefined,
+        }
+    }
+}
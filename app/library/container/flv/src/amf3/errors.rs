@@ -0,0 +1,97 @@
+use {
+    bytesio::bytes_errors::{BytesReadError, BytesWriteError},
+    failure::{Backtrace, Fail},
+    std::{fmt, string},
+};
+
+#[derive(Debug, Fail)]
+pub enum Amf3ReadErrorValue {
+    #[fail(display = "Encountered unknown marker: {}", marker)]
+    UnknownMarker { marker: u8 },
+    #[fail(display = "parser string error: {}", _0)]
+    StringParseError(#[cause] string::FromUtf8Error),
+    #[fail(display = "bytes read error :{}", _0)]
+    BytesReadError(BytesReadError),
+    #[fail(display = "reference not found: {}", index)]
+    ReferenceNotFound { index: u32 },
+    #[fail(display = "externalizable object not supported: {}", class_name)]
+    ExternalizableNotSupported { class_name: String },
+    #[fail(display = "values nested deeper than {}", max_depth)]
+    TooDeep { max_depth: usize },
+    #[fail(display = "more than {} nodes", max_nodes)]
+    TooManyNodes { max_nodes: usize },
+}
+
+#[derive(Debug)]
+pub struct Amf3ReadError {
+    pub value: Amf3ReadErrorValue,
+}
+
+impl From<string::FromUtf8Error> for Amf3ReadError {
+    fn from(error: string::FromUtf8Error) -> Self {
+        Amf3ReadError {
+            value: Amf3ReadErrorValue::StringParseError(error),
+        }
+    }
+}
+
+impl From<BytesReadError> for Amf3ReadError {
+    fn from(error: BytesReadError) -> Self {
+        Amf3ReadError {
+            value: Amf3ReadErrorValue::BytesReadError(error),
+        }
+    }
+}
+
+#[derive(Debug, Fail)]
+pub enum Amf3WriteErrorValue {
+    #[fail(display = "value too long")]
+    ValueTooLong,
+    #[fail(display = "bytes write error")]
+    BytesWriteError(BytesWriteError),
+}
+
+#[derive(Debug)]
+pub struct Amf3WriteError {
+    pub value: Amf3WriteErrorValue,
+}
+
+impl From<BytesWriteError> for Amf3WriteError {
+    fn from(error: BytesWriteError) -> Self {
+        Amf3WriteError {
+            value: Amf3WriteErrorValue::BytesWriteError(error),
+        }
+    }
+}
+
+impl fmt::Display for Amf3ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.value, f)
+    }
+}
+
+impl Fail for Amf3ReadError {
+    fn cause(&self) -> Option<&dyn Fail> {
+        self.value.cause()
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        self.value.backtrace()
+    }
+}
+
+impl fmt::Display for Amf3WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.value, f)
+    }
+}
+
+impl Fail for Amf3WriteError {
+    fn cause(&self) -> Option<&dyn Fail> {
+        self.value.cause()
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        self.value.backtrace()
+    }
+}
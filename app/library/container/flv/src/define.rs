@@ -3,6 +3,9 @@ use serde::Serialize;
 
 #[derive(Debug, Clone, Serialize, Default)]
 pub enum SoundFormat {
+    MP3 = 2,
+    //enhanced rtmp, the real codec is carried by the fourcc
+    ExHeader = 9,
     #[default]
     AAC = 10,
     OPUS = 13,
@@ -13,6 +16,21 @@ pub mod aac_packet_type {
     pub const AAC_RAW: u8 = 1;
 }
 
+//enhanced rtmp audio packet type, the lower 4 bits of the first byte
+pub mod audio_packet_type {
+    pub const SEQUENCE_START: u8 = 0;
+    pub const CODED_FRAMES: u8 = 1;
+    pub const SEQUENCE_END: u8 = 2;
+    pub const MULTICHANNEL_CONFIG: u8 = 4;
+    pub const MULTITRACK: u8 = 5;
+}
+
+pub mod audio_four_cc {
+    pub const OPUS: u32 = 0x4F70_7573; // "Opus"
+    pub const MP3: u32 = 0x2E6D_7033; // ".mp3"
+    pub const AAC: u32 = 0x6D70_3461; // "mp4a"
+}
+
 pub mod avc_packet_type {
     pub const AVC_SEQHDR: u8 = 0;
     pub const AVC_NALU: u8 = 1;
@@ -5,7 +5,10 @@ use crate::{
 
 use {
     super::{
-        define::{aac_packet_type, avc_packet_type, tag_type, AvcCodecId, FlvData, SoundFormat},
+        define::{
+            aac_packet_type, audio_four_cc, audio_packet_type, avc_packet_type, tag_type,
+            AvcCodecId, FlvData, SoundFormat,
+        },
         errors::FlvDemuxerError,
         mpeg4_aac::Mpeg4AacProcessor,
         mpeg4_avc::Mpeg4AvcProcessor,
@@ -162,12 +165,15 @@ impl FlvVideoTagDemuxer {
 #[derive(Default)]
 pub struct FlvAudioTagDemuxer {
     aac_processor: Mpeg4AacProcessor,
+    /*channel count of the opus stream, taken from the OpusHead or the sound type*/
+    pub opus_channel_count: u8,
 }
 
 impl FlvAudioTagDemuxer {
     pub fn new() -> Self {
         Self {
             aac_processor: Mpeg4AacProcessor::new(),
+            opus_channel_count: 2,
         }
     }
 
@@ -179,37 +185,116 @@ impl FlvAudioTagDemuxer {
         let mut reader = BytesReader::new(data);
 
         let tag_header = AudioTagHeader::unmarshal(&mut reader)?;
-        self.aac_processor
-            .extend_data(reader.extract_remaining_bytes());
 
         if tag_header.sound_format == SoundFormat::AAC as u8 {
-            match tag_header.aac_packet_type {
-                aac_packet_type::AAC_SEQHDR => {
-                    if self.aac_processor.bytes_reader.len() >= 2 {
-                        self.aac_processor.audio_specific_config_load()?;
-                    }
+            return self.demux_aac(timestamp, tag_header.aac_packet_type, reader);
+        } else if tag_header.sound_format == SoundFormat::MP3 as u8 {
+            return Ok(Self::coded_frames(SoundFormat::MP3, timestamp, reader));
+        } else if tag_header.sound_format == SoundFormat::OPUS as u8 {
+            /*legacy opus has no id header, the channel count comes from the sound type*/
+            self.opus_channel_count = tag_header.sound_type + 1;
+            return Ok(Self::coded_frames(SoundFormat::OPUS, timestamp, reader));
+        } else if tag_header.sound_format == SoundFormat::ExHeader as u8 {
+            return self.demux_ex_header(timestamp, &tag_header, reader);
+        }
 
-                    return Ok(FlvDemuxerAudioData::new());
+        Ok(FlvDemuxerAudioData::new())
+    }
+
+    fn demux_aac(
+        &mut self,
+        timestamp: u32,
+        aac_packet_type: u8,
+        mut reader: BytesReader,
+    ) -> Result<FlvDemuxerAudioData, FlvDemuxerError> {
+        self.aac_processor
+            .extend_data(reader.extract_remaining_bytes());
+
+        match aac_packet_type {
+            aac_packet_type::AAC_SEQHDR => {
+                if self.aac_processor.bytes_reader.len() >= 2 {
+                    self.aac_processor.audio_specific_config_load()?;
                 }
-                aac_packet_type::AAC_RAW => {
-                    self.aac_processor.adts_save()?;
 
-                    let audio_data = FlvDemuxerAudioData {
-                        has_data: true,
-                        sound_format: tag_header.sound_format,
-                        pts: timestamp as i64,
-                        dts: timestamp as i64,
-                        data: self.aac_processor.bytes_writer.extract_current_bytes(),
-                    };
-                    //print!("flv demux audio payload length {}\n", audio_data.data.len());
-                    return Ok(audio_data);
+                Ok(FlvDemuxerAudioData {
+                    sound_format: SoundFormat::AAC as u8,
+                    ..Default::default()
+                })
+            }
+            aac_packet_type::AAC_RAW => {
+                self.aac_processor.adts_save()?;
+
+                let audio_data = FlvDemuxerAudioData {
+                    has_data: true,
+                    sound_format: SoundFormat::AAC as u8,
+                    pts: timestamp as i64,
+                    dts: timestamp as i64,
+                    data: self.aac_processor.bytes_writer.extract_current_bytes(),
+                };
+                //print!("flv demux audio payload length {}\n", audio_data.data.len());
+                Ok(audio_data)
+            }
+            _ => Ok(FlvDemuxerAudioData::new()),
+        }
+    }
+
+    //https://veovera.org/docs/enhanced/enhanced-rtmp-v2
+    fn demux_ex_header(
+        &mut self,
+        timestamp: u32,
+        tag_header: &AudioTagHeader,
+        mut reader: BytesReader,
+    ) -> Result<FlvDemuxerAudioData, FlvDemuxerError> {
+        let packet_type = tag_header.audio_packet_type;
+
+        match tag_header.four_cc {
+            audio_four_cc::AAC => {
+                let aac_packet_type = match packet_type {
+                    audio_packet_type::SEQUENCE_START => aac_packet_type::AAC_SEQHDR,
+                    audio_packet_type::CODED_FRAMES => aac_packet_type::AAC_RAW,
+                    _ => return Ok(FlvDemuxerAudioData::new()),
+                };
+                return self.demux_aac(timestamp, aac_packet_type, reader);
+            }
+            audio_four_cc::MP3 if packet_type == audio_packet_type::CODED_FRAMES => {
+                return Ok(Self::coded_frames(SoundFormat::MP3, timestamp, reader));
+            }
+            audio_four_cc::OPUS => match packet_type {
+                audio_packet_type::SEQUENCE_START => {
+                    /*OpusHead: magic signature(8 bytes), version(1 byte), channel count(1 byte)...*/
+                    if reader.len() >= 10 {
+                        reader.read_bytes(9)?;
+                        self.opus_channel_count = reader.read_u8()?;
+                    }
+                    return Ok(FlvDemuxerAudioData {
+                        sound_format: SoundFormat::OPUS as u8,
+                        ..Default::default()
+                    });
+                }
+                audio_packet_type::CODED_FRAMES => {
+                    return Ok(Self::coded_frames(SoundFormat::OPUS, timestamp, reader));
                 }
                 _ => {}
-            }
+            },
+            _ => {}
         }
 
         Ok(FlvDemuxerAudioData::new())
     }
+
+    fn coded_frames(
+        sound_format: SoundFormat,
+        timestamp: u32,
+        mut reader: BytesReader,
+    ) -> FlvDemuxerAudioData {
+        FlvDemuxerAudioData {
+            has_data: true,
+            sound_format: sound_format as u8,
+            pts: timestamp as i64,
+            dts: timestamp as i64,
+            data: reader.extract_remaining_bytes(),
+        }
+    }
 }
 
 pub struct FlvDemuxer {
@@ -223,6 +308,11 @@ impl FlvDemuxer {
         }
     }
 
+    /*only the size of the last tag is left*/
+    pub fn is_finished(&self) -> bool {
+        self.bytes_reader.len() <= 4
+    }
+
     pub fn read_flv_header(&mut self) -> Result<(), FlvDemuxerError> {
         /*flv header*/
         self.bytes_reader.read_bytes(9)?;
@@ -269,3 +359,42 @@ impl FlvDemuxer {
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{FlvAudioTagDemuxer, SoundFormat};
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_demux_enhanced_opus() {
+        let mut demuxer = FlvAudioTagDemuxer::new();
+
+        //ExHeader + SequenceStart + "Opus" + OpusHead(mono)
+        let mut seq_start = BytesMut::from(&[0x90, b'O', b'p', b'u', b's'][..]);
+        seq_start.extend_from_slice(b"OpusHead");
+        seq_start.extend_from_slice(&[0x01, 0x01, 0x38, 0x01, 0x80, 0xBB, 0x00, 0x00, 0, 0, 0]);
+
+        let audio_data = demuxer.demux(0, seq_start).unwrap();
+        assert!(!audio_data.has_data);
+        assert_eq!(audio_data.sound_format, SoundFormat::OPUS as u8);
+        assert_eq!(demuxer.opus_channel_count, 1);
+
+        //ExHeader + CodedFrames + "Opus" + payload
+        let coded_frames = BytesMut::from(&[0x91, b'O', b'p', b'u', b's', 0xFC, 0xFF, 0xFE][..]);
+        let audio_data = demuxer.demux(20, coded_frames).unwrap();
+        assert!(audio_data.has_data);
+        assert_eq!(audio_data.dts, 20);
+        assert_eq!(&audio_data.data[..], &[0xFC, 0xFF, 0xFE]);
+    }
+
+    #[test]
+    fn test_demux_mp3() {
+        let mut demuxer = FlvAudioTagDemuxer::new();
+
+        let data = BytesMut::from(&[0x2F, 0xFF, 0xFB, 0x90, 0x64][..]);
+        let audio_data = demuxer.demux(40, data).unwrap();
+        assert!(audio_data.has_data);
+        assert_eq!(audio_data.sound_format, SoundFormat::MP3 as u8);
+        assert_eq!(&audio_data.data[..], &[0xFF, 0xFB, 0x90, 0x64]);
+    }
+}
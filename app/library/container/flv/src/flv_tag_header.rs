@@ -6,6 +6,7 @@ use {
         errors::{FlvDemuxerError, FlvMuxerError},
     },
     super::{Marshal, Unmarshal},
+    byteorder::BigEndian,
     bytes::BytesMut,
     bytesio::bytes_reader::BytesReader,
 };
@@ -24,7 +25,7 @@ pub struct AudioTagHeader {
         6 = Nellymoser
         7 = G.711 A-law logarithmic PCM
         8 = G.711 mu-law logarithmic PCM
-        9 = reserved
+        9 = ExHeader (enhanced rtmp)
         10 = AAC
         11 = Speex
         14 = MP3 8-Khz
@@ -66,6 +67,14 @@ pub struct AudioTagHeader {
         1: AAC raw
     */
     pub aac_packet_type: u8,
+
+    /*
+        Only for SoundFormat 9 (ExHeader):
+        AudioPacketType: UB[4], replaces the sound rate/size/type bits
+        AudioFourCc: FOURCC, the real codec of the payload
+    */
+    pub audio_packet_type: u8,
+    pub four_cc: u32,
 }
 
 impl AudioTagHeader {
@@ -76,6 +85,8 @@ impl AudioTagHeader {
             sound_size: 0,
             sound_type: 0,
             aac_packet_type: 0,
+            audio_packet_type: 0,
+            four_cc: 0,
         }
     }
 }
@@ -95,6 +106,12 @@ impl Unmarshal<&mut BytesReader, Result<Self, FlvDemuxerError>> for AudioTagHead
 
         if tag_header.sound_format == define::SoundFormat::AAC as u8 {
             tag_header.aac_packet_type = reader.read_u8()?;
+        } else if tag_header.sound_format == define::SoundFormat::ExHeader as u8 {
+            tag_header.audio_packet_type = flags & 0x0f;
+            /*multitrack packets carry their own fourcc per track, they are not supported.*/
+            if tag_header.audio_packet_type != define::audio_packet_type::MULTITRACK {
+                tag_header.four_cc = reader.read_u32::<BigEndian>()?;
+            }
         }
 
         Ok(tag_header)
@@ -105,6 +122,14 @@ impl Marshal<Result<BytesMut, FlvMuxerError>> for AudioTagHeader {
     fn marshal(&self) -> Result<BytesMut, FlvMuxerError> {
         let mut writer = BytesWriter::default();
 
+        if self.sound_format == define::SoundFormat::ExHeader as u8 {
+            writer.write_u8(self.sound_format << 4 | self.audio_packet_type)?;
+            if self.audio_packet_type != define::audio_packet_type::MULTITRACK {
+                writer.write_u32::<BigEndian>(self.four_cc)?;
+            }
+            return Ok(writer.extract_current_bytes());
+        }
+
         let byte_1st =
             self.sound_format << 4 | self.sound_rate << 2 | self.sound_size << 1 | self.sound_type;
         writer.write_u8(byte_1st)?;
@@ -1,4 +1,5 @@
 pub mod amf0;
+pub mod amf3;
 pub mod define;
 pub mod demuxer;
 pub mod errors;
@@ -0,0 +1,388 @@
+use {
+    super::errors::{Mp4Error, Mp4ErrorValue},
+    byteorder::{BigEndian, ByteOrder},
+    bytes::BytesMut,
+    bytesio::bytes_reader::BytesReader,
+    std::ops::Range,
+};
+
+/*the sizes of the sample entry fields before the child boxes*/
+const VISUAL_SAMPLE_ENTRY_SIZE: usize = 78;
+const AUDIO_SAMPLE_ENTRY_SIZE: usize = 28;
+
+const ES_DESCRIPTOR_TAG: u8 = 0x03;
+const DECODER_CONFIG_DESCRIPTOR_TAG: u8 = 0x04;
+const DECODER_SPECIFIC_INFO_TAG: u8 = 0x05;
+
+pub enum Mp4Codec {
+    /*the AVCDecoderConfigurationRecord*/
+    H264 { avc_config: BytesMut },
+    Aac { audio_specific_config: BytesMut },
+    Unsupported(String),
+}
+
+pub struct Mp4Sample {
+    /*from the beginning of the file*/
+    pub offset: u64,
+    pub size: u32,
+    /*in the timescale of the track*/
+    pub dts: u64,
+    pub composition_offset: i64,
+    pub is_sync: bool,
+}
+
+pub struct Mp4Track {
+    pub timescale: u32,
+    pub codec: Mp4Codec,
+    pub samples: Vec<Mp4Sample>,
+}
+
+fn size_error() -> Mp4Error {
+    Mp4Error {
+        value: Mp4ErrorValue::BoxSizeError,
+    }
+}
+
+fn sample_table_error() -> Mp4Error {
+    Mp4Error {
+        value: Mp4ErrorValue::SampleTableError,
+    }
+}
+
+/*the type and the body range of a box*/
+pub type BoxRange = ([u8; 4], Range<usize>);
+
+/*The types and the body ranges of the boxes in the data, a size of 0 extends the
+box to the end of the data and a size of 1 is followed by a 64 bits size.*/
+pub fn box_ranges(data: &[u8]) -> Result<Vec<BoxRange>, Mp4Error> {
+    let mut boxes = Vec::new();
+    let mut offset = 0;
+
+    while offset + 8 <= data.len() {
+        let size = BigEndian::read_u32(&data[offset..]) as u64;
+        let mut box_type = [0; 4];
+        box_type.copy_from_slice(&data[offset + 4..offset + 8]);
+
+        let (header_size, box_size) = match size {
+            0 => (8, (data.len() - offset) as u64),
+            1 => {
+                let large_size = data.get(offset + 8..offset + 16).ok_or_else(size_error)?;
+                (16, BigEndian::read_u64(large_size))
+            }
+            _ => (8, size),
+        };
+        if box_size < header_size || box_size > (data.len() - offset) as u64 {
+            return Err(size_error());
+        }
+
+        let end = offset + box_size as usize;
+        boxes.push((box_type, offset + header_size as usize..end));
+        offset = end;
+    }
+
+    Ok(boxes)
+}
+
+/*the body of the first box found along the path*/
+pub fn find_box<'a>(data: &'a [u8], path: &[&[u8; 4]]) -> Result<Option<&'a [u8]>, Mp4Error> {
+    let mut body = data;
+
+    for box_type in path {
+        let Some((_, range)) = box_ranges(body)?
+            .into_iter()
+            .find(|(cur_type, _)| cur_type == *box_type)
+        else {
+            return Ok(None);
+        };
+        body = &body[range];
+    }
+
+    Ok(Some(body))
+}
+
+/*the reader of a full box, after its version and flags*/
+fn full_box_reader(data: &[u8]) -> Result<(u8, BytesReader), Mp4Error> {
+    let mut reader = BytesReader::new(BytesMut::from(data));
+    let version = reader.read_u8()?;
+    reader.read_u24::<BigEndian>()?;
+
+    Ok((version, reader))
+}
+
+/*Returns None for the tracks without samples, e.g. the chapters of some files.*/
+pub fn parse_track(trak: &[u8]) -> Result<Option<Mp4Track>, Mp4Error> {
+    let Some(mdia) = find_box(trak, &[b"mdia"])? else {
+        return Ok(None);
+    };
+    let (Some(mdhd), Some(stbl)) = (
+        find_box(mdia, &[b"mdhd"])?,
+        find_box(mdia, &[b"minf", b"stbl"])?,
+    ) else {
+        return Ok(None);
+    };
+
+    let timescale = parse_timescale(mdhd)?;
+    let codec = match find_box(stbl, &[b"stsd"])? {
+        Some(stsd) => parse_sample_description(stsd)?,
+        None => return Ok(None),
+    };
+    let samples = parse_sample_table(stbl)?;
+    if samples.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(Mp4Track {
+        timescale,
+        codec,
+        samples,
+    }))
+}
+
+fn parse_timescale(mdhd: &[u8]) -> Result<u32, Mp4Error> {
+    let (version, mut reader) = full_box_reader(mdhd)?;
+    /*the creation and modification times*/
+    if version == 1 {
+        reader.read_bytes(16)?;
+    } else {
+        reader.read_bytes(8)?;
+    }
+
+    let timescale = reader.read_u32::<BigEndian>()?;
+    if timescale == 0 {
+        return Err(sample_table_error());
+    }
+
+    Ok(timescale)
+}
+
+/*only the first sample entry is used*/
+fn parse_sample_description(stsd: &[u8]) -> Result<Mp4Codec, Mp4Error> {
+    let entries = stsd.get(8..).ok_or_else(size_error)?;
+    let Some((entry_type, range)) = box_ranges(entries)?.into_iter().next() else {
+        return Err(sample_table_error());
+    };
+    let entry = &entries[range];
+
+    let codec = match &entry_type {
+        b"avc1" | b"avc3" => {
+            let children = entry
+                .get(VISUAL_SAMPLE_ENTRY_SIZE..)
+                .ok_or_else(size_error)?;
+            find_box(children, &[b"avcC"])?.map(|avc_config| Mp4Codec::H264 {
+                avc_config: BytesMut::from(avc_config),
+            })
+        }
+        b"mp4a" => {
+            /*the quicktime sound sample descriptions of version 1 and 2 are longer*/
+            let version = entry.get(8..10).map_or(0, BigEndian::read_u16);
+            let entry_size = match version {
+                1 => AUDIO_SAMPLE_ENTRY_SIZE + 16,
+                2 => AUDIO_SAMPLE_ENTRY_SIZE + 36,
+                _ => AUDIO_SAMPLE_ENTRY_SIZE,
+            };
+            let children = entry.get(entry_size..).ok_or_else(size_error)?;
+            match find_box(children, &[b"esds"])? {
+                Some(esds) => parse_esds(esds)?,
+                None => None,
+            }
+        }
+        _ => None,
+    };
+
+    Ok(codec
+        .unwrap_or_else(|| Mp4Codec::Unsupported(String::from_utf8_lossy(&entry_type).to_string())))
+}
+
+fn read_descriptor_header(reader: &mut BytesReader) -> Result<(u8, usize), Mp4Error> {
+    let tag = reader.read_u8()?;
+    let mut length = 0;
+    for _ in 0..4 {
+        let byte = reader.read_u8()?;
+        length = (length << 7) | (byte & 0x7F) as usize;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+
+    Ok((tag, length))
+}
+
+/*The AAC audio specific config in the decoder specific info of the ES descriptor,
+None if it is not MPEG-4 audio, e.g. MP3.*/
+fn parse_esds(esds: &[u8]) -> Result<Option<Mp4Codec>, Mp4Error> {
+    let (_, mut reader) = full_box_reader(esds)?;
+
+    let (tag, _) = read_descriptor_header(&mut reader)?;
+    if tag != ES_DESCRIPTOR_TAG {
+        return Ok(None);
+    }
+    /*ES_ID*/
+    reader.read_u16::<BigEndian>()?;
+    let flags = reader.read_u8()?;
+    if flags & 0x80 > 0 {
+        /*dependsOn_ES_ID*/
+        reader.read_u16::<BigEndian>()?;
+    }
+    if flags & 0x40 > 0 {
+        let url_length = reader.read_u8()?;
+        reader.read_bytes(url_length as usize)?;
+    }
+    if flags & 0x20 > 0 {
+        /*OCR_ES_Id*/
+        reader.read_u16::<BigEndian>()?;
+    }
+
+    let (tag, _) = read_descriptor_header(&mut reader)?;
+    if tag != DECODER_CONFIG_DESCRIPTOR_TAG {
+        return Ok(None);
+    }
+    let object_type_indication = reader.read_u8()?;
+    if object_type_indication != 0x40 {
+        return Ok(None);
+    }
+    /*stream type, buffer size, max and average bitrates*/
+    reader.read_bytes(12)?;
+
+    let (tag, length) = read_descriptor_header(&mut reader)?;
+    if tag != DECODER_SPECIFIC_INFO_TAG {
+        return Ok(None);
+    }
+
+    Ok(Some(Mp4Codec::Aac {
+        audio_specific_config: reader.read_bytes(length)?,
+    }))
+}
+
+fn read_entries(
+    stbl: &[u8],
+    box_type: &[u8; 4],
+    fields: usize,
+) -> Result<Option<(u8, Vec<u64>)>, Mp4Error> {
+    let Some(body) = find_box(stbl, &[box_type])? else {
+        return Ok(None);
+    };
+    let (version, mut reader) = full_box_reader(body)?;
+    let count = reader.read_u32::<BigEndian>()? as usize;
+    if count * fields * 4 > reader.len() {
+        return Err(sample_table_error());
+    }
+
+    let mut values = Vec::with_capacity(count * fields);
+    for _ in 0..count * fields {
+        values.push(reader.read_u32::<BigEndian>()? as u64);
+    }
+
+    Ok(Some((version, values)))
+}
+
+fn parse_sample_sizes(stbl: &[u8]) -> Result<Vec<u32>, Mp4Error> {
+    let stsz = find_box(stbl, &[b"stsz"])?.ok_or_else(sample_table_error)?;
+    let (_, mut reader) = full_box_reader(stsz)?;
+    let sample_size = reader.read_u32::<BigEndian>()?;
+    let count = reader.read_u32::<BigEndian>()? as usize;
+
+    if sample_size > 0 {
+        return Ok(vec![sample_size; count]);
+    }
+    if count * 4 > reader.len() {
+        return Err(sample_table_error());
+    }
+
+    let mut sizes = Vec::with_capacity(count);
+    for _ in 0..count {
+        sizes.push(reader.read_u32::<BigEndian>()?);
+    }
+
+    Ok(sizes)
+}
+
+fn parse_chunk_offsets(stbl: &[u8]) -> Result<Vec<u64>, Mp4Error> {
+    if let Some((_, offsets)) = read_entries(stbl, b"stco", 1)? {
+        return Ok(offsets);
+    }
+
+    let co64 = find_box(stbl, &[b"co64"])?.ok_or_else(sample_table_error)?;
+    let (_, mut reader) = full_box_reader(co64)?;
+    let count = reader.read_u32::<BigEndian>()? as usize;
+    if count * 8 > reader.len() {
+        return Err(sample_table_error());
+    }
+
+    let mut offsets = Vec::with_capacity(count);
+    for _ in 0..count {
+        offsets.push(reader.read_u64::<BigEndian>()?);
+    }
+
+    Ok(offsets)
+}
+
+/*Locates the samples in the chunks and gives them the timestamps of stts and ctts,
+all the samples are sync samples without a stss box.*/
+fn parse_sample_table(stbl: &[u8]) -> Result<Vec<Mp4Sample>, Mp4Error> {
+    let sizes = parse_sample_sizes(stbl)?;
+    let chunk_offsets = parse_chunk_offsets(stbl)?;
+    let (_, sample_to_chunks) = read_entries(stbl, b"stsc", 3)?.ok_or_else(sample_table_error)?;
+    let sample_to_chunks: Vec<&[u64]> = sample_to_chunks.chunks_exact(3).collect();
+
+    let mut samples = Vec::with_capacity(sizes.len());
+    let mut entry_index = 0;
+    for (chunk_index, chunk_offset) in chunk_offsets.iter().enumerate() {
+        let chunk_number = chunk_index as u64 + 1;
+        while entry_index + 1 < sample_to_chunks.len()
+            && sample_to_chunks[entry_index + 1][0] <= chunk_number
+        {
+            entry_index += 1;
+        }
+        let Some(entry) = sample_to_chunks.get(entry_index) else {
+            break;
+        };
+
+        let mut offset = *chunk_offset;
+        for _ in 0..entry[1] {
+            let Some(size) = sizes.get(samples.len()) else {
+                break;
+            };
+            samples.push(Mp4Sample {
+                offset,
+                size: *size,
+                dts: 0,
+                composition_offset: 0,
+                is_sync: true,
+            });
+            offset += *size as u64;
+        }
+    }
+
+    let (_, time_to_samples) = read_entries(stbl, b"stts", 2)?.ok_or_else(sample_table_error)?;
+    let mut sample_iter = samples.iter_mut();
+    let mut dts = 0;
+    for entry in time_to_samples.chunks_exact(2) {
+        for sample in sample_iter.by_ref().take(entry[0] as usize) {
+            sample.dts = dts;
+            dts += entry[1];
+        }
+    }
+
+    /*the offsets are signed in version 1 and in practice also in version 0*/
+    if let Some((_, composition_offsets)) = read_entries(stbl, b"ctts", 2)? {
+        let mut sample_iter = samples.iter_mut();
+        for entry in composition_offsets.chunks_exact(2) {
+            for sample in sample_iter.by_ref().take(entry[0] as usize) {
+                sample.composition_offset = entry[1] as u32 as i32 as i64;
+            }
+        }
+    }
+
+    if let Some((_, sync_samples)) = read_entries(stbl, b"stss", 1)? {
+        for sample in samples.iter_mut() {
+            sample.is_sync = false;
+        }
+        for sample_number in sync_samples {
+            if let Some(sample) = samples.get_mut((sample_number as usize).wrapping_sub(1)) {
+                sample.is_sync = true;
+            }
+        }
+    }
+
+    Ok(samples)
+}
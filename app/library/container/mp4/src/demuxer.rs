@@ -0,0 +1,199 @@
+use {
+    super::{
+        boxes::{box_ranges, parse_track, Mp4Track},
+        errors::{Mp4Error, Mp4ErrorValue},
+    },
+    bytes::BytesMut,
+};
+
+/*a sample with the timestamps in milliseconds*/
+pub struct Mp4Frame {
+    pub track_index: usize,
+    pub dts: i64,
+    pub pts: i64,
+    pub is_sync: bool,
+    pub data: BytesMut,
+}
+
+/*Reads the samples of a progressive MP4 file which is loaded in memory, the
+fragmented files and the edit lists are not supported.*/
+pub struct Mp4Demuxer {
+    data: BytesMut,
+    pub tracks: Vec<Mp4Track>,
+    /*the index of the next sample of each track*/
+    next_samples: Vec<usize>,
+}
+
+impl Mp4Demuxer {
+    pub fn new(data: BytesMut) -> Result<Self, Mp4Error> {
+        let Some((_, moov_range)) = box_ranges(&data)?
+            .into_iter()
+            .find(|(box_type, _)| box_type == b"moov")
+        else {
+            return Err(Mp4Error {
+                value: Mp4ErrorValue::MoovNotFound,
+            });
+        };
+
+        let moov = &data[moov_range];
+        let mut tracks = Vec::new();
+        for (box_type, range) in box_ranges(moov)? {
+            if &box_type != b"trak" {
+                continue;
+            }
+            if let Some(track) = parse_track(&moov[range])? {
+                tracks.push(track);
+            }
+        }
+
+        let next_samples = vec![0; tracks.len()];
+        Ok(Self {
+            data,
+            tracks,
+            next_samples,
+        })
+    }
+
+    fn to_milliseconds(track: &Mp4Track, timestamp: i64) -> i64 {
+        timestamp * 1000 / track.timescale as i64
+    }
+
+    /*The samples of all the tracks interleaved by their decoding timestamps.*/
+    pub fn read_frame(&mut self) -> Result<Option<Mp4Frame>, Mp4Error> {
+        let next_track = self
+            .tracks
+            .iter()
+            .enumerate()
+            .filter_map(|(track_index, track)| {
+                let sample = track.samples.get(self.next_samples[track_index])?;
+                Some((track_index, Self::to_milliseconds(track, sample.dts as i64)))
+            })
+            .min_by_key(|(_, dts)| *dts);
+
+        let Some((track_index, dts)) = next_track else {
+            return Ok(None);
+        };
+        let track = &self.tracks[track_index];
+        let sample = &track.samples[self.next_samples[track_index]];
+        self.next_samples[track_index] += 1;
+
+        let start = sample.offset as usize;
+        let Some(data) = self.data.get(start..start + sample.size as usize) else {
+            return Err(Mp4Error {
+                value: Mp4ErrorValue::SampleOutOfRange,
+            });
+        };
+
+        Ok(Some(Mp4Frame {
+            track_index,
+            dts,
+            pts: Self::to_milliseconds(track, sample.dts as i64 + sample.composition_offset),
+            is_sync: sample.is_sync,
+            data: BytesMut::from(data),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::Mp4Demuxer, crate::boxes::Mp4Codec, bytes::BytesMut};
+
+    fn mp4_box(box_type: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut data = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(box_type);
+        data.extend_from_slice(body);
+        data
+    }
+
+    fn full_box(box_type: &[u8; 4], values: &[u32]) -> Vec<u8> {
+        let mut body = vec![0; 4];
+        for value in values {
+            body.extend_from_slice(&value.to_be_bytes());
+        }
+        mp4_box(box_type, &body)
+    }
+
+    /*the samples are 4 bytes each, the chunk offsets are from the beginning of mdat*/
+    fn trak(
+        timescale: u32,
+        sample_entry: Vec<u8>,
+        sample_delta: u32,
+        chunk_offsets: &[u32],
+        mdat_offset: u32,
+    ) -> Vec<u8> {
+        let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        stsd.extend(sample_entry);
+        let chunk_offsets: Vec<u32> = chunk_offsets
+            .iter()
+            .map(|offset| offset + mdat_offset)
+            .collect();
+        let sample_count = chunk_offsets.len() as u32 * 2;
+
+        let stbl = [
+            mp4_box(b"stsd", &stsd),
+            full_box(b"stts", &[1, sample_count, sample_delta]),
+            full_box(b"stsc", &[1, 1, 2, 1]),
+            full_box(b"stsz", &[4, sample_count]),
+            full_box(
+                b"stco",
+                &[&[chunk_offsets.len() as u32], &chunk_offsets[..]].concat(),
+            ),
+            full_box(b"stss", &[1, 1]),
+        ]
+        .concat();
+        let mdhd = full_box(b"mdhd", &[0, 0, timescale, 0, 0]);
+        let minf = mp4_box(b"minf", &mp4_box(b"stbl", &stbl));
+
+        mp4_box(b"trak", &mp4_box(b"mdia", &[mdhd, minf].concat()))
+    }
+
+    #[test]
+    fn test_read_interleaved_frames() {
+        let mut avc1 = vec![0; 78];
+        avc1.extend(mp4_box(b"avcC", &[1, 0x64, 0, 0x1F, 0xFF]));
+        let mut mp4a = vec![0; 28];
+        let esds = [
+            0, 0, 0, 0, 0x03, 23, 0, 1, 0, 0x04, 15, 0x40, 0x15, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            0x05, 2, 0x11, 0x90,
+        ];
+        mp4a.extend(mp4_box(b"esds", &esds));
+
+        /*the video frames last 40ms, the audio frames about 23ms*/
+        let mdat_body: Vec<u8> = (0..8).flat_map(|index| [index; 4]).collect();
+        let ftyp = mp4_box(b"ftyp", b"isom");
+        let moov = |mdat_offset| {
+            let video = trak(1000, mp4_box(b"avc1", &avc1), 40, &[0, 8], mdat_offset);
+            let audio = trak(44100, mp4_box(b"mp4a", &mp4a), 1024, &[16, 24], mdat_offset);
+            mp4_box(b"moov", &[video, audio].concat())
+        };
+        let mdat_offset = (ftyp.len() + moov(0).len() + 8) as u32;
+        let data = [ftyp, moov(mdat_offset), mp4_box(b"mdat", &mdat_body)].concat();
+
+        let mut demuxer = Mp4Demuxer::new(BytesMut::from(&data[..])).unwrap();
+        assert_eq!(demuxer.tracks.len(), 2);
+        assert!(
+            matches!(&demuxer.tracks[0].codec, Mp4Codec::H264 { avc_config } if avc_config[1] == 0x64)
+        );
+        assert!(
+            matches!(&demuxer.tracks[1].codec, Mp4Codec::Aac { audio_specific_config } if audio_specific_config[..] == [0x11, 0x90])
+        );
+
+        let mut frames = Vec::new();
+        while let Some(frame) = demuxer.read_frame().unwrap() {
+            frames.push((frame.track_index, frame.dts, frame.is_sync, frame.data[0]));
+        }
+        assert_eq!(
+            frames,
+            vec![
+                (0, 0, true, 0),
+                (1, 0, true, 4),
+                (1, 23, false, 5),
+                (0, 40, false, 1),
+                (1, 46, false, 6),
+                (1, 69, false, 7),
+                (0, 80, false, 2),
+                (0, 120, false, 3),
+            ]
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use {
+    bytesio::bytes_errors::{BytesReadError, BytesWriteError},
+    failure::{Backtrace, Fail},
+    std::fmt,
+};
+
+#[derive(Debug, Fail)]
+pub enum Mp4ErrorValue {
+    #[fail(display = "bytes read error")]
+    BytesReadError(BytesReadError),
+
+    #[fail(display = "bytes write error")]
+    BytesWriteError(BytesWriteError),
+
+    #[fail(display = "box size error")]
+    BoxSizeError,
+
+    #[fail(display = "moov box not found")]
+    MoovNotFound,
+
+    #[fail(display = "sample table error")]
+    SampleTableError,
+
+    #[fail(display = "sample out of the file")]
+    SampleOutOfRange,
+}
+
+#[derive(Debug)]
+pub struct Mp4Error {
+    pub value: Mp4ErrorValue,
+}
+
+impl From<BytesReadError> for Mp4Error {
+    fn from(error: BytesReadError) -> Self {
+        Mp4Error {
+            value: Mp4ErrorValue::BytesReadError(error),
+        }
+    }
+}
+
+impl From<BytesWriteError> for Mp4Error {
+    fn from(error: BytesWriteError) -> Self {
+        Mp4Error {
+            value: Mp4ErrorValue::BytesWriteError(error),
+        }
+    }
+}
+
+impl fmt::Display for Mp4Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.value, f)
+    }
+}
+
+impl Fail for Mp4Error {
+    fn cause(&self) -> Option<&dyn Fail> {
+        self.value.cause()
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        self.value.backtrace()
+    }
+}
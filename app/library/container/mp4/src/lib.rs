@@ -0,0 +1,4 @@
+pub mod boxes;
+pub mod demuxer;
+pub mod errors;
+pub mod muxer;
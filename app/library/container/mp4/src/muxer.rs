@@ -0,0 +1,585 @@
+use {
+    super::{boxes::Mp4Codec, errors::Mp4Error},
+    byteorder::BigEndian,
+    bytes::BytesMut,
+    bytesio::bytes_writer::BytesWriter,
+};
+
+/*the timestamps of the samples are in milliseconds*/
+const TIMESCALE: u32 = 1000;
+const MATRIX: [u32; 9] = [0x00010000, 0, 0, 0, 0x00010000, 0, 0, 0, 0x40000000];
+const SAMPLING_FREQUENCIES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+struct MuxSample {
+    /*from the beginning of mdat*/
+    offset: u64,
+    size: u32,
+    dts: i64,
+    composition_offset: i64,
+    is_sync: bool,
+}
+
+struct MuxTrack {
+    codec: Mp4Codec,
+    width: u16,
+    height: u16,
+    samples: Vec<MuxSample>,
+}
+
+impl MuxTrack {
+    fn is_video(&self) -> bool {
+        matches!(self.codec, Mp4Codec::H264 { .. })
+    }
+
+    /*a sample lasts until the next one, the last one as long as the one before it*/
+    fn sample_durations(&self) -> Vec<u32> {
+        let mut durations: Vec<u32> = self
+            .samples
+            .windows(2)
+            .map(|samples| std::cmp::max(samples[1].dts - samples[0].dts, 0) as u32)
+            .collect();
+        durations.push(durations.last().copied().unwrap_or_default());
+        durations
+    }
+
+    fn duration(&self) -> u32 {
+        self.sample_durations().iter().sum()
+    }
+}
+
+fn box_data(box_type: &[u8; 4], body: &[u8]) -> Result<BytesMut, Mp4Error> {
+    let mut writer = BytesWriter::new();
+    writer.write_u32::<BigEndian>(body.len() as u32 + 8)?;
+    writer.write(box_type)?;
+    writer.write(body)?;
+    Ok(writer.extract_current_bytes())
+}
+
+fn full_box_data(
+    box_type: &[u8; 4],
+    version: u8,
+    flags: u32,
+    body: &[u8],
+) -> Result<BytesMut, Mp4Error> {
+    let mut writer = BytesWriter::new();
+    writer.write_u8(version)?;
+    writer.write_u24::<BigEndian>(flags)?;
+    writer.write(body)?;
+    box_data(box_type, &writer.extract_current_bytes())
+}
+
+/*Writes a progressive MP4 file of H.264 and AAC tracks, the moov box is placed
+before mdat so that the file can be played while it is downloaded. The sample data
+can be taken out while muxing, it follows the header written by finish_header.*/
+pub struct Mp4Muxer {
+    tracks: Vec<MuxTrack>,
+    mdat: BytesWriter,
+    /*including the sample data which has been taken*/
+    mdat_size: u64,
+}
+
+impl Default for Mp4Muxer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Mp4Muxer {
+    pub fn new() -> Self {
+        Self {
+            tracks: Vec::new(),
+            mdat: BytesWriter::new(),
+            mdat_size: 0,
+        }
+    }
+
+    /*returns the index of the track, the width and height are of the video*/
+    pub fn add_track(&mut self, codec: Mp4Codec, width: u16, height: u16) -> usize {
+        self.tracks.push(MuxTrack {
+            codec,
+            width,
+            height,
+            samples: Vec::new(),
+        });
+        self.tracks.len() - 1
+    }
+
+    /*the samples of a track are written in the decoding order, the H.264 samples are
+    in the format of the avcC of the track*/
+    pub fn write_sample(
+        &mut self,
+        track_index: usize,
+        dts: i64,
+        pts: i64,
+        is_sync: bool,
+        data: &[u8],
+    ) -> Result<(), Mp4Error> {
+        let offset = self.mdat_size;
+        self.mdat.write(data)?;
+        self.mdat_size += data.len() as u64;
+
+        if let Some(track) = self.tracks.get_mut(track_index) {
+            track.samples.push(MuxSample {
+                offset,
+                size: data.len() as u32,
+                dts,
+                composition_offset: pts - dts,
+                is_sync,
+            });
+        }
+
+        Ok(())
+    }
+
+    /*the sample data written since it was last taken*/
+    pub fn take_sample_data(&mut self) -> BytesMut {
+        self.mdat.extract_current_bytes()
+    }
+
+    pub fn finish(&mut self) -> Result<BytesMut, Mp4Error> {
+        let mut data = self.finish_header()?;
+        data.unsplit(self.take_sample_data());
+        Ok(data)
+    }
+
+    /*ftyp, moov and the header of mdat*/
+    pub fn finish_header(&mut self) -> Result<BytesMut, Mp4Error> {
+        let mut ftyp = BytesWriter::new();
+        ftyp.write(b"isom")?;
+        ftyp.write_u32::<BigEndian>(0x200)?;
+        ftyp.write(b"isomiso2avc1mp41")?;
+        let ftyp = box_data(b"ftyp", &ftyp.extract_current_bytes())?;
+
+        let mdat_size = self.mdat_size + 8;
+        let mdat_header_size = if mdat_size > u32::MAX as u64 { 16 } else { 8 };
+
+        /*the chunk offsets depend on the size of moov, which depends on whether they
+        need 64 bits, so it settles in a few rounds*/
+        let mut mdat_offset = 0;
+        let moov = loop {
+            let moov = self.write_moov(mdat_offset)?;
+            let cur_mdat_offset = (ftyp.len() + moov.len() + mdat_header_size) as u64;
+            if cur_mdat_offset == mdat_offset {
+                break moov;
+            }
+            mdat_offset = cur_mdat_offset;
+        };
+
+        let mut writer = BytesWriter::new();
+        writer.write(&ftyp)?;
+        writer.write(&moov)?;
+        if mdat_header_size == 16 {
+            writer.write_u32::<BigEndian>(1)?;
+            writer.write(b"mdat")?;
+            writer.write_u64::<BigEndian>(mdat_size + 8)?;
+        } else {
+            writer.write_u32::<BigEndian>(mdat_size as u32)?;
+            writer.write(b"mdat")?;
+        }
+
+        Ok(writer.extract_current_bytes())
+    }
+
+    fn write_moov(&self, mdat_offset: u64) -> Result<BytesMut, Mp4Error> {
+        let tracks: Vec<&MuxTrack> = self
+            .tracks
+            .iter()
+            .filter(|track| !track.samples.is_empty())
+            .collect();
+        let duration = tracks
+            .iter()
+            .map(|track| track.duration())
+            .max()
+            .unwrap_or_default();
+
+        let mut mvhd = BytesWriter::new();
+        mvhd.write_u32::<BigEndian>(0)?;
+        mvhd.write_u32::<BigEndian>(0)?;
+        mvhd.write_u32::<BigEndian>(TIMESCALE)?;
+        mvhd.write_u32::<BigEndian>(duration)?;
+        mvhd.write_u32::<BigEndian>(0x00010000)?;
+        mvhd.write_u16::<BigEndian>(0x0100)?;
+        mvhd.write(&[0; 10])?;
+        for value in MATRIX {
+            mvhd.write_u32::<BigEndian>(value)?;
+        }
+        mvhd.write(&[0; 24])?;
+        mvhd.write_u32::<BigEndian>(tracks.len() as u32 + 1)?;
+
+        let mut moov = BytesWriter::new();
+        moov.write(&full_box_data(
+            b"mvhd",
+            0,
+            0,
+            &mvhd.extract_current_bytes(),
+        )?)?;
+        for (index, track) in tracks.iter().enumerate() {
+            moov.write(&Self::write_trak(track, index as u32 + 1, mdat_offset)?)?;
+        }
+
+        box_data(b"moov", &moov.extract_current_bytes())
+    }
+
+    fn write_trak(track: &MuxTrack, track_id: u32, mdat_offset: u64) -> Result<BytesMut, Mp4Error> {
+        let duration = track.duration();
+
+        let mut tkhd = BytesWriter::new();
+        tkhd.write_u32::<BigEndian>(0)?;
+        tkhd.write_u32::<BigEndian>(0)?;
+        tkhd.write_u32::<BigEndian>(track_id)?;
+        tkhd.write_u32::<BigEndian>(0)?;
+        tkhd.write_u32::<BigEndian>(duration)?;
+        tkhd.write(&[0; 12])?;
+        tkhd.write_u16::<BigEndian>(if track.is_video() { 0 } else { 0x0100 })?;
+        tkhd.write_u16::<BigEndian>(0)?;
+        for value in MATRIX {
+            tkhd.write_u32::<BigEndian>(value)?;
+        }
+        tkhd.write_u32::<BigEndian>((track.width as u32) << 16)?;
+        tkhd.write_u32::<BigEndian>((track.height as u32) << 16)?;
+
+        let mut mdhd = BytesWriter::new();
+        mdhd.write_u32::<BigEndian>(0)?;
+        mdhd.write_u32::<BigEndian>(0)?;
+        mdhd.write_u32::<BigEndian>(TIMESCALE)?;
+        mdhd.write_u32::<BigEndian>(duration)?;
+        /*und*/
+        mdhd.write_u16::<BigEndian>(0x55C4)?;
+        mdhd.write_u16::<BigEndian>(0)?;
+
+        let (handler_type, handler_name, media_header) = if track.is_video() {
+            (
+                b"vide",
+                &b"VideoHandler\0"[..],
+                full_box_data(b"vmhd", 0, 1, &[0; 8])?,
+            )
+        } else {
+            (
+                b"soun",
+                &b"SoundHandler\0"[..],
+                full_box_data(b"smhd", 0, 0, &[0; 4])?,
+            )
+        };
+        let mut hdlr = BytesWriter::new();
+        hdlr.write_u32::<BigEndian>(0)?;
+        hdlr.write(handler_type)?;
+        hdlr.write(&[0; 12])?;
+        hdlr.write(handler_name)?;
+
+        let mut dref = BytesWriter::new();
+        dref.write_u32::<BigEndian>(1)?;
+        dref.write(&full_box_data(b"url ", 0, 1, &[])?)?;
+        let dinf = box_data(
+            b"dinf",
+            &full_box_data(b"dref", 0, 0, &dref.extract_current_bytes())?,
+        )?;
+
+        let minf = [media_header, dinf, Self::write_stbl(track, mdat_offset)?].concat();
+        let mdia = [
+            full_box_data(b"mdhd", 0, 0, &mdhd.extract_current_bytes())?,
+            full_box_data(b"hdlr", 0, 0, &hdlr.extract_current_bytes())?,
+            box_data(b"minf", &minf)?,
+        ]
+        .concat();
+        let trak = [
+            full_box_data(b"tkhd", 0, 3, &tkhd.extract_current_bytes())?,
+            box_data(b"mdia", &mdia)?,
+        ]
+        .concat();
+
+        box_data(b"trak", &trak)
+    }
+
+    fn write_sample_entry(track: &MuxTrack) -> Result<BytesMut, Mp4Error> {
+        let mut entry = BytesWriter::new();
+        /*reserved and the data reference index*/
+        entry.write(&[0; 6])?;
+        entry.write_u16::<BigEndian>(1)?;
+
+        match &track.codec {
+            Mp4Codec::H264 { avc_config } => {
+                entry.write(&[0; 16])?;
+                entry.write_u16::<BigEndian>(track.width)?;
+                entry.write_u16::<BigEndian>(track.height)?;
+                entry.write_u32::<BigEndian>(0x00480000)?;
+                entry.write_u32::<BigEndian>(0x00480000)?;
+                entry.write_u32::<BigEndian>(0)?;
+                entry.write_u16::<BigEndian>(1)?;
+                entry.write(&[0; 32])?;
+                entry.write_u16::<BigEndian>(0x0018)?;
+                entry.write_u16::<BigEndian>(0xFFFF)?;
+                entry.write(&box_data(b"avcC", avc_config)?)?;
+
+                box_data(b"avc1", &entry.extract_current_bytes())
+            }
+            Mp4Codec::Aac {
+                audio_specific_config,
+            } => {
+                let (sampling_frequency, channels) = match audio_specific_config.get(..2) {
+                    Some(config) => (
+                        SAMPLING_FREQUENCIES
+                            .get((((config[0] & 0x07) << 1) | (config[1] >> 7)) as usize)
+                            .copied()
+                            .unwrap_or(44100),
+                        ((config[1] >> 3) & 0x0F) as u16,
+                    ),
+                    None => (44100, 2),
+                };
+
+                entry.write(&[0; 8])?;
+                entry.write_u16::<BigEndian>(channels)?;
+                entry.write_u16::<BigEndian>(16)?;
+                entry.write_u32::<BigEndian>(0)?;
+                entry.write_u32::<BigEndian>(sampling_frequency << 16)?;
+
+                let config_length = audio_specific_config.len() as u8;
+                let mut esds = BytesWriter::new();
+                /*ES_Descriptor*/
+                esds.write(&[0x03, 3 + 15 + config_length + 3])?;
+                esds.write_u16::<BigEndian>(0)?;
+                esds.write_u8(0)?;
+                /*DecoderConfigDescriptor of MPEG-4 audio*/
+                esds.write(&[0x04, 13 + 2 + config_length, 0x40, 0x15])?;
+                esds.write(&[0; 11])?;
+                /*DecoderSpecificInfo*/
+                esds.write(&[0x05, config_length])?;
+                esds.write(audio_specific_config)?;
+                /*SLConfigDescriptor*/
+                esds.write(&[0x06, 0x01, 0x02])?;
+                entry.write(&full_box_data(
+                    b"esds",
+                    0,
+                    0,
+                    &esds.extract_current_bytes(),
+                )?)?;
+
+                box_data(b"mp4a", &entry.extract_current_bytes())
+            }
+            Mp4Codec::Unsupported(codec) => {
+                box_data(&codec_box_type(codec), &entry.extract_current_bytes())
+            }
+        }
+    }
+
+    /*every sample is a chunk of its own*/
+    fn write_stbl(track: &MuxTrack, mdat_offset: u64) -> Result<BytesMut, Mp4Error> {
+        let mut stsd = BytesWriter::new();
+        stsd.write_u32::<BigEndian>(1)?;
+        stsd.write(&Self::write_sample_entry(track)?)?;
+
+        let mut time_to_samples: Vec<(u32, u32)> = Vec::new();
+        for duration in track.sample_durations() {
+            match time_to_samples.last_mut() {
+                Some((count, delta)) if *delta == duration => *count += 1,
+                _ => time_to_samples.push((1, duration)),
+            }
+        }
+        let mut stts = BytesWriter::new();
+        stts.write_u32::<BigEndian>(time_to_samples.len() as u32)?;
+        for (count, delta) in time_to_samples {
+            stts.write_u32::<BigEndian>(count)?;
+            stts.write_u32::<BigEndian>(delta)?;
+        }
+
+        let mut stbl = BytesWriter::new();
+        stbl.write(&full_box_data(
+            b"stsd",
+            0,
+            0,
+            &stsd.extract_current_bytes(),
+        )?)?;
+        stbl.write(&full_box_data(
+            b"stts",
+            0,
+            0,
+            &stts.extract_current_bytes(),
+        )?)?;
+
+        if track
+            .samples
+            .iter()
+            .any(|sample| sample.composition_offset != 0)
+        {
+            let mut composition_offsets: Vec<(u32, i64)> = Vec::new();
+            for sample in &track.samples {
+                match composition_offsets.last_mut() {
+                    Some((count, offset)) if *offset == sample.composition_offset => *count += 1,
+                    _ => composition_offsets.push((1, sample.composition_offset)),
+                }
+            }
+            let mut ctts = BytesWriter::new();
+            ctts.write_u32::<BigEndian>(composition_offsets.len() as u32)?;
+            for (count, offset) in composition_offsets {
+                ctts.write_u32::<BigEndian>(count)?;
+                ctts.write_u32::<BigEndian>(offset as i32 as u32)?;
+            }
+            /*the version 1 offsets are signed*/
+            stbl.write(&full_box_data(
+                b"ctts",
+                1,
+                0,
+                &ctts.extract_current_bytes(),
+            )?)?;
+        }
+
+        if !track.samples.iter().all(|sample| sample.is_sync) {
+            let sync_samples: Vec<u32> = (1..)
+                .zip(&track.samples)
+                .filter(|(_, sample)| sample.is_sync)
+                .map(|(number, _)| number)
+                .collect();
+            let mut stss = BytesWriter::new();
+            stss.write_u32::<BigEndian>(sync_samples.len() as u32)?;
+            for number in sync_samples {
+                stss.write_u32::<BigEndian>(number)?;
+            }
+            stbl.write(&full_box_data(
+                b"stss",
+                0,
+                0,
+                &stss.extract_current_bytes(),
+            )?)?;
+        }
+
+        let mut stsc = BytesWriter::new();
+        for value in [1, 1, 1, 1] {
+            stsc.write_u32::<BigEndian>(value)?;
+        }
+        stbl.write(&full_box_data(
+            b"stsc",
+            0,
+            0,
+            &stsc.extract_current_bytes(),
+        )?)?;
+
+        let mut stsz = BytesWriter::new();
+        stsz.write_u32::<BigEndian>(0)?;
+        stsz.write_u32::<BigEndian>(track.samples.len() as u32)?;
+        for sample in &track.samples {
+            stsz.write_u32::<BigEndian>(sample.size)?;
+        }
+        stbl.write(&full_box_data(
+            b"stsz",
+            0,
+            0,
+            &stsz.extract_current_bytes(),
+        )?)?;
+
+        let large_offsets = track
+            .samples
+            .last()
+            .is_some_and(|sample| mdat_offset + sample.offset > u32::MAX as u64);
+        let mut chunk_offsets = BytesWriter::new();
+        chunk_offsets.write_u32::<BigEndian>(track.samples.len() as u32)?;
+        for sample in &track.samples {
+            if large_offsets {
+                chunk_offsets.write_u64::<BigEndian>(mdat_offset + sample.offset)?;
+            } else {
+                chunk_offsets.write_u32::<BigEndian>((mdat_offset + sample.offset) as u32)?;
+            }
+        }
+        let chunk_offset_type = if large_offsets { b"co64" } else { b"stco" };
+        stbl.write(&full_box_data(
+            chunk_offset_type,
+            0,
+            0,
+            &chunk_offsets.extract_current_bytes(),
+        )?)?;
+
+        box_data(b"stbl", &stbl.extract_current_bytes())
+    }
+}
+
+fn codec_box_type(codec: &str) -> [u8; 4] {
+    let mut box_type = [b' '; 4];
+    for (byte, cur_byte) in box_type.iter_mut().zip(codec.bytes()) {
+        *byte = cur_byte;
+    }
+    box_type
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::Mp4Muxer,
+        crate::{boxes::Mp4Codec, demuxer::Mp4Demuxer},
+        bytes::BytesMut,
+    };
+
+    #[test]
+    fn test_mux_and_demux() {
+        let mut muxer = Mp4Muxer::new();
+        let video = muxer.add_track(
+            Mp4Codec::H264 {
+                avc_config: BytesMut::from(&[1, 0x64, 0, 0x1F, 0xFF][..]),
+            },
+            1280,
+            720,
+        );
+        let audio = muxer.add_track(
+            Mp4Codec::Aac {
+                audio_specific_config: BytesMut::from(&[0x12, 0x10][..]),
+            },
+            0,
+            0,
+        );
+
+        muxer.write_sample(video, 0, 40, true, &[1; 10]).unwrap();
+        muxer.write_sample(audio, 0, 0, true, &[2; 5]).unwrap();
+        muxer.write_sample(audio, 21, 21, true, &[3; 5]).unwrap();
+        muxer.write_sample(video, 40, 120, false, &[4; 8]).unwrap();
+        muxer.write_sample(video, 80, 80, false, &[5; 8]).unwrap();
+
+        let mut demuxer = Mp4Demuxer::new(muxer.finish().unwrap()).unwrap();
+        assert!(
+            matches!(&demuxer.tracks[1].codec, Mp4Codec::Aac { audio_specific_config } if audio_specific_config[..] == [0x12, 0x10])
+        );
+
+        let mut frames = Vec::new();
+        while let Some(frame) = demuxer.read_frame().unwrap() {
+            frames.push((
+                frame.track_index,
+                frame.dts,
+                frame.pts,
+                frame.is_sync,
+                frame.data.to_vec(),
+            ));
+        }
+        assert_eq!(
+            frames,
+            vec![
+                (0, 0, 40, true, vec![1; 10]),
+                (1, 0, 0, true, vec![2; 5]),
+                (1, 21, 21, true, vec![3; 5]),
+                (0, 40, 120, false, vec![4; 8]),
+                (0, 80, 80, false, vec![5; 8]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_take_sample_data() {
+        let mut muxer = Mp4Muxer::new();
+        let audio = muxer.add_track(
+            Mp4Codec::Aac {
+                audio_specific_config: BytesMut::from(&[0x12, 0x10][..]),
+            },
+            0,
+            0,
+        );
+
+        muxer.write_sample(audio, 0, 0, true, &[1; 5]).unwrap();
+        let mut sample_data = muxer.take_sample_data();
+        muxer.write_sample(audio, 21, 21, true, &[2; 6]).unwrap();
+        sample_data.unsplit(muxer.take_sample_data());
+
+        let mut data = muxer.finish_header().unwrap();
+        data.unsplit(sample_data);
+        let mut demuxer = Mp4Demuxer::new(data).unwrap();
+        assert_eq!(demuxer.read_frame().unwrap().unwrap().data[..], [1; 5]);
+        assert_eq!(demuxer.read_frame().unwrap().unwrap().data[..], [2; 6]);
+        assert!(demuxer.read_frame().unwrap().is_none());
+    }
+}
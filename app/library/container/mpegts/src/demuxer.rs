@@ -0,0 +1,271 @@
+use {
+    super::{
+        define::{epat_pid, PTS_NO_VALUE, TS_PACKET_SIZE},
+        errors::{MpegTsError, MpegTsErrorValue},
+    },
+    bytes::BytesMut,
+    std::collections::HashMap,
+};
+
+const SYNC_BYTE: u8 = 0x47;
+
+/*an access unit of an elementary stream, the timestamps are in 90kHz*/
+pub struct TsFrame {
+    pub stream_type: u8,
+    pub pts: i64,
+    pub dts: i64,
+    pub data: BytesMut,
+}
+
+struct PesStream {
+    stream_type: u8,
+    pts: i64,
+    dts: i64,
+    data: BytesMut,
+    started: bool,
+}
+
+impl PesStream {
+    fn new(stream_type: u8) -> Self {
+        Self {
+            stream_type,
+            pts: PTS_NO_VALUE,
+            dts: PTS_NO_VALUE,
+            data: BytesMut::new(),
+            started: false,
+        }
+    }
+
+    fn take_frame(&mut self) -> Option<TsFrame> {
+        if !self.started || self.data.is_empty() {
+            return None;
+        }
+        self.started = false;
+
+        Some(TsFrame {
+            stream_type: self.stream_type,
+            pts: self.pts,
+            dts: self.dts,
+            data: self.data.split(),
+        })
+    }
+}
+
+/*Reads the elementary streams of the programs listed in the PAT. The PSI sections
+are expected to fit in one packet and the PES packets end at the next payload unit
+start of the same pid, which is how the muxers of the segments write them.*/
+pub struct TsDemuxer {
+    remaining: BytesMut,
+    pmt_pids: Vec<u16>,
+    streams: HashMap<u16, PesStream>,
+}
+
+impl Default for TsDemuxer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TsDemuxer {
+    pub fn new() -> Self {
+        Self {
+            remaining: BytesMut::new(),
+            pmt_pids: Vec::new(),
+            streams: HashMap::new(),
+        }
+    }
+
+    /*Returns the frames completed by the data, a partial packet is kept for the next call.*/
+    pub fn demux(&mut self, data: &[u8]) -> Result<Vec<TsFrame>, MpegTsError> {
+        self.remaining.extend_from_slice(data);
+
+        let mut frames = Vec::new();
+        while self.remaining.len() >= TS_PACKET_SIZE {
+            let packet = self.remaining.split_to(TS_PACKET_SIZE);
+            if packet[0] != SYNC_BYTE {
+                return Err(MpegTsError {
+                    value: MpegTsErrorValue::SyncByteError,
+                });
+            }
+            if let Some(frame) = self.read_packet(&packet) {
+                frames.push(frame);
+            }
+        }
+
+        Ok(frames)
+    }
+
+    /*The last frame of each stream is completed at the end of the data.*/
+    pub fn flush(&mut self) -> Vec<TsFrame> {
+        let mut pids: Vec<u16> = self.streams.keys().copied().collect();
+        pids.sort_unstable();
+
+        pids.iter()
+            .filter_map(|pid| self.streams.get_mut(pid)?.take_frame())
+            .collect()
+    }
+
+    fn read_packet(&mut self, packet: &[u8]) -> Option<TsFrame> {
+        let payload_unit_start = packet[1] & 0x40 > 0;
+        let pid = (((packet[1] & 0x1F) as u16) << 8) | packet[2] as u16;
+        let adaptation_field_control = (packet[3] >> 4) & 0x03;
+
+        if adaptation_field_control & 0x01 == 0 {
+            return None;
+        }
+        let mut offset = 4;
+        if adaptation_field_control & 0x02 > 0 {
+            offset += 1 + packet[4] as usize;
+        }
+        let payload = packet.get(offset..)?;
+
+        if pid == epat_pid::PAT_TID_PAS {
+            self.read_pat(payload_unit_start, payload);
+            None
+        } else if self.pmt_pids.contains(&pid) {
+            self.read_pmt(payload_unit_start, payload);
+            None
+        } else {
+            self.read_pes(pid, payload_unit_start, payload)
+        }
+    }
+
+    /*skips the pointer field, returns the section without its CRC*/
+    fn psi_section(payload_unit_start: bool, payload: &[u8]) -> Option<&[u8]> {
+        if !payload_unit_start {
+            return None;
+        }
+        let section = payload.get(1 + *payload.first()? as usize..)?;
+        let section_length = (((section.get(1)? & 0x0F) as usize) << 8) | *section.get(2)? as usize;
+
+        section.get(..(3 + section_length).checked_sub(4)?)
+    }
+
+    fn read_pat(&mut self, payload_unit_start: bool, payload: &[u8]) {
+        let Some(section) = Self::psi_section(payload_unit_start, payload) else {
+            return;
+        };
+
+        for program in section.get(8..).unwrap_or_default().chunks_exact(4) {
+            let program_number = ((program[0] as u16) << 8) | program[1] as u16;
+            let pid = (((program[2] & 0x1F) as u16) << 8) | program[3] as u16;
+            if program_number != 0 && !self.pmt_pids.contains(&pid) {
+                self.pmt_pids.push(pid);
+            }
+        }
+    }
+
+    fn read_pmt(&mut self, payload_unit_start: bool, payload: &[u8]) {
+        let Some(section) = Self::psi_section(payload_unit_start, payload) else {
+            return;
+        };
+        let Some(program_info) = section.get(10..12) else {
+            return;
+        };
+        let program_info_length =
+            (((program_info[0] & 0x0F) as usize) << 8) | program_info[1] as usize;
+
+        let mut offset = 12 + program_info_length;
+        while let Some(stream) = section.get(offset..offset + 5) {
+            let stream_type = stream[0];
+            let pid = (((stream[1] & 0x1F) as u16) << 8) | stream[2] as u16;
+            let es_info_length = (((stream[3] & 0x0F) as usize) << 8) | stream[4] as usize;
+
+            self.streams
+                .entry(pid)
+                .or_insert_with(|| PesStream::new(stream_type));
+            offset += 5 + es_info_length;
+        }
+    }
+
+    fn read_pes(&mut self, pid: u16, payload_unit_start: bool, payload: &[u8]) -> Option<TsFrame> {
+        let stream = self.streams.get_mut(&pid)?;
+
+        if !payload_unit_start {
+            if stream.started {
+                stream.data.extend_from_slice(payload);
+            }
+            return None;
+        }
+
+        let frame = stream.take_frame();
+
+        if payload.len() < 9 || payload[..3] != [0x00, 0x00, 0x01] {
+            return frame;
+        }
+        let pts_dts_flags = payload[7] >> 6;
+        let header_data_length = payload[8] as usize;
+
+        stream.pts = PTS_NO_VALUE;
+        stream.dts = PTS_NO_VALUE;
+        if pts_dts_flags & 0x02 > 0 {
+            stream.pts = payload.get(9..14).map_or(PTS_NO_VALUE, read_timestamp);
+            stream.dts = stream.pts;
+        }
+        if pts_dts_flags == 0x03 {
+            stream.dts = payload.get(14..19).map_or(PTS_NO_VALUE, read_timestamp);
+        }
+
+        if let Some(data) = payload.get(9 + header_data_length..) {
+            stream.data.extend_from_slice(data);
+            stream.started = true;
+        }
+
+        frame
+    }
+}
+
+fn read_timestamp(data: &[u8]) -> i64 {
+    (((data[0] >> 1) & 0x07) as i64) << 30
+        | (data[1] as i64) << 22
+        | ((data[2] >> 1) as i64) << 15
+        | (data[3] as i64) << 7
+        | (data[4] >> 1) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::TsDemuxer,
+        crate::{define::epsi_stream_type, ts::TsMuxer},
+        bytes::BytesMut,
+    };
+
+    #[test]
+    fn test_demux_muxed_frames() {
+        let mut muxer = TsMuxer::new();
+        let video_pid = muxer
+            .add_stream(epsi_stream_type::PSI_STREAM_H264, BytesMut::new())
+            .unwrap();
+        let audio_pid = muxer
+            .add_stream(epsi_stream_type::PSI_STREAM_AAC, BytesMut::new())
+            .unwrap();
+
+        let video_data = [0x00, 0x00, 0x00, 0x01, 0x65, 0x88].repeat(100);
+        let audio_data = BytesMut::from(&[0xFF, 0xF1, 0x50, 0x80, 0x02, 0x1F, 0xFC][..]);
+        muxer
+            .write(video_pid, 3600, 0, 0, BytesMut::from(&video_data[..]))
+            .unwrap();
+        muxer
+            .write(audio_pid, 1800, 1800, 0, audio_data.clone())
+            .unwrap();
+        muxer
+            .write(video_pid, 7200, 3600, 0, BytesMut::from(&video_data[..]))
+            .unwrap();
+
+        let mut demuxer = TsDemuxer::new();
+        let mut frames = demuxer.demux(&muxer.get_data()).unwrap();
+        frames.extend(demuxer.flush());
+
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].stream_type, epsi_stream_type::PSI_STREAM_H264);
+        assert_eq!((frames[0].pts, frames[0].dts), (3600, 0));
+        /*an access unit delimiter is added by the muxer*/
+        assert!(frames[0].data.ends_with(&video_data));
+        /*the audio frame is completed at the end*/
+        assert_eq!((frames[1].pts, frames[1].dts), (7200, 3600));
+        assert_eq!(frames[2].stream_type, epsi_stream_type::PSI_STREAM_AAC);
+        assert_eq!((frames[2].pts, frames[2].dts), (1800, 1800));
+        assert_eq!(&frames[2].data[..], &audio_data[..]);
+    }
+}
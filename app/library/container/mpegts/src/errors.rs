@@ -27,6 +27,9 @@ pub enum MpegTsErrorValue {
 
     #[fail(display = "stream not found")]
     StreamNotFound,
+
+    #[fail(display = "sync byte error")]
+    SyncByteError,
 }
 #[derive(Debug)]
 pub struct MpegTsError {
@@ -0,0 +1,84 @@
+use {
+    super::errors::MpegTsError, byteorder::BigEndian, bytes::BytesMut,
+    bytesio::bytes_writer::BytesWriter,
+};
+
+/*text encoding of the frames: UTF-8*/
+const ID3_ENCODING_UTF8: u8 = 0x03;
+
+//An ID3v2.4 tag which only contains user defined text (TXXX) frames,
+//it is carried as timed metadata in the ts segments.
+#[derive(Default)]
+pub struct Id3Tag {
+    frames: Vec<(String, String)>,
+}
+
+impl Id3Tag {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_text(&mut self, description: &str, value: &str) {
+        self.frames
+            .push((description.to_string(), value.to_string()));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn marshal(&self) -> Result<BytesMut, MpegTsError> {
+        let mut frames_writer = BytesWriter::new();
+
+        for (description, value) in &self.frames {
+            /*encoding + description + terminator + value*/
+            let frame_size = 1 + description.len() + 1 + value.len();
+
+            frames_writer.write(b"TXXX")?;
+            frames_writer.write_u32::<BigEndian>(syncsafe(frame_size as u32))?;
+            /*frame flags*/
+            frames_writer.write_u16::<BigEndian>(0x0000)?;
+            frames_writer.write_u8(ID3_ENCODING_UTF8)?;
+            frames_writer.write(description.as_bytes())?;
+            frames_writer.write_u8(0x00)?;
+            frames_writer.write(value.as_bytes())?;
+        }
+
+        let mut writer = BytesWriter::new();
+        writer.write(b"ID3")?;
+        /*version 2.4.0*/
+        writer.write_u8(0x04)?;
+        writer.write_u8(0x00)?;
+        /*flags*/
+        writer.write_u8(0x00)?;
+        writer.write_u32::<BigEndian>(syncsafe(frames_writer.len() as u32))?;
+        writer.append(&mut frames_writer);
+
+        Ok(writer.extract_current_bytes())
+    }
+}
+
+/*7 bits per byte, the most significant bit is always zero.*/
+fn syncsafe(size: u32) -> u32 {
+    (size & 0x7F) | ((size & 0x3F80) << 1) | ((size & 0x1F_C000) << 2) | ((size & 0x0FE0_0000) << 3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{syncsafe, Id3Tag};
+
+    #[test]
+    fn test_id3_tag() {
+        assert_eq!(syncsafe(0x7F), 0x7F);
+        assert_eq!(syncsafe(0x80), 0x0100);
+        assert_eq!(syncsafe(0x3FFF), 0x7F7F);
+
+        let mut tag = Id3Tag::new();
+        tag.add_text("blob_id", "abc");
+        let data = tag.marshal().unwrap();
+
+        assert_eq!(&data[..10], b"ID3\x04\x00\x00\x00\x00\x00\x16");
+        assert_eq!(&data[10..20], b"TXXX\x00\x00\x00\x0C\x00\x00");
+        assert_eq!(&data[20..], b"\x03blob_id\x00abc");
+    }
+}
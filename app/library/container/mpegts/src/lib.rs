@@ -1,6 +1,8 @@
 pub mod crc32;
 pub mod define;
+pub mod demuxer;
 pub mod errors;
+pub mod id3;
 pub mod ts;
 pub mod pat;
 pub mod pes;
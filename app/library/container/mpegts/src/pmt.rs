@@ -90,8 +90,7 @@ impl PmtMuxer {
             tmp_bytes_writer.write_u8(stream_type)?;
             /*elementary_PID*/
             tmp_bytes_writer.write_u16::<BigEndian>(0xE000 | stream.pid)?;
-            /*ES_info_length*/
-            tmp_bytes_writer.write_u16::<BigEndian>(0xF000)?;
+            self.write_descriptor(&mut tmp_bytes_writer, stream)?;
         }
 
         /*section_length*/
@@ -108,7 +107,65 @@ impl PmtMuxer {
         Ok(self.bytes_writer.extract_current_bytes())
     }
 
-    pub fn write_descriptor(&mut self) -> Result<(), MpegTsError> {
+    pub fn write_descriptor(
+        &mut self,
+        writer: &mut BytesWriter,
+        stream: &pes::Pes,
+    ) -> Result<(), MpegTsError> {
+        /*ES_info_length*/
+        let es_info_length = stream.esinfo.len() as u16;
+        writer.write_u16::<BigEndian>(0xF000 | es_info_length)?;
+
+        if es_info_length > 0 {
+            writer.write(&stream.esinfo[..])?;
+        }
         Ok(())
     }
 }
+
+//the es info of an opus stream: a registration descriptor with "Opus"
+//followed by an extension descriptor carrying the channel config code.
+pub fn opus_descriptors(channel_count: u8) -> Result<BytesMut, MpegTsError> {
+    let mut writer = BytesWriter::new();
+
+    /*registration_descriptor*/
+    writer.write_u8(0x05)?;
+    writer.write_u8(0x04)?;
+    writer.write(b"Opus")?;
+
+    /*extension_descriptor*/
+    writer.write_u8(0x7F)?;
+    writer.write_u8(0x02)?;
+    /*descriptor_tag_extension*/
+    writer.write_u8(0x80)?;
+    /*channel_config_code, 1~8 channels with the vorbis channel order*/
+    let channel_config_code = match channel_count {
+        1..=8 => channel_count,
+        _ => 2,
+    };
+    writer.write_u8(channel_config_code)?;
+
+    Ok(writer.extract_current_bytes())
+}
+
+//the es info of a timed metadata stream carrying id3 tags, see the
+//metadata_descriptor of ISO/IEC 13818-1 2.6.60 and the apple timed metadata spec.
+pub fn id3_metadata_descriptor() -> Result<BytesMut, MpegTsError> {
+    let mut writer = BytesWriter::new();
+
+    /*metadata_descriptor*/
+    writer.write_u8(0x26)?;
+    writer.write_u8(0x0D)?;
+    /*metadata_application_format, 0xFFFF means the identifier follows*/
+    writer.write_u16::<BigEndian>(0xFFFF)?;
+    writer.write(b"ID3 ")?;
+    /*metadata_format, 0xFF means the identifier follows*/
+    writer.write_u8(0xFF)?;
+    writer.write(b"ID3 ")?;
+    /*metadata_service_id*/
+    writer.write_u8(0x00)?;
+    /*decoder_config_flags 000, DSM-CC_flag 0, reserved 1111*/
+    writer.write_u8(0x0F)?;
+
+    Ok(writer.extract_current_bytes())
+}
@@ -1,7 +1,7 @@
 use {
     super::{
         define,
-        define::{epat_pid, epes_stream_id, ts},
+        define::{epat_pid, epes_stream_id, epsi_stream_type, ts},
         errors::{MpegTsError, MpegTsErrorValue},
         pat, pes,
         pes::PesMuxer,
@@ -23,6 +23,8 @@ pub struct TsMuxer {
     pat: pat::Pat,
     cur_pmt_index: usize,
     cur_stream_index: usize,
+    /*the length of the PAT/PMT written along with the last frame*/
+    pub pat_pmt_size: usize,
 
     packet_number: usize,
 }
@@ -46,6 +48,7 @@ impl TsMuxer {
             pat: pat::Pat::new(),
             cur_pmt_index: 0,
             cur_stream_index: 0,
+            pat_pmt_size: 0,
             packet_number: 0,
         }
     }
@@ -103,8 +106,18 @@ impl TsMuxer {
             cur_stream.data_alignment_indicator = 0; // idr frame
         }
 
+        let payload = if cur_stream.codec_id == epsi_stream_type::PSI_STREAM_AUDIO_OPUS {
+            let mut opus_payload = utils::opus_control_header(payload.len())?;
+            opus_payload.extend_from_slice(&payload[..]);
+            opus_payload
+        } else {
+            payload
+        };
+
+        self.pat_pmt_size = 0;
         if 0 == self.pat_period || (self.pat_period + define::PAT_PERIOD) <= dts {
             self.pat_period = dts;
+            let pat_pmt_offset = self.bytes_writer.len();
             let pat_data = pat::PatMuxer::new().write(self.pat.clone())?;
 
             self.write_ts_header_for_pat_pmt(
@@ -125,6 +138,7 @@ impl TsMuxer {
                 self.pmt_continuity_counter = (self.pmt_continuity_counter + 1) % 16;
                 self.packet_number += 1;
             }
+            self.pat_pmt_size = self.bytes_writer.len() - pat_pmt_offset;
         }
 
         self.write_pes(payload)?;
@@ -383,6 +397,9 @@ impl TsMuxer {
 
         if utils::is_steam_type_video(codecid) {
             cur_stream.stream_id = epes_stream_id::PES_SID_VIDEO;
+        } else if codecid == epsi_stream_type::PSI_STREAM_AUDIO_OPUS {
+            /*opus is carried as private data*/
+            cur_stream.stream_id = epes_stream_id::PES_SID_PRIVATE_1;
         } else if utils::is_steam_type_audio(codecid) {
             cur_stream.stream_id = epes_stream_id::PES_SID_AUDIO;
         } else {
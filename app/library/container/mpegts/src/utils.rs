@@ -1,5 +1,6 @@
 use {
     super::define::epsi_stream_type,
+    bytes::BytesMut,
     bytesio::{bytes_errors::BytesWriteError, bytes_writer::BytesWriter},
 };
 
@@ -17,6 +18,23 @@ pub fn pcr_write(pcr_result: &mut BytesWriter, pcr: i64) -> Result<(), BytesWrit
     Ok(())
 }
 
+//every opus access unit in the pes payload is prefixed with a control header:
+//0x3ff prefix(11 bits), no trim/extension flags, then the au size as a run of 0xff bytes.
+pub fn opus_control_header(au_size: usize) -> Result<BytesMut, BytesWriteError> {
+    let mut writer = BytesWriter::new();
+    writer.write_u8(0x7F)?;
+    writer.write_u8(0xE0)?;
+
+    let mut size = au_size;
+    while size >= 0xFF {
+        writer.write_u8(0xFF)?;
+        size -= 0xFF;
+    }
+    writer.write_u8(size as u8)?;
+
+    Ok(writer.extract_current_bytes())
+}
+
 pub fn is_steam_type_video(stream_type: u8) -> bool {
     matches!(stream_type, epsi_stream_type::PSI_STREAM_H264)
 }
@@ -7,7 +7,7 @@ use crate::utils;
 
 use {
     super::errors::StreamHubError,
-    crate::statistics::StatisticsStream,
+    crate::statistics::{StatisticRelay, StatisticsStream},
     crate::stream::StreamIdentifier,
     async_trait::async_trait,
     bytes::BytesMut,
@@ -16,7 +16,7 @@ use {
     serde::Serializer,
     std::fmt,
     std::sync::Arc,
-    tokio::sync::{broadcast, mpsc, oneshot},
+    tokio::sync::{broadcast, mpsc, oneshot, Mutex},
     utils::Uuid,
 };
 
@@ -71,12 +71,47 @@ impl Serialize for SubscriberInfo {
     }
 }
 
+/* What the hub does with a second publisher of a stream which is being published */
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PublishConflictPolicy {
+    /* Reject the newcomer. */
+    #[default]
+    Reject,
+    /* Kick the former publisher and let the newcomer take over. */
+    Replace,
+    /* Keep the newcomer as a hot standby, it takes over once the former publisher leaves. */
+    Queue,
+}
+
+#[derive(Debug, Serialize, Clone, Eq, PartialEq)]
+pub enum PublishConflictOutcome {
+    Rejected,
+    Replaced,
+    Queued,
+    /* The standby publisher took over. */
+    Promoted,
+}
+
+/* Why the server closed a stalled session */
+#[derive(Debug, Serialize, Clone, Copy, Eq, PartialEq)]
+pub enum SessionTimeoutReason {
+    /* Nothing was received, e.g. the connection is half-open. */
+    Idle,
+    /* The publisher sent no audio or video. */
+    NoMedia,
+    /* The publisher sent no video keyframe. */
+    NoKeyframe,
+}
+
 #[derive(Debug, Clone)]
 pub struct PublisherInfo {
     pub id: Uuid,
     pub pub_type: PublishType,
     pub pub_data_type: PubDataType,
     pub notify_info: NotifyInfo,
+    /* A backup publisher waits as a hot standby and takes over if the publisher stalls or leaves. */
+    pub backup: bool,
 }
 
 impl Serialize for PublisherInfo {
@@ -84,12 +119,13 @@ impl Serialize for PublisherInfo {
     where
         S: Serializer,
     {
-        // 3 is the number of fields in the struct.
-        let mut state = serializer.serialize_struct("PublisherInfo", 3)?;
+        // 4 is the number of fields in the struct.
+        let mut state = serializer.serialize_struct("PublisherInfo", 4)?;
 
         state.serialize_field("id", &self.id.to_string())?;
         state.serialize_field("pub_type", &self.pub_type)?;
         state.serialize_field("notify_info", &self.notify_info)?;
+        state.serialize_field("backup", &self.backup)?;
         state.end()
     }
 }
@@ -113,6 +149,8 @@ pub enum FrameData {
     Audio { timestamp: u32, data: BytesMut },
     MetaData { timestamp: u32, data: BytesMut },
     MediaInfo { media_info: MediaInfo },
+    /* Another publisher took over the stream, its timestamps go on from the former one. */
+    Discontinuity {},
 }
 
 //Used to pass rtp raw data.
@@ -175,7 +213,10 @@ pub type PubEventExecuteResultSender = oneshot::Sender<
 // The trait bound `BroadcastEvent: Clone` should be satisfied, so here we cannot use oneshot.
 pub type BroadcastEventExecuteResultSender = mpsc::Sender<Result<(), StreamHubError>>;
 pub type ApiRelayStreamResultSender = oneshot::Sender<Result<(), StreamHubError>>;
+pub type ApiClipStreamResultSender = oneshot::Sender<Result<(), StreamHubError>>;
 pub type TransceiverEventExecuteResultSender = oneshot::Sender<StatisticDataSender>;
+//shared by the stream hub and the relay client
+pub type RelayStatistics = Arc<Mutex<StatisticRelay>>;
 
 #[async_trait]
 pub trait TStreamHandler: Send + Sync {
@@ -232,6 +273,19 @@ pub enum StreamHubEventMessage {
         identifier: StreamIdentifier,
         info: PublisherInfo,
     },
+    Relay {
+        relay: StatisticRelay,
+    },
+    PublishConflict {
+        identifier: StreamIdentifier,
+        info: PublisherInfo,
+        outcome: PublishConflictOutcome,
+    },
+    SessionTimeout {
+        identifier: StreamIdentifier,
+        info: NotifyInfo,
+        reason: SessionTimeoutReason,
+    },
     NotSupport {},
 }
 
@@ -290,6 +344,51 @@ pub enum StreamHubEvent {
         description: String,
         result_sender: StatisticApiResultSender,    
     },
+    /*the VOD edits are added as new VOD streams, the sources are kept*/
+    #[serde(skip_serializing)]
+    ApiTrimVod {
+        index: u64,
+        /*the segment range, the end is excluded*/
+        start: usize,
+        end: usize,
+        result_sender: StatisticApiResultSender,
+    },
+    #[serde(skip_serializing)]
+    ApiConcatVod {
+        indexes: Vec<u64>,
+        name: String,
+        description: String,
+        result_sender: StatisticApiResultSender,
+    },
+    #[serde(skip_serializing)]
+    ApiUpdateVodInfo {
+        index: u64,
+        name: String,
+        description: String,
+        result_sender: StatisticApiResultSender,
+    },
+    #[serde(skip_serializing)]
+    ApiSetVodListed {
+        index: u64,
+        listed: bool,
+        result_sender: StatisticApiResultSender,
+    },
+    /*the playlist is cleared on chain, the recorded blobs are permanent*/
+    #[serde(skip_serializing)]
+    ApiDeleteVod {
+        index: u64,
+        result_sender: StatisticApiResultSender,
+    },
+    #[serde(skip_serializing)]
+    ApiClipStream {
+        identifier: StreamIdentifier,
+        /*seconds from the clip start to the live edge*/
+        start: u64,
+        duration: u64,
+        name: String,
+        description: String,
+        result_sender: ApiClipStreamResultSender,
+    },
     #[serde(skip_serializing)]
     ApiKickClient { id: Uuid },
     #[serde(skip_serializing)]
@@ -306,6 +405,31 @@ pub enum StreamHubEvent {
         relay_type: RelayType,
         result_sender: ApiRelayStreamResultSender,
     },
+    /*the timers of the streams pulled from the origin on demand*/
+    #[serde(skip_serializing)]
+    OriginPullTimeout { identifier: StreamIdentifier },
+    #[serde(skip_serializing)]
+    EdgeIdleCheck { identifier: StreamIdentifier },
+    /*the publisher sent no media for the stall timeout*/
+    #[serde(skip_serializing)]
+    PublisherStalled {
+        identifier: StreamIdentifier,
+        id: Uuid,
+    },
+    /*the server sessions report the stalled sessions before closing them*/
+    #[serde(skip_serializing)]
+    SessionTimeout {
+        identifier: StreamIdentifier,
+        info: NotifyInfo,
+        reason: SessionTimeoutReason,
+    },
+    /*the relay clients report the state changes of the relays*/
+    #[serde(skip_serializing)]
+    RelayStateChange { statistics: RelayStatistics },
+    #[serde(skip_serializing)]
+    ApiQueryRelays {
+        result_sender: StatisticApiResultSender,
+    },
     #[serde(skip_serializing)]
     Request {
         identifier: StreamIdentifier,
@@ -389,6 +513,8 @@ pub enum BroadcastEvent {
         id: String,
         identifier: StreamIdentifier,
         server_address: Option<String>,
+        /*only the pulls started by the api have statistics*/
+        statistics: Option<RelayStatistics>,
         result_sender: Option<BroadcastEventExecuteResultSender>,
     },
     UnSubscribe {
@@ -397,6 +523,27 @@ pub enum BroadcastEvent {
         //identifier: StreamIdentifier,
         //server_address: Option<String>,
     },
+    /*Push a local stream to a rtmp url, started by the api*/
+    StartPush {
+        id: String,
+        identifier: StreamIdentifier,
+        url: String,
+        statistics: RelayStatistics,
+        result_sender: BroadcastEventExecuteResultSender,
+    },
+    StopPush {
+        id: String,
+        result_sender: BroadcastEventExecuteResultSender,
+    },
+    /*Register the recent segments of a live stream as a VOD*/
+    Clip {
+        identifier: StreamIdentifier,
+        start: u64,
+        duration: u64,
+        name: String,
+        description: String,
+        result_sender: BroadcastEventExecuteResultSender,
+    },
 }
 
 pub enum StatisticData {
@@ -426,6 +573,10 @@ pub enum StatisticData {
         is_key_frame: Option<bool>,
         duration: usize,
     },
+    /*measured by the hls remuxer when it cuts the segments, milliseconds*/
+    GopDuration {
+        duration: i64,
+    },
     Publisher {
         id: Uuid,
         remote_addr: String,
@@ -21,6 +21,8 @@ pub enum StreamHubErrorValue {
     SendVideoError,
     #[fail(display = "send audio error")]
     SendAudioError,
+    #[fail(display = "send metadata error")]
+    SendMetaDataError,
     #[fail(display = "bytes read error")]
     BytesReadError(BytesReadError),
     #[fail(display = "bytes write error")]
@@ -31,6 +33,14 @@ pub enum StreamHubErrorValue {
     RecvError(RecvError),
     #[fail(display = "Serde json error")]
     SerdeError(Error),
+    #[fail(display = "no hls session of the stream")]
+    NoHlsSession,
+    #[fail(display = "clip error: {}", _0)]
+    ClipError(String),
+    #[fail(display = "relay not found: {}", _0)]
+    RelayNotFound(String),
+    #[fail(display = "relay error: {}", _0)]
+    RelayError(String),
 }
 #[derive(Debug)]
 pub struct StreamHubError {
@@ -0,0 +1,309 @@
+use {
+    crate::{
+        define::{
+            DataSender, FrameData, FrameDataReceiver, FrameDataSender, InformationSender,
+            StreamHubEvent, StreamHubEventSender, SubscribeType, TStreamHandler,
+        },
+        errors::StreamHubError,
+        statistics::StatisticsStream,
+        stream::StreamIdentifier,
+        utils::Uuid,
+    },
+    async_trait::async_trait,
+    std::{sync::Arc, time::Duration},
+    tokio::{
+        sync::{mpsc, watch, Mutex},
+        time::{self, Instant},
+    },
+    xflv::define::{aac_packet_type, audio_packet_type, avc_packet_type, SoundFormat},
+};
+
+//the media of a publisher which takes over a stream
+pub struct FrameSource {
+    pub id: Uuid,
+    pub receiver: FrameDataReceiver,
+    pub stream_handler: Arc<dyn TStreamHandler>,
+}
+
+pub type FrameSourceSender = mpsc::UnboundedSender<FrameSource>;
+pub type FrameSourceReceiver = mpsc::UnboundedReceiver<FrameSource>;
+
+/*Serves the prior data of the current publisher of a stream to its new
+subscribers.*/
+pub struct SwitchableStreamHandler {
+    stream_handler: Mutex<Arc<dyn TStreamHandler>>,
+}
+
+impl SwitchableStreamHandler {
+    pub fn new(stream_handler: Arc<dyn TStreamHandler>) -> Self {
+        Self {
+            stream_handler: Mutex::new(stream_handler),
+        }
+    }
+
+    async fn set(&self, stream_handler: Arc<dyn TStreamHandler>) {
+        *self.stream_handler.lock().await = stream_handler;
+    }
+
+    async fn get(&self) -> Arc<dyn TStreamHandler> {
+        self.stream_handler.lock().await.clone()
+    }
+}
+
+#[async_trait]
+impl TStreamHandler for SwitchableStreamHandler {
+    async fn send_prior_data(
+        &self,
+        sender: DataSender,
+        sub_type: SubscribeType,
+    ) -> Result<(), StreamHubError> {
+        self.get().await.send_prior_data(sender, sub_type).await
+    }
+    async fn get_statistic_data(&self) -> Option<StatisticsStream> {
+        self.get().await.get_statistic_data().await
+    }
+    async fn send_information(&self, sender: InformationSender) {
+        self.get().await.send_information(sender).await
+    }
+}
+
+/*the sequence headers and the metadata keep the timestamps of when they were
+received, they do not start the timeline of a publisher*/
+fn is_header(data: &FrameData) -> bool {
+    match data {
+        FrameData::MetaData { .. } => true,
+        FrameData::Audio { data, .. } if !data.is_empty() => {
+            if data[0] >> 4 == SoundFormat::ExHeader as u8 {
+                data[0] & 0x0f == audio_packet_type::SEQUENCE_START
+            } else {
+                data.len() > 1
+                    && data[0] >> 4 == SoundFormat::AAC as u8
+                    && data[1] == aac_packet_type::AAC_SEQHDR
+            }
+        }
+        /*the enhanced RTMP sets the high bit and carries the packet type in the
+        lower 4 bits*/
+        FrameData::Video { data, .. } if !data.is_empty() => {
+            if data[0] & 0x80 != 0 {
+                data[0] & 0x0f == avc_packet_type::AVC_SEQHDR
+            } else {
+                data.len() > 1 && data[1] == avc_packet_type::AVC_SEQHDR
+            }
+        }
+        _ => false,
+    }
+}
+
+/*The timestamps of a publisher which takes over go on from the last ones of the
+former publisher, so the subscribers which ignore the discontinuity, like the RTMP
+players, keep an increasing timeline.*/
+#[derive(Default)]
+struct TimestampRebaser {
+    offset: u32,
+    last_timestamp: u32,
+    pending: bool,
+}
+
+impl TimestampRebaser {
+    fn switch(&mut self) {
+        self.pending = true;
+    }
+
+    fn rebase(&mut self, mut data: FrameData) -> FrameData {
+        let is_header = is_header(&data);
+        let timestamp = match &mut data {
+            FrameData::Video { timestamp, .. }
+            | FrameData::Audio { timestamp, .. }
+            | FrameData::MetaData { timestamp, .. } => timestamp,
+            _ => return data,
+        };
+
+        if self.pending {
+            /*the headers of the new publisher come before its first frame*/
+            if is_header {
+                *timestamp = self.last_timestamp;
+                return data;
+            }
+            self.offset = self.last_timestamp.wrapping_add(1).wrapping_sub(*timestamp);
+            self.pending = false;
+        }
+
+        *timestamp = timestamp.wrapping_add(self.offset);
+        self.last_timestamp = *timestamp;
+        data
+    }
+}
+
+/*Forwards the frames of the current publisher of a stream to its transceiver.
+When another publisher takes over, the subscribers receive a discontinuity and
+then the headers and the gop of the new publisher. A publisher which sends
+nothing for the stall timeout is reported to the hub while a standby publisher
+can take over.*/
+pub struct FrameForwarder {
+    identifier: StreamIdentifier,
+    source: FrameSource,
+    source_receiver: FrameSourceReceiver,
+    output: FrameDataSender,
+    stream_handler: Arc<SwitchableStreamHandler>,
+    hub_event_sender: StreamHubEventSender,
+    stall_timeout: Duration,
+    /*whether the stream has a standby publisher*/
+    standby_receiver: watch::Receiver<bool>,
+    rebaser: TimestampRebaser,
+}
+
+impl FrameForwarder {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        identifier: StreamIdentifier,
+        source: FrameSource,
+        source_receiver: FrameSourceReceiver,
+        output: FrameDataSender,
+        stream_handler: Arc<SwitchableStreamHandler>,
+        hub_event_sender: StreamHubEventSender,
+        stall_timeout: Duration,
+        standby_receiver: watch::Receiver<bool>,
+    ) -> Self {
+        Self {
+            identifier,
+            source,
+            source_receiver,
+            output,
+            stream_handler,
+            hub_event_sender,
+            stall_timeout,
+            standby_receiver,
+            rebaser: TimestampRebaser::default(),
+        }
+    }
+
+    fn forward(&mut self, data: FrameData) -> bool {
+        let data = self.rebaser.rebase(data);
+        self.output.send(data).is_ok()
+    }
+
+    async fn switch(&mut self, source: FrameSource) {
+        log::info!(
+            "stream {} is switched to the publisher {}",
+            self.identifier,
+            source.id
+        );
+        if self.output.send(FrameData::Discontinuity {}).is_err() {
+            log::error!("frame forwarder send discontinuity error");
+        }
+        self.stream_handler.set(source.stream_handler.clone()).await;
+        self.rebaser.switch();
+
+        /*the remuxers and the players restart from a keyframe of the new publisher*/
+        let (prior_sender, mut prior_receiver) = mpsc::unbounded_channel();
+        let sender = DataSender::Frame {
+            sender: prior_sender,
+        };
+        if let Err(err) = source
+            .stream_handler
+            .send_prior_data(sender, SubscribeType::RtmpRemux2Hls)
+            .await
+        {
+            log::error!("frame forwarder send prior data error: {}", err);
+        }
+        while let Ok(data) = prior_receiver.try_recv() {
+            if !self.forward(data) {
+                log::error!("frame forwarder send prior data error");
+                break;
+            }
+        }
+
+        /*the former publisher is kicked off once its receiver is dropped*/
+        self.source = source;
+    }
+
+    pub async fn run(mut self) {
+        let mut source_closed = false;
+        let mut deadline = Instant::now() + self.stall_timeout;
+
+        loop {
+            tokio::select! {
+                data = self.source.receiver.recv(), if !source_closed => match data {
+                    Some(data) => {
+                        deadline = Instant::now() + self.stall_timeout;
+                        if !self.forward(data) {
+                            break;
+                        }
+                    }
+                    /*the publisher left, wait for the hub to switch or unpublish the stream*/
+                    None => source_closed = true,
+                },
+                source = self.source_receiver.recv() => match source {
+                    Some(source) => {
+                        self.switch(source).await;
+                        source_closed = false;
+                        deadline = Instant::now() + self.stall_timeout;
+                    }
+                    /*the stream is unpublished*/
+                    None => break,
+                },
+                /*the stall is counted from when the standby publisher is registered*/
+                Ok(()) = self.standby_receiver.changed() => {
+                    deadline = Instant::now() + self.stall_timeout;
+                }
+                _ = time::sleep_until(deadline), if !source_closed && *self.standby_receiver.borrow() => {
+                    deadline = Instant::now() + self.stall_timeout;
+                    let event = StreamHubEvent::PublisherStalled {
+                        identifier: self.identifier.clone(),
+                        id: self.source.id,
+                    };
+                    if self.hub_event_sender.send(event).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::TimestampRebaser, crate::define::FrameData, bytes::BytesMut};
+
+    fn timestamp(data: FrameData) -> u32 {
+        match data {
+            FrameData::Video { timestamp, .. }
+            | FrameData::Audio { timestamp, .. }
+            | FrameData::MetaData { timestamp, .. } => timestamp,
+            _ => panic!("not a frame"),
+        }
+    }
+
+    fn video(timestamp: u32, data: &[u8]) -> FrameData {
+        FrameData::Video {
+            timestamp,
+            data: BytesMut::from(data),
+        }
+    }
+
+    #[test]
+    fn test_rebase_timestamps() {
+        let mut rebaser = TimestampRebaser::default();
+        assert_eq!(timestamp(rebaser.rebase(video(5000, &[0x17, 0x01]))), 5000);
+        assert_eq!(timestamp(rebaser.rebase(video(5040, &[0x27, 0x01]))), 5040);
+
+        /*the headers of the new publisher are received long before its gop*/
+        rebaser.switch();
+        assert_eq!(timestamp(rebaser.rebase(video(0, &[0x17, 0x00]))), 5040);
+        let audio_header = FrameData::Audio {
+            timestamp: 0,
+            data: BytesMut::from(&[0xaf, 0x00][..]),
+        };
+        assert_eq!(timestamp(rebaser.rebase(audio_header)), 5040);
+        assert_eq!(timestamp(rebaser.rebase(video(100, &[0x17, 0x01]))), 5041);
+        assert_eq!(timestamp(rebaser.rebase(video(140, &[0x27, 0x01]))), 5081);
+
+        /*a publisher whose timestamps wrap around*/
+        rebaser.switch();
+        assert_eq!(
+            timestamp(rebaser.rebase(video(u32::MAX - 10, &[0x17, 0x01]))),
+            5082
+        );
+        assert_eq!(timestamp(rebaser.rebase(video(29, &[0x27, 0x01]))), 5122);
+    }
+}
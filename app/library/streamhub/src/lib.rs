@@ -1,9 +1,10 @@
 use define::{
-    FrameDataReceiver, PacketDataReceiver, PacketDataSender, RelayType, StatisticData,
-    StatisticDataReceiver, StatisticDataSender,
+    FrameDataReceiver, PacketDataReceiver, PacketDataSender, PubDataType, PublishConflictOutcome,
+    PublishConflictPolicy, PublisherInfo, RelayStatistics, RelayType, StatisticData,
+    StatisticDataReceiver, StatisticDataSender, SubEventExecuteResultSender,
 };
 use serde_json::{json, Value};
-use statistics::{StatisticSubscriber, StatisticsStream};
+use statistics::{RelayState, StatisticRelay, StatisticSubscriber, StatisticsStream};
 use tokio::sync::oneshot;
 use xflv::define::aac_packet_type;
 
@@ -11,6 +12,7 @@ use crate::define::PacketData;
 
 pub mod define;
 pub mod errors;
+pub mod failover;
 pub mod notify;
 pub mod statistics;
 pub mod stream;
@@ -20,16 +22,21 @@ use {
     commonlib::{move_call, errors::SuiError},
     crate::notify::Notifier,
     define::{
-        BroadcastEvent, BroadcastEventReceiver, BroadcastEventSender, DataReceiver, DataSender,
-        FrameData, FrameDataSender, Information, StreamHubEvent, StreamHubEventReceiver,
-        StreamHubEventSender, SubscribeType, SubscriberInfo, TStreamHandler, TransceiverEvent,
-        TransceiverEventReceiver, TransceiverEventSender,
+        ApiClipStreamResultSender, ApiRelayStreamResultSender, BroadcastEvent,
+        BroadcastEventReceiver, BroadcastEventSender, DataReceiver, DataSender, FrameData,
+        FrameDataSender, Information, StatisticApiResultSender, StreamHubEvent,
+        StreamHubEventMessage, StreamHubEventReceiver, StreamHubEventSender, SubscribeType,
+        SubscriberInfo, TStreamHandler, TransceiverEvent, TransceiverEventReceiver,
+        TransceiverEventSender,
     },
     errors::{StreamHubError, StreamHubErrorValue},
-    std::collections::HashMap,
+    failover::{FrameForwarder, FrameSource, FrameSourceSender, SwitchableStreamHandler},
+    std::collections::{HashMap, HashSet, VecDeque},
     std::sync::Arc,
+    std::time::{Duration, Instant},
     stream::StreamIdentifier,
-    tokio::sync::{broadcast, mpsc, mpsc::UnboundedReceiver, Mutex},
+    tokio::sync::{broadcast, mpsc, mpsc::UnboundedReceiver, watch, Mutex},
+    tokio::task::JoinHandle,
     utils::Uuid,
 };
 
@@ -80,10 +87,17 @@ impl StreamDataTransceiver {
     ) {
         if let Some(val) = data {
             match val {
-                FrameData::MetaData {
-                    timestamp: _,
-                    data: _,
-                } => {}
+                FrameData::MetaData { timestamp, data } => {
+                    /*e.g. the timed text of onTextData/onCaption*/
+                    let data = FrameData::MetaData { timestamp, data };
+                    for (_, v) in frame_senders.lock().await.iter() {
+                        if let Err(meta_err) = v.send(data.clone()).map_err(|_| StreamHubError {
+                            value: StreamHubErrorValue::SendMetaDataError,
+                        }) {
+                            log::error!("Transmiter send error: {}", meta_err);
+                        }
+                    }
+                }
                 FrameData::Audio { timestamp, data } => {
                     let data: FrameData = FrameData::Audio {
                         timestamp,
@@ -125,6 +139,13 @@ impl StreamDataTransceiver {
                         }
                     }
                 }
+                FrameData::Discontinuity {} => {
+                    for (_, v) in frame_senders.lock().await.iter() {
+                        if v.send(FrameData::Discontinuity {}).is_err() {
+                            log::error!("Transmiter send discontinuity error");
+                        }
+                    }
+                }
             }
         }
     }
@@ -274,6 +295,9 @@ impl StreamDataTransceiver {
                         }
                     }
                 }
+                StatisticData::GopDuration { duration } => {
+                    statistics_data.lock().await.publisher.video.gop_duration = duration;
+                }
                 StatisticData::AudioCodec {
                     sound_format,
                     profile,
@@ -515,6 +539,103 @@ pub struct StreamsHub {
     hls_enabled: bool,
     //http notifier on sub/pub event
     notifier: Option<Arc<dyn Notifier>>,
+    //the relays started by the api
+    relays: HashMap<String, RelayStatistics>,
+    //the relays started by the configuration or by the players
+    static_relays: HashMap<String, RelayStatistics>,
+    //the players of an unknown stream wait for it to be pulled from the origin
+    pending_subscribes: HashMap<StreamIdentifier, Vec<PendingSubscribe>>,
+    //the streams pulled from the origin on demand
+    edge_pulls: HashMap<StreamIdentifier, EdgePull>,
+    //the pull from the origin is stopped after no players for the duration
+    edge_idle_timeout: Duration,
+    //the id of the publisher of each stream
+    publishers: HashMap<StreamIdentifier, Uuid>,
+    //the hot standby publishers of each stream, the first one takes over once the publisher leaves
+    standby_publishers: HashMap<StreamIdentifier, VecDeque<StandbyPublisher>>,
+    //what to do with a second publisher, the app policies take precedence
+    publish_conflict_policy: PublishConflictPolicy,
+    app_publish_conflict_policies: HashMap<String, PublishConflictPolicy>,
+    //the streams whose frames go through a forwarder
+    forwarded_streams: HashMap<StreamIdentifier, ForwardedStream>,
+    //a publisher which sends no media for the duration is switched to a standby one
+    stall_timeout: Duration,
+}
+
+//how long a player waits for the stream pulled from the origin
+const ORIGIN_PULL_WAIT: Duration = Duration::from_secs(15);
+const EDGE_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+const STALL_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct PendingSubscribe {
+    info: SubscriberInfo,
+    result_sender: SubEventExecuteResultSender,
+    start_time: Instant,
+}
+
+#[derive(Default)]
+struct EdgePull {
+    players: HashSet<Uuid>,
+    //when the last player left
+    idle_since: Option<Instant>,
+}
+
+struct ForwardedStream {
+    source_sender: FrameSourceSender,
+    statistic_data_sender: StatisticDataSender,
+    //the forwarder only watches for the stalls while the stream has a standby publisher
+    standby_sender: watch::Sender<bool>,
+}
+
+struct StandbyPublisher {
+    info: PublisherInfo,
+    stream_handler: Arc<dyn TStreamHandler>,
+    //stop dropping the media and give the receiver back
+    take_over_sender: oneshot::Sender<()>,
+    drainer: JoinHandle<DataReceiver>,
+}
+
+//the media of a standby publisher is dropped until it takes over
+async fn drain_standby_data(
+    receiver: DataReceiver,
+    mut take_over_receiver: oneshot::Receiver<()>,
+) -> DataReceiver {
+    let DataReceiver {
+        mut frame_receiver,
+        mut packet_receiver,
+    } = receiver;
+
+    loop {
+        tokio::select! {
+            Some(_) = async { frame_receiver.as_mut()?.recv().await } => {}
+            Some(_) = async { packet_receiver.as_mut()?.recv().await } => {}
+            _ = &mut take_over_receiver => break,
+        }
+    }
+
+    DataReceiver {
+        frame_receiver,
+        packet_receiver,
+    }
+}
+
+//the relay id of the pull from the origin
+fn edge_pull_id(identifier: &StreamIdentifier) -> String {
+    match identifier {
+        StreamIdentifier::Rtmp {
+            app_name,
+            stream_name,
+        } => format!("edge/{}/{}", app_name, stream_name),
+        StreamIdentifier::Unkonwn => String::from("edge"),
+    }
+}
+
+//the remuxers and the relays keep no edge pull alive
+fn is_player(sub_type: &SubscribeType) -> bool {
+    !matches!(
+        sub_type,
+        SubscribeType::RtmpRemux2Hls | SubscribeType::RtmpRelay
+    )
 }
 
 impl StreamsHub {
@@ -533,6 +654,17 @@ impl StreamsHub {
             rtmp_remuxer_enabled: false,
             hls_enabled: false,
             notifier,
+            relays: HashMap::new(),
+            static_relays: HashMap::new(),
+            pending_subscribes: HashMap::new(),
+            edge_pulls: HashMap::new(),
+            edge_idle_timeout: EDGE_IDLE_TIMEOUT,
+            publishers: HashMap::new(),
+            standby_publishers: HashMap::new(),
+            publish_conflict_policy: PublishConflictPolicy::default(),
+            app_publish_conflict_policies: HashMap::new(),
+            forwarded_streams: HashMap::new(),
+            stall_timeout: STALL_TIMEOUT,
         }
     }
     pub async fn run(&mut self) {
@@ -547,6 +679,26 @@ impl StreamsHub {
         self.rtmp_pull_enabled = enabled;
     }
 
+    pub fn set_edge_idle_timeout(&mut self, timeout: Duration) {
+        self.edge_idle_timeout = timeout;
+    }
+
+    pub fn set_publish_conflict_policy(&mut self, policy: PublishConflictPolicy) {
+        self.publish_conflict_policy = policy;
+    }
+
+    pub fn set_app_publish_conflict_policy(
+        &mut self,
+        app_name: String,
+        policy: PublishConflictPolicy,
+    ) {
+        self.app_publish_conflict_policies.insert(app_name, policy);
+    }
+
+    pub fn set_stall_timeout(&mut self, timeout: Duration) {
+        self.stall_timeout = timeout;
+    }
+
     pub fn set_rtmp_remuxer_enabled(&mut self, enabled: bool) {
         self.rtmp_remuxer_enabled = enabled;
     }
@@ -614,16 +766,66 @@ impl StreamsHub {
                         }
                     };
 
+                    if self.streams.contains_key(&identifier) {
+                        //a backup publisher always waits as a standby
+                        let policy = if info.backup {
+                            PublishConflictPolicy::Queue
+                        } else {
+                            self.get_publish_conflict_policy(&identifier)
+                        };
+                        let outcome = match policy {
+                            PublishConflictPolicy::Reject => PublishConflictOutcome::Rejected,
+                            PublishConflictPolicy::Replace => {
+                                //the former publisher is kicked off once its receiver is dropped
+                                if let Err(err) = self.unpublish(&identifier) {
+                                    log::error!("event_loop Publish replace err: {}", err);
+                                }
+                                //the replaced publisher leaves as a standby one, so it is notified here
+                                let replaced_message = self
+                                    .publishers
+                                    .remove(&identifier)
+                                    .and_then(|id| self.un_pub_sub_events.get(&id))
+                                    .map(|event| event.to_message());
+                                if let (Some(notifier), Some(replaced_message)) =
+                                    (&self.notifier, replaced_message)
+                                {
+                                    notifier.on_unpublish_notify(&replaced_message).await;
+                                }
+                                PublishConflictOutcome::Replaced
+                            }
+                            PublishConflictPolicy::Queue => PublishConflictOutcome::Queued,
+                        };
+                        self.notify_publish_conflict(&identifier, &info, outcome.clone())
+                            .await;
+
+                        if outcome == PublishConflictOutcome::Queued {
+                            self.queue_publisher(identifier, info, receiver, stream_handler);
+                            if result_sender
+                                .send(Ok((frame_sender, packet_sender, None)))
+                                .is_err()
+                            {
+                                log::error!("event_loop Publish error: The receiver dropped.")
+                            }
+                            continue;
+                        }
+                    }
+
                     let result = match self
-                        .publish(identifier.clone(), receiver, stream_handler)
+                        .publish(identifier.clone(), info.id, receiver, stream_handler)
                         .await
                     {
                         Ok(statistic_data_sender) => {
                             if let Some(notifier) = &self.notifier {
                                 notifier.on_publish_notify(&message).await;
                             }
-                            self.un_pub_sub_events
-                                .insert(info.id, StreamHubEvent::UnPublish { identifier, info });
+                            self.publishers.insert(identifier.clone(), info.id);
+                            self.un_pub_sub_events.insert(
+                                info.id,
+                                StreamHubEvent::UnPublish {
+                                    identifier: identifier.clone(),
+                                    info,
+                                },
+                            );
 
                             Ok((frame_sender, packet_sender, Some(statistic_data_sender)))
                         }
@@ -636,12 +838,33 @@ impl StreamsHub {
                     if result_sender.send(result).is_err() {
                         log::error!("event_loop Subscribe error: The receiver dropped.")
                     }
+
+                    //the players waiting for the stream pulled from the origin
+                    if let Some(pending_subscribes) = self.pending_subscribes.remove(&identifier) {
+                        for pending in pending_subscribes {
+                            self.on_subscribe(
+                                identifier.clone(),
+                                pending.info,
+                                pending.result_sender,
+                            )
+                            .await;
+                        }
+                    }
+                    self.check_edge_idle(&identifier);
                 }
 
-                StreamHubEvent::UnPublish {
-                    identifier,
-                    info: _,
-                } => {
+                StreamHubEvent::UnPublish { identifier, info } => {
+                    //a standby publisher or a replaced one leaves
+                    if self.publishers.get(&identifier) != Some(&info.id) {
+                        self.remove_standby_publisher(&identifier, info.id);
+                        continue;
+                    }
+                    //the subscribers are kept if a standby publisher takes over in place
+                    if self.switch_to_standby_publisher(&identifier).await {
+                        continue;
+                    }
+                    self.publishers.remove(&identifier);
+
                     if let Err(err) = self.unpublish(&identifier) {
                         log::error!(
                             "event_loop Unpublish err: {} with identifier: {}",
@@ -653,69 +876,51 @@ impl StreamsHub {
                     if let Some(notifier) = &self.notifier {
                         notifier.on_unpublish_notify(&message).await;
                     }
+
+                    self.promote_standby_publisher(&identifier).await;
                 }
                 StreamHubEvent::Subscribe {
                     identifier,
                     info,
                     result_sender,
                 } => {
-                    let sub_id = info.id;
-                    let info_clone = info.clone();
-
-                    //new chan for Frame/Packet sender and receiver
-                    let (sender, receiver) = match info.sub_data_type {
-                        define::SubDataType::Frame => {
-                            let (sender_chan, receiver_chan) = mpsc::unbounded_channel();
-                            (
-                                DataSender::Frame {
-                                    sender: sender_chan,
-                                },
-                                DataReceiver {
-                                    frame_receiver: Some(receiver_chan),
-                                    packet_receiver: None,
-                                },
-                            )
-                        }
-                        define::SubDataType::Packet => {
-                            let (sender_chan, receiver_chan) = mpsc::unbounded_channel();
-                            (
-                                DataSender::Packet {
-                                    sender: sender_chan,
-                                },
-                                DataReceiver {
-                                    frame_receiver: None,
-                                    packet_receiver: Some(receiver_chan),
-                                },
-                            )
-                        }
-                    };
-
-                    let rv = match self.subscribe(&identifier, info_clone, sender).await {
-                        Ok(statistic_data_sender) => {
-                            if let Some(notifier) = &self.notifier {
-                                notifier.on_play_notify(&message).await;
-                            }
-
-                            self.un_pub_sub_events
-                                .insert(sub_id, StreamHubEvent::UnSubscribe { identifier, info });
-                            Ok((receiver, Some(statistic_data_sender)))
-                        }
-                        Err(err) => {
-                            log::error!("event_loop Subscribe error: {}", err);
-                            Err(err)
-                        }
-                    };
-
-                    if result_sender.send(rv).is_err() {
-                        log::error!("event_loop Subscribe error: The receiver dropped.")
+                    if self.rtmp_pull_enabled && !self.streams.contains_key(&identifier) {
+                        self.pull_from_origin(identifier, info, result_sender);
+                    } else {
+                        self.on_subscribe(identifier, info, result_sender).await;
                     }
                 }
                 StreamHubEvent::UnSubscribe { identifier, info } => {
+                    let sub_id = info.id;
                     if self.unsubscribe(&identifier, info).is_ok() {
                         if let Some(notifier) = &self.notifier {
                             notifier.on_stop_notify(&message).await;
                         }
                     }
+
+                    if let Some(edge_pull) = self.edge_pulls.get_mut(&identifier) {
+                        if edge_pull.players.remove(&sub_id) {
+                            self.check_edge_idle(&identifier);
+                        }
+                    }
+                }
+                StreamHubEvent::OriginPullTimeout { identifier } => {
+                    self.on_origin_pull_timeout(&identifier);
+                }
+                StreamHubEvent::EdgeIdleCheck { identifier } => {
+                    self.on_edge_idle_check(&identifier);
+                }
+                StreamHubEvent::PublisherStalled { identifier, id } => {
+                    if self.publishers.get(&identifier) == Some(&id)
+                        && self.standby_publishers.contains_key(&identifier)
+                    {
+                        log::warn!(
+                            "publisher {} stalled, switch to the standby, identifier: {}",
+                            id,
+                            identifier
+                        );
+                        self.switch_to_standby_publisher(&identifier).await;
+                    }
                 }
 
                 StreamHubEvent::ApiStatistic {
@@ -790,6 +995,80 @@ impl StreamsHub {
                         log::error!("event_loop api error: {}", err);
                     }
                 }
+                /*the VOD edits wait for the Sui transactions, which must not block the event loop*/
+                StreamHubEvent::ApiTrimVod {
+                    index,
+                    start,
+                    end,
+                    result_sender,
+                } => {
+                    tokio::spawn(async move {
+                        let result = move_call::trim_vod_stream(index, start, end).await;
+                        Self::send_vod_result(result, result_sender);
+                    });
+                }
+                StreamHubEvent::ApiConcatVod {
+                    indexes,
+                    name,
+                    description,
+                    result_sender,
+                } => {
+                    tokio::spawn(async move {
+                        let result =
+                            move_call::concat_vod_streams(indexes, name, description).await;
+                        Self::send_vod_result(result, result_sender);
+                    });
+                }
+                StreamHubEvent::ApiUpdateVodInfo {
+                    index,
+                    name,
+                    description,
+                    result_sender,
+                } => {
+                    tokio::spawn(async move {
+                        let result =
+                            move_call::update_vod_stream_info(index, name, description).await;
+                        Self::send_vod_result(result, result_sender);
+                    });
+                }
+                StreamHubEvent::ApiSetVodListed {
+                    index,
+                    listed,
+                    result_sender,
+                } => {
+                    tokio::spawn(async move {
+                        let result = move_call::set_vod_stream_listed(index, listed)
+                            .await
+                            .map(|_| index);
+                        Self::send_vod_result(result, result_sender);
+                    });
+                }
+                StreamHubEvent::ApiDeleteVod {
+                    index,
+                    result_sender,
+                } => {
+                    tokio::spawn(async move {
+                        let result = move_call::delete_vod_stream(index).await.map(|_| index);
+                        Self::send_vod_result(result, result_sender);
+                    });
+                }
+                StreamHubEvent::ApiClipStream {
+                    identifier,
+                    start,
+                    duration,
+                    name,
+                    description,
+                    result_sender,
+                } => {
+                    self.api_clip_stream(
+                        identifier,
+                        start,
+                        duration,
+                        name,
+                        description,
+                        result_sender,
+                    );
+                }
                 StreamHubEvent::ApiKickClient { id } => {
                     if let Err(err) = self.api_kick_off_client(id) {
                         log::error!("api_kick_off_client api error: {}", err);
@@ -802,25 +1081,79 @@ impl StreamsHub {
                     relay_type,
                     result_sender,
                 } => {
-                    let result = self
-                        .api_start_relay_stream(id, &relay_type, identifier, server_address)
-                        .await;
-
-                    if let Err(err) = result_sender.send(result) {
-                        log::error!("event_loop api error: {:?}", err);
-                    }
+                    self.api_start_relay_stream(
+                        id,
+                        &relay_type,
+                        identifier,
+                        server_address,
+                        result_sender,
+                    );
                 }
                 StreamHubEvent::ApiStopRelayStream {
                     id,
-                    relay_type,
+                    relay_type: _,
                     result_sender,
                 } => {
-                    let result = self.api_stop_relay_stream(id, &relay_type).await;
+                    let result = self.api_stop_relay_stream(id).await;
 
                     if let Err(err) = result_sender.send(result) {
                         log::error!("event_loop api error: {:?}", err);
                     }
                 }
+                StreamHubEvent::SessionTimeout {
+                    identifier,
+                    info,
+                    reason,
+                } => {
+                    log::warn!(
+                        "session timeout, identifier: {}, remote_addr: {}, reason: {:?}",
+                        identifier,
+                        info.remote_addr,
+                        reason
+                    );
+                    if let Some(notifier) = &self.notifier {
+                        let message = StreamHubEventMessage::SessionTimeout {
+                            identifier,
+                            info,
+                            reason,
+                        };
+                        notifier.on_session_timeout_notify(&message).await;
+                    }
+                }
+                StreamHubEvent::RelayStateChange { statistics } => {
+                    let relay = statistics.lock().await.clone();
+                    let relays = if relay.started_by_api {
+                        &mut self.relays
+                    } else {
+                        &mut self.static_relays
+                    };
+
+                    /*the relays which end are removed, unless the id is taken by a newer
+                    relay, the api relays are kept by the api from the start*/
+                    if matches!(relay.state, RelayState::Stopped | RelayState::Failed) {
+                        if relays
+                            .get(&relay.id)
+                            .is_some_and(|cur| Arc::ptr_eq(cur, &statistics))
+                        {
+                            relays.remove(&relay.id);
+                        }
+                    } else if !relay.started_by_api {
+                        relays.insert(relay.id.clone(), statistics);
+                    }
+
+                    if let Some(notifier) = &self.notifier {
+                        notifier
+                            .on_relay_notify(&StreamHubEventMessage::Relay { relay })
+                            .await;
+                    }
+                }
+                StreamHubEvent::ApiQueryRelays { result_sender } => {
+                    let result = self.api_query_relays().await;
+
+                    if let Err(err) = result_sender.send(result) {
+                        log::error!("event_loop api error: {}", err);
+                    }
+                }
                 StreamHubEvent::Request { identifier, sender } => {
                     if let Err(err) = self.request(&identifier, sender) {
                         log::error!("event_loop request error: {}", err);
@@ -869,6 +1202,9 @@ impl StreamsHub {
                 event_sender.send(event).map_err(|_| StreamHubError {
                     value: StreamHubErrorValue::SendError,
                 })?;
+            } else {
+                /*no one will answer, do not wait for the statistics*/
+                return Ok(json!({}));
             }
         } else {
             stream_count = self.streams.len();
@@ -946,62 +1282,569 @@ impl StreamsHub {
         Ok(())
     }
 
-    async fn api_start_relay_stream(
+    /*the index of the new or the changed VOD stream, or the error*/
+    fn send_vod_result(result: Result<u64, SuiError>, result_sender: StatisticApiResultSender) {
+        let result = match result {
+            Ok(index) => json!(index),
+            Err(SuiError { value: err }) => {
+                log::error!("vod api error: {}", err);
+                json!(err.to_string())
+            }
+        };
+
+        if let Err(err) = result_sender.send(result) {
+            log::error!("event_loop api error: {}", err);
+        }
+    }
+
+    /*The clip is committed by a transaction in the hls session, wait for it without
+    blocking the event loop.*/
+    fn api_clip_stream(
         &mut self,
-        id: String,
-        relay_type: &RelayType,
         identifier: StreamIdentifier,
-        server_address: String,
-    ) -> Result<(), StreamHubError> {
-        let (result_sender, mut result_receiver) = mpsc::channel(1);
+        start: u64,
+        duration: u64,
+        name: String,
+        description: String,
+        result_sender: ApiClipStreamResultSender,
+    ) {
+        let (clip_result_sender, mut clip_result_receiver) = mpsc::channel(1);
+        let client_event = BroadcastEvent::Clip {
+            identifier,
+            start,
+            duration,
+            name,
+            description,
+            result_sender: clip_result_sender,
+        };
 
-        match relay_type {
-            RelayType::Pull => {
-                let client_event = BroadcastEvent::Subscribe {
-                    id,
-                    identifier,
-                    server_address: Some(server_address),
-                    result_sender: Some(result_sender),
-                };
+        if self.client_event_sender.send(client_event).is_err() {
+            let result = Err(StreamHubError {
+                value: StreamHubErrorValue::SendError,
+            });
+            if let Err(err) = result_sender.send(result) {
+                log::error!("event_loop api error: {:?}", err);
+            }
+            return;
+        }
 
-                //send subscribe info to pull clients
-                self.client_event_sender
-                    .send(client_event)
-                    .map_err(|_| StreamHubError {
-                        value: StreamHubErrorValue::SendError,
-                    })?;
+        tokio::spawn(async move {
+            /*all the senders are dropped if no hls session takes the clip*/
+            let result = clip_result_receiver
+                .recv()
+                .await
+                .unwrap_or(Err(StreamHubError {
+                    value: StreamHubErrorValue::NoHlsSession,
+                }));
+            if let Err(err) = result_sender.send(result) {
+                log::error!("event_loop api error: {:?}", err);
+            }
+        });
+    }
+
+    async fn on_subscribe(
+        &mut self,
+        identifier: StreamIdentifier,
+        info: SubscriberInfo,
+        result_sender: SubEventExecuteResultSender,
+    ) {
+        let message = StreamHubEventMessage::Subscribe {
+            identifier: identifier.clone(),
+            info: info.clone(),
+        };
+        let sub_id = info.id;
+        let info_clone = info.clone();
+
+        //new chan for Frame/Packet sender and receiver
+        let (sender, receiver) = match info.sub_data_type {
+            define::SubDataType::Frame => {
+                let (sender_chan, receiver_chan) = mpsc::unbounded_channel();
+                (
+                    DataSender::Frame {
+                        sender: sender_chan,
+                    },
+                    DataReceiver {
+                        frame_receiver: Some(receiver_chan),
+                        packet_receiver: None,
+                    },
+                )
+            }
+            define::SubDataType::Packet => {
+                let (sender_chan, receiver_chan) = mpsc::unbounded_channel();
+                (
+                    DataSender::Packet {
+                        sender: sender_chan,
+                    },
+                    DataReceiver {
+                        frame_receiver: None,
+                        packet_receiver: Some(receiver_chan),
+                    },
+                )
+            }
+        };
+
+        let rv = match self.subscribe(&identifier, info_clone, sender).await {
+            Ok(statistic_data_sender) => {
+                if let Some(notifier) = &self.notifier {
+                    notifier.on_play_notify(&message).await;
+                }
+
+                if let Some(edge_pull) = self.edge_pulls.get_mut(&identifier) {
+                    if is_player(&info.sub_type) {
+                        edge_pull.players.insert(sub_id);
+                        edge_pull.idle_since = None;
+                    }
+                }
+                self.un_pub_sub_events
+                    .insert(sub_id, StreamHubEvent::UnSubscribe { identifier, info });
+                Ok((receiver, Some(statistic_data_sender)))
             }
-            RelayType::Push => {}
+            Err(err) => {
+                log::error!("event_loop Subscribe error: {}", err);
+                Err(err)
+            }
+        };
+
+        if result_sender.send(rv).is_err() {
+            log::error!("event_loop Subscribe error: The receiver dropped.")
         }
+    }
 
-        if let Some(received_message) = result_receiver.recv().await {
-            return received_message;
+    /*The player waits for the unknown stream to be pulled from the origin, the
+    players of the same stream share one pull.*/
+    fn pull_from_origin(
+        &mut self,
+        identifier: StreamIdentifier,
+        info: SubscriberInfo,
+        result_sender: SubEventExecuteResultSender,
+    ) {
+        let pending_subscribes = self
+            .pending_subscribes
+            .entry(identifier.clone())
+            .or_default();
+        pending_subscribes.push(PendingSubscribe {
+            info,
+            result_sender,
+            start_time: Instant::now(),
+        });
+
+        let hub_event_sender = self.hub_event_sender.clone();
+        let timeout_identifier = identifier.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(ORIGIN_PULL_WAIT).await;
+            let event = StreamHubEvent::OriginPullTimeout {
+                identifier: timeout_identifier,
+            };
+            if let Err(err) = hub_event_sender.send(event) {
+                log::error!("send origin pull timeout error: {}", err);
+            }
+        });
+
+        if pending_subscribes.len() > 1 {
+            return;
+        }
+
+        log::info!("subscribe: try to pull stream, identifier: {}", identifier);
+        self.edge_pulls.entry(identifier.clone()).or_default();
+
+        let client_event = BroadcastEvent::Subscribe {
+            id: edge_pull_id(&identifier),
+            identifier,
+            server_address: None,
+            statistics: None,
+            result_sender: None,
+        };
+
+        //send subscribe info to pull clients
+        if self.client_event_sender.send(client_event).is_err() {
+            log::error!("pull_from_origin error: no pull client");
         }
-        Ok(())
     }
 
-    async fn api_stop_relay_stream(
+    fn on_origin_pull_timeout(&mut self, identifier: &StreamIdentifier) {
+        if let Some(pending_subscribes) = self.pending_subscribes.remove(identifier) {
+            let (expired, waiting): (Vec<_>, Vec<_>) = pending_subscribes
+                .into_iter()
+                .partition(|pending| pending.start_time.elapsed() >= ORIGIN_PULL_WAIT);
+
+            for pending in expired {
+                log::warn!("pull from origin timeout, identifier: {}", identifier);
+                let result = Err(StreamHubError {
+                    value: StreamHubErrorValue::NoAppOrStreamName,
+                });
+                if pending.result_sender.send(result).is_err() {
+                    log::error!("event_loop Subscribe error: The receiver dropped.")
+                }
+            }
+            if !waiting.is_empty() {
+                self.pending_subscribes.insert(identifier.clone(), waiting);
+            }
+        }
+        self.check_edge_idle(identifier);
+    }
+
+    //start the idle timer once no player is on the stream pulled from the origin
+    fn check_edge_idle(&mut self, identifier: &StreamIdentifier) {
+        let edge_pull = match self.edge_pulls.get_mut(identifier) {
+            Some(edge_pull) => edge_pull,
+            None => return,
+        };
+        if !edge_pull.players.is_empty()
+            || edge_pull.idle_since.is_some()
+            || self.pending_subscribes.contains_key(identifier)
+        {
+            return;
+        }
+        edge_pull.idle_since = Some(Instant::now());
+
+        let hub_event_sender = self.hub_event_sender.clone();
+        let idle_timeout = self.edge_idle_timeout;
+        let identifier = identifier.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(idle_timeout).await;
+            if let Err(err) = hub_event_sender.send(StreamHubEvent::EdgeIdleCheck { identifier }) {
+                log::error!("send edge idle check error: {}", err);
+            }
+        });
+    }
+
+    fn on_edge_idle_check(&mut self, identifier: &StreamIdentifier) {
+        match self.edge_pulls.get_mut(identifier) {
+            Some(edge_pull) => match edge_pull.idle_since {
+                Some(idle_since) if idle_since.elapsed() >= self.edge_idle_timeout => {}
+                /*a player came during the timer*/
+                _ => return,
+            },
+            None => return,
+        }
+        if self.pending_subscribes.contains_key(identifier) {
+            return;
+        }
+
+        log::info!("stop the idle pull from origin, identifier: {}", identifier);
+        self.edge_pulls.remove(identifier);
+        let client_event = BroadcastEvent::UnSubscribe {
+            id: edge_pull_id(identifier),
+            result_sender: None,
+        };
+        if self.client_event_sender.send(client_event).is_err() {
+            log::error!("stop pull from origin error: no pull client");
+        }
+    }
+
+    fn get_publish_conflict_policy(&self, identifier: &StreamIdentifier) -> PublishConflictPolicy {
+        if let StreamIdentifier::Rtmp { app_name, .. } = identifier {
+            if let Some(policy) = self.app_publish_conflict_policies.get(app_name) {
+                return *policy;
+            }
+        }
+        self.publish_conflict_policy
+    }
+
+    async fn notify_publish_conflict(
+        &self,
+        identifier: &StreamIdentifier,
+        info: &PublisherInfo,
+        outcome: PublishConflictOutcome,
+    ) {
+        log::info!(
+            "publish conflict, identifier: {}, publisher: {}, outcome: {:?}",
+            identifier,
+            info.id,
+            outcome
+        );
+        if let Some(notifier) = &self.notifier {
+            let message = StreamHubEventMessage::PublishConflict {
+                identifier: identifier.clone(),
+                info: info.clone(),
+                outcome,
+            };
+            notifier.on_publish_conflict_notify(&message).await;
+        }
+    }
+
+    fn queue_publisher(
+        &mut self,
+        identifier: StreamIdentifier,
+        info: PublisherInfo,
+        receiver: DataReceiver,
+        stream_handler: Arc<dyn TStreamHandler>,
+    ) {
+        let (take_over_sender, take_over_receiver) = oneshot::channel();
+        let drainer = tokio::spawn(drain_standby_data(receiver, take_over_receiver));
+
+        self.standby_publishers
+            .entry(identifier.clone())
+            .or_default()
+            .push_back(StandbyPublisher {
+                info,
+                stream_handler,
+                take_over_sender,
+                drainer,
+            });
+        self.update_standby_state(&identifier);
+    }
+
+    fn update_standby_state(&self, identifier: &StreamIdentifier) {
+        if let Some(forwarded_stream) = self.forwarded_streams.get(identifier) {
+            forwarded_stream
+                .standby_sender
+                .send_replace(self.standby_publishers.contains_key(identifier));
+        }
+    }
+
+    fn remove_standby_publisher(&mut self, identifier: &StreamIdentifier, id: Uuid) {
+        if let Some(standby_publishers) = self.standby_publishers.get_mut(identifier) {
+            //the drainer stops once its take over sender is dropped
+            standby_publishers.retain(|standby| standby.info.id != id);
+            if standby_publishers.is_empty() {
+                self.standby_publishers.remove(identifier);
+            }
+        }
+        self.update_standby_state(identifier);
+    }
+
+    //the media of the first standby publisher is no longer dropped
+    async fn take_standby_publisher(
+        &mut self,
+        identifier: &StreamIdentifier,
+    ) -> Option<(PublisherInfo, DataReceiver, Arc<dyn TStreamHandler>)> {
+        let standby_publishers = self.standby_publishers.get_mut(identifier)?;
+        let standby = standby_publishers.pop_front();
+        if standby_publishers.is_empty() {
+            self.standby_publishers.remove(identifier);
+        }
+        self.update_standby_state(identifier);
+        let standby = standby?;
+
+        let _ = standby.take_over_sender.send(());
+        match standby.drainer.await {
+            Ok(receiver) => Some((standby.info, receiver, standby.stream_handler)),
+            Err(err) => {
+                log::error!("take standby publisher join err: {}", err);
+                None
+            }
+        }
+    }
+
+    async fn on_standby_promoted(
+        &mut self,
+        identifier: &StreamIdentifier,
+        info: PublisherInfo,
+        statistic_data_sender: &StatisticDataSender,
+    ) {
+        let statistic_publisher = StatisticData::Publisher {
+            id: info.id,
+            remote_addr: info.notify_info.remote_addr.clone(),
+            start_time: chrono::Local::now(),
+        };
+        if let Err(err) = statistic_data_sender.send(statistic_publisher) {
+            log::error!("send statistic_publisher err: {}", err);
+        }
+        self.publishers.insert(identifier.clone(), info.id);
+
+        if let Some(notifier) = &self.notifier {
+            let message = StreamHubEventMessage::Publish {
+                identifier: identifier.clone(),
+                info: info.clone(),
+            };
+            notifier.on_publish_notify(&message).await;
+        }
+        self.notify_publish_conflict(identifier, &info, PublishConflictOutcome::Promoted)
+            .await;
+
+        self.un_pub_sub_events.insert(
+            info.id,
+            StreamHubEvent::UnPublish {
+                identifier: identifier.clone(),
+                info,
+            },
+        );
+    }
+
+    /*The first standby publisher takes over the stream in place, so the
+    subscribers are kept. Returns false if the stream can not be switched.*/
+    async fn switch_to_standby_publisher(&mut self, identifier: &StreamIdentifier) -> bool {
+        let standby = self
+            .standby_publishers
+            .get(identifier)
+            .and_then(|standby_publishers| standby_publishers.front());
+        //only the frames are forwarded
+        let switchable = match standby {
+            Some(standby) => matches!(standby.info.pub_data_type, PubDataType::Frame),
+            None => false,
+        };
+        if !switchable || !self.forwarded_streams.contains_key(identifier) {
+            return false;
+        }
+
+        let (info, receiver, stream_handler) = match self.take_standby_publisher(identifier).await {
+            Some(standby) => standby,
+            None => return false,
+        };
+        let (source_sender, statistic_data_sender) = match (
+            receiver.frame_receiver,
+            self.forwarded_streams.get(identifier),
+        ) {
+            (Some(frame_receiver), Some(forwarded_stream)) => {
+                let source = FrameSource {
+                    id: info.id,
+                    receiver: frame_receiver,
+                    stream_handler,
+                };
+                (
+                    forwarded_stream.source_sender.send(source),
+                    forwarded_stream.statistic_data_sender.clone(),
+                )
+            }
+            _ => return false,
+        };
+        if source_sender.is_err() {
+            log::error!("switch standby publisher error: no frame forwarder");
+            return false;
+        }
+
+        self.on_standby_promoted(identifier, info, &statistic_data_sender)
+            .await;
+        true
+    }
+
+    //the first standby publisher publishes the stream again after it is unpublished
+    async fn promote_standby_publisher(&mut self, identifier: &StreamIdentifier) {
+        let (info, receiver, stream_handler) = match self.take_standby_publisher(identifier).await {
+            Some(standby) => standby,
+            None => return,
+        };
+
+        match self
+            .publish(identifier.clone(), info.id, receiver, stream_handler)
+            .await
+        {
+            Ok(statistic_data_sender) => {
+                self.on_standby_promoted(identifier, info, &statistic_data_sender)
+                    .await;
+            }
+            Err(err) => {
+                log::error!("promote standby publisher err: {}", err);
+            }
+        }
+    }
+
+    /*The relay is kept from the start so that its id cannot be taken twice, the
+    result of the first connection is waited for without blocking the event loop.*/
+    fn api_start_relay_stream(
         &mut self,
         id: String,
         relay_type: &RelayType,
-    ) -> Result<(), StreamHubError> {
+        identifier: StreamIdentifier,
+        server_address: String,
+        api_result_sender: ApiRelayStreamResultSender,
+    ) {
+        if self.relays.contains_key(&id) {
+            let result = Err(StreamHubError {
+                value: StreamHubErrorValue::Exists,
+            });
+            if let Err(err) = api_result_sender.send(result) {
+                log::error!("event_loop api error: {:?}", err);
+            }
+            return;
+        }
+
         let (result_sender, mut result_receiver) = mpsc::channel(1);
-        match relay_type {
-            RelayType::Pull => {
-                let client_event = BroadcastEvent::UnSubscribe {
-                    id,
-                    result_sender: Some(result_sender),
-                };
+        let mut relay = StatisticRelay::new(
+            id.clone(),
+            relay_type.clone(),
+            identifier.clone(),
+            server_address.clone(),
+        );
+        relay.started_by_api = true;
+        let statistics = Arc::new(Mutex::new(relay));
+
+        let client_event = match relay_type {
+            RelayType::Pull => BroadcastEvent::Subscribe {
+                id: id.clone(),
+                identifier,
+                server_address: Some(server_address),
+                statistics: Some(statistics.clone()),
+                result_sender: Some(result_sender),
+            },
+            RelayType::Push => BroadcastEvent::StartPush {
+                id: id.clone(),
+                identifier,
+                url: server_address,
+                statistics: statistics.clone(),
+                result_sender,
+            },
+        };
 
-                //send subscribe info to pull clients
-                self.client_event_sender
-                    .send(client_event)
-                    .map_err(|_| StreamHubError {
-                        value: StreamHubErrorValue::SendError,
-                    })?;
+        //send relay info to pull/push clients
+        if self.client_event_sender.send(client_event).is_err() {
+            let result = Err(StreamHubError {
+                value: StreamHubErrorValue::SendError,
+            });
+            if let Err(err) = api_result_sender.send(result) {
+                log::error!("event_loop api error: {:?}", err);
             }
-            RelayType::Push => {}
+            return;
         }
+        self.relays.insert(id, statistics.clone());
+
+        let hub_event_sender = self.hub_event_sender.clone();
+        tokio::spawn(async move {
+            /*all the senders are dropped if no relay client takes the event*/
+            let result = result_receiver.recv().await.unwrap_or(Err(StreamHubError {
+                value: StreamHubErrorValue::RelayError(String::from("no relay client")),
+            }));
+
+            /*a relay which cannot be started leaves the api like a failed one, the
+            supervisor reports the failed connections itself*/
+            if let Err(err) = &result {
+                let reported = {
+                    let mut relay = statistics.lock().await;
+                    let reported = relay.state == RelayState::Failed;
+                    relay.state = RelayState::Failed;
+                    relay.error.get_or_insert_with(|| err.to_string());
+                    reported
+                };
+                if !reported {
+                    let state_event = StreamHubEvent::RelayStateChange { statistics };
+                    if let Err(err) = hub_event_sender.send(state_event) {
+                        log::error!("send relay state err: {}", err);
+                    }
+                }
+            }
+            if let Err(err) = api_result_sender.send(result) {
+                log::error!("event_loop api error: {:?}", err);
+            }
+        });
+    }
+
+    /*the relay is stopped by the type it is started with*/
+    async fn api_stop_relay_stream(&mut self, id: String) -> Result<(), StreamHubError> {
+        let relay_type = match self.relays.remove(&id) {
+            Some(statistics) => statistics.lock().await.relay_type.clone(),
+            None => {
+                return Err(StreamHubError {
+                    value: StreamHubErrorValue::RelayNotFound(id),
+                });
+            }
+        };
+
+        let (result_sender, mut result_receiver) = mpsc::channel(1);
+        let client_event = match relay_type {
+            RelayType::Pull => BroadcastEvent::UnSubscribe {
+                id,
+                result_sender: Some(result_sender),
+            },
+            RelayType::Push => BroadcastEvent::StopPush { id, result_sender },
+        };
+
+        //send stop info to pull/push clients
+        self.client_event_sender
+            .send(client_event)
+            .map_err(|_| StreamHubError {
+                value: StreamHubErrorValue::SendError,
+            })?;
 
         if let Some(received_message) = result_receiver.recv().await {
             return received_message;
@@ -1009,6 +1852,15 @@ impl StreamsHub {
         Ok(())
     }
 
+    async fn api_query_relays(&self) -> Value {
+        let mut relays = Vec::new();
+        for statistics in self.relays.values().chain(self.static_relays.values()) {
+            relays.push(statistics.lock().await.clone());
+        }
+        relays.sort_by_key(|relay| relay.start_time);
+        json!(relays)
+    }
+
     //player subscribe a stream
     pub async fn subscribe(
         &mut self,
@@ -1031,24 +1883,6 @@ impl StreamsHub {
             return Ok(result_receiver.await?);
         }
 
-        if self.rtmp_pull_enabled {
-            log::info!("subscribe: try to pull stream, identifier: {}", identifer);
-
-            let client_event = BroadcastEvent::Subscribe {
-                id: String::from("rtmp_relay"),
-                identifier: identifer.clone(),
-                server_address: None,
-                result_sender: None,
-            };
-
-            //send subscribe info to pull clients
-            self.client_event_sender
-                .send(client_event)
-                .map_err(|_| StreamHubError {
-                    value: StreamHubErrorValue::SendError,
-                })?;
-        }
-
         Err(StreamHubError {
             value: StreamHubErrorValue::NoAppOrStreamName,
         })
@@ -1082,6 +1916,7 @@ impl StreamsHub {
     pub async fn publish(
         &mut self,
         identifier: StreamIdentifier,
+        publisher_id: Uuid,
         receiver: DataReceiver,
         handler: Arc<dyn TStreamHandler>,
     ) -> Result<StatisticDataSender, StreamHubError> {
@@ -1091,6 +1926,45 @@ impl StreamsHub {
             });
         }
 
+        /*the frames go through a forwarder, so a standby publisher can take over in place*/
+        let mut forwarder_senders = None;
+        let (receiver, handler) = match receiver {
+            DataReceiver {
+                frame_receiver: Some(frame_receiver),
+                packet_receiver: None,
+            } => {
+                let (sender, source_receiver) = mpsc::unbounded_channel();
+                let (output_sender, output_receiver) = mpsc::unbounded_channel();
+                let switchable_handler = Arc::new(SwitchableStreamHandler::new(handler.clone()));
+                let (standby_sender, standby_receiver) =
+                    watch::channel(self.standby_publishers.contains_key(&identifier));
+
+                let forwarder = FrameForwarder::new(
+                    identifier.clone(),
+                    FrameSource {
+                        id: publisher_id,
+                        receiver: frame_receiver,
+                        stream_handler: handler,
+                    },
+                    source_receiver,
+                    output_sender,
+                    switchable_handler.clone(),
+                    self.hub_event_sender.clone(),
+                    self.stall_timeout,
+                    standby_receiver,
+                );
+                tokio::spawn(forwarder.run());
+                forwarder_senders = Some((sender, standby_sender));
+
+                let receiver = DataReceiver {
+                    frame_receiver: Some(output_receiver),
+                    packet_receiver: None,
+                };
+                (receiver, switchable_handler as Arc<dyn TStreamHandler>)
+            }
+            receiver => (receiver, handler),
+        };
+
         let (event_sender, event_receiver) = mpsc::unbounded_channel();
         let transceiver =
             StreamDataTransceiver::new(receiver, event_receiver, identifier.clone(), handler);
@@ -1109,6 +1983,16 @@ impl StreamsHub {
         }
 
         self.streams.insert(identifier.clone(), event_sender);
+        if let Some((source_sender, standby_sender)) = forwarder_senders {
+            self.forwarded_streams.insert(
+                identifier.clone(),
+                ForwardedStream {
+                    source_sender,
+                    statistic_data_sender: statistic_data_sender.clone(),
+                    standby_sender,
+                },
+            );
+        }
 
         if self.rtmp_push_enabled || self.hls_enabled || self.rtmp_remuxer_enabled {
             let client_event = BroadcastEvent::Publish { identifier };
@@ -1132,7 +2016,18 @@ impl StreamsHub {
                     value: StreamHubErrorValue::SendError,
                 })?;
                 self.streams.remove(identifier);
+                //the frame forwarder stops once its source sender is dropped
+                self.forwarded_streams.remove(identifier);
                 log::info!("unpublish remove stream, stream identifier: {}", identifier);
+
+                if self.rtmp_push_enabled || self.hls_enabled || self.rtmp_remuxer_enabled {
+                    let client_event = BroadcastEvent::UnPublish {
+                        identifier: identifier.clone(),
+                    };
+                    if let Err(err) = self.client_event_sender.send(client_event) {
+                        log::error!("unpublish send client event err: {}", err);
+                    }
+                }
             }
             None => {
                 return Err(StreamHubError {
@@ -1144,3 +2039,201 @@ impl StreamsHub {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use {
+        super::{
+            define::{
+                DataSender, FrameData, FrameDataReceiver, FrameDataSender, InformationSender,
+                NotifyInfo, PubDataType, PublishConflictPolicy, PublishType, PublisherInfo,
+                StreamHubEvent, StreamHubEventSender, SubDataType, SubscribeType, SubscriberInfo,
+                TStreamHandler,
+            },
+            errors::{StreamHubError, StreamHubErrorValue},
+            statistics::StatisticsStream,
+            stream::StreamIdentifier,
+            utils::{RandomDigitCount, Uuid},
+            StreamsHub,
+        },
+        async_trait::async_trait,
+        bytes::BytesMut,
+        std::{sync::Arc, time::Duration},
+        tokio::{sync::oneshot, time},
+    };
+
+    struct EmptyHandler;
+
+    #[async_trait]
+    impl TStreamHandler for EmptyHandler {
+        async fn send_prior_data(
+            &self,
+            _sender: DataSender,
+            _sub_type: SubscribeType,
+        ) -> Result<(), StreamHubError> {
+            Ok(())
+        }
+        async fn get_statistic_data(&self) -> Option<StatisticsStream> {
+            None
+        }
+        async fn send_information(&self, _sender: InformationSender) {}
+    }
+
+    fn notify_info() -> NotifyInfo {
+        NotifyInfo {
+            request_url: String::from("rtmp://localhost/live/test"),
+            remote_addr: String::from("127.0.0.1:5000"),
+        }
+    }
+
+    fn publisher_info() -> PublisherInfo {
+        PublisherInfo {
+            id: Uuid::new(RandomDigitCount::Four),
+            pub_type: PublishType::RtmpPush,
+            pub_data_type: PubDataType::Frame,
+            notify_info: notify_info(),
+            backup: false,
+        }
+    }
+
+    fn identifier(app_name: &str) -> StreamIdentifier {
+        StreamIdentifier::Rtmp {
+            app_name: String::from(app_name),
+            stream_name: String::from("test"),
+        }
+    }
+
+    //returns the frame sender and if the publisher got the stream
+    async fn publish(
+        sender: &StreamHubEventSender,
+        identifier: StreamIdentifier,
+        info: PublisherInfo,
+    ) -> Result<(FrameDataSender, bool), StreamHubError> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        let event = StreamHubEvent::Publish {
+            identifier,
+            info,
+            result_sender,
+            stream_handler: Arc::new(EmptyHandler),
+        };
+        assert!(sender.send(event).is_ok());
+
+        let (frame_sender, _, statistic_data_sender) = result_receiver.await.unwrap()?;
+        Ok((frame_sender.unwrap(), statistic_data_sender.is_some()))
+    }
+
+    async fn subscribe(
+        sender: &StreamHubEventSender,
+        identifier: StreamIdentifier,
+    ) -> FrameDataReceiver {
+        let (result_sender, result_receiver) = oneshot::channel();
+        let event = StreamHubEvent::Subscribe {
+            identifier,
+            info: SubscriberInfo {
+                id: Uuid::new(RandomDigitCount::Four),
+                sub_type: SubscribeType::RtmpPull,
+                notify_info: notify_info(),
+                sub_data_type: SubDataType::Frame,
+            },
+            result_sender,
+        };
+        assert!(sender.send(event).is_ok());
+
+        let (receiver, _) = result_receiver.await.unwrap().unwrap();
+        receiver.frame_receiver.unwrap()
+    }
+
+    fn video(timestamp: u32) -> FrameData {
+        FrameData::Video {
+            timestamp,
+            data: BytesMut::from(&[0x17, 0x01][..]),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publish_conflict() {
+        let mut hub = StreamsHub::new(None);
+        hub.set_app_publish_conflict_policy(String::from("live"), PublishConflictPolicy::Queue);
+        let sender = hub.get_hub_event_sender();
+        tokio::spawn(async move { hub.run().await });
+
+        /*the second publisher is rejected by default*/
+        assert!(
+            publish(&sender, identifier("other"), publisher_info())
+                .await
+                .unwrap()
+                .1
+        );
+        let err = publish(&sender, identifier("other"), publisher_info())
+            .await
+            .unwrap_err();
+        assert!(matches!(err.value, StreamHubErrorValue::Exists));
+
+        /*the second publisher waits as a standby*/
+        let first = publisher_info();
+        assert!(
+            publish(&sender, identifier("live"), first.clone())
+                .await
+                .unwrap()
+                .1
+        );
+        assert!(
+            !publish(&sender, identifier("live"), publisher_info())
+                .await
+                .unwrap()
+                .1
+        );
+
+        /*the standby takes over, so the third publisher waits too*/
+        let event = StreamHubEvent::UnPublish {
+            identifier: identifier("live"),
+            info: first,
+        };
+        assert!(sender.send(event).is_ok());
+        assert!(
+            !publish(&sender, identifier("live"), publisher_info())
+                .await
+                .unwrap()
+                .1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_publisher_failover() {
+        let mut hub = StreamsHub::new(None);
+        hub.set_stall_timeout(Duration::from_millis(200));
+        let sender = hub.get_hub_event_sender();
+        tokio::spawn(async move { hub.run().await });
+
+        let (primary_sender, _) = publish(&sender, identifier("live"), publisher_info())
+            .await
+            .unwrap();
+        let mut backup = publisher_info();
+        backup.backup = true;
+        let (backup_sender, published) =
+            publish(&sender, identifier("live"), backup).await.unwrap();
+        assert!(!published);
+
+        let mut receiver = subscribe(&sender, identifier("live")).await;
+        assert!(primary_sender.send(video(40)).is_ok());
+        assert!(backup_sender.send(video(1000)).is_ok());
+        let data = time::timeout(Duration::from_secs(1), receiver.recv()).await;
+        assert!(matches!(
+            data,
+            Ok(Some(FrameData::Video { timestamp: 40, .. }))
+        ));
+
+        /*the primary stalls and the subscriber goes on with the backup*/
+        let data = time::timeout(Duration::from_secs(1), receiver.recv()).await;
+        assert!(matches!(data, Ok(Some(FrameData::Discontinuity {}))));
+        /*and its timestamps go on from the primary*/
+        assert!(backup_sender.send(video(1040)).is_ok());
+        let data = time::timeout(Duration::from_secs(1), receiver.recv()).await;
+        assert!(matches!(
+            data,
+            Ok(Some(FrameData::Video { timestamp: 41, .. }))
+        ));
+        assert!(primary_sender.is_closed());
+    }
+}
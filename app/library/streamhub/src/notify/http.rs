@@ -23,6 +23,9 @@ pub struct HttpNotifier {
     on_unpublish_url: Option<String>,
     on_play_url: Option<String>,
     on_stop_url: Option<String>,
+    on_relay_url: Option<String>,
+    on_publish_conflict_url: Option<String>,
+    on_session_timeout_url: Option<String>,
 }
 
 impl HttpNotifier {
@@ -31,6 +34,9 @@ impl HttpNotifier {
         on_unpublish_url: Option<String>,
         on_play_url: Option<String>,
         on_stop_url: Option<String>,
+        on_relay_url: Option<String>,
+        on_publish_conflict_url: Option<String>,
+        on_session_timeout_url: Option<String>,
     ) -> Self {
         Self {
             request_client: reqwest::Client::new(),
@@ -38,6 +44,9 @@ impl HttpNotifier {
             on_unpublish_url,
             on_play_url,
             on_stop_url,
+            on_relay_url,
+            on_publish_conflict_url,
+            on_session_timeout_url,
         }
     }
 }
@@ -119,4 +128,61 @@ impl Notifier for HttpNotifier {
             }
         }
     }
+
+    async fn on_relay_notify(&self, event: &StreamHubEventMessage) {
+        if let Some(on_relay_url) = &self.on_relay_url {
+            match self
+                .request_client
+                .post(on_relay_url)
+                .body(serialize_event!(event))
+                .send()
+                .await
+            {
+                Err(err) => {
+                    log::error!("on_relay error: {}", err);
+                }
+                Ok(response) => {
+                    log::info!("on_relay success: {:?}", response);
+                }
+            }
+        }
+    }
+
+    async fn on_publish_conflict_notify(&self, event: &StreamHubEventMessage) {
+        if let Some(on_publish_conflict_url) = &self.on_publish_conflict_url {
+            match self
+                .request_client
+                .post(on_publish_conflict_url)
+                .body(serialize_event!(event))
+                .send()
+                .await
+            {
+                Err(err) => {
+                    log::error!("on_publish_conflict error: {}", err);
+                }
+                Ok(response) => {
+                    log::info!("on_publish_conflict success: {:?}", response);
+                }
+            }
+        }
+    }
+
+    async fn on_session_timeout_notify(&self, event: &StreamHubEventMessage) {
+        if let Some(on_session_timeout_url) = &self.on_session_timeout_url {
+            match self
+                .request_client
+                .post(on_session_timeout_url)
+                .body(serialize_event!(event))
+                .send()
+                .await
+            {
+                Err(err) => {
+                    log::error!("on_session_timeout error: {}", err);
+                }
+                Ok(response) => {
+                    log::info!("on_session_timeout success: {:?}", response);
+                }
+            }
+        }
+    }
 }
@@ -9,4 +9,7 @@ pub trait Notifier: Sync + Send {
     async fn on_unpublish_notify(&self, event: &StreamHubEventMessage);
     async fn on_play_notify(&self, event: &StreamHubEventMessage);
     async fn on_stop_notify(&self, event: &StreamHubEventMessage);
+    async fn on_relay_notify(&self, event: &StreamHubEventMessage);
+    async fn on_publish_conflict_notify(&self, event: &StreamHubEventMessage);
+    async fn on_session_timeout_notify(&self, event: &StreamHubEventMessage);
 }
\ No newline at end of file
@@ -1,6 +1,9 @@
 use {
     super::stream::StreamIdentifier,
-    crate::{define::SubscribeType, utils::Uuid},
+    crate::{
+        define::{RelayType, SubscribeType},
+        utils::Uuid,
+    },
     chrono::{DateTime, Local},
     serde::Serialize,
     std::{collections::HashMap, sync::Arc, time::Duration},
@@ -31,6 +34,8 @@ pub struct VideoInfo {
     #[serde(skip_serializing)]
     pub recv_frame_count_for_gop: usize,
     pub gop: usize,
+    #[serde(rename = "gop_duration(ms)")]
+    pub gop_duration: i64,
 }
 #[derive(Debug, Clone, Serialize, Default)]
 pub struct AudioInfo {
@@ -97,6 +102,63 @@ pub struct StatisticSubscriber {
     pub total_send_bytes: usize,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub enum RelayState {
+    Connecting,
+    Active,
+    /*waiting to reconnect after the session dropped*/
+    BackingOff,
+    Stopped,
+    Failed,
+}
+
+/*the status of a relay started by the api, it is updated by the relay client*/
+#[derive(Debug, Clone, Serialize)]
+pub struct StatisticRelay {
+    pub id: String,
+    pub relay_type: RelayType,
+    pub identifier: StreamIdentifier,
+    pub url: String,
+    /*or started by the configuration or by the players*/
+    pub started_by_api: bool,
+    pub state: RelayState,
+    pub start_time: DateTime<Local>,
+    pub send_bytes: usize,
+    pub recv_bytes: usize,
+    /*the reconnections in a row since the relay was last active*/
+    pub retries: u32,
+    /*why the relay failed or reconnected last time*/
+    pub error: Option<String>,
+}
+
+impl StatisticRelay {
+    pub fn new(
+        id: String,
+        relay_type: RelayType,
+        identifier: StreamIdentifier,
+        url: String,
+    ) -> Self {
+        Self {
+            id,
+            relay_type,
+            identifier,
+            url,
+            started_by_api: false,
+            state: RelayState::Connecting,
+            start_time: Local::now(),
+            send_bytes: 0,
+            recv_bytes: 0,
+            retries: 0,
+            error: None,
+        }
+    }
+
+    pub fn set_failed(&mut self, error: String) {
+        self.state = RelayState::Failed;
+        self.error = Some(error);
+    }
+}
+
 impl StatisticsStream {
     pub fn new(identifier: StreamIdentifier) -> Self {
         Self {
@@ -0,0 +1,255 @@
+use {
+    super::{errors::MediaError, m3u8::upload_to_walrus},
+    bytes::BytesMut,
+    commonlib::playlist_archive::archive_line,
+    std::collections::VecDeque,
+};
+
+/*segments per archive blob*/
+const ARCHIVE_CHUNK_SIZE: usize = 20;
+/*the archives of an event are merged beyond this count*/
+const MAX_ARCHIVE_COUNT: usize = 16;
+
+/*uploads a blob and returns its id*/
+pub type ArchiveUploader = Box<dyn FnMut(BytesMut) -> Result<String, MediaError> + Send>;
+
+/*the playlist lines of a ts segment*/
+pub struct ArchiveEntry {
+    pub lines: String,
+    /*how many media segments are listed in the lines, e.g. the I-frames*/
+    pub media_count: u64,
+}
+
+struct Archive {
+    blob_id: String,
+    /*the blobs of the playlist lines, a merged archive refers them directly so the
+    archives are never nested deeper than one merge*/
+    chunk_blob_ids: Vec<String>,
+    /*the ts segments in it*/
+    first_sequence_no: u64,
+    segment_count: u64,
+    /*the media segments in it*/
+    first_media_sequence: u64,
+    media_count: u64,
+}
+
+/*Keeps the playlist on chain compact for the DVR windows and the events, the older
+segments are uploaded to walrus in chunks and only referred by an archive line.
+Between ARCHIVE_CHUNK_SIZE and twice of it of the latest segments are listed as they
+are, so the updates of the live edge do not touch the archives.
+
+In a sliding window the archives are dropped once all their segments leave it, so
+the window on chain is rounded up to whole chunks. An event never drops segments,
+its archives are merged to keep the live entry bounded.*/
+pub struct PlaylistArchive {
+    archives: VecDeque<Archive>,
+    /*the first segment which is not archived*/
+    next_sequence_no: u64,
+    merge: bool,
+    uploader: ArchiveUploader,
+}
+
+impl PlaylistArchive {
+    pub fn new(merge: bool) -> Self {
+        Self::with_uploader(merge, Box::new(upload_to_walrus))
+    }
+
+    pub fn with_uploader(merge: bool, uploader: ArchiveUploader) -> Self {
+        Self {
+            archives: VecDeque::new(),
+            next_sequence_no: 0,
+            merge,
+            uploader,
+        }
+    }
+
+    /*entries are the ts segments in the window, starting from first_sequence_no and
+    first_media_sequence. Returns the media sequence and the lines of the compact playlist.*/
+    pub fn compact(
+        &mut self,
+        first_sequence_no: u64,
+        first_media_sequence: u64,
+        entries: &[ArchiveEntry],
+    ) -> Result<(u64, String), MediaError> {
+        self.archives.retain(|archive| {
+            archive.first_sequence_no + archive.segment_count > first_sequence_no
+        });
+        self.next_sequence_no = std::cmp::max(self.next_sequence_no, first_sequence_no);
+
+        let mut inline_start = std::cmp::min(
+            (self.next_sequence_no - first_sequence_no) as usize,
+            entries.len(),
+        );
+        let mut media_sequence = first_media_sequence
+            + entries[..inline_start]
+                .iter()
+                .map(|entry| entry.media_count)
+                .sum::<u64>();
+
+        while entries.len() - inline_start >= 2 * ARCHIVE_CHUNK_SIZE {
+            let chunk = &entries[inline_start..inline_start + ARCHIVE_CHUNK_SIZE];
+            let content: String = chunk.iter().map(|entry| entry.lines.as_str()).collect();
+            let blob_id = (self.uploader)(BytesMut::from(content.as_bytes()))?;
+            let media_count = chunk.iter().map(|entry| entry.media_count).sum();
+
+            self.archives.push_back(Archive {
+                chunk_blob_ids: vec![blob_id.clone()],
+                blob_id,
+                first_sequence_no: self.next_sequence_no,
+                segment_count: ARCHIVE_CHUNK_SIZE as u64,
+                first_media_sequence: media_sequence,
+                media_count,
+            });
+            media_sequence += media_count;
+            self.next_sequence_no += ARCHIVE_CHUNK_SIZE as u64;
+            inline_start += ARCHIVE_CHUNK_SIZE;
+        }
+
+        if self.merge && self.archives.len() > MAX_ARCHIVE_COUNT {
+            self.merge_archives()?;
+        }
+
+        if let Some(archive) = self.archives.front() {
+            media_sequence = archive.first_media_sequence;
+        }
+        let mut content: String = self
+            .archives
+            .iter()
+            .map(|archive| archive_line(&archive.blob_id))
+            .collect();
+        for entry in &entries[inline_start..] {
+            content += entry.lines.as_str();
+        }
+
+        Ok((media_sequence, content))
+    }
+
+    /*the previous merged archive is replaced by a new one of all the chunks*/
+    fn merge_archives(&mut self) -> Result<(), MediaError> {
+        let chunk_blob_ids: Vec<String> = self
+            .archives
+            .iter()
+            .flat_map(|archive| archive.chunk_blob_ids.iter().cloned())
+            .collect();
+        let content: String = chunk_blob_ids
+            .iter()
+            .map(|blob_id| archive_line(blob_id))
+            .collect();
+        let blob_id = (self.uploader)(BytesMut::from(content.as_bytes()))?;
+
+        let first_archive = self.archives.front().unwrap();
+        let first_sequence_no = first_archive.first_sequence_no;
+        let first_media_sequence = first_archive.first_media_sequence;
+        let segment_count = self
+            .archives
+            .iter()
+            .map(|archive| archive.segment_count)
+            .sum();
+        let media_count = self
+            .archives
+            .iter()
+            .map(|archive| archive.media_count)
+            .sum();
+        log::info!(
+            "merge {} playlist archives of {} segments into {}",
+            self.archives.len(),
+            segment_count,
+            blob_id
+        );
+
+        self.archives.clear();
+        self.archives.push_back(Archive {
+            blob_id,
+            chunk_blob_ids,
+            first_sequence_no,
+            segment_count,
+            first_media_sequence,
+            media_count,
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{ArchiveEntry, PlaylistArchive},
+        commonlib::playlist_archive::ARCHIVE_TAG,
+        std::{
+            collections::HashMap,
+            sync::{Arc, Mutex},
+        },
+    };
+
+    fn entry(sequence_no: u64) -> ArchiveEntry {
+        ArchiveEntry {
+            lines: format!("#EXTINF:2.000\nblob_{sequence_no}\n"),
+            media_count: 1,
+        }
+    }
+
+    /*returns the lines and how many levels of archives were expanded*/
+    fn expand(content: &str, blobs: &HashMap<String, String>) -> (String, usize) {
+        let mut content = content.to_string();
+        let mut depth = 0;
+        while content.contains(ARCHIVE_TAG) {
+            content = content
+                .lines()
+                .map(|line| match line.strip_prefix(ARCHIVE_TAG) {
+                    Some(blob_id) => blobs[blob_id].clone(),
+                    None => format!("{line}\n"),
+                })
+                .collect();
+            depth += 1;
+        }
+        (content, depth)
+    }
+
+    #[test]
+    fn test_short_window_is_inline() {
+        let mut archive = PlaylistArchive::new(false);
+        let entries: Vec<ArchiveEntry> = (0..3)
+            .map(|index| ArchiveEntry {
+                lines: format!("#EXTINF:2.000\nblob_{index}\n"),
+                media_count: 1,
+            })
+            .collect();
+
+        let (media_sequence, content) = archive.compact(5, 5, &entries).unwrap();
+        assert_eq!(media_sequence, 5);
+        assert_eq!(
+            content,
+            "#EXTINF:2.000\nblob_0\n#EXTINF:2.000\nblob_1\n#EXTINF:2.000\nblob_2\n"
+        );
+    }
+
+    #[test]
+    fn test_merged_archives_are_flat() {
+        let blobs = Arc::new(Mutex::new(HashMap::new()));
+        let uploaded_blobs = blobs.clone();
+        let mut archive = PlaylistArchive::with_uploader(
+            true,
+            Box::new(move |data| {
+                let mut blobs = uploaded_blobs.lock().unwrap();
+                let blob_id = format!("archive_{}", blobs.len());
+                blobs.insert(blob_id.clone(), String::from_utf8(data.to_vec()).unwrap());
+                Ok(blob_id)
+            }),
+        );
+
+        /*an event of 2000 segments, the media sequence starts from 7*/
+        let mut entries = Vec::new();
+        for sequence_no in 0..2000 {
+            entries.push(entry(sequence_no));
+            let (media_sequence, content) = archive.compact(0, 7, &entries).unwrap();
+            assert_eq!(media_sequence, 7);
+
+            let (expanded, depth) = expand(&content, &blobs.lock().unwrap());
+            assert!(depth <= 2);
+            let expected: String = entries.iter().map(|entry| entry.lines.as_str()).collect();
+            assert_eq!(expanded, expected);
+        }
+        assert!(archive.archives.len() <= super::MAX_ARCHIVE_COUNT + 1);
+    }
+}
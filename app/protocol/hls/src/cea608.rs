@@ -0,0 +1,324 @@
+use super::webvtt::TextCue;
+
+/*A CEA-608 decoder of the CC1 channel (field 1, data channel 1), it only keeps the
+text of the captions, the styles and the positions are ignored.
+
+The text on the screen is turned into cues: a cue starts when the displayed text
+changes and ends at the next change.*/
+
+const MAX_ROLL_UP_ROWS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CaptionMode {
+    /*the text is loaded off screen and displayed by EOC*/
+    PopOn,
+    /*the text is displayed on the bottom rows and scrolled by CR*/
+    RollUp(usize),
+    /*the text is displayed as soon as it arrives*/
+    PaintOn,
+}
+
+pub struct Cea608Decoder {
+    mode: CaptionMode,
+    displayed: Vec<String>,
+    non_displayed: Vec<String>,
+    /*the data channel selected by the last control code, only 1 is decoded*/
+    channel: u8,
+    /*the control codes are sent twice, the second one is skipped*/
+    last_control: Option<(u8, u8)>,
+
+    /*the displayed text and the time since when*/
+    cue_text: String,
+    cue_start: i64,
+}
+
+impl Default for Cea608Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cea608Decoder {
+    pub fn new() -> Self {
+        Self {
+            mode: CaptionMode::PopOn,
+            displayed: Vec::new(),
+            non_displayed: Vec::new(),
+            channel: 1,
+            last_control: None,
+            cue_text: String::new(),
+            cue_start: 0,
+        }
+    }
+
+    /*Decode a byte pair of field 1, returns the cue which ends at this time if the
+    displayed text changes.*/
+    pub fn decode(&mut self, timestamp: i64, cc_data_1: u8, cc_data_2: u8) -> Option<TextCue> {
+        /*remove the odd parity bit*/
+        let data_1 = cc_data_1 & 0x7F;
+        let data_2 = cc_data_2 & 0x7F;
+
+        /*padding*/
+        if data_1 == 0x00 && data_2 == 0x00 {
+            return None;
+        }
+
+        if (0x10..=0x1F).contains(&data_1) {
+            if self.last_control == Some((data_1, data_2)) {
+                self.last_control = None;
+                return None;
+            }
+            self.last_control = Some((data_1, data_2));
+
+            self.channel = if data_1 & 0x08 == 0 { 1 } else { 2 };
+            if self.channel == 1 {
+                self.decode_control(data_1 & 0x17, data_2);
+            }
+        } else {
+            self.last_control = None;
+            if self.channel == 1 {
+                self.write_char(basic_char(data_1));
+                if data_2 >= 0x20 {
+                    self.write_char(basic_char(data_2));
+                }
+            }
+        }
+
+        self.update_cue(timestamp)
+    }
+
+    pub fn is_displaying(&self) -> bool {
+        !self.cue_text.is_empty()
+    }
+
+    /*End the displayed cue at the boundary of a segment, the rest of it starts the
+    next segment.*/
+    pub fn split(&mut self, timestamp: i64) -> Option<TextCue> {
+        if self.cue_text.is_empty() || timestamp <= self.cue_start {
+            return None;
+        }
+
+        let cue = TextCue {
+            start: self.cue_start,
+            end: timestamp,
+            text: self.cue_text.clone(),
+        };
+        self.cue_start = timestamp;
+
+        Some(cue)
+    }
+
+    fn decode_control(&mut self, data_1: u8, data_2: u8) {
+        match (data_1, data_2) {
+            /*miscellaneous control codes*/
+            (0x14, 0x20) => self.mode = CaptionMode::PopOn,
+            (0x14, 0x21) => self.backspace(),
+            (0x14, 0x25..=0x27) => {
+                let rows = (data_2 - 0x23) as usize;
+                if !matches!(self.mode, CaptionMode::RollUp(_)) {
+                    self.displayed.clear();
+                }
+                self.mode = CaptionMode::RollUp(rows);
+            }
+            (0x14, 0x29) => self.mode = CaptionMode::PaintOn,
+            (0x14, 0x2C) => self.displayed.clear(),
+            (0x14, 0x2D) => self.carriage_return(),
+            (0x14, 0x2E) => self.non_displayed.clear(),
+            (0x14, 0x2F) => {
+                std::mem::swap(&mut self.displayed, &mut self.non_displayed);
+            }
+            /*special characters*/
+            (0x11, 0x30..=0x3F) => self.write_char(special_char(data_2)),
+            /*extended characters replace the standard one sent before them*/
+            (0x12 | 0x13, 0x20..=0x3F) => {
+                self.backspace();
+                self.write_char(extended_char(data_1, data_2));
+            }
+            /*preamble address codes begin a new row*/
+            (0x10..=0x17, 0x40..=0x7F) => self.new_row(),
+            _ => {}
+        }
+    }
+
+    fn memory(&mut self) -> &mut Vec<String> {
+        match self.mode {
+            CaptionMode::PopOn => &mut self.non_displayed,
+            _ => &mut self.displayed,
+        }
+    }
+
+    fn write_char(&mut self, ch: char) {
+        let memory = self.memory();
+        if memory.is_empty() {
+            memory.push(String::new());
+        }
+        memory.last_mut().unwrap().push(ch);
+    }
+
+    fn backspace(&mut self) {
+        if let Some(row) = self.memory().last_mut() {
+            row.pop();
+        }
+    }
+
+    fn new_row(&mut self) {
+        if let CaptionMode::RollUp(_) = self.mode {
+            return;
+        }
+        let memory = self.memory();
+        if memory.last().is_some_and(|row| !row.is_empty()) {
+            memory.push(String::new());
+        }
+    }
+
+    fn carriage_return(&mut self) {
+        match self.mode {
+            CaptionMode::RollUp(rows) => {
+                self.displayed.push(String::new());
+                let rows = std::cmp::min(rows, MAX_ROLL_UP_ROWS);
+                while self.displayed.len() > rows {
+                    self.displayed.remove(0);
+                }
+            }
+            _ => self.new_row(),
+        }
+    }
+
+    fn update_cue(&mut self, timestamp: i64) -> Option<TextCue> {
+        let text = self
+            .displayed
+            .iter()
+            .map(|row| row.trim())
+            .filter(|row| !row.is_empty())
+            .collect::<Vec<&str>>()
+            .join("\n");
+
+        if text == self.cue_text {
+            return None;
+        }
+
+        let cue = if self.cue_text.is_empty() || timestamp <= self.cue_start {
+            None
+        } else {
+            Some(TextCue {
+                start: self.cue_start,
+                end: timestamp,
+                text: self.cue_text.clone(),
+            })
+        };
+        self.cue_text = text;
+        self.cue_start = timestamp;
+
+        cue
+    }
+}
+
+/*the basic characters are ASCII except for some of them*/
+fn basic_char(data: u8) -> char {
+    match data {
+        0x2A => 'á',
+        0x5C => 'é',
+        0x5E => 'í',
+        0x5F => 'ó',
+        0x60 => 'ú',
+        0x7B => 'ç',
+        0x7C => '÷',
+        0x7D => 'Ñ',
+        0x7E => 'ñ',
+        0x7F => '█',
+        _ => data as char,
+    }
+}
+
+fn special_char(data: u8) -> char {
+    const SPECIAL_CHARS: [char; 16] = [
+        '®', '°', '½', '¿', '™', '¢', '£', '♪', 'à', ' ', 'è', 'â', 'ê', 'î', 'ô', 'û',
+    ];
+    SPECIAL_CHARS[(data - 0x30) as usize]
+}
+
+fn extended_char(data_1: u8, data_2: u8) -> char {
+    const SPANISH_FRENCH_CHARS: [char; 32] = [
+        'Á', 'É', 'Ó', 'Ú', 'Ü', 'ü', '‘', '¡', '*', '\'', '—', '©', '℠', '•', '“', '”', 'À', 'Â',
+        'Ç', 'È', 'Ê', 'Ë', 'ë', 'Î', 'Ï', 'ï', 'Ô', 'Ù', 'ù', 'Û', '«', '»',
+    ];
+    const PORTUGUESE_GERMAN_CHARS: [char; 32] = [
+        'Ã', 'ã', 'Í', 'Ì', 'ì', 'Ò', 'ò', 'Õ', 'õ', '{', '}', '\\', '^', '_', '|', '~', 'Ä', 'ä',
+        'Ö', 'ö', 'ß', '¥', '¤', '¦', 'Å', 'å', 'Ø', 'ø', '┌', '┐', '└', '┘',
+    ];
+
+    let index = (data_2 - 0x20) as usize;
+    if data_1 == 0x12 {
+        SPANISH_FRENCH_CHARS[index]
+    } else {
+        PORTUGUESE_GERMAN_CHARS[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cea608Decoder;
+
+    /*add the odd parity bit*/
+    fn parity(data: u8) -> u8 {
+        if data.count_ones() & 1 == 0 {
+            data | 0x80
+        } else {
+            data
+        }
+    }
+
+    fn send(decoder: &mut Cea608Decoder, timestamp: i64, data_1: u8, data_2: u8) {
+        assert!(decoder
+            .decode(timestamp, parity(data_1), parity(data_2))
+            .is_none());
+    }
+
+    #[test]
+    fn test_pop_on() {
+        let mut decoder = Cea608Decoder::new();
+        /*RCL twice, the second one is skipped*/
+        send(&mut decoder, 0, 0x14, 0x20);
+        send(&mut decoder, 0, 0x14, 0x20);
+        send(&mut decoder, 0, 0x14, 0x70);
+        send(&mut decoder, 0, b'H', b'i');
+        send(&mut decoder, 0, 0x14, 0x2F);
+
+        let cue = decoder.split(1000).unwrap();
+        assert_eq!(cue.start, 0);
+        assert_eq!(cue.end, 1000);
+        assert_eq!(cue.text, "Hi");
+
+        /*EDM*/
+        let cue = decoder.decode(2500, parity(0x14), parity(0x2C)).unwrap();
+        assert_eq!(cue.start, 1000);
+        assert_eq!(cue.end, 2500);
+        assert_eq!(cue.text, "Hi");
+    }
+
+    #[test]
+    fn test_roll_up() {
+        let mut decoder = Cea608Decoder::new();
+        /*RU2*/
+        send(&mut decoder, 0, 0x14, 0x25);
+        send(&mut decoder, 0, b'A', 0x00);
+        /*CR*/
+        send(&mut decoder, 500, 0x14, 0x2D);
+
+        let cue = decoder.decode(1000, parity(b'B'), parity(0x00)).unwrap();
+        assert_eq!((cue.start, cue.end), (0, 1000));
+        assert_eq!(cue.text, "A");
+
+        /*only 2 rows are kept, the first one is rolled off*/
+        let cue = decoder.decode(1500, parity(0x14), parity(0x2D)).unwrap();
+        assert_eq!((cue.start, cue.end), (1000, 1500));
+        assert_eq!(cue.text, "A\nB");
+
+        let cue = decoder.decode(2000, parity(b'C'), parity(0x00)).unwrap();
+        assert_eq!((cue.start, cue.end), (1500, 2000));
+        assert_eq!(cue.text, "B");
+
+        let cue = decoder.split(3000).unwrap();
+        assert_eq!(cue.text, "B\nC");
+    }
+}
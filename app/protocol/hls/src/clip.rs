@@ -0,0 +1,51 @@
+use std::ops::Range;
+
+/*Find the segments which overlap a clip. The durations are of the segments in the
+live window, the clip starts start_offset milliseconds before the live edge and is
+cut at the edge. A clip is made of whole segments, so it may be a bit longer.*/
+pub fn clip_range(durations: &[i64], start_offset: i64, duration: i64) -> Option<Range<usize>> {
+    let window_duration: i64 = durations.iter().sum();
+    if start_offset <= 0 || duration <= 0 || start_offset > window_duration {
+        return None;
+    }
+
+    let clip_start = window_duration - start_offset;
+    let clip_end = std::cmp::min(clip_start + duration, window_duration);
+
+    let mut first = None;
+    let mut last = 0;
+    let mut segment_start = 0;
+    for (index, segment_duration) in durations.iter().enumerate() {
+        let segment_end = segment_start + segment_duration;
+        if segment_end > clip_start && segment_start < clip_end {
+            first.get_or_insert(index);
+            last = index;
+        }
+        segment_start = segment_end;
+    }
+
+    first.map(|first| first..last + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::clip_range;
+
+    #[test]
+    fn test_clip_range() {
+        let durations = [2000, 2000, 2000, 2000, 2000];
+
+        /*the last 3 seconds*/
+        assert_eq!(clip_range(&durations, 3000, 3000), Some(3..5));
+        /*from 7 seconds ago, 4 seconds long*/
+        assert_eq!(clip_range(&durations, 7000, 4000), Some(1..4));
+        /*cut at the live edge*/
+        assert_eq!(clip_range(&durations, 2000, 6000), Some(4..5));
+        /*the whole window*/
+        assert_eq!(clip_range(&durations, 10000, 10000), Some(0..5));
+
+        assert_eq!(clip_range(&durations, 11000, 2000), None);
+        assert_eq!(clip_range(&durations, 3000, 0), None);
+        assert_eq!(clip_range(&[], 3000, 3000), None);
+    }
+}
@@ -1,4 +1,8 @@
-use xflv::demuxer::{FlvDemuxerAudioData, FlvDemuxerVideoData};
+use {
+    streamhub::define::BroadcastEventExecuteResultSender,
+    tokio::sync::mpsc,
+    xflv::demuxer::{FlvDemuxerAudioData, FlvDemuxerVideoData},
+};
 
 pub const HLS_DURATION: u8 = 10;
 
@@ -7,3 +11,21 @@ pub enum FlvDemuxerData {
     Audio { data: FlvDemuxerAudioData },
     None,
 }
+
+/*Sent from the hls remuxer to the receiver of a stream.*/
+pub enum HlsStreamEvent {
+    UnPublish,
+    /*the same stream is published again while the receiver is still alive*/
+    Republish,
+    /*register the recent segments as a VOD, see M3u8::clip_playlist*/
+    Clip {
+        start: u64,
+        duration: u64,
+        name: String,
+        description: String,
+        result_sender: BroadcastEventExecuteResultSender,
+    },
+}
+
+pub type HlsStreamEventSender = mpsc::UnboundedSender<HlsStreamEvent>;
+pub type HlsStreamEventReceiver = mpsc::UnboundedReceiver<HlsStreamEvent>;
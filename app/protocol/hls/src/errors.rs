@@ -43,6 +43,12 @@ pub enum MediaErrorValue {
     PlaylistUploadError,
     #[fail(display = "live to vod upload error")]
     LiveToVodUploadError,
+    #[fail(display = "clips need the walrus storage")]
+    ClipStorageError,
+    #[fail(display = "the clip is out of the live window")]
+    ClipRangeError,
+    #[fail(display = "add vod stream error")]
+    AddVodStreamError,
 }
 
 impl From<FlvDemuxerError> for MediaError {
@@ -85,6 +91,7 @@ impl Fail for MediaError {
     }
 }
 
+#[derive(Debug)]
 pub struct HlsError {
     pub value: HlsErrorValue,
 }
@@ -107,6 +114,8 @@ pub enum HlsErrorValue {
     OneshotRecvError(#[cause] OneshotRecvError),
     #[fail(display = "stream hub event send error")]
     StreamHubEventSendErr,
+    #[fail(display = "invalid hls settings: {}", _0)]
+    InvalidSettings(String),
 }
 impl From<RecvError> for HlsError {
     fn from(error: RecvError) -> Self {
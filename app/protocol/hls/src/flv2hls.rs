@@ -1,12 +1,26 @@
 use {
-    super::{define::FlvDemuxerData, errors::MediaError, m3u8::M3u8},
+    super::{
+        cea608::Cea608Decoder,
+        define::FlvDemuxerData,
+        errors::MediaError,
+        m3u8::{IFrame, M3u8},
+        master_playlist::{MasterPlaylist, RenditionGroup, Variant},
+        segment_policy::{FrameKind, SegmentPolicy},
+        settings::HlsSettings,
+        webvtt::{parse_text_data, SubtitlePlaylist},
+    },
     bytes::BytesMut,
+    h264_decoder::sei::{self, NALU_TYPE_SEI},
+    std::sync::Arc,
+    tokio::sync::Mutex,
     xflv::{
-        define::{frame_type, FlvData},
+        define::{frame_type, FlvData, SoundFormat},
         demuxer::{FlvAudioTagDemuxer, FlvVideoTagDemuxer},
     },
     xmpegts::{
         define::{epsi_stream_type, MPEG_FLAG_IDR_FRAME},
+        id3::Id3Tag,
+        pmt,
         ts::TsMuxer,
     },
 };
@@ -23,24 +37,61 @@ pub struct Flv2HlsRemuxer {
     last_dts: i64,
     last_pts: i64,
 
-    duration: i64,
+    segment_policy: SegmentPolicy,
     need_new_segment: bool,
+    //the stream was republished, mark the next segment
+    pending_discontinuity: bool,
+    //another publisher took over, the timestamps start from its next frame
+    pending_rebase: bool,
 
     video_pid: u16,
-    audio_pid: u16,
+    //registered once the sound format of the stream is known
+    audio_pid: Option<u16>,
+    //timed id3 metadata
+    metadata_pid: u16,
+    //the IDR frames written in the current segment
+    segment_iframes: Vec<IFrame>,
+    //the PAT/PMT at the beginning of the current segment
+    segment_map_size: usize,
 
     m3u8_handler: M3u8,
+
+    app_name: String,
+    stream_name: String,
+    //enabled by the first onTextData/onCaption message or CEA-608 caption
+    subtitles_enabled: bool,
+    subtitle_playlist: SubtitlePlaylist,
+    //the captions carried in the SEI of the video frames
+    cea608_decoder: Cea608Decoder,
+    //the master playlist of the rendition group, if the stream belongs to one
+    group_master_playlist: Option<Arc<Mutex<MasterPlaylist>>>,
+    //otherwise a master playlist of the stream itself is needed to refer the subtitles
+    stream_master_playlist: Option<MasterPlaylist>,
+    //bits per second
+    peak_bandwidth: usize,
+    iframe_playlist: bool,
+    //the master playlists are only committed along with the live playlists, otherwise
+    //the subtitles are published as a VOD stream of their own
+    commit_live: bool,
 }
 
 impl Flv2HlsRemuxer {
-    pub fn new(duration: i64, app_name: String, stream_name: String, need_record: bool) -> Self {
+    pub fn new(
+        app_name: String,
+        stream_name: String,
+        settings: &HlsSettings,
+        group_master_playlist: Option<Arc<Mutex<MasterPlaylist>>>,
+    ) -> Self {
         let mut ts_muxer = TsMuxer::new();
-        let audio_pid = ts_muxer
-            .add_stream(epsi_stream_type::PSI_STREAM_AAC, BytesMut::new())
-            .unwrap();
         let video_pid = ts_muxer
             .add_stream(epsi_stream_type::PSI_STREAM_H264, BytesMut::new())
             .unwrap();
+        let metadata_pid = ts_muxer
+            .add_stream(
+                epsi_stream_type::PSI_STREAM_METADATA,
+                pmt::id3_metadata_descriptor().unwrap(),
+            )
+            .unwrap();
 
         Self {
             video_demuxer: FlvVideoTagDemuxer::new(),
@@ -54,13 +105,34 @@ impl Flv2HlsRemuxer {
             last_dts: 0,
             last_pts: 0,
 
-            duration,
+            segment_policy: SegmentPolicy::new(settings.segment_policy_config()),
             need_new_segment: false,
+            pending_discontinuity: false,
+            pending_rebase: false,
 
             video_pid,
-            audio_pid,
-
-            m3u8_handler: M3u8::new(duration, 6, app_name, stream_name, need_record),
+            audio_pid: None,
+            metadata_pid,
+            segment_iframes: Vec::new(),
+            segment_map_size: 0,
+
+            m3u8_handler: M3u8::new(app_name.clone(), stream_name.clone(), settings),
+
+            subtitles_enabled: false,
+            subtitle_playlist: SubtitlePlaylist::new(
+                app_name.clone(),
+                stream_name.clone(),
+                settings,
+            ),
+            cea608_decoder: Cea608Decoder::new(),
+            group_master_playlist,
+            stream_master_playlist: None,
+            peak_bandwidth: 0,
+            iframe_playlist: settings.iframe_playlist,
+            commit_live: settings.commit_live,
+
+            app_name,
+            stream_name,
         }
     }
 
@@ -77,7 +149,12 @@ impl Flv2HlsRemuxer {
                     return Ok(());
                 }
             }
-            _ => return Ok(()),
+            FlvData::MetaData { timestamp, data } => {
+                if let Some(text) = parse_text_data(data) {
+                    self.process_text_data(timestamp as i64, text).await;
+                }
+                return Ok(());
+            }
         };
 
         self.process_demux_data(&flv_demux_data).await?;
@@ -87,21 +164,85 @@ impl Flv2HlsRemuxer {
 
     pub async fn flush_remaining_data(&mut self) -> Result<(), MediaError> {
         let data = self.ts_muxer.get_data();
-        let mut discontinuity: bool = false;
+        let mut discontinuity: bool = self.pending_discontinuity;
         if self.last_dts > self.last_ts_dts + 15 * 1000 {
             discontinuity = true;
         }
+        self.update_bandwidth(data.len(), self.last_dts - self.last_ts_dts)
+            .await;
         self.m3u8_handler.add_segment(
             self.last_dts - self.last_ts_dts,
             discontinuity,
             true,
             data,
+            std::mem::take(&mut self.segment_iframes),
+            self.segment_map_size,
         )?;
         self.m3u8_handler.refresh_playlist().await?;
+        self.add_subtitle_segment(self.last_ts_dts, self.last_dts, discontinuity)
+            .await;
+
+        Ok(())
+    }
+
+    /*The stream is interrupted but may be published again, so cut the segment here
+    without ending the playlist. The republished stream starts its timestamps over.*/
+    pub async fn suspend(&mut self) -> Result<(), MediaError> {
+        let data = self.ts_muxer.get_data();
+        if !data.is_empty() {
+            let mut discontinuity: bool = self.pending_discontinuity;
+            if self.last_dts > self.last_ts_dts + 15 * 1000 {
+                discontinuity = true;
+            }
+            self.update_bandwidth(data.len(), self.last_dts - self.last_ts_dts)
+                .await;
+            self.m3u8_handler.add_segment(
+                self.last_dts - self.last_ts_dts,
+                discontinuity,
+                false,
+                data,
+                std::mem::take(&mut self.segment_iframes),
+                self.segment_map_size,
+            )?;
+            self.m3u8_handler.refresh_playlist().await?;
+            self.add_subtitle_segment(self.last_ts_dts, self.last_dts, discontinuity)
+                .await;
+        }
+
+        self.ts_muxer.reset();
+        self.last_ts_dts = 0;
+        self.last_ts_pts = 0;
+        self.last_dts = 0;
+        self.last_pts = 0;
+        self.pending_discontinuity = true;
+        self.cea608_decoder = Cea608Decoder::new();
 
         Ok(())
     }
 
+    /*Another publisher took over the stream, the timestamps of which may begin
+    anywhere, so they are rebased on its first frame.*/
+    pub fn last_gop(&self) -> i64 {
+        self.segment_policy.last_gop()
+    }
+
+    pub async fn switch_source(&mut self) -> Result<(), MediaError> {
+        self.suspend().await?;
+        self.pending_rebase = true;
+
+        Ok(())
+    }
+
+    fn rebase_timestamps(&mut self, pts: i64, dts: i64) {
+        if self.pending_rebase {
+            self.pending_rebase = false;
+            self.last_ts_dts = dts;
+            self.last_ts_pts = pts;
+            self.last_dts = dts;
+            self.last_pts = pts;
+        }
+    }
+
     pub async fn process_demux_data(
         &mut self,
         flv_demux_data: &FlvDemuxerData,
@@ -119,59 +260,330 @@ impl Flv2HlsRemuxer {
                 pts = data.pts;
                 dts = data.dts;
                 pid = self.video_pid;
+                self.rebase_timestamps(pts, dts);
                 payload.extend_from_slice(&data.data[..]);
 
-                if data.frame_type == frame_type::KEY_FRAME {
+                let frame = if data.frame_type == frame_type::KEY_FRAME {
                     flags = MPEG_FLAG_IDR_FRAME;
-                    if dts - self.last_ts_dts >= self.duration * 1000 {
-                        self.need_new_segment = true;
-                    }
-                }
+                    FrameKind::KeyFrame
+                } else {
+                    FrameKind::InterFrame
+                };
+                self.need_new_segment =
+                    self.segment_policy
+                        .need_new_segment(frame, dts, dts - self.last_ts_dts);
+
+                self.process_captions(pts, &data.data[..]).await;
             }
             FlvDemuxerData::Audio { data } => {
+                if self.audio_pid.is_none() {
+                    self.add_audio_stream(data.sound_format)?;
+                }
+
                 if !data.has_data {
                     return Ok(());
                 }
 
                 pts = data.pts;
                 dts = data.dts;
-                pid = self.audio_pid;
+                self.rebase_timestamps(pts, dts);
+                pid = match self.audio_pid {
+                    Some(audio_pid) => audio_pid,
+                    None => return Ok(()),
+                };
                 payload.extend_from_slice(&data.data[..]);
 
-                if dts - self.last_ts_dts >= self.duration * 1000 {
-                    self.need_new_segment = true;
-                }
+                self.need_new_segment = self.segment_policy.need_new_segment(
+                    FrameKind::Audio,
+                    dts,
+                    dts - self.last_ts_dts,
+                );
             }
             _ => return Ok(()),
         }
 
         if self.need_new_segment {
-            let mut discontinuity: bool = false;
+            let mut discontinuity: bool = self.pending_discontinuity;
             if dts > self.last_ts_dts + 15 * 1000 {
                 discontinuity = true;
             }
             let data = self.ts_muxer.get_data();
 
-            self.m3u8_handler
-                .add_segment(dts - self.last_ts_dts, discontinuity, false, data)?;
+            self.update_bandwidth(data.len(), dts - self.last_ts_dts)
+                .await;
+            self.m3u8_handler.add_segment(
+                dts - self.last_ts_dts,
+                discontinuity,
+                false,
+                data,
+                std::mem::take(&mut self.segment_iframes),
+                self.segment_map_size,
+            )?;
             self.m3u8_handler.refresh_playlist().await?;
+            self.add_subtitle_segment(self.last_ts_dts, dts, discontinuity)
+                .await;
 
             self.ts_muxer.reset();
             self.last_ts_dts = dts;
             self.last_ts_pts = pts;
             self.need_new_segment = false;
+            self.pending_discontinuity = false;
+
+            self.write_segment_proof(pts)?;
         }
 
         self.last_dts = dts;
         self.last_pts = pts;
 
+        let offset = self.write_ts(pid, pts, dts, flags, payload)?;
+
+        if flags & MPEG_FLAG_IDR_FRAME > 0 {
+            self.segment_iframes.push(IFrame {
+                time: dts - self.last_ts_dts,
+                offset,
+                size: self.ts_muxer.bytes_writer.len() - offset,
+            });
+        }
+
+        Ok(())
+    }
+
+    /*Carry the blob id of the previous segment and the digest of the transaction
+    which committed it at the beginning of the new segment.*/
+    fn write_segment_proof(&mut self, pts: i64) -> Result<(), MediaError> {
+        let Some((blob_id, tx_digest)) = self.m3u8_handler.latest_segment_proof() else {
+            return Ok(());
+        };
+
+        let mut id3_tag = Id3Tag::new();
+        id3_tag.add_text("blob_id", &blob_id);
+        id3_tag.add_text("tx_digest", &tx_digest);
+
+        self.write_ts(self.metadata_pid, pts, pts, 0, id3_tag.marshal()?)?;
+
+        Ok(())
+    }
+
+    /*Returns the offset of the PES packets of the frame, which follow the PAT/PMT
+    written along with it. The PAT/PMT at the beginning of a segment is the media
+    initialization section of its I-frames.*/
+    fn write_ts(
+        &mut self,
+        pid: u16,
+        pts: i64,
+        dts: i64,
+        flags: u16,
+        payload: BytesMut,
+    ) -> Result<usize, MediaError> {
+        let offset = self.ts_muxer.bytes_writer.len();
         self.ts_muxer
             .write(pid, pts * 90, dts * 90, flags, payload)?;
+        if offset == 0 {
+            self.segment_map_size = self.ts_muxer.pat_pmt_size;
+        }
+
+        Ok(offset + self.ts_muxer.pat_pmt_size)
+    }
+
+    fn add_audio_stream(&mut self, sound_format: u8) -> Result<(), MediaError> {
+        let (codec_id, extra_data) = if sound_format == SoundFormat::AAC as u8 {
+            (epsi_stream_type::PSI_STREAM_AAC, BytesMut::new())
+        } else if sound_format == SoundFormat::MP3 as u8 {
+            (epsi_stream_type::PSI_STREAM_MP3, BytesMut::new())
+        } else if sound_format == SoundFormat::OPUS as u8 {
+            (
+                epsi_stream_type::PSI_STREAM_AUDIO_OPUS,
+                pmt::opus_descriptors(self.audio_demuxer.opus_channel_count)?,
+            )
+        } else {
+            return Ok(());
+        };
+
+        log::info!("add audio stream to ts, stream type: 0x{:02x}", codec_id);
+        self.audio_pid = Some(self.ts_muxer.add_stream(codec_id, extra_data)?);
+
+        Ok(())
+    }
+
+    async fn process_text_data(&mut self, timestamp: i64, text: String) {
+        self.enable_subtitles().await;
+        self.subtitle_playlist.add_cue(timestamp, text);
+    }
+
+    /*Decode the CEA-608 captions (CC1) of the ATSC A/53 user data SEI.*/
+    async fn process_captions(&mut self, pts: i64, annexb_data: &[u8]) {
+        let mut cues = Vec::new();
+
+        for nalu in sei::split_annexb_nalus(annexb_data) {
+            if nalu.is_empty() || nalu[0] & 0x1F != NALU_TYPE_SEI {
+                continue;
+            }
+
+            let messages = match sei::parse_sei_nalu(nalu) {
+                Ok(messages) => messages,
+                Err(err) => {
+                    log::warn!("parse sei error: {}", err);
+                    continue;
+                }
+            };
+
+            for message in &messages {
+                let Ok(Some(cc_datas)) = sei::parse_a53_cc_data(message) else {
+                    continue;
+                };
+                for cc_data in cc_datas {
+                    /*only the field 1 data*/
+                    if !cc_data.cc_valid || cc_data.cc_type != 0 {
+                        continue;
+                    }
+                    if let Some(cue) =
+                        self.cea608_decoder
+                            .decode(pts, cc_data.cc_data_1, cc_data.cc_data_2)
+                    {
+                        cues.push(cue);
+                    }
+                }
+            }
+        }
+
+        if !self.subtitles_enabled && !self.cea608_decoder.is_displaying() {
+            return;
+        }
+
+        self.enable_subtitles().await;
+        for cue in cues {
+            self.subtitle_playlist.add_text_cue(cue);
+        }
+    }
+
+    async fn enable_subtitles(&mut self) {
+        if self.subtitles_enabled {
+            return;
+        }
+
+        log::info!(
+            "receive text data of {}/{}, enable the subtitles",
+            self.app_name,
+            self.stream_name
+        );
+        self.subtitles_enabled = true;
+        if !self.commit_live {
+            return;
+        }
+
+        if let Some(master_playlist) = &self.group_master_playlist {
+            let mut master_playlist = master_playlist.lock().await;
+            master_playlist.set_subtitles(&self.stream_name);
+            if let Err(err) = master_playlist.refresh_playlist().await {
+                log::error!("refresh master playlist error: {}", err);
+            }
+        } else {
+            let mut master_playlist = MasterPlaylist::new(RenditionGroup {
+                app_name: self.app_name.clone(),
+                group_name: format!("{}_master", self.stream_name),
+                stream_names: vec![self.stream_name.clone()],
+            });
+            master_playlist.set_subtitles(&self.stream_name);
+            self.stream_master_playlist = Some(master_playlist);
+            self.refresh_stream_master_playlist().await;
+        }
+    }
+
+    async fn add_subtitle_segment(&mut self, start: i64, end: i64, discontinuity: bool) {
+        if !self.subtitles_enabled {
+            return;
+        }
+
+        /*the caption on the screen is cut with the segment*/
+        if let Some(cue) = self.cea608_decoder.split(end) {
+            self.subtitle_playlist.add_text_cue(cue);
+        }
+
+        /*the subtitles are optional, do not stop the stream on errors*/
+        if let Err(err) = self
+            .subtitle_playlist
+            .add_segment(start, end, discontinuity)
+        {
+            log::error!("add subtitle segment error: {}", err);
+            return;
+        }
+        if let Err(err) = self.subtitle_playlist.refresh_playlist().await {
+            log::error!("refresh subtitle playlist error: {}", err);
+        }
+    }
+
+    async fn update_bandwidth(&mut self, segment_size: usize, duration: i64) {
+        if duration <= 0 {
+            return;
+        }
+
+        let bandwidth = segment_size * 8 * 1000 / duration as usize;
+        if bandwidth > self.peak_bandwidth {
+            self.peak_bandwidth = bandwidth;
+            self.refresh_stream_master_playlist().await;
+        }
+    }
+
+    async fn refresh_stream_master_playlist(&mut self) {
+        /*wait for the first segment to know the bandwidth*/
+        if self.peak_bandwidth == 0 {
+            return;
+        }
+
+        if let Some(master_playlist) = &mut self.stream_master_playlist {
+            master_playlist.update_variant(Variant {
+                stream_name: self.stream_name.clone(),
+                bandwidth: self.peak_bandwidth,
+                iframe_playlist: self.iframe_playlist,
+                ..Default::default()
+            });
+            if let Err(err) = master_playlist.refresh_playlist().await {
+                log::error!("refresh master playlist error: {}", err);
+            }
+        }
+    }
+
+    pub fn clip_playlist(&mut self, start: i64, duration: i64) -> Result<String, MediaError> {
+        self.m3u8_handler.clip_playlist(start, duration)
+    }
+
+    pub async fn publish_vod(
+        &mut self,
+        name: String,
+        description: String,
+    ) -> Result<(), MediaError> {
+        self.m3u8_handler
+            .publish_vod(name.clone(), description.clone())
+            .await?;
+
+        /*the subtitles are optional, the VOD is kept without them on errors*/
+        if self.subtitles_enabled {
+            if let Err(err) = self
+                .subtitle_playlist
+                .publish_vod(format!("{name} subtitles"), description)
+                .await
+            {
+                log::error!("publish subtitles of {} error: {}", name, err);
+            }
+        }
 
         Ok(())
     }
 
     pub async fn clear_files(&mut self) -> Result<(), MediaError> {
+        if self.subtitles_enabled {
+            if let Err(err) = self.subtitle_playlist.clear() {
+                log::error!("clear subtitle files error: {}", err);
+            }
+        }
+
+        if let Some(master_playlist) = &mut self.stream_master_playlist {
+            master_playlist.remove_variant(&self.stream_name);
+            if let Err(err) = master_playlist.refresh_playlist().await {
+                log::error!("refresh master playlist error: {}", err);
+            }
+        }
+
         self.m3u8_handler.clear().await
     }
 }
@@ -2,19 +2,27 @@ use tokio::sync::oneshot;
 
 use {
     super::{
+        define::{HlsStreamEvent, HlsStreamEventReceiver},
         errors::{HlsError, HlsErrorValue},
         flv2hls::Flv2HlsRemuxer,
+        master_playlist::MasterPlaylist,
+        settings::HlsSettings,
     },
-    std::time::Duration,
+    commonlib::move_call::add_vod_stream,
+    std::{sync::Arc, time::Duration},
     streamhub::{
         define::{
-            FrameData, FrameDataReceiver, NotifyInfo, StreamHubEvent, StreamHubEventSender,
-            SubscribeType, SubscriberInfo,
+            FrameData, FrameDataReceiver, NotifyInfo, StatisticData, StatisticDataSender,
+            StreamHubEvent, StreamHubEventSender, SubscribeType, SubscriberInfo,
         },
+        errors::{StreamHubError, StreamHubErrorValue},
         stream::StreamIdentifier,
         utils::{RandomDigitCount, Uuid},
     },
-    tokio::{sync::mpsc, time::sleep},
+    tokio::{
+        sync::{mpsc, Mutex},
+        time::timeout,
+    },
     xflv::define::FlvData,
 };
 
@@ -27,6 +35,11 @@ pub struct FlvDataReceiver {
     data_consumer: FrameDataReceiver,
     media_processor: Flv2HlsRemuxer,
     subscriber_id: Uuid,
+    stream_event_consumer: HlsStreamEventReceiver,
+    republish_grace_period: u64,
+    statistic_data_sender: Option<StatisticDataSender>,
+    /*the gop duration which the stream statistics have*/
+    reported_gop: i64,
 }
 
 impl FlvDataReceiver {
@@ -34,8 +47,9 @@ impl FlvDataReceiver {
         app_name: String,
         stream_name: String,
         event_producer: StreamHubEventSender,
-        duration: i64,
-        need_record: bool,
+        stream_event_consumer: HlsStreamEventReceiver,
+        settings: HlsSettings,
+        master_playlist: Option<Arc<Mutex<MasterPlaylist>>>,
     ) -> Self {
         let (_, data_consumer) = mpsc::unbounded_channel();
         let subscriber_id = Uuid::new(RandomDigitCount::Four);
@@ -45,48 +59,185 @@ impl FlvDataReceiver {
             stream_name: stream_name.clone(),
             data_consumer,
             event_producer,
-            media_processor: Flv2HlsRemuxer::new(duration, app_name, stream_name, need_record),
+            media_processor: Flv2HlsRemuxer::new(app_name, stream_name, &settings, master_playlist),
             subscriber_id,
+            stream_event_consumer,
+            republish_grace_period: settings.republish_grace_period,
+            statistic_data_sender: None,
+            reported_gop: 0,
         }
     }
 
     pub async fn run(&mut self) -> Result<(), HlsError> {
-        self.subscribe_from_stream_hub(self.app_name.clone(), self.stream_name.clone())
-            .await?;
-        self.receive_flv_data().await?;
+        loop {
+            self.subscribe_from_stream_hub(self.app_name.clone(), self.stream_name.clone())
+                .await?;
+            let republished = self.receive_flv_data().await?;
+            self.unsubscribe_from_stream_hub().await?;
+
+            if !republished && !self.wait_for_republish().await {
+                break;
+            }
+            log::info!(
+                "stream {}/{} is republished, continue the playlist",
+                self.app_name,
+                self.stream_name
+            );
+        }
+
+        self.media_processor.clear_files().await?;
 
         Ok(())
     }
 
-    pub async fn receive_flv_data(&mut self) -> Result<(), HlsError> {
-        let mut retry_count = 0;
+    /*Returns true if the stream has been published again before its end is detected.*/
+    pub async fn receive_flv_data(&mut self) -> Result<bool, HlsError> {
+        let mut republished = false;
 
         loop {
-            if let Some(data) = self.data_consumer.recv().await {
-                let flv_data: FlvData = match data {
-                    FrameData::Audio { timestamp, data } => FlvData::Audio { timestamp, data },
-                    FrameData::Video { timestamp, data } => FlvData::Video { timestamp, data },
-                    _ => continue,
-                };
-                retry_count = 0;
-                self.media_processor.process_flv_data(flv_data).await?;
-            } else {
-                sleep(Duration::from_millis(100)).await;
-                retry_count += 1;
+            tokio::select! {
+                data = self.data_consumer.recv() => {
+                    match data {
+                        Some(data) => self.process_frame_data(data).await?,
+                        /*all the senders are dropped after the stream is unpublished*/
+                        None => break,
+                    }
+                }
+                Some(event) = self.stream_event_consumer.recv() => {
+                    if let HlsStreamEvent::Clip { .. } = event {
+                        self.clip(event);
+                        continue;
+                    }
+                    republished = matches!(event, HlsStreamEvent::Republish);
+                    /*process the frames which have been sent before unpublishing*/
+                    while let Ok(data) = self.data_consumer.try_recv() {
+                        self.process_frame_data(data).await?;
+                    }
+                    break;
+                }
             }
-            //When rtmp stream is interupted here we retry 10 times.
-            //maybe have a better way to judge the stream status.
-            //will do an optimization in the future.
-            //todo
-            if retry_count > 10 {
-                log::info!("RTMP steram ends detected! Clearing....");
-                self.media_processor.flush_remaining_data().await?;
-                break;
+        }
+
+        log::info!("RTMP steram ends detected! Clearing....");
+        if republished || self.republish_grace_period > 0 {
+            self.media_processor.suspend().await?;
+        } else {
+            self.media_processor.flush_remaining_data().await?;
+        }
+
+        Ok(republished)
+    }
+
+    async fn process_frame_data(&mut self, data: FrameData) -> Result<(), HlsError> {
+        let flv_data: FlvData = match data {
+            FrameData::Audio { timestamp, data } => FlvData::Audio { timestamp, data },
+            FrameData::Video { timestamp, data } => FlvData::Video { timestamp, data },
+            FrameData::MetaData { timestamp, data } => FlvData::MetaData { timestamp, data },
+            /*the playlist goes on with the publisher which took over*/
+            FrameData::Discontinuity {} => {
+                self.media_processor.switch_source().await?;
+                return Ok(());
             }
+            _ => return Ok(()),
+        };
+        self.media_processor.process_flv_data(flv_data).await?;
+        self.report_gop();
+
+        Ok(())
+    }
+
+    fn report_gop(&mut self) {
+        let last_gop = self.media_processor.last_gop();
+        if last_gop == self.reported_gop {
+            return;
         }
+        self.reported_gop = last_gop;
 
-        self.media_processor.clear_files().await?;
-        self.unsubscribe_from_stream_hub().await
+        if let Some(statistic_data_sender) = &self.statistic_data_sender {
+            let gop_duration = StatisticData::GopDuration { duration: last_gop };
+            if let Err(err) = statistic_data_sender.send(gop_duration) {
+                log::error!("send gop duration error: {}", err);
+            }
+        }
+    }
+
+    async fn wait_for_republish(&mut self) -> bool {
+        if self.republish_grace_period == 0 {
+            return false;
+        }
+
+        let grace_period = Duration::from_secs(self.republish_grace_period);
+        let republished = timeout(grace_period, async {
+            while let Some(event) = self.stream_event_consumer.recv().await {
+                match event {
+                    HlsStreamEvent::Republish => return true,
+                    HlsStreamEvent::Clip { .. } => self.clip(event),
+                    HlsStreamEvent::UnPublish => {}
+                }
+            }
+            false
+        })
+        .await
+        .unwrap_or(false);
+
+        if republished {
+            return true;
+        }
+
+        /*refuse the later events, the remuxer starts a new receiver for a publish
+        after that, but one may have been sent just before closing.*/
+        self.stream_event_consumer.close();
+        while let Ok(event) = self.stream_event_consumer.try_recv() {
+            if let HlsStreamEvent::Republish = event {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /*The segments are selected here, the transaction which registers the VOD runs
+    aside so the stream is not held up.*/
+    fn clip(&mut self, event: HlsStreamEvent) {
+        let HlsStreamEvent::Clip {
+            start,
+            duration,
+            name,
+            description,
+            result_sender,
+        } = event
+        else {
+            return;
+        };
+
+        let m3u8_content = self
+            .media_processor
+            .clip_playlist(start as i64 * 1000, duration as i64 * 1000);
+        log::info!(
+            "clip {}/{} from {} seconds ago, {} seconds",
+            self.app_name,
+            self.stream_name,
+            start,
+            duration
+        );
+
+        tokio::spawn(async move {
+            let result = match m3u8_content {
+                Ok(m3u8_content) => add_vod_stream(name, description, &m3u8_content)
+                    .await
+                    .map_err(|err| err.to_string()),
+                Err(err) => Err(err.to_string()),
+            };
+            let result = result.map_err(|err| {
+                log::error!("clip error: {}", err);
+                StreamHubError {
+                    value: StreamHubErrorValue::ClipError(err),
+                }
+            });
+            if let Err(err) = result_sender.send(result).await {
+                log::error!("send clip result error: {}", err);
+            }
+        });
     }
 
     pub fn flush_response_data(&mut self) -> Result<(), HlsError> {
@@ -129,9 +280,12 @@ impl FlvDataReceiver {
             });
         }
 
-        let receiver = event_result_receiver.await??.0.frame_receiver.unwrap();
+        let (receiver, statistic_data_sender) = event_result_receiver.await??;
 
-        self.data_consumer = receiver;
+        self.data_consumer = receiver.frame_receiver.unwrap();
+        self.statistic_data_sender = statistic_data_sender;
+        /*the statistics of a republished stream start over*/
+        self.reported_gop = 0;
 
         Ok(())
     }
@@ -1,8 +1,15 @@
+pub mod archive;
+pub mod cea608;
+pub mod clip;
 pub mod define;
 pub mod errors;
 pub mod flv2hls;
 pub mod flv_data_receiver;
 pub mod m3u8;
+pub mod master_playlist;
 pub mod remuxer;
+pub mod segment_policy;
 pub mod server;
-pub mod ts;
\ No newline at end of file
+pub mod settings;
+pub mod ts;
+pub mod webvtt;
\ No newline at end of file
@@ -1,6 +1,6 @@
 use {
-    commonlib::move_call::{upload_playlist_to_contract, live_to_vod},
-    super::{errors::{MediaError, MediaErrorValue}, ts::Ts}, bytes::BytesMut, rand::prelude::*, regex::Regex, std::{collections::VecDeque, fs::{self, File}, io::{Cursor, Write}, time::SystemTime}, 
+    commonlib::{blob_cleanup::{deletable_blob_owner, release_blobs}, move_call::{add_vod_stream, upload_playlist_to_contract, live_to_vod}},
+    super::{archive::{ArchiveEntry, PlaylistArchive}, clip::clip_range, errors::{MediaError, MediaErrorValue}, settings::{HlsSettings, PlaylistType, StorageBackend}, ts::Ts}, bytes::BytesMut, chrono::{DateTime, Duration, SecondsFormat, Utc}, rand::prelude::*, regex::Regex, std::{collections::VecDeque, fs::{self, File}, io::{Cursor, Write}, time::SystemTime}, 
 };
 
 const PUBLIC_PUBLISHERS: [&str;1] = [
@@ -25,6 +25,66 @@ const PUBLIC_PUBLISHERS: [&str;1] = [
 
 const BLOBID_REGEXP_STR: &str = "\"blobId\":\"(.*?)\",";
 
+/*stores the data as a blob on walrus, returns the blob id*/
+pub fn upload_to_walrus(data: BytesMut) -> Result<String, MediaError> {
+    store_on_walrus(data, None)
+}
+
+/*the blob object is sent to the owner, who can delete it later*/
+pub fn upload_deletable_to_walrus(data: BytesMut, owner: &str) -> Result<String, MediaError> {
+    store_on_walrus(data, Some(owner))
+}
+
+fn store_on_walrus(data: BytesMut, deletable_owner: Option<&str>) -> Result<String, MediaError> {
+    let count = PUBLIC_PUBLISHERS.len();
+    let mut rng = rand::thread_rng();
+    let index = (rng.gen::<f64>() * count as f64).trunc() as usize;
+    let aggr_url = PUBLIC_PUBLISHERS.get(index).unwrap();
+    
+    let mut publish_url = (*aggr_url).to_owned() + "/v1/store?epochs=10";
+    if let Some(owner) = deletable_owner {
+        publish_url += format!("&deletable=true&send_object_to={owner}").as_str();
+    }
+    log::info!("publish to: {}", publish_url);
+
+    let now = SystemTime::now();
+   
+    let res = ureq::put(publish_url.as_str())
+        .set("Content-Length", &data.len().to_string())
+        .send(Cursor::new(data.freeze().to_vec()));
+    if res.is_err() {
+        return Err(MediaError{value: MediaErrorValue::WalrusUploadError});
+    }
+    let res = res.unwrap();
+
+    let span = SystemTime::now().duration_since(now).unwrap().as_secs();
+    
+    let text = res.into_string().unwrap(); 
+
+    let regexp = Regex::new(BLOBID_REGEXP_STR).unwrap();
+    let Some(caps) = regexp.captures(text.as_str()) else {
+        log::error!("blobId not match: {}", text);
+        return Err(MediaError{value: MediaErrorValue::BlobIdParseError});
+    };
+
+    let blob_id = &caps[1];
+    log::info!("blob_id: {}", blob_id);
+    log::info!("seconds: {:?}", span);
+    log::info!("{}", "--------------------");
+
+    Ok(blob_id.to_owned())
+}
+
+/*an IDR access unit inside a ts segment*/
+#[derive(Debug, Clone, Copy)]
+pub struct IFrame {
+    /*milliseconds from the beginning of the segment*/
+    pub time: i64,
+    /*the byte range in the ts data*/
+    pub offset: usize,
+    pub size: usize,
+}
+
 pub struct Segment {
     /*ts duration*/
     pub duration: i64,
@@ -34,9 +94,17 @@ pub struct Segment {
     path: String,
     pub is_eof: bool,
     pub blob_id: String,
+    /*wall clock time of the first frame*/
+    pub program_date_time: DateTime<Utc>,
+    pub iframes: Vec<IFrame>,
+    /*the length of the PAT/PMT at the beginning of the ts data*/
+    pub map_size: usize,
+    /*a clip refers the blob, so it is not released with the segment*/
+    pub retained: bool,
 }
 
 impl Segment {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         duration: i64,
         discontinuity: bool,
@@ -44,6 +112,9 @@ impl Segment {
         path: String,
         is_eof: bool,
         blob_id: String,
+        program_date_time: DateTime<Utc>,
+        iframes: Vec<IFrame>,
+        map_size: usize,
     ) -> Self {
         Self {
             duration,
@@ -52,7 +123,50 @@ impl Segment {
             path,
             is_eof,
             blob_id,
+            program_date_time,
+            iframes,
+            map_size,
+            retained: false,
+        }
+    }
+
+    pub fn program_date_time_tag(&self) -> String {
+        format!(
+            "#EXT-X-PROGRAM-DATE-TIME:{}\n",
+            self.program_date_time
+                .to_rfc3339_opts(SecondsFormat::Millis, true)
+        )
+    }
+
+    /*Each I-frame lasts until the next one or the end of the segment. The I-frames
+    in the middle of a segment have no PAT/PMT, so the ones at the beginning of the
+    segment are the media initialization section of all its I-frames.*/
+    pub fn iframe_entries(&self, uri: &str) -> String {
+        if self.iframes.is_empty() {
+            return String::new();
+        }
+        let mut entries = format!(
+            "#EXT-X-MAP:URI=\"{}\",BYTERANGE=\"{}@0\"\n",
+            uri,
+            self.map_size
+        );
+
+        for (index, iframe) in self.iframes.iter().enumerate() {
+            let end = self
+                .iframes
+                .get(index + 1)
+                .map_or(self.duration, |next_iframe| next_iframe.time);
+            entries += format!(
+                "#EXTINF:{:.3}\n#EXT-X-BYTERANGE:{}@{}\n{}\n",
+                (end - iframe.time) as f64 / 1000.0,
+                iframe.size,
+                iframe.offset,
+                uri
+            )
+            .as_str();
         }
+
+        entries
     }
 }
 
@@ -66,6 +180,8 @@ pub struct M3u8 {
     /*How many files should be listed in the index file during a continuous, ongoing session?
     The normal recommendation is 3, but the optimum number may be larger.*/
     live_ts_count: usize,
+    /*milliseconds of the time-shift window, it replaces live_ts_count*/
+    dvr_window: Option<i64>,
 
     segments: VecDeque<Segment>,
 
@@ -75,84 +191,105 @@ pub struct M3u8 {
     ts_handler: Ts,
 
     need_record: bool,
+    storage: StorageBackend,
+    commit_live: bool,
+    /*the segments which are not recorded are uploaded as deletable blobs and released
+    once they leave the playlist, see commonlib::blob_cleanup*/
+    deletable_blob_owner: Option<&'static str>,
+    playlist_type: PlaylistType,
+    /*the segments of the record, the header is written once it ends*/
     vod_m3u8_content: String,
     vod_m3u8_name: String,
+
+    /*the I-frame only playlists refer the byte ranges of the IDR frames in the segments*/
+    iframe_playlist: bool,
+    iframes_sequence_no: u64,
+    iframes_m3u8_name: String,
+    vod_iframes_m3u8_content: String,
+    vod_iframes_m3u8_name: String,
+    /*the longest segment of the record*/
+    vod_target_duration: i64,
+
+    /*the segments of a continuous stream are anchored to the start time of the first one*/
+    next_program_date_time: Option<DateTime<Utc>>,
+    /*the transaction which committed the playlist with the latest segment*/
+    last_tx_digest: Option<String>,
+
+    /*the long playlists are committed in a compact form, see PlaylistArchive*/
+    archive: Option<PlaylistArchive>,
+    iframes_archive: Option<PlaylistArchive>,
 }
 
 impl M3u8 {
-    pub fn new(
-        duration: i64,
-        live_ts_count: usize,
-        app_name: String,
-        stream_name: String,
-        need_record: bool,
-    ) -> Self {
+    pub fn new(app_name: String, stream_name: String, settings: &HlsSettings) -> Self {
+        let need_record = settings.need_record;
         let m3u8_folder = format!("./{app_name}/{stream_name}");
         fs::create_dir_all(m3u8_folder.clone()).unwrap();
  
         let live_m3u8_name = format!("{stream_name}.m3u8");
-        let vod_m3u8_name = if need_record {
-            format!("vod_{stream_name}.m3u8")
+        let iframes_m3u8_name = Self::iframes_m3u8_name(&stream_name);
+        let (vod_m3u8_name, vod_iframes_m3u8_name) = if need_record {
+            (format!("vod_{stream_name}.m3u8"), format!("vod_{iframes_m3u8_name}"))
         } else {
-            String::default()
+            (String::default(), String::default())
         };
 
-        let mut m3u8 = Self {
+        let dvr_window = settings.dvr_window.map(|window| window as i64 * 1000);
+        let compact = settings.storage == StorageBackend::Walrus
+            && (dvr_window.is_some() || settings.playlist_type == PlaylistType::Event);
+        let merge_archives = settings.playlist_type == PlaylistType::Event;
+
+        Self {
             version: 3,
             sequence_no: 0,
-            duration,
-            live_ts_count,
+            duration: settings.target_duration,
+            live_ts_count: settings.live_window,
+            dvr_window,
             segments: VecDeque::new(),
             m3u8_folder,
             live_m3u8_name,
             ts_handler: Ts::new(app_name, stream_name),
             // record,
             need_record,
+            storage: settings.storage,
+            commit_live: settings.commit_live,
+            deletable_blob_owner: if need_record { None } else { deletable_blob_owner() },
+            playlist_type: settings.playlist_type,
             vod_m3u8_content: String::default(),
             vod_m3u8_name,
-        };
-
-        if need_record {
-            m3u8.vod_m3u8_content = m3u8.generate_m3u8_header(true);
+            iframe_playlist: settings.iframe_playlist,
+            iframes_sequence_no: 0,
+            iframes_m3u8_name,
+            vod_iframes_m3u8_content: String::default(),
+            vod_iframes_m3u8_name,
+            vod_target_duration: 0,
+            next_program_date_time: None,
+            last_tx_digest: None,
+            archive: compact.then(|| PlaylistArchive::new(merge_archives)),
+            iframes_archive: compact.then(|| PlaylistArchive::new(merge_archives)),
         }
-        m3u8
     }
 
-    pub fn upload_walrus(&self, data: BytesMut) -> Result<String, MediaError> {
-        let count = PUBLIC_PUBLISHERS.len();
-        let mut rng = rand::thread_rng();
-        let index = (rng.gen::<f64>() * count as f64).trunc() as usize;
-        let aggr_url = PUBLIC_PUBLISHERS.get(index).unwrap();
-        
-        let publish_url = (*aggr_url).to_owned() + "/v1/store?epochs=10";
-        log::info!("publish to: {}", publish_url);
-
-        let now = SystemTime::now();
-       
-        let res = ureq::put(publish_url.as_str())
-            .set("Content-Length", &data.len().to_string())
-            .send(Cursor::new(data.freeze().to_vec()));
-        if res.is_err() {
-            return Err(MediaError{value: MediaErrorValue::WalrusUploadError});
-        }
-        let res = res.unwrap();
-
-        let span = SystemTime::now().duration_since(now).unwrap().as_secs();
-        
-        let text = res.into_string().unwrap(); 
+    pub fn iframes_m3u8_name(stream_name: &str) -> String {
+        format!("{stream_name}_iframes.m3u8")
+    }
 
-        let regexp = Regex::new(BLOBID_REGEXP_STR).unwrap();
-        let Some(caps) = regexp.captures(text.as_str()) else {
-            log::error!("blobId not match: {}", text);
-            return Err(MediaError{value: MediaErrorValue::BlobIdParseError});
-        };
+    /*the url of the I-frame only playlist on the contract*/
+    pub fn get_iframes_live_path(app_name: &str, stream_name: &str) -> String {
+        format!("./{app_name}/{stream_name}_iframes")
+    }
 
-        let blob_id = &caps[1];
-        log::info!("blob_id: {}", blob_id);
-        log::info!("seconds: {:?}", span);
-        log::info!("{}", "--------------------");
+    pub fn upload_walrus(&self, data: BytesMut) -> Result<String, MediaError> {
+        match self.deletable_blob_owner {
+            Some(owner) => upload_deletable_to_walrus(data, owner),
+            None => upload_to_walrus(data),
+        }
+    }
 
-        Ok(blob_id.to_owned())
+    fn release_segment_blob(&self, segment: &Segment) {
+        if self.deletable_blob_owner.is_some() && !segment.retained && !segment.blob_id.is_empty() {
+            release_blobs(vec![segment.blob_id.clone()]);
+        }
     }
 
     pub fn add_segment(
@@ -161,23 +298,36 @@ impl M3u8 {
         discontinuity: bool,
         is_eof: bool,
         ts_data: BytesMut,
+        iframes: Vec<IFrame>,
+        map_size: usize,
     ) -> Result<(), MediaError> {
-        let segment_count = self.segments.len();
-
-        if segment_count >= self.live_ts_count {
+        while self.is_front_segment_expired(duration) {
             let segment = self.segments.pop_front().unwrap();
             if !self.need_record {
+                self.release_segment_blob(&segment);
                 self.ts_handler.delete(segment.path);
             }
 
             self.sequence_no += 1;
+            self.iframes_sequence_no += segment.iframes.len() as u64;
         }
         self.duration = std::cmp::max(duration, self.duration);
         let (ts_name, ts_path) = self.ts_handler.write(ts_data.clone())?;
 
-        let blob_id = self.upload_walrus(ts_data)?;
+        let blob_id = match self.storage {
+            StorageBackend::Walrus => self.upload_walrus(ts_data)?,
+            StorageBackend::Local => String::default(),
+        };
 
-        let segment = Segment::new(duration, discontinuity, ts_name, ts_path, is_eof, blob_id);
+        /*the segment is added once it ends, so it began a duration ago*/
+        let program_date_time = match self.next_program_date_time {
+            Some(date_time) if !discontinuity => date_time,
+            _ => Utc::now() - Duration::milliseconds(duration),
+        };
+        self.next_program_date_time = Some(program_date_time + Duration::milliseconds(duration));
+        self.last_tx_digest = None;
+
+        let segment = Segment::new(duration, discontinuity, ts_name, ts_path, is_eof, blob_id, program_date_time, iframes, map_size);
         if self.need_record {
             self.update_vod_m3u8(&segment);
         }
@@ -187,16 +337,37 @@ impl M3u8 {
         Ok(())
     }
 
+    /*An event playlist keeps all the segments of the session, a DVR window keeps the
+    segments which cover its duration along with the one to be added.*/
+    fn is_front_segment_expired(&self, new_duration: i64) -> bool {
+        let Some(front_segment) = self.segments.front() else {
+            return false;
+        };
+
+        match (self.playlist_type, self.dvr_window) {
+            (PlaylistType::Event, _) => false,
+            (PlaylistType::Live, Some(dvr_window)) => {
+                let window_duration: i64 = self.segments.iter().map(|segment| segment.duration).sum();
+                window_duration + new_duration - front_segment.duration >= dvr_window
+            }
+            (PlaylistType::Live, None) => self.segments.len() >= self.live_ts_count,
+        }
+    }
+
     pub async fn clear(&mut self) -> Result<(), MediaError> {
-        if self.need_record {
-            let vod_m3u8_path = format!("{}/{}", self.m3u8_folder, self.vod_m3u8_name);
-            let mut file_handler = File::create(vod_m3u8_path).unwrap();
-            self.vod_m3u8_content += "#EXT-X-ENDLIST\n";
-            file_handler.write_all(self.vod_m3u8_content.as_bytes())?;
+        if self.need_record && self.commit_live {
+            self.write_vod_m3u8()?;
 
-            live_to_vod(self.ts_handler.get_live_path(), &self.vod_m3u8_content).await.map_err(|_| MediaError{value: MediaErrorValue::LiveToVodUploadError})?;
-        } else {
+            if self.storage == StorageBackend::Walrus {
+                live_to_vod(self.ts_handler.get_live_path(), &self.vod_m3u8_content).await.map_err(|_| MediaError{value: MediaErrorValue::LiveToVodUploadError})?;
+            }
+
+            if self.iframe_playlist {
+                self.finish_vod_iframes_m3u8().await;
+            }
+        } else if !self.need_record {
             for segment in &self.segments {
+                self.release_segment_blob(segment);
                 self.ts_handler.delete(segment.path.clone());
             }
         }
@@ -205,12 +376,91 @@ impl M3u8 {
         let live_m3u8_path = format!("{}/{}", self.m3u8_folder, self.live_m3u8_name);
         fs::remove_file(live_m3u8_path)?;
 
+        let iframes_m3u8_path = format!("{}/{}", self.m3u8_folder, self.iframes_m3u8_name);
+        if fs::metadata(&iframes_m3u8_path).is_ok() {
+            fs::remove_file(iframes_m3u8_path)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_vod_m3u8(&mut self) -> Result<(), MediaError> {
+        let vod_m3u8_path = format!("{}/{}", self.m3u8_folder, self.vod_m3u8_name);
+        let mut file_handler = File::create(vod_m3u8_path)?;
+        self.vod_m3u8_content = self.generate_vod_header(self.version) + self.vod_m3u8_content.as_str();
+        self.vod_m3u8_content += "#EXT-X-ENDLIST\n";
+        file_handler.write_all(self.vod_m3u8_content.as_bytes())?;
+
         Ok(())
     }
 
+    /*The record of a stream which is not committed live, e.g. an imported file, is
+    added as a new VOD stream. Call it once the last segment is added.*/
+    pub async fn publish_vod(&mut self, name: String, description: String) -> Result<(), MediaError> {
+        if !self.need_record || self.storage != StorageBackend::Walrus {
+            return Err(MediaError{value: MediaErrorValue::AddVodStreamError});
+        }
+        self.write_vod_m3u8()?;
+
+        add_vod_stream(name, description, &self.vod_m3u8_content).await.map_err(|_| MediaError{value: MediaErrorValue::AddVodStreamError})
+    }
+
+    /*the I-frame only playlist is optional, so its errors do not fail the recording*/
+    async fn finish_vod_iframes_m3u8(&mut self) {
+        let mut vod_iframes_m3u8_header = self.generate_vod_header(std::cmp::max(self.version, 5));
+        vod_iframes_m3u8_header += "#EXT-X-I-FRAMES-ONLY\n";
+        self.vod_iframes_m3u8_content = vod_iframes_m3u8_header + self.vod_iframes_m3u8_content.as_str();
+        self.vod_iframes_m3u8_content += "#EXT-X-ENDLIST\n";
+
+        let vod_iframes_m3u8_path = format!("{}/{}", self.m3u8_folder, self.vod_iframes_m3u8_name);
+        if let Err(err) = File::create(vod_iframes_m3u8_path).and_then(|mut file_handler| file_handler.write_all(self.vod_iframes_m3u8_content.as_bytes())) {
+            log::error!("write vod iframes m3u8 error: {}", err);
+        }
+
+        if self.storage == StorageBackend::Walrus {
+            if let Err(err) = live_to_vod(self.get_iframes_path(), &self.vod_iframes_m3u8_content).await {
+                log::error!("move iframes playlist to vod error: {}", err);
+            }
+        }
+    }
+
+    fn get_iframes_path(&self) -> String {
+        format!("{}_iframes", self.ts_handler.get_live_path())
+    }
+
     pub fn generate_m3u8_header(&self, is_vod: bool) -> String {
+        self.generate_header(self.version, is_vod, self.sequence_no)
+    }
+
+    pub fn generate_iframes_m3u8_header(&self, is_vod: bool) -> String {
+        self.generate_iframes_header(is_vod, self.iframes_sequence_no)
+    }
+
+    /*EXT-X-BYTERANGE and EXT-X-I-FRAMES-ONLY need version 4, EXT-X-MAP needs version 5*/
+    fn generate_iframes_header(&self, is_vod: bool, sequence_no: u64) -> String {
+        let mut m3u8_header = self.generate_header(std::cmp::max(self.version, 5), is_vod, sequence_no);
+        m3u8_header += "#EXT-X-I-FRAMES-ONLY\n";
+        m3u8_header
+    }
+
+    /*the target duration of a finished playlist comes from its longest segment*/
+    fn generate_vod_header(&self, version: u16) -> String {
+        Self::vod_header(version, self.vod_target_duration)
+    }
+
+    fn vod_header(version: u16, target_duration: i64) -> String {
         let mut m3u8_header = "#EXTM3U\n".to_string();
-        m3u8_header += format!("#EXT-X-VERSION:{}\n", self.version).as_str();
+        m3u8_header += format!("#EXT-X-VERSION:{}\n", version).as_str();
+        m3u8_header += format!("#EXT-X-TARGETDURATION:{}\n", (target_duration + 999) / 1000).as_str();
+        m3u8_header += "#EXT-X-MEDIA-SEQUENCE:0\n";
+        m3u8_header += "#EXT-X-PLAYLIST-TYPE:VOD\n";
+        m3u8_header += "#EXT-X-ALLOW-CACHE:YES\n";
+        m3u8_header
+    }
+
+    fn generate_header(&self, version: u16, is_vod: bool, sequence_no: u64) -> String {
+        let mut m3u8_header = "#EXTM3U\n".to_string();
+        m3u8_header += format!("#EXT-X-VERSION:{}\n", version).as_str();
         m3u8_header += format!("#EXT-X-TARGETDURATION:{}\n", (self.duration + 999) / 1000).as_str();
 
         if is_vod {
@@ -218,7 +468,10 @@ impl M3u8 {
             m3u8_header += "#EXT-X-PLAYLIST-TYPE:VOD\n";
             m3u8_header += "#EXT-X-ALLOW-CACHE:YES\n";
         } else {
-            m3u8_header += format!("#EXT-X-MEDIA-SEQUENCE:{}\n", self.sequence_no).as_str();
+            m3u8_header += format!("#EXT-X-MEDIA-SEQUENCE:{}\n", sequence_no).as_str();
+            if self.playlist_type == PlaylistType::Event {
+                m3u8_header += "#EXT-X-PLAYLIST-TYPE:EVENT\n";
+            }
         }
 
         m3u8_header
@@ -226,26 +479,21 @@ impl M3u8 {
 
     pub async fn refresh_playlist(&mut self) -> Result<String, MediaError> {
         let mut m3u8_content = self.generate_m3u8_header(false);
-        let mut m3u8_content_blob = m3u8_content.clone();
+        let mut blob_entries = Vec::new();
 
         for segment in &self.segments {
+            let mut lines = String::new();
             if segment.discontinuity {
-                m3u8_content += "#EXT-X-DISCONTINUITY\n";
-                m3u8_content_blob += "#EXT-X-DISCONTINUITY\n";
+                lines += "#EXT-X-DISCONTINUITY\n";
             }
-            m3u8_content += format!(
-                "#EXTINF:{:.3}\n{}\n",
-                segment.duration as f64 / 1000.0,
-                segment.name
-            )
-            .as_str();
+            lines += segment.program_date_time_tag().as_str();
+            let duration = segment.duration as f64 / 1000.0;
 
-            m3u8_content_blob += format!(
-                "#EXTINF:{:.3}\n{}\n",
-                segment.duration as f64 / 1000.0,
-                segment.blob_id
-            )
-            .as_str();
+            m3u8_content += format!("{}#EXTINF:{:.3}\n{}\n", lines, duration, segment.name).as_str();
+            blob_entries.push(ArchiveEntry {
+                lines: format!("{}#EXTINF:{:.3}\n{}\n", lines, duration, segment.blob_id),
+                media_count: 1,
+            });
 
             if segment.is_eof {
                 m3u8_content += "#EXT-X-ENDLIST\n";
@@ -258,20 +506,194 @@ impl M3u8 {
         let mut file_handler = File::create(m3u8_path).unwrap();
         file_handler.write_all(m3u8_content.as_bytes())?;
 
-        upload_playlist_to_contract(self.ts_handler.get_live_path(), &m3u8_content_blob).await.map_err(|_| MediaError{value: MediaErrorValue::PlaylistUploadError})?;
+        if self.storage == StorageBackend::Walrus && self.commit_live {
+            let (media_sequence, body) = match &mut self.archive {
+                Some(archive) => archive.compact(self.sequence_no, self.sequence_no, &blob_entries)?,
+                None => (self.sequence_no, blob_entries.into_iter().map(|entry| entry.lines).collect()),
+            };
+            let m3u8_content_blob = self.generate_header(self.version, false, media_sequence) + body.as_str();
+
+            let tx_digest = upload_playlist_to_contract(self.ts_handler.get_live_path(), &m3u8_content_blob).await.map_err(|_| MediaError{value: MediaErrorValue::PlaylistUploadError})?;
+            self.last_tx_digest = Some(tx_digest);
+        }
+
+        if self.iframe_playlist {
+            if let Err(err) = self.refresh_iframes_playlist().await {
+                log::error!("refresh iframes playlist error: {}", err);
+            }
+        }
 
         Ok(m3u8_content)
     }
 
+    async fn refresh_iframes_playlist(&mut self) -> Result<(), MediaError> {
+        let mut m3u8_content = self.generate_iframes_m3u8_header(false);
+        let mut blob_entries = Vec::new();
+        /*a segment without IDR frames is skipped, keep its discontinuity for the next one*/
+        let mut discontinuity = false;
+
+        for segment in &self.segments {
+            let mut lines = String::new();
+            let mut blob_lines = String::new();
+
+            discontinuity |= segment.discontinuity;
+            if !segment.iframes.is_empty() {
+                if discontinuity {
+                    lines += "#EXT-X-DISCONTINUITY\n";
+                    blob_lines += "#EXT-X-DISCONTINUITY\n";
+                    discontinuity = false;
+                }
+                lines += segment.iframe_entries(&segment.name).as_str();
+                blob_lines += segment.iframe_entries(&segment.blob_id).as_str();
+            }
+
+            m3u8_content += lines.as_str();
+            blob_entries.push(ArchiveEntry {
+                lines: blob_lines,
+                media_count: segment.iframes.len() as u64,
+            });
+
+            if segment.is_eof {
+                m3u8_content += "#EXT-X-ENDLIST\n";
+                break;
+            }
+        }
+
+        let m3u8_path = format!("{}/{}", self.m3u8_folder, self.iframes_m3u8_name);
+        let mut file_handler = File::create(m3u8_path)?;
+        file_handler.write_all(m3u8_content.as_bytes())?;
+
+        if self.storage == StorageBackend::Walrus && self.commit_live {
+            let (media_sequence, body) = match &mut self.iframes_archive {
+                Some(archive) => archive.compact(self.sequence_no, self.iframes_sequence_no, &blob_entries)?,
+                None => (self.iframes_sequence_no, blob_entries.into_iter().map(|entry| entry.lines).collect()),
+            };
+            let m3u8_content_blob = self.generate_iframes_header(false, media_sequence) + body.as_str();
+
+            upload_playlist_to_contract(self.get_iframes_path(), &m3u8_content_blob).await.map_err(|_| MediaError{value: MediaErrorValue::PlaylistUploadError})?;
+        }
+
+        Ok(())
+    }
+
+    /*A VOD playlist of the uploaded segments which cover a clip, it starts start
+    milliseconds before the live edge.*/
+    pub fn clip_playlist(&mut self, start: i64, duration: i64) -> Result<String, MediaError> {
+        if self.storage != StorageBackend::Walrus {
+            return Err(MediaError{value: MediaErrorValue::ClipStorageError});
+        }
+
+        let durations: Vec<i64> = self.segments.iter().map(|segment| segment.duration).collect();
+        let Some(range) = clip_range(&durations, start, duration) else {
+            return Err(MediaError{value: MediaErrorValue::ClipRangeError});
+        };
+
+        for segment in self.segments.range_mut(range.clone()) {
+            segment.retained = true;
+        }
+        let segments: Vec<&Segment> = self.segments.range(range).collect();
+        let target_duration = segments.iter().map(|segment| segment.duration).max().unwrap_or_default();
+
+        let mut m3u8_content = Self::vod_header(self.version, target_duration);
+
+        for (index, segment) in segments.iter().enumerate() {
+            if segment.discontinuity && index > 0 {
+                m3u8_content += "#EXT-X-DISCONTINUITY\n";
+            }
+            m3u8_content += segment.program_date_time_tag().as_str();
+            m3u8_content += format!("#EXTINF:{:.3}\n{}\n", segment.duration as f64 / 1000.0, segment.blob_id).as_str();
+        }
+        m3u8_content += "#EXT-X-ENDLIST\n";
+
+        Ok(m3u8_content)
+    }
+
+    /*The blob id of the latest segment and the digest of the transaction which
+    committed it, they are only available with the walrus storage.*/
+    pub fn latest_segment_proof(&self) -> Option<(String, String)> {
+        let segment = self.segments.back()?;
+        let tx_digest = self.last_tx_digest.clone()?;
+        if segment.blob_id.is_empty() {
+            return None;
+        }
+
+        Some((segment.blob_id.clone(), tx_digest))
+    }
+
     pub fn update_vod_m3u8(&mut self, segment: &Segment) {
+        self.vod_target_duration = std::cmp::max(segment.duration, self.vod_target_duration);
         if segment.discontinuity {
             self.vod_m3u8_content += "#EXT-X-DISCONTINUITY\n";
         }
+        self.vod_m3u8_content += segment.program_date_time_tag().as_str();
+        /*the local record refers the ts files next to it*/
+        let uri = match self.storage {
+            StorageBackend::Walrus => &segment.blob_id,
+            StorageBackend::Local => &segment.name,
+        };
         self.vod_m3u8_content += format!(
             "#EXTINF:{:.3}\n{}\n",
             segment.duration as f64 / 1000.0,
-            segment.blob_id
+            uri
         )
         .as_str();
+
+        if self.iframe_playlist && !segment.iframes.is_empty() {
+            if segment.discontinuity {
+                self.vod_iframes_m3u8_content += "#EXT-X-DISCONTINUITY\n";
+            }
+            self.vod_iframes_m3u8_content += segment.iframe_entries(uri).as_str();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{IFrame, M3u8, Segment},
+        chrono::Utc,
+    };
+
+    #[test]
+    fn test_iframe_entries() {
+        let iframes = vec![
+            IFrame {
+                time: 0,
+                offset: 564,
+                size: 2000,
+            },
+            IFrame {
+                time: 2000,
+                offset: 50000,
+                size: 1500,
+            },
+        ];
+        let segment = Segment::new(
+            3000,
+            false,
+            String::from("0.ts"),
+            String::from("./live/test/0.ts"),
+            false,
+            String::default(),
+            Utc::now(),
+            iframes,
+            376,
+        );
+
+        assert_eq!(
+            segment.iframe_entries("0.ts"),
+            "#EXT-X-MAP:URI=\"0.ts\",BYTERANGE=\"376@0\"\n\
+             #EXTINF:2.000\n#EXT-X-BYTERANGE:2000@564\n0.ts\n\
+             #EXTINF:1.000\n#EXT-X-BYTERANGE:1500@50000\n0.ts\n"
+        );
+    }
+
+    #[test]
+    fn test_vod_header() {
+        assert_eq!(
+            M3u8::vod_header(5, 6200),
+            "#EXTM3U\n#EXT-X-VERSION:5\n#EXT-X-TARGETDURATION:7\n\
+             #EXT-X-MEDIA-SEQUENCE:0\n#EXT-X-PLAYLIST-TYPE:VOD\n#EXT-X-ALLOW-CACHE:YES\n"
+        );
     }
 }
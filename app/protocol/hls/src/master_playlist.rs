@@ -0,0 +1,280 @@
+use {
+    super::{
+        errors::{MediaError, MediaErrorValue},
+        m3u8::M3u8,
+        webvtt::SubtitlePlaylist,
+    },
+    commonlib::move_call::{upload_playlist_to_contract, LIVE_PLAYLIST_QUERY},
+    std::{
+        fs::{self, File},
+        io::Write,
+    },
+};
+
+const SUBTITLES_GROUP_ID: &str = "subs";
+
+/*Several rtmp streams of the same show pushed with different resolutions,
+e.g. show_1080, show_720 and show_480 are grouped as show.*/
+#[derive(Debug, Clone)]
+pub struct RenditionGroup {
+    pub app_name: String,
+    pub group_name: String,
+    pub stream_names: Vec<String>,
+}
+
+impl RenditionGroup {
+    pub fn contains(&self, app_name: &str, stream_name: &str) -> bool {
+        self.app_name == app_name && self.stream_names.iter().any(|name| name == stream_name)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Variant {
+    pub stream_name: String,
+    /*bits per second*/
+    pub bandwidth: usize,
+    pub width: u32,
+    pub height: u32,
+    /*the rendition also has an I-frame only playlist*/
+    pub iframe_playlist: bool,
+}
+
+pub struct MasterPlaylist {
+    version: u16,
+    group: RenditionGroup,
+    /*the published renditions*/
+    variants: Vec<Variant>,
+    /*the stream whose text track is the subtitles rendition of all the variants*/
+    subtitles_stream_name: Option<String>,
+    m3u8_folder: String,
+    m3u8_name: String,
+}
+
+impl MasterPlaylist {
+    pub fn new(group: RenditionGroup) -> Self {
+        let m3u8_folder = format!("./{}/{}", group.app_name, group.group_name);
+        let m3u8_name = format!("{}.m3u8", group.group_name);
+
+        Self {
+            version: 3,
+            group,
+            variants: Vec::new(),
+            subtitles_stream_name: None,
+            m3u8_folder,
+            m3u8_name,
+        }
+    }
+
+    /*the url of the group on the contract, in the same form as the live streams*/
+    pub fn get_live_path(&self) -> String {
+        self.m3u8_folder.clone()
+    }
+
+    pub fn update_variant(&mut self, variant: Variant) {
+        self.variants
+            .retain(|cur_variant| cur_variant.stream_name != variant.stream_name);
+        self.variants.push(variant);
+        /*players pick the first entry to start with, so put the highest bandwidth first*/
+        self.variants
+            .sort_by_key(|variant| std::cmp::Reverse(variant.bandwidth));
+    }
+
+    pub fn remove_variant(&mut self, stream_name: &str) {
+        self.variants
+            .retain(|variant| variant.stream_name != stream_name);
+    }
+
+    pub fn has_variants(&self) -> bool {
+        !self.variants.is_empty()
+    }
+
+    pub fn set_subtitles(&mut self, stream_name: &str) {
+        self.subtitles_stream_name = Some(stream_name.to_string());
+    }
+
+    pub fn remove_subtitles(&mut self, stream_name: &str) {
+        if self.subtitles_stream_name.as_deref() == Some(stream_name) {
+            self.subtitles_stream_name = None;
+        }
+    }
+
+    /*The local playlist refers the renditions served by the hls server, while the
+    one on chain refers the live playlists which can be queried from the http api.*/
+    pub fn generate_m3u8(&self, on_chain: bool) -> String {
+        /*EXT-X-I-FRAME-STREAM-INF needs version 4*/
+        let version = if self.variants.iter().any(|variant| variant.iframe_playlist) {
+            std::cmp::max(self.version, 4)
+        } else {
+            self.version
+        };
+        let mut m3u8_content = "#EXTM3U\n".to_string();
+        m3u8_content += format!("#EXT-X-VERSION:{}\n", version).as_str();
+
+        if let Some(stream_name) = &self.subtitles_stream_name {
+            let uri = if on_chain {
+                format!(
+                    "{}{}",
+                    LIVE_PLAYLIST_QUERY,
+                    SubtitlePlaylist::get_live_path(&self.group.app_name, stream_name)
+                )
+            } else {
+                format!(
+                    "../{}/{}",
+                    stream_name,
+                    SubtitlePlaylist::m3u8_name(stream_name)
+                )
+            };
+            m3u8_content += format!(
+                "#EXT-X-MEDIA:TYPE=SUBTITLES,GROUP-ID=\"{}\",NAME=\"{}\",DEFAULT=YES,AUTOSELECT=YES,URI=\"{}\"\n",
+                SUBTITLES_GROUP_ID, stream_name, uri
+            )
+            .as_str();
+        }
+
+        for variant in &self.variants {
+            m3u8_content += format!("#EXT-X-STREAM-INF:BANDWIDTH={}", variant.bandwidth).as_str();
+            if variant.width > 0 && variant.height > 0 {
+                m3u8_content +=
+                    format!(",RESOLUTION={}x{}", variant.width, variant.height).as_str();
+            }
+            if self.subtitles_stream_name.is_some() {
+                m3u8_content += format!(",SUBTITLES=\"{}\"", SUBTITLES_GROUP_ID).as_str();
+            }
+            m3u8_content += "\n";
+
+            if on_chain {
+                m3u8_content += format!(
+                    "{}./{}/{}\n",
+                    LIVE_PLAYLIST_QUERY, self.group.app_name, variant.stream_name
+                )
+                .as_str();
+            } else {
+                m3u8_content +=
+                    format!("../{}/{}.m3u8\n", variant.stream_name, variant.stream_name).as_str();
+            }
+        }
+
+        /*the bandwidth of the whole rendition is an upper bound of its I-frames*/
+        for variant in self
+            .variants
+            .iter()
+            .filter(|variant| variant.iframe_playlist)
+        {
+            let uri = if on_chain {
+                format!(
+                    "{}{}",
+                    LIVE_PLAYLIST_QUERY,
+                    M3u8::get_iframes_live_path(&self.group.app_name, &variant.stream_name)
+                )
+            } else {
+                format!(
+                    "../{}/{}",
+                    variant.stream_name,
+                    M3u8::iframes_m3u8_name(&variant.stream_name)
+                )
+            };
+            m3u8_content +=
+                format!("#EXT-X-I-FRAME-STREAM-INF:BANDWIDTH={}", variant.bandwidth).as_str();
+            if variant.width > 0 && variant.height > 0 {
+                m3u8_content +=
+                    format!(",RESOLUTION={}x{}", variant.width, variant.height).as_str();
+            }
+            m3u8_content += format!(",URI=\"{}\"\n", uri).as_str();
+        }
+
+        m3u8_content
+    }
+
+    pub async fn refresh_playlist(&mut self) -> Result<String, MediaError> {
+        let m3u8_path = format!("{}/{}", self.m3u8_folder, self.m3u8_name);
+
+        if self.variants.is_empty() {
+            if fs::metadata(&m3u8_path).is_ok() {
+                fs::remove_file(&m3u8_path)?;
+            }
+        } else {
+            fs::create_dir_all(&self.m3u8_folder)?;
+            let mut file_handler = File::create(m3u8_path)?;
+            file_handler.write_all(self.generate_m3u8(false).as_bytes())?;
+        }
+
+        let m3u8_content_chain = self.generate_m3u8(true);
+        upload_playlist_to_contract(self.get_live_path(), &m3u8_content_chain)
+            .await
+            .map_err(|_| MediaError {
+                value: MediaErrorValue::PlaylistUploadError,
+            })?;
+
+        Ok(m3u8_content_chain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MasterPlaylist, RenditionGroup, Variant};
+
+    #[test]
+    fn test_generate_master_m3u8() {
+        let group = RenditionGroup {
+            app_name: String::from("live"),
+            group_name: String::from("show"),
+            stream_names: vec![String::from("show_720"), String::from("show_1080")],
+        };
+        assert!(group.contains("live", "show_720"));
+        assert!(!group.contains("live", "show"));
+
+        let mut master = MasterPlaylist::new(group);
+        master.update_variant(Variant {
+            stream_name: String::from("show_720"),
+            bandwidth: 2_500_000,
+            width: 1280,
+            height: 720,
+            iframe_playlist: false,
+        });
+        master.update_variant(Variant {
+            stream_name: String::from("show_1080"),
+            bandwidth: 5_000_000,
+            width: 1920,
+            height: 1080,
+            iframe_playlist: false,
+        });
+
+        assert_eq!(
+            master.generate_m3u8(false),
+            "#EXTM3U\n#EXT-X-VERSION:3\n\
+             #EXT-X-STREAM-INF:BANDWIDTH=5000000,RESOLUTION=1920x1080\n../show_1080/show_1080.m3u8\n\
+             #EXT-X-STREAM-INF:BANDWIDTH=2500000,RESOLUTION=1280x720\n../show_720/show_720.m3u8\n"
+        );
+
+        master.set_subtitles("show_720");
+        assert!(master
+            .generate_m3u8(false)
+            .starts_with("#EXTM3U\n#EXT-X-VERSION:3\n\
+             #EXT-X-MEDIA:TYPE=SUBTITLES,GROUP-ID=\"subs\",NAME=\"show_720\",DEFAULT=YES,AUTOSELECT=YES,URI=\"../show_720/show_720_subtitles.m3u8\"\n\
+             #EXT-X-STREAM-INF:BANDWIDTH=5000000,RESOLUTION=1920x1080,SUBTITLES=\"subs\"\n"));
+        master.remove_subtitles("show_720");
+
+        master.update_variant(Variant {
+            stream_name: String::from("show_1080"),
+            bandwidth: 5_000_000,
+            width: 1920,
+            height: 1080,
+            iframe_playlist: true,
+        });
+        assert_eq!(
+            master.generate_m3u8(false),
+            "#EXTM3U\n#EXT-X-VERSION:4\n\
+             #EXT-X-STREAM-INF:BANDWIDTH=5000000,RESOLUTION=1920x1080\n../show_1080/show_1080.m3u8\n\
+             #EXT-X-STREAM-INF:BANDWIDTH=2500000,RESOLUTION=1280x720\n../show_720/show_720.m3u8\n\
+             #EXT-X-I-FRAME-STREAM-INF:BANDWIDTH=5000000,RESOLUTION=1920x1080,URI=\"../show_1080/show_1080_iframes.m3u8\"\n"
+        );
+
+        master.remove_variant("show_1080");
+        assert_eq!(
+            master.generate_m3u8(true),
+            "#EXTM3U\n#EXT-X-VERSION:3\n\
+             #EXT-X-STREAM-INF:BANDWIDTH=2500000,RESOLUTION=1280x720\n\
+             query_live_m3u8?stream_name=./live/show_720\n"
+        );
+    }
+}
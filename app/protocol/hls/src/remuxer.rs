@@ -1,55 +1,181 @@
 use {
-    super::{errors::HlsError, flv_data_receiver::FlvDataReceiver},
+    super::{
+        define::{HlsStreamEvent, HlsStreamEventSender},
+        errors::HlsError,
+        flv_data_receiver::FlvDataReceiver,
+        master_playlist::{MasterPlaylist, RenditionGroup, Variant},
+        settings::HlsSettingsResolver,
+    },
+    std::{collections::HashMap, sync::Arc, time::Duration},
     streamhub::{
-        define::{BroadcastEvent, BroadcastEventReceiver, StreamHubEventSender},
+        define::{BroadcastEvent, BroadcastEventReceiver, StreamHubEvent, StreamHubEventSender},
         stream::StreamIdentifier,
     },
+    tokio::{
+        sync::{mpsc, oneshot, Mutex},
+        time::sleep,
+    },
 };
 
+/*the bitrate statistics are caculated every 5 seconds, wait for at least one round.*/
+const VARIANT_STATISTIC_DELAY: u64 = 6;
+const VARIANT_STATISTIC_RETRY: usize = 5;
+
 pub struct HlsRemuxer {
     client_event_consumer: BroadcastEventReceiver,
     event_producer: StreamHubEventSender,
-    need_record: bool,
+    settings_resolver: HlsSettingsResolver,
+    master_playlists: Vec<(RenditionGroup, Arc<Mutex<MasterPlaylist>>)>,
+    /*the receivers which are running or waiting for a republish*/
+    stream_event_senders: HashMap<StreamIdentifier, HlsStreamEventSender>,
 }
 
 impl HlsRemuxer {
     pub fn new(
         consumer: BroadcastEventReceiver,
         event_producer: StreamHubEventSender,
-        need_record: bool,
+        settings_resolver: HlsSettingsResolver,
+        rendition_groups: Vec<RenditionGroup>,
     ) -> Self {
+        let master_playlists = rendition_groups
+            .into_iter()
+            .map(|group| {
+                let master_playlist = MasterPlaylist::new(group.clone());
+                (group, Arc::new(Mutex::new(master_playlist)))
+            })
+            .collect();
+
         Self {
             client_event_consumer: consumer,
             event_producer,
-            need_record,
+            settings_resolver,
+            master_playlists,
+            stream_event_senders: HashMap::new(),
         }
     }
 
+    fn find_master_playlist(
+        &self,
+        app_name: &str,
+        stream_name: &str,
+    ) -> Option<Arc<Mutex<MasterPlaylist>>> {
+        self.master_playlists
+            .iter()
+            .find(|(group, _)| group.contains(app_name, stream_name))
+            .map(|(_, master_playlist)| master_playlist.clone())
+    }
+
     pub async fn run(&mut self) -> Result<(), HlsError> {
         loop {
             let val = self.client_event_consumer.recv().await?;
             match val {
                 BroadcastEvent::Publish { identifier } => {
+                    self.stream_event_senders
+                        .retain(|_, sender| !sender.is_closed());
+                    if let Some(sender) = self.stream_event_senders.get(&identifier) {
+                        if sender.send(HlsStreamEvent::Republish).is_ok() {
+                            continue;
+                        }
+                    }
+
                     if let StreamIdentifier::Rtmp {
                         app_name,
                         stream_name,
-                    } = identifier
+                    } = identifier.clone()
                     {
-                        let mut rtmp_subscriber = FlvDataReceiver::new(
+                        let master_playlist = self.find_master_playlist(&app_name, &stream_name);
+                        let settings = self.settings_resolver.resolve(&app_name, &stream_name);
+                        log::info!(
+                            "hls settings of {}/{}: {:?}",
                             app_name,
                             stream_name,
+                            settings
+                        );
+                        let iframe_playlist = settings.iframe_playlist;
+
+                        let (stream_event_sender, stream_event_consumer) =
+                            mpsc::unbounded_channel();
+                        self.stream_event_senders
+                            .insert(identifier.clone(), stream_event_sender);
+
+                        let mut rtmp_subscriber = FlvDataReceiver::new(
+                            app_name,
+                            stream_name.clone(),
                             self.event_producer.clone(),
-                            25,   // calvin NOTE: the duration length depends on walrus confirmation speed
-                            self.need_record,
+                            stream_event_consumer,
+                            settings,
+                            master_playlist.clone(),
                         );
 
+                        let add_variant_handle = master_playlist.as_ref().map(|master_playlist| {
+                            let event_producer = self.event_producer.clone();
+                            let master_playlist = master_playlist.clone();
+                            let identifier = identifier.clone();
+                            let stream_name = stream_name.clone();
+                            tokio::spawn(async move {
+                                add_variant(
+                                    event_producer,
+                                    identifier,
+                                    stream_name,
+                                    iframe_playlist,
+                                    master_playlist,
+                                )
+                                .await;
+                            })
+                        });
+
                         tokio::spawn(async move {
                             if let Err(err) = rtmp_subscriber.run().await {
                                 println!("hls handler run error {err}");
                             }
+
+                            /*the variant may be still waiting for the statistics, stop it
+                            before removing it so that it is not added afterwards*/
+                            if let Some(add_variant_handle) = add_variant_handle {
+                                add_variant_handle.abort();
+                                let _ = add_variant_handle.await;
+                            }
+
+                            if let Some(master_playlist) = master_playlist {
+                                let mut master_playlist = master_playlist.lock().await;
+                                master_playlist.remove_variant(&stream_name);
+                                master_playlist.remove_subtitles(&stream_name);
+                                if let Err(err) = master_playlist.refresh_playlist().await {
+                                    log::error!("refresh master playlist error: {}", err);
+                                }
+                            }
                         });
                     }
                 }
+                BroadcastEvent::UnPublish { identifier } => {
+                    if let Some(sender) = self.stream_event_senders.get(&identifier) {
+                        if sender.send(HlsStreamEvent::UnPublish).is_err() {
+                            self.stream_event_senders.remove(&identifier);
+                        }
+                    }
+                }
+                /*the result sender is dropped if the stream has no hls session*/
+                BroadcastEvent::Clip {
+                    identifier,
+                    start,
+                    duration,
+                    name,
+                    description,
+                    result_sender,
+                } => {
+                    if let Some(sender) = self.stream_event_senders.get(&identifier) {
+                        let clip_event = HlsStreamEvent::Clip {
+                            start,
+                            duration,
+                            name,
+                            description,
+                            result_sender,
+                        };
+                        if sender.send(clip_event).is_err() {
+                            self.stream_event_senders.remove(&identifier);
+                        }
+                    }
+                }
                 _ => {
                     log::trace!("other infos...");
                 }
@@ -57,3 +183,76 @@ impl HlsRemuxer {
         }
     }
 }
+
+/*Get the bandwidth and resolution of a rendition from the stream statistics and
+add it to the master playlist of its group.*/
+async fn add_variant(
+    event_producer: StreamHubEventSender,
+    identifier: StreamIdentifier,
+    stream_name: String,
+    iframe_playlist: bool,
+    master_playlist: Arc<Mutex<MasterPlaylist>>,
+) {
+    let mut variant = Variant {
+        stream_name,
+        iframe_playlist,
+        ..Default::default()
+    };
+
+    for _ in 0..VARIANT_STATISTIC_RETRY {
+        sleep(Duration::from_secs(VARIANT_STATISTIC_DELAY)).await;
+
+        let (result_sender, result_receiver) = oneshot::channel();
+        let statistic_event = StreamHubEvent::ApiStatistic {
+            top_n: None,
+            identifier: Some(identifier.clone()),
+            uuid: None,
+            result_sender,
+        };
+        if event_producer.send(statistic_event).is_err() {
+            log::error!("add_variant: send statistic event error");
+            return;
+        }
+
+        let statistics = match result_receiver.await {
+            Ok(statistics) => statistics,
+            Err(err) => {
+                log::error!("add_variant: receive statistic result error: {}", err);
+                return;
+            }
+        };
+
+        /*the stream has been unpublished*/
+        let publisher = &statistics[0]["publisher"];
+        if publisher.is_null() {
+            return;
+        }
+
+        let video = &publisher["video"];
+        variant.width = video["width"].as_u64().unwrap_or_default() as u32;
+        variant.height = video["height"].as_u64().unwrap_or_default() as u32;
+        /*keep the peak value, the bandwidth of a variant is an upper bound*/
+        let recv_bitrate = publisher["recv_bitrate(kbits/s)"]
+            .as_u64()
+            .unwrap_or_default() as usize;
+        variant.bandwidth = std::cmp::max(variant.bandwidth, recv_bitrate * 1000);
+
+        if variant.bandwidth > 0 && variant.width > 0 {
+            break;
+        }
+    }
+
+    if variant.bandwidth == 0 {
+        log::warn!(
+            "add_variant: no bitrate statistics of {}, skip it",
+            variant.stream_name
+        );
+        return;
+    }
+
+    let mut master_playlist = master_playlist.lock().await;
+    master_playlist.update_variant(variant);
+    if let Err(err) = master_playlist.refresh_playlist().await {
+        log::error!("refresh master playlist error: {}", err);
+    }
+}
@@ -0,0 +1,194 @@
+/*Decides where the ts segments are cut.
+
+A segment should start with an IDR frame, otherwise the players can not seek to it,
+so the video streams are cut on the first keyframe after the target duration. In
+keyframe only mode that is the only place to cut, and a segment longer than the max
+duration is only reported. In the other mode a cut is forced on any frame once the
+max duration is reached, which bounds the segment size when the encoder sends very
+long gops. The streams which have no video are cut on the audio frames.*/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameKind {
+    KeyFrame,
+    InterFrame,
+    Audio,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SegmentPolicyConfig {
+    /*milliseconds*/
+    pub target_duration: i64,
+    pub max_duration: i64,
+    pub keyframe_only: bool,
+}
+
+pub struct SegmentPolicy {
+    config: SegmentPolicyConfig,
+    has_video: bool,
+    last_keyframe_dts: Option<i64>,
+    /*milliseconds of the last gop*/
+    last_gop: i64,
+    /*only report an over long segment once*/
+    max_duration_reported: bool,
+    /*only report the long gops once until the gops are short again*/
+    long_gop_reported: bool,
+}
+
+impl SegmentPolicy {
+    pub fn new(config: SegmentPolicyConfig) -> Self {
+        if config.keyframe_only {
+            log::info!("segment policy: {:?}, cut on keyframes only", config);
+        } else {
+            log::info!("segment policy: {:?}", config);
+        }
+
+        Self {
+            config,
+            has_video: false,
+            last_keyframe_dts: None,
+            last_gop: 0,
+            max_duration_reported: false,
+            long_gop_reported: false,
+        }
+    }
+
+    /*segment_duration is the duration from the beginning of the current segment to this frame*/
+    pub fn need_new_segment(&mut self, frame: FrameKind, dts: i64, segment_duration: i64) -> bool {
+        match frame {
+            FrameKind::KeyFrame => {
+                self.has_video = true;
+                self.update_gop(dts);
+            }
+            FrameKind::InterFrame => {
+                self.has_video = true;
+            }
+            FrameKind::Audio => {}
+        }
+
+        if segment_duration < self.config.target_duration {
+            return false;
+        }
+
+        if !self.has_video {
+            return self.cut(frame, segment_duration);
+        }
+
+        match frame {
+            FrameKind::KeyFrame => self.cut(frame, segment_duration),
+            _ if segment_duration < self.config.max_duration => false,
+            _ if self.config.keyframe_only => {
+                if !self.max_duration_reported {
+                    self.max_duration_reported = true;
+                    log::warn!(
+                        "segment exceeds the max duration {} ms, last gop: {} ms, consider a shorter keyframe interval",
+                        self.config.max_duration,
+                        self.last_gop
+                    );
+                }
+                false
+            }
+            _ => self.cut(frame, segment_duration),
+        }
+    }
+
+    pub fn last_gop(&self) -> i64 {
+        self.last_gop
+    }
+
+    fn cut(&mut self, frame: FrameKind, segment_duration: i64) -> bool {
+        self.max_duration_reported = false;
+
+        if self.has_video {
+            log::info!(
+                "cut segment of {} ms on {:?}, last gop: {} ms",
+                segment_duration,
+                frame,
+                self.last_gop
+            );
+        }
+        true
+    }
+
+    fn update_gop(&mut self, dts: i64) {
+        if let Some(last_keyframe_dts) = self.last_keyframe_dts {
+            let gop_duration = dts - last_keyframe_dts;
+            if gop_duration > 0 {
+                self.last_gop = gop_duration;
+                if gop_duration <= self.config.target_duration {
+                    self.long_gop_reported = false;
+                } else if !self.long_gop_reported {
+                    self.long_gop_reported = true;
+                    log::warn!(
+                        "gop of {} ms is longer than the segment target duration {} ms",
+                        gop_duration,
+                        self.config.target_duration
+                    );
+                }
+            }
+        }
+        self.last_keyframe_dts = Some(dts);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FrameKind, SegmentPolicy, SegmentPolicyConfig};
+
+    #[test]
+    fn test_keyframe_only() {
+        let mut policy = SegmentPolicy::new(SegmentPolicyConfig {
+            target_duration: 2000,
+            max_duration: 4000,
+            keyframe_only: true,
+        });
+
+        assert!(!policy.need_new_segment(FrameKind::KeyFrame, 0, 0));
+        assert!(!policy.need_new_segment(FrameKind::Audio, 2500, 2500));
+        assert!(!policy.need_new_segment(FrameKind::InterFrame, 4500, 4500));
+        assert!(policy.need_new_segment(FrameKind::KeyFrame, 5000, 5000));
+        assert!(!policy.need_new_segment(FrameKind::KeyFrame, 6000, 1000));
+        assert_eq!(policy.last_gop, 1000);
+    }
+
+    #[test]
+    fn test_max_duration() {
+        let mut policy = SegmentPolicy::new(SegmentPolicyConfig {
+            target_duration: 2000,
+            max_duration: 4000,
+            keyframe_only: false,
+        });
+
+        assert!(!policy.need_new_segment(FrameKind::KeyFrame, 0, 0));
+        assert!(!policy.need_new_segment(FrameKind::Audio, 2500, 2500));
+        assert!(policy.need_new_segment(FrameKind::InterFrame, 4000, 4000));
+    }
+
+    #[test]
+    fn test_long_gop_reported_once() {
+        let mut policy = SegmentPolicy::new(SegmentPolicyConfig {
+            target_duration: 2000,
+            max_duration: 4000,
+            keyframe_only: true,
+        });
+
+        policy.need_new_segment(FrameKind::KeyFrame, 0, 0);
+        policy.need_new_segment(FrameKind::KeyFrame, 3000, 3000);
+        assert!(policy.long_gop_reported);
+        policy.need_new_segment(FrameKind::KeyFrame, 6000, 3000);
+        assert!(policy.long_gop_reported);
+        policy.need_new_segment(FrameKind::KeyFrame, 7000, 1000);
+        assert!(!policy.long_gop_reported);
+    }
+
+    #[test]
+    fn test_audio_only() {
+        let mut policy = SegmentPolicy::new(SegmentPolicyConfig {
+            target_duration: 2000,
+            max_duration: 4000,
+            keyframe_only: true,
+        });
+
+        assert!(!policy.need_new_segment(FrameKind::Audio, 1000, 1000));
+        assert!(policy.need_new_segment(FrameKind::Audio, 2000, 2000));
+    }
+}
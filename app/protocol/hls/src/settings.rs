@@ -0,0 +1,298 @@
+use {
+    super::{
+        errors::{HlsError, HlsErrorValue},
+        segment_policy::SegmentPolicyConfig,
+    },
+    regex::Regex,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageBackend {
+    /*upload the segments to walrus and commit the playlists on chain*/
+    Walrus,
+    /*only keep the segments and playlists on the local disk*/
+    Local,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistType {
+    /*sliding window of the latest segments*/
+    Live,
+    /*segments are only appended, EXT-X-PLAYLIST-TYPE:EVENT*/
+    Event,
+}
+
+#[derive(Debug, Clone)]
+pub struct HlsSettings {
+    /*segment duration in seconds*/
+    pub target_duration: i64,
+    /*seconds, twice the target duration if not set*/
+    pub max_duration: Option<i64>,
+    /*never cut a video segment on a non keyframe, even beyond the max duration*/
+    pub keyframe_only: bool,
+    /*how many segments are listed in the live playlist*/
+    pub live_window: usize,
+    /*seconds of the time-shift window of a live playlist, it keeps the segments by
+    duration instead of live_window. An event playlist keeps the whole event.*/
+    pub dvr_window: Option<u64>,
+    pub need_record: bool,
+    pub storage: StorageBackend,
+    pub playlist_type: PlaylistType,
+    /*seconds to wait for the stream to be published again before finishing the
+    playlist, 0 finishes it once the stream ends*/
+    pub republish_grace_period: u64,
+    /*also write the I-frame only playlists for trick play, which commits one more
+    playlist on chain per segment*/
+    pub iframe_playlist: bool,
+    /*commit the live playlists on chain while segmenting, an imported file is only
+    added as a VOD stream once it is done*/
+    pub commit_live: bool,
+}
+
+impl HlsSettings {
+    /*in milliseconds*/
+    pub fn segment_policy_config(&self) -> SegmentPolicyConfig {
+        let max_duration = self.max_duration.unwrap_or(self.target_duration * 2);
+
+        SegmentPolicyConfig {
+            target_duration: self.target_duration * 1000,
+            max_duration: std::cmp::max(max_duration, self.target_duration) * 1000,
+            keyframe_only: self.keyframe_only,
+        }
+    }
+
+    /*the playlists cannot be written with a zero segment duration or window*/
+    pub fn validate(&self) -> Result<(), HlsError> {
+        if self.target_duration <= 0 {
+            return Err(invalid_settings(format!(
+                "target_duration must be positive, got {}",
+                self.target_duration
+            )));
+        }
+        if self.live_window < 1 {
+            return Err(invalid_settings(String::from(
+                "live_window must be at least 1",
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn invalid_settings(reason: String) -> HlsError {
+    HlsError {
+        value: HlsErrorValue::InvalidSettings(reason),
+    }
+}
+
+impl Default for HlsSettings {
+    fn default() -> Self {
+        Self {
+            target_duration: 25, // calvin NOTE: the duration length depends on walrus confirmation speed
+            max_duration: None,
+            keyframe_only: true,
+            live_window: 6,
+            dvr_window: None,
+            need_record: true,
+            storage: StorageBackend::Walrus,
+            playlist_type: PlaylistType::Live,
+            republish_grace_period: 0,
+            iframe_playlist: false,
+            commit_live: true,
+        }
+    }
+}
+
+/*Settings applied to the streams of an app and/or whose names match a pattern,
+the fields which are None inherit the values resolved so far.*/
+#[derive(Debug, Clone, Default)]
+pub struct HlsSettingsOverride {
+    pub app_name: Option<String>,
+    /*regular expression on the stream name*/
+    pub stream_pattern: Option<String>,
+    pub target_duration: Option<i64>,
+    pub max_duration: Option<i64>,
+    pub keyframe_only: Option<bool>,
+    pub live_window: Option<usize>,
+    pub dvr_window: Option<u64>,
+    pub need_record: Option<bool>,
+    pub storage: Option<StorageBackend>,
+    pub playlist_type: Option<PlaylistType>,
+    pub republish_grace_period: Option<u64>,
+    pub iframe_playlist: Option<bool>,
+}
+
+#[derive(Clone)]
+pub struct HlsSettingsResolver {
+    default_settings: HlsSettings,
+    overrides: Vec<(Option<Regex>, HlsSettingsOverride)>,
+}
+
+impl HlsSettingsResolver {
+    /*a typo in the config is an error at startup instead of falling back to the defaults*/
+    pub fn new(
+        default_settings: HlsSettings,
+        overrides: Vec<HlsSettingsOverride>,
+    ) -> Result<Self, HlsError> {
+        default_settings.validate()?;
+
+        let mut compiled_overrides = Vec::new();
+        for cur_override in overrides {
+            if cur_override
+                .target_duration
+                .is_some_and(|duration| duration <= 0)
+            {
+                return Err(invalid_settings(String::from(
+                    "target_duration of an override must be positive",
+                )));
+            }
+            if cur_override.live_window.is_some_and(|window| window < 1) {
+                return Err(invalid_settings(String::from(
+                    "live_window of an override must be at least 1",
+                )));
+            }
+
+            let stream_regex = match &cur_override.stream_pattern {
+                Some(pattern) => match Regex::new(pattern) {
+                    Ok(regex) => Some(regex),
+                    Err(err) => {
+                        return Err(invalid_settings(format!(
+                            "invalid stream pattern: {}, err: {}",
+                            pattern, err
+                        )));
+                    }
+                },
+                None => None,
+            };
+            compiled_overrides.push((stream_regex, cur_override));
+        }
+
+        Ok(Self {
+            default_settings,
+            overrides: compiled_overrides,
+        })
+    }
+
+    /*The overrides are applied in the configured order, so the later ones win.*/
+    pub fn resolve(&self, app_name: &str, stream_name: &str) -> HlsSettings {
+        let mut settings = self.default_settings.clone();
+
+        for (stream_regex, cur_override) in &self.overrides {
+            if let Some(override_app_name) = &cur_override.app_name {
+                if override_app_name != app_name {
+                    continue;
+                }
+            }
+            if let Some(regex) = stream_regex {
+                if !regex.is_match(stream_name) {
+                    continue;
+                }
+            }
+
+            if let Some(target_duration) = cur_override.target_duration {
+                settings.target_duration = target_duration;
+            }
+            if let Some(max_duration) = cur_override.max_duration {
+                settings.max_duration = Some(max_duration);
+            }
+            if let Some(keyframe_only) = cur_override.keyframe_only {
+                settings.keyframe_only = keyframe_only;
+            }
+            if let Some(live_window) = cur_override.live_window {
+                settings.live_window = live_window;
+            }
+            if let Some(dvr_window) = cur_override.dvr_window {
+                settings.dvr_window = Some(dvr_window);
+            }
+            if let Some(need_record) = cur_override.need_record {
+                settings.need_record = need_record;
+            }
+            if let Some(storage) = cur_override.storage {
+                settings.storage = storage;
+            }
+            if let Some(playlist_type) = cur_override.playlist_type {
+                settings.playlist_type = playlist_type;
+            }
+            if let Some(grace_period) = cur_override.republish_grace_period {
+                settings.republish_grace_period = grace_period;
+            }
+            if let Some(iframe_playlist) = cur_override.iframe_playlist {
+                settings.iframe_playlist = iframe_playlist;
+            }
+        }
+
+        settings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        HlsSettings, HlsSettingsOverride, HlsSettingsResolver, PlaylistType, StorageBackend,
+    };
+
+    #[test]
+    fn test_resolve_settings() {
+        let resolver = HlsSettingsResolver::new(
+            HlsSettings::default(),
+            vec![
+                HlsSettingsOverride {
+                    app_name: Some(String::from("live")),
+                    target_duration: Some(10),
+                    ..Default::default()
+                },
+                HlsSettingsOverride {
+                    app_name: Some(String::from("live")),
+                    stream_pattern: Some(String::from("^test_")),
+                    need_record: Some(false),
+                    storage: Some(StorageBackend::Local),
+                    ..Default::default()
+                },
+                HlsSettingsOverride {
+                    stream_pattern: Some(String::from("^show$")),
+                    live_window: Some(1),
+                    ..Default::default()
+                },
+            ],
+        )
+        .unwrap();
+
+        let settings = resolver.resolve("live", "show");
+        assert_eq!(settings.target_duration, 10);
+        assert!(settings.need_record);
+        assert_eq!(settings.storage, StorageBackend::Walrus);
+
+        let settings = resolver.resolve("live", "test_1");
+        assert_eq!(settings.target_duration, 10);
+        assert!(!settings.need_record);
+        assert_eq!(settings.storage, StorageBackend::Local);
+        assert_eq!(settings.live_window, 6);
+
+        let settings = resolver.resolve("event", "show");
+        assert_eq!(settings.live_window, 1);
+
+        let settings = resolver.resolve("event", "test_1");
+        assert_eq!(settings.target_duration, 25);
+        assert_eq!(settings.playlist_type, PlaylistType::Live);
+    }
+
+    #[test]
+    fn test_invalid_settings() {
+        let invalid_pattern = HlsSettingsOverride {
+            stream_pattern: Some(String::from("(")),
+            ..Default::default()
+        };
+        assert!(HlsSettingsResolver::new(HlsSettings::default(), vec![invalid_pattern]).is_err());
+
+        let zero_window = HlsSettingsOverride {
+            live_window: Some(0),
+            ..Default::default()
+        };
+        assert!(HlsSettingsResolver::new(HlsSettings::default(), vec![zero_window]).is_err());
+
+        let zero_duration = HlsSettings {
+            target_duration: 0,
+            ..Default::default()
+        };
+        assert!(HlsSettingsResolver::new(zero_duration, Vec::new()).is_err());
+    }
+}
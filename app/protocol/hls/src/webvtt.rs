@@ -0,0 +1,433 @@
+use {
+    super::{
+        archive::{ArchiveEntry, PlaylistArchive},
+        errors::{MediaError, MediaErrorValue},
+        m3u8::upload_to_walrus,
+        settings::{HlsSettings, PlaylistType, StorageBackend},
+    },
+    bytes::BytesMut,
+    bytesio::bytes_reader::BytesReader,
+    commonlib::move_call::{add_vod_stream, upload_playlist_to_contract},
+    std::{
+        collections::VecDeque,
+        fs::{self, File},
+        io::Write,
+    },
+    xflv::amf0::{amf0_reader::Amf0Reader, Amf0ValueType},
+};
+
+/*onTextData carries no duration, a cue lasts until the next one or this long*/
+const DEFAULT_CUE_DURATION: i64 = 4000;
+
+/*Get the text of an onTextData/onCaption data message, e.g.
+["onTextData", {"text": "hello", "trackid": 0}], the message may also be
+prefixed by "@setDataFrame".*/
+pub fn parse_text_data(data: BytesMut) -> Option<String> {
+    let values = Amf0Reader::new(BytesReader::new(data)).read_all().ok()?;
+    let mut values = values.into_iter().skip_while(
+        |value| matches!(value, Amf0ValueType::UTF8String(name) if name == "@setDataFrame"),
+    );
+
+    match values.next()? {
+        Amf0ValueType::UTF8String(name) if name == "onTextData" || name == "onCaption" => {}
+        _ => return None,
+    }
+
+    let properties = match values.next()? {
+        Amf0ValueType::Object(properties) | Amf0ValueType::EcmaArray(properties) => properties,
+        _ => return None,
+    };
+
+    match properties.get("text")? {
+        Amf0ValueType::UTF8String(text) | Amf0ValueType::LongUTF8String(text) => {
+            let text = text.trim();
+            if text.is_empty() {
+                None
+            } else {
+                Some(text.to_string())
+            }
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextCue {
+    /*milliseconds, on the same time line as the ts segments*/
+    pub start: i64,
+    pub end: i64,
+    pub text: String,
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    let timestamp = std::cmp::max(timestamp, 0);
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        timestamp / 3_600_000,
+        timestamp / 60_000 % 60,
+        timestamp / 1000 % 60,
+        timestamp % 1000
+    )
+}
+
+/*The pts of the ts segments is the flv timestamp * 90 without an offset, so the
+local time of a cue is the flv timestamp.*/
+pub fn generate_webvtt(cues: &[TextCue]) -> String {
+    let mut content = "WEBVTT\nX-TIMESTAMP-MAP=MPEGTS:0,LOCAL:00:00:00.000\n".to_string();
+
+    for cue in cues {
+        content += format!(
+            "\n{} --> {}\n{}\n",
+            format_timestamp(cue.start),
+            format_timestamp(cue.end),
+            cue.text
+        )
+        .as_str();
+    }
+
+    content
+}
+
+struct SubtitleSegment {
+    duration: i64,
+    discontinuity: bool,
+    name: String,
+    path: String,
+    blob_id: String,
+}
+
+/*The WebVTT rendition of a stream, it is cut at the same time as the ts segments.*/
+pub struct SubtitlePlaylist {
+    app_name: String,
+    stream_name: String,
+    sequence_no: u64,
+    vtt_number: u64,
+    duration: i64,
+    live_window: usize,
+    /*milliseconds*/
+    dvr_window: Option<i64>,
+    need_record: bool,
+    storage: StorageBackend,
+    playlist_type: PlaylistType,
+    archive: Option<PlaylistArchive>,
+    commit_live: bool,
+    /*the lines of all the segments, a stream which is not committed live is
+    published as a VOD once it is done*/
+    vod_segment_lines: Option<String>,
+
+    /*the cues which have not ended before the current segment*/
+    cues: VecDeque<TextCue>,
+    segments: VecDeque<SubtitleSegment>,
+
+    m3u8_folder: String,
+    m3u8_name: String,
+}
+
+impl SubtitlePlaylist {
+    pub fn new(app_name: String, stream_name: String, settings: &HlsSettings) -> Self {
+        let m3u8_folder = format!("./{app_name}/{stream_name}");
+        let m3u8_name = Self::m3u8_name(&stream_name);
+
+        Self {
+            app_name,
+            stream_name,
+            sequence_no: 0,
+            vtt_number: 0,
+            duration: 0,
+            live_window: settings.live_window,
+            dvr_window: settings.dvr_window.map(|window| window as i64 * 1000),
+            need_record: settings.need_record,
+            storage: settings.storage,
+            playlist_type: settings.playlist_type,
+            /*the same window as the media playlist*/
+            archive: (settings.storage == StorageBackend::Walrus
+                && (settings.dvr_window.is_some()
+                    || settings.playlist_type == PlaylistType::Event))
+                .then(|| PlaylistArchive::new(settings.playlist_type == PlaylistType::Event)),
+            commit_live: settings.commit_live,
+            vod_segment_lines: (settings.need_record && !settings.commit_live).then(String::new),
+            cues: VecDeque::new(),
+            segments: VecDeque::new(),
+            m3u8_folder,
+            m3u8_name,
+        }
+    }
+
+    pub fn m3u8_name(stream_name: &str) -> String {
+        format!("{stream_name}_subtitles.m3u8")
+    }
+
+    /*the url of the playlist on the contract*/
+    pub fn get_live_path(app_name: &str, stream_name: &str) -> String {
+        format!("./{app_name}/{stream_name}_subtitles")
+    }
+
+    pub fn add_cue(&mut self, timestamp: i64, text: String) {
+        if let Some(last_cue) = self.cues.back_mut() {
+            last_cue.end = std::cmp::min(last_cue.end, timestamp);
+        }
+
+        self.cues.push_back(TextCue {
+            start: timestamp,
+            end: timestamp + DEFAULT_CUE_DURATION,
+            text,
+        });
+    }
+
+    /*a cue of which the end is already known, e.g. decoded from the captions*/
+    pub fn add_text_cue(&mut self, cue: TextCue) {
+        self.cues.push_back(cue);
+    }
+
+    /*Write the cues between [start, end) as a segment, a cue across the boundary is
+    written in both segments.*/
+    pub fn add_segment(
+        &mut self,
+        start: i64,
+        end: i64,
+        discontinuity: bool,
+    ) -> Result<(), MediaError> {
+        let cues: Vec<TextCue> = self
+            .cues
+            .iter()
+            .filter(|cue| cue.start < end && cue.end > start)
+            .cloned()
+            .collect();
+        self.cues.retain(|cue| cue.end > end);
+
+        let duration = end - start;
+        while self.is_front_segment_expired(duration) {
+            let segment = self.segments.pop_front().unwrap();
+            if !self.need_record {
+                fs::remove_file(segment.path)?;
+            }
+            self.sequence_no += 1;
+        }
+
+        let content = generate_webvtt(&cues);
+        let name = format!("{}.vtt", self.vtt_number);
+        let path = format!("{}/{}", self.m3u8_folder, name);
+        self.vtt_number += 1;
+
+        let mut file_handler = File::create(&path)?;
+        file_handler.write_all(content.as_bytes())?;
+
+        let blob_id = match self.storage {
+            StorageBackend::Walrus => upload_to_walrus(BytesMut::from(content.as_bytes()))?,
+            StorageBackend::Local => String::default(),
+        };
+
+        let segment = SubtitleSegment {
+            duration,
+            discontinuity,
+            name,
+            path,
+            blob_id,
+        };
+        if let Some(vod_segment_lines) = &mut self.vod_segment_lines {
+            *vod_segment_lines += Self::segment_lines(&segment, true).as_str();
+        }
+
+        self.duration = std::cmp::max(self.duration, duration);
+        self.segments.push_back(segment);
+
+        Ok(())
+    }
+
+    /*the same rules as the media playlist*/
+    fn is_front_segment_expired(&self, new_duration: i64) -> bool {
+        let Some(front_segment) = self.segments.front() else {
+            return false;
+        };
+
+        match (self.playlist_type, self.dvr_window) {
+            (PlaylistType::Event, _) => false,
+            (PlaylistType::Live, Some(dvr_window)) => {
+                let window_duration: i64 =
+                    self.segments.iter().map(|segment| segment.duration).sum();
+                window_duration + new_duration - front_segment.duration >= dvr_window
+            }
+            (PlaylistType::Live, None) => self.segments.len() >= self.live_window,
+        }
+    }
+
+    fn generate_header(&self, sequence_no: u64) -> String {
+        let mut m3u8_header = "#EXTM3U\n#EXT-X-VERSION:3\n".to_string();
+        m3u8_header += format!("#EXT-X-TARGETDURATION:{}\n", (self.duration + 999) / 1000).as_str();
+        m3u8_header += format!("#EXT-X-MEDIA-SEQUENCE:{}\n", sequence_no).as_str();
+        if self.playlist_type == PlaylistType::Event {
+            m3u8_header += "#EXT-X-PLAYLIST-TYPE:EVENT\n";
+        }
+
+        m3u8_header
+    }
+
+    fn segment_lines(segment: &SubtitleSegment, on_chain: bool) -> String {
+        let mut lines = String::new();
+        if segment.discontinuity {
+            lines += "#EXT-X-DISCONTINUITY\n";
+        }
+        let uri = if on_chain {
+            &segment.blob_id
+        } else {
+            &segment.name
+        };
+        lines += format!("#EXTINF:{:.3}\n{}\n", segment.duration as f64 / 1000.0, uri).as_str();
+
+        lines
+    }
+
+    pub fn generate_m3u8(&self, on_chain: bool) -> String {
+        let mut m3u8_content = self.generate_header(self.sequence_no);
+        for segment in &self.segments {
+            m3u8_content += Self::segment_lines(segment, on_chain).as_str();
+        }
+
+        m3u8_content
+    }
+
+    /*the playlist on chain, it is compacted for the long playlists*/
+    fn generate_chain_m3u8(&mut self) -> Result<String, MediaError> {
+        let Some(archive) = &mut self.archive else {
+            return Ok(self.generate_m3u8(true));
+        };
+
+        let entries: Vec<ArchiveEntry> = self
+            .segments
+            .iter()
+            .map(|segment| ArchiveEntry {
+                lines: Self::segment_lines(segment, true),
+                media_count: 1,
+            })
+            .collect();
+        let (media_sequence, body) =
+            archive.compact(self.sequence_no, self.sequence_no, &entries)?;
+
+        Ok(self.generate_header(media_sequence) + body.as_str())
+    }
+
+    pub async fn refresh_playlist(&mut self) -> Result<(), MediaError> {
+        let m3u8_path = format!("{}/{}", self.m3u8_folder, self.m3u8_name);
+        let mut file_handler = File::create(m3u8_path)?;
+        file_handler.write_all(self.generate_m3u8(false).as_bytes())?;
+
+        if self.storage == StorageBackend::Walrus && self.commit_live {
+            let m3u8_content = self.generate_chain_m3u8()?;
+            upload_playlist_to_contract(
+                Self::get_live_path(&self.app_name, &self.stream_name),
+                &m3u8_content,
+            )
+            .await
+            .map_err(|_| MediaError {
+                value: MediaErrorValue::PlaylistUploadError,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /*Add all the segments as a VOD stream, e.g. the captions of an imported file.*/
+    pub async fn publish_vod(
+        &mut self,
+        name: String,
+        description: String,
+    ) -> Result<(), MediaError> {
+        let Some(vod_segment_lines) = &self.vod_segment_lines else {
+            return Err(MediaError {
+                value: MediaErrorValue::AddVodStreamError,
+            });
+        };
+        if self.storage != StorageBackend::Walrus {
+            return Err(MediaError {
+                value: MediaErrorValue::AddVodStreamError,
+            });
+        }
+
+        let mut m3u8_content = "#EXTM3U\n#EXT-X-VERSION:3\n".to_string();
+        m3u8_content +=
+            format!("#EXT-X-TARGETDURATION:{}\n", (self.duration + 999) / 1000).as_str();
+        m3u8_content += "#EXT-X-MEDIA-SEQUENCE:0\n#EXT-X-PLAYLIST-TYPE:VOD\n";
+        m3u8_content += vod_segment_lines.as_str();
+        m3u8_content += "#EXT-X-ENDLIST\n";
+
+        add_vod_stream(name, description, &m3u8_content)
+            .await
+            .map_err(|_| MediaError {
+                value: MediaErrorValue::AddVodStreamError,
+            })
+    }
+
+    pub fn clear(&mut self) -> Result<(), MediaError> {
+        if !self.need_record {
+            for segment in &self.segments {
+                fs::remove_file(&segment.path)?;
+            }
+        }
+
+        let m3u8_path = format!("{}/{}", self.m3u8_folder, self.m3u8_name);
+        fs::remove_file(m3u8_path)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{parse_text_data, SubtitlePlaylist},
+        crate::settings::{HlsSettings, StorageBackend},
+        bytes::BytesMut,
+        std::fs,
+    };
+
+    #[test]
+    fn test_parse_text_data() {
+        let mut data = BytesMut::new();
+        /*"onTextData"*/
+        data.extend_from_slice(b"\x02\x00\x0aonTextData");
+        /*{"text": "hello"}*/
+        data.extend_from_slice(b"\x03\x00\x04text\x02\x00\x05hello\x00\x00\x09");
+        assert_eq!(parse_text_data(data), Some(String::from("hello")));
+
+        let mut data = BytesMut::new();
+        data.extend_from_slice(b"\x02\x00\x0aonMetaData\x03\x00\x00\x09");
+        assert_eq!(parse_text_data(data), None);
+    }
+
+    #[test]
+    fn test_split_cues() {
+        let settings = HlsSettings {
+            storage: StorageBackend::Local,
+            ..Default::default()
+        };
+        let mut playlist = SubtitlePlaylist::new(
+            String::from("webvtt_test"),
+            String::from("split"),
+            &settings,
+        );
+        fs::create_dir_all("./webvtt_test/split").unwrap();
+
+        playlist.add_cue(1000, String::from("first"));
+        playlist.add_cue(3000, String::from("second"));
+
+        /*the second cue is across the boundary and written in both segments*/
+        playlist.add_segment(0, 4000, false).unwrap();
+        playlist.add_segment(4000, 8000, false).unwrap();
+        let first = fs::read_to_string(&playlist.segments[0].path).unwrap();
+        let second = fs::read_to_string(&playlist.segments[1].path).unwrap();
+        fs::remove_dir_all("./webvtt_test").unwrap();
+
+        assert_eq!(
+            first,
+            "WEBVTT\nX-TIMESTAMP-MAP=MPEGTS:0,LOCAL:00:00:00.000\n\
+             \n00:00:01.000 --> 00:00:03.000\nfirst\n\
+             \n00:00:03.000 --> 00:00:07.000\nsecond\n"
+        );
+        assert_eq!(
+            second,
+            "WEBVTT\nX-TIMESTAMP-MAP=MPEGTS:0,LOCAL:00:00:00.000\n\
+             \n00:00:03.000 --> 00:00:07.000\nsecond\n"
+        );
+        assert!(playlist.cues.is_empty());
+    }
+}
@@ -79,6 +79,14 @@ impl Cache {
 
         let remain_bytes = reader.extract_remaining_bytes();
 
+        /*keep the enhanced rtmp sequence start (e.g. OpusHead) for the late subscribers*/
+        if tag_header.sound_format == define::SoundFormat::ExHeader as u8
+            && tag_header.audio_packet_type == define::audio_packet_type::SEQUENCE_START
+        {
+            self.audio_seq = chunk_body.clone();
+            self.audio_timestamp = timestamp;
+        }
+
         if remain_bytes.len() >= 2
             && tag_header.sound_format == define::SoundFormat::AAC as u8
             && tag_header.aac_packet_type == define::aac_packet_type::AAC_SEQHDR
@@ -0,0 +1,142 @@
+use {
+    byteorder::BigEndian,
+    bytes::BytesMut,
+    bytesio::{bytes_errors::BytesWriteError, bytes_writer::BytesWriter},
+    std::time::Duration,
+};
+
+/*a sub-message has an 11 bytes header and a 4 bytes back pointer*/
+pub const SUB_MESSAGE_HEADER_SIZE: usize = 11;
+pub const BACK_POINTER_SIZE: usize = 4;
+/*an aggregate message is sent once it reaches the size*/
+pub const AGGREGATE_MAX_SIZE: usize = 64 * 1024;
+
+/*Batches the audio, video and data messages into an aggregate message, the
+sub-messages keep their own timestamps and the aggregate message has the timestamp
+of the first one.*/
+pub struct AggregateWriter {
+    writer: BytesWriter,
+    first_timestamp: Option<u32>,
+    last_timestamp: u32,
+    /*milliseconds of the media in one aggregate message*/
+    duration: u32,
+}
+
+impl AggregateWriter {
+    pub fn new(duration: u32) -> Self {
+        Self {
+            writer: BytesWriter::new(),
+            first_timestamp: None,
+            last_timestamp: 0,
+            duration,
+        }
+    }
+
+    pub fn write_message(
+        &mut self,
+        msg_type_id: u8,
+        timestamp: u32,
+        data: &[u8],
+    ) -> Result<(), BytesWriteError> {
+        self.writer.write_u8(msg_type_id)?;
+        self.writer.write_u24::<BigEndian>(data.len() as u32)?;
+        self.writer.write_u24::<BigEndian>(timestamp & 0xffffff)?;
+        self.writer.write_u8((timestamp >> 24) as u8)?;
+        /*the stream id of a sub-message is always 0*/
+        self.writer.write_u24::<BigEndian>(0)?;
+        self.writer.write(data)?;
+        self.writer
+            .write_u32::<BigEndian>((data.len() + SUB_MESSAGE_HEADER_SIZE) as u32)?;
+
+        self.first_timestamp.get_or_insert(timestamp);
+        self.last_timestamp = timestamp;
+        Ok(())
+    }
+
+    pub fn is_full(&self) -> bool {
+        match self.first_timestamp {
+            Some(first_timestamp) => {
+                self.writer.len() >= AGGREGATE_MAX_SIZE
+                    || self.last_timestamp.wrapping_sub(first_timestamp) >= self.duration
+            }
+            None => false,
+        }
+    }
+
+    /*how long the batched messages wait for more before they are sent, None if
+    nothing is batched*/
+    pub fn flush_interval(&self) -> Option<Duration> {
+        self.first_timestamp
+            .map(|_| Duration::from_millis(self.duration as u64))
+    }
+
+    /*returns the timestamp and the payload of the aggregate message*/
+    pub fn extract_message(&mut self) -> Option<(u32, BytesMut)> {
+        let timestamp = self.first_timestamp.take()?;
+        Some((timestamp, self.writer.extract_current_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::AggregateWriter;
+    use crate::chunk::ChunkInfo;
+    use crate::messages::define::{msg_type_id, RtmpMessageData};
+    use crate::messages::parser::MessageParser;
+    use std::time::Duration;
+
+    #[test]
+    fn test_aggregate_write_and_parse() {
+        let mut writer = AggregateWriter::new(100);
+        assert!(writer.flush_interval().is_none());
+        writer
+            .write_message(msg_type_id::VIDEO, 1000, &[0x17, 0x01])
+            .unwrap();
+        writer
+            .write_message(msg_type_id::AUDIO, 1020, &[0xaf, 0x01])
+            .unwrap();
+        assert!(!writer.is_full());
+        assert_eq!(writer.flush_interval(), Some(Duration::from_millis(100)));
+        writer
+            .write_message(msg_type_id::DATA_AMF3, 1100, &[0x00, 0x05])
+            .unwrap();
+        assert!(writer.is_full());
+
+        let (timestamp, data) = writer.extract_message().unwrap();
+        assert_eq!(timestamp, 1000);
+        assert!(writer.extract_message().is_none());
+        assert!(writer.flush_interval().is_none());
+
+        /*the aggregate message is received with another timestamp*/
+        let chunk_info = ChunkInfo::new(
+            5,
+            0,
+            5000,
+            data.len() as u32,
+            msg_type_id::AGGREGATE,
+            1,
+            data,
+        );
+        let messages = match MessageParser::new(chunk_info).parse().unwrap() {
+            Some(RtmpMessageData::AggregateData { messages }) => messages,
+            _ => panic!("not an aggregate message"),
+        };
+
+        let timestamps: Vec<u32> = messages.iter().map(|(timestamp, _)| *timestamp).collect();
+        assert_eq!(timestamps, vec![5000, 5020, 5100]);
+
+        match &messages[0].1 {
+            RtmpMessageData::VideoData { data } => assert_eq!(&data[..], &[0x17, 0x01]),
+            _ => panic!("not a video message"),
+        }
+        match &messages[1].1 {
+            RtmpMessageData::AudioData { data } => assert_eq!(&data[..], &[0xaf, 0x01]),
+            _ => panic!("not an audio message"),
+        }
+        match &messages[2].1 {
+            RtmpMessageData::AmfData { raw_data } => assert_eq!(&raw_data[..], &[0x05]),
+            _ => panic!("not a data message"),
+        }
+    }
+}
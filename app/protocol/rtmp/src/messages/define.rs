@@ -46,6 +46,11 @@ pub enum RtmpMessageData {
     VideoData {
         data: BytesMut,
     },
+    /*the audio, video and data sub-messages of an aggregate message with their
+    timestamps*/
+    AggregateData {
+        messages: Vec<(u32, RtmpMessageData)>,
+    },
     SetBufferLength {
         stream_id: u32,
         buffer_length: u32,
@@ -56,6 +61,12 @@ pub enum RtmpMessageData {
     StreamIsRecorded {
         stream_id: u32,
     },
+    PingRequest {
+        timestamp: u32,
+    },
+    PingResponse {
+        timestamp: u32,
+    },
 
     Unknow,
 }
@@ -1,3 +1,4 @@
+pub mod aggregate;
 pub mod parser;
 pub mod errors;
 pub mod define;
@@ -1,5 +1,6 @@
 use {
     super::{
+        aggregate::{BACK_POINTER_SIZE, SUB_MESSAGE_HEADER_SIZE},
         define::{msg_type_id, RtmpMessageData},
         errors::MessageError,
     },
@@ -9,6 +10,7 @@ use {
         user_control_messages::reader::EventMessagesReader,
         // utils,
     },
+    byteorder::BigEndian,
     bytesio::bytes_reader::BytesReader,
     xflv::amf0::{amf0_markers, amf0_reader::Amf0Reader},
 };
@@ -110,6 +112,13 @@ impl MessageParser {
                 return Ok(Some(RtmpMessageData::SetPeerBandwidth { properties }));
             }
             msg_type_id::DATA_AMF0 | msg_type_id::DATA_AMF3 => {
+                /*the AMF0 data after the zero byte of an AMF3 data message is forwarded
+                as an AMF0 data message*/
+                if self.chunk_info.message_header.msg_type_id == msg_type_id::DATA_AMF3
+                    && reader.get(0)? == 0
+                {
+                    reader.read_u8()?;
+                }
                 //let values = Amf0Reader::new(reader).read_all()?;
                 return Ok(Some(RtmpMessageData::AmfData {
                     raw_data: reader.extract_remaining_bytes(),
@@ -118,7 +127,11 @@ impl MessageParser {
 
             msg_type_id::SHARED_OBJ_AMF3 | msg_type_id::SHARED_OBJ_AMF0 => {}
 
-            msg_type_id::AGGREGATE => {}
+            msg_type_id::AGGREGATE => {
+                let messages =
+                    Self::parse_aggregate(reader, self.chunk_info.message_header.timestamp)?;
+                return Ok(Some(RtmpMessageData::AggregateData { messages }));
+            }
 
             _ => {}
         }
@@ -128,6 +141,53 @@ impl MessageParser {
         );
         Ok(None)
     }
+
+    /*The timestamps of the sub-messages are rebased on the timestamp of the aggregate
+    message, which is the timestamp of the first sub-message.*/
+    fn parse_aggregate(
+        mut reader: BytesReader,
+        timestamp: u32,
+    ) -> Result<Vec<(u32, RtmpMessageData)>, MessageError> {
+        let mut messages = Vec::new();
+        let mut first_timestamp = None;
+
+        while reader.len() >= SUB_MESSAGE_HEADER_SIZE {
+            let msg_type_id = reader.read_u8()?;
+            let data_size = reader.read_u24::<BigEndian>()?;
+            let sub_timestamp =
+                reader.read_u24::<BigEndian>()? | ((reader.read_u8()? as u32) << 24);
+            let _msg_stream_id = reader.read_u24::<BigEndian>()?;
+            let mut data = reader.read_bytes(data_size as usize)?;
+            /*some encoders omit the back pointer of the last sub-message*/
+            if reader.len() >= BACK_POINTER_SIZE {
+                reader.read_u32::<BigEndian>()?;
+            }
+
+            let first_timestamp = *first_timestamp.get_or_insert(sub_timestamp);
+            let sub_timestamp = timestamp.wrapping_add(sub_timestamp.wrapping_sub(first_timestamp));
+
+            let message = match msg_type_id {
+                msg_type_id::AUDIO => RtmpMessageData::AudioData { data },
+                msg_type_id::VIDEO => RtmpMessageData::VideoData { data },
+                msg_type_id::DATA_AMF0 | msg_type_id::DATA_AMF3 => {
+                    if msg_type_id == msg_type_id::DATA_AMF3 && data.first() == Some(&0) {
+                        let _ = data.split_to(1);
+                    }
+                    RtmpMessageData::AmfData { raw_data: data }
+                }
+                _ => {
+                    log::warn!(
+                        "the sub-message of the aggregate message is not processed: {}",
+                        msg_type_id
+                    );
+                    continue;
+                }
+            };
+            messages.push((sub_timestamp, message));
+        }
+
+        Ok(messages)
+    }
 }
 
 #[cfg(test)]
@@ -3,7 +3,9 @@ use {
     crate::{
         chunk::{define as chunk_define, packetizer::ChunkPacketizer, ChunkInfo},
         messages::define as messages_define,
+        session::define::{OBJENCODING_AMF0, OBJENCODING_AMF3},
     },
+    bytes::BytesMut,
     bytesio::bytesio::TNetIO,
     indexmap::IndexMap,
     std::sync::Arc,
@@ -64,24 +66,51 @@ impl ConnectProperties {
 pub struct NetConnection {
     amf0_writer: Amf0Writer,
     packetizer: ChunkPacketizer,
+    /*the commands are sent as AMF3 command messages if the object encoding is 3*/
+    is_amf3: bool,
 }
 
 impl NetConnection {
     pub fn new(io: Arc<Mutex<Box<dyn TNetIO + Send + Sync>>>) -> Self {
+        Self::new_with_encoding(io, OBJENCODING_AMF0)
+    }
+
+    pub fn new_with_encoding(
+        io: Arc<Mutex<Box<dyn TNetIO + Send + Sync>>>,
+        object_encoding: f64,
+    ) -> Self {
+        let is_amf3 = object_encoding == OBJENCODING_AMF3;
+        let mut amf0_writer = Amf0Writer::new();
+        amf0_writer.set_amf3_objects(is_amf3);
+
         Self {
-            amf0_writer: Amf0Writer::new(),
+            amf0_writer,
             packetizer: ChunkPacketizer::new(io),
+            is_amf3,
         }
     }
 
-    async fn write_chunk(&mut self) -> Result<(), NetConnectionError> {
+    /*an AMF3 command message starts with a zero byte*/
+    fn extract_command(&mut self) -> (u8, BytesMut) {
         let data = self.amf0_writer.extract_current_bytes();
+        if !self.is_amf3 {
+            return (messages_define::msg_type_id::COMMAND_AMF0, data);
+        }
+
+        let mut amf3_data = BytesMut::with_capacity(data.len() + 1);
+        amf3_data.extend_from_slice(&[0]);
+        amf3_data.extend_from_slice(&data);
+        (messages_define::msg_type_id::COMMAND_AMF3, amf3_data)
+    }
+
+    async fn write_chunk(&mut self) -> Result<(), NetConnectionError> {
+        let (msg_type_id, data) = self.extract_command();
         let mut chunk_info = ChunkInfo::new(
             chunk_define::csid_type::COMMAND_AMF0_AMF3,
             chunk_define::chunk_type::TYPE_0,
             0,
             data.len() as u32,
-            messages_define::msg_type_id::COMMAND_AMF0,
+            msg_type_id,
             0,
             data,
         );
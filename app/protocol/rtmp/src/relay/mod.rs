@@ -1,3 +1,38 @@
 pub mod pull_client;
 pub mod push_client;
-pub mod errors;
\ No newline at end of file
+pub mod push_relay;
+pub mod supervisor;
+pub mod errors;
+
+use {
+    std::collections::HashMap,
+    streamhub::{
+        define::BroadcastEventExecuteResultSender,
+        errors::{StreamHubError, StreamHubErrorValue},
+    },
+    tokio::sync::oneshot,
+};
+
+pub(crate) fn relay_error(msg: String) -> StreamHubError {
+    StreamHubError {
+        value: StreamHubErrorValue::RelayError(msg),
+    }
+}
+
+/*report the result of starting or stopping a relay to the api caller*/
+pub(crate) async fn send_result(
+    result_sender: BroadcastEventExecuteResultSender,
+    result: Result<(), StreamHubError>,
+) {
+    if let Err(err) = result_sender.send(result).await {
+        log::error!("relay send result error: {}", err);
+    }
+}
+
+/*A relay which fails or finishes by itself is removed once its supervisor exits,
+unless the id has been taken by a newer relay whose supervisor is running.*/
+pub(crate) fn remove_finished_relay(relays: &mut HashMap<String, oneshot::Sender<()>>, id: &str) {
+    if relays.get(id).is_some_and(oneshot::Sender::is_closed) {
+        relays.remove(id);
+    }
+}
@@ -1,70 +1,207 @@
 use streamhub::stream::StreamIdentifier;
 
 use {
-    super::errors::ClientError,
-    crate::session::client_session::{ClientSession, ClientSessionType},
-    streamhub::define::{BroadcastEvent, BroadcastEventReceiver, StreamHubEventSender},
-    tokio::net::TcpStream,
+    super::{
+        errors::ClientError,
+        relay_error, remove_finished_relay, send_result,
+        supervisor::{ReconnectPolicy, RelaySupervisor},
+    },
+    crate::session::client_session::ClientSessionType,
+    std::{collections::HashMap, sync::Arc},
+    streamhub::{
+        define::{
+            BroadcastEvent, BroadcastEventExecuteResultSender, BroadcastEventReceiver,
+            RelayStatistics, RelayType, StreamHubEventSender,
+        },
+        errors::StreamHubError,
+        statistics::StatisticRelay,
+    },
+    tokio::sync::{mpsc, oneshot, Mutex},
 };
 
+const DEFAULT_PORT: &str = "1935";
+
 pub struct PullClient {
-    address: String,
+    /*the origin address, used by the subscribe events without a server address*/
+    address: Option<String>,
+    /*app name to the origin address, takes precedence over the address*/
+    routes: HashMap<String, String>,
     client_event_consumer: BroadcastEventReceiver,
     channel_event_producer: StreamHubEventSender,
+    /*relay id to the stop signal of the pull*/
+    relays: HashMap<String, oneshot::Sender<()>>,
+    /*the ids of the relays whose supervisors have exited*/
+    finished_sender: mpsc::UnboundedSender<String>,
+    finished_receiver: mpsc::UnboundedReceiver<String>,
+    reconnect_policy: ReconnectPolicy,
 }
 
 impl PullClient {
     pub fn new(
-        address: String,
+        address: Option<String>,
         consumer: BroadcastEventReceiver,
         producer: StreamHubEventSender,
     ) -> Self {
+        let (finished_sender, finished_receiver) = mpsc::unbounded_channel();
         Self {
             address,
+            routes: HashMap::new(),
 
             client_event_consumer: consumer,
             channel_event_producer: producer,
+            relays: HashMap::new(),
+            finished_sender,
+            finished_receiver,
+            reconnect_policy: ReconnectPolicy::default(),
+        }
+    }
+
+    pub fn set_reconnect_policy(&mut self, policy: ReconnectPolicy) {
+        self.reconnect_policy = policy;
+    }
+
+    pub fn set_routes(&mut self, routes: HashMap<String, String>) {
+        self.routes = routes;
+    }
+
+    fn get_origin_address(&self, identifier: &StreamIdentifier) -> Option<String> {
+        if let StreamIdentifier::Rtmp { app_name, .. } = identifier {
+            if let Some(address) = self.routes.get(app_name) {
+                return Some(address.clone());
+            }
         }
+        self.address.clone()
     }
 
     pub async fn run(&mut self) -> Result<(), ClientError> {
         loop {
-            let event = self.client_event_consumer.recv().await?;
-
-            if let BroadcastEvent::Subscribe {
-                id: _,
-                identifier:
-                    StreamIdentifier::Rtmp {
-                        app_name,
-                        stream_name,
-                    },
-                server_address: _,
-                result_sender: _,
-            } = event
-            {
-                log::info!(
-                    "receive pull event, app_name :{}, stream_name: {}",
-                    app_name,
-                    stream_name
-                );
-                let stream = TcpStream::connect(self.address.clone()).await?;
-
-                let mut client_session = ClientSession::new(
-                    stream,
-                    ClientSessionType::Pull,
-                    self.address.clone(),
-                    app_name.clone(),
-                    stream_name.clone(),
-                    self.channel_event_producer.clone(),
-                    0,
-                );
-
-                tokio::spawn(async move {
-                    if let Err(err) = client_session.run().await {
-                        log::error!("client_session as pull client run error: {}", err);
+            let event = tokio::select! {
+                event = self.client_event_consumer.recv() => event?,
+                Some(id) = self.finished_receiver.recv() => {
+                    remove_finished_relay(&mut self.relays, &id);
+                    continue;
+                }
+            };
+
+            match event {
+                BroadcastEvent::Subscribe {
+                    id,
+                    identifier,
+                    server_address: Some(server_address),
+                    statistics,
+                    result_sender,
+                } => {
+                    if let Err(err) = self.start_pull(
+                        id,
+                        identifier,
+                        server_address,
+                        statistics,
+                        result_sender.clone(),
+                    ) {
+                        if let Some(sender) = result_sender {
+                            send_result(sender, Err(err)).await;
+                        }
+                    }
+                }
+                BroadcastEvent::Subscribe {
+                    id,
+                    identifier,
+                    server_address: None,
+                    statistics: _,
+                    result_sender: _,
+                } => {
+                    let address = match self.get_origin_address(&identifier) {
+                        Some(address) => address,
+                        None => {
+                            log::warn!("no origin to pull from, identifier: {}", identifier);
+                            continue;
+                        }
+                    };
+                    /*the players of the same stream share one pull*/
+                    if let Some(stop_sender) = self.relays.get(&id) {
+                        if !stop_sender.is_closed() {
+                            continue;
+                        }
+                    }
+
+                    log::info!("receive pull event, identifier: {}", identifier);
+                    if let Err(err) = self.start_pull(id, identifier, address, None, None) {
+                        log::error!("start static pull error: {}", err);
+                    }
+                }
+                BroadcastEvent::UnSubscribe { id, result_sender } => {
+                    /*the pull may have finished by itself*/
+                    if let Some(stop_sender) = self.relays.remove(&id) {
+                        let _ = stop_sender.send(());
+                    }
+                    if let Some(sender) = result_sender {
+                        send_result(sender, Ok(())).await;
                     }
-                });
+                }
+                _ => {}
             }
         }
     }
+
+    /*pull the stream from the server address and publish it with the same name*/
+    fn start_pull(
+        &mut self,
+        id: String,
+        identifier: StreamIdentifier,
+        server_address: String,
+        statistics: Option<RelayStatistics>,
+        result_sender: Option<BroadcastEventExecuteResultSender>,
+    ) -> Result<(), StreamHubError> {
+        let (app_name, stream_name) = match &identifier {
+            StreamIdentifier::Rtmp {
+                app_name,
+                stream_name,
+            } => (app_name.clone(), stream_name.clone()),
+            _ => return Err(relay_error(String::from("only rtmp streams can be pulled"))),
+        };
+
+        let address = if server_address.contains(':') {
+            server_address
+        } else {
+            format!("{}:{}", server_address, DEFAULT_PORT)
+        };
+
+        log::info!(
+            "start pull relay id: {} app_name: {} stream_name: {} address: {}",
+            id,
+            app_name,
+            stream_name,
+            address
+        );
+
+        /*the static pulls are not started by the api and have no statistics yet*/
+        let statistics = statistics.unwrap_or_else(|| {
+            Arc::new(Mutex::new(StatisticRelay::new(
+                id.clone(),
+                RelayType::Pull,
+                identifier,
+                address.clone(),
+            )))
+        });
+
+        let (stop_sender, stop_receiver) = oneshot::channel();
+        self.relays.insert(id.clone(), stop_sender);
+
+        let mut supervisor = RelaySupervisor::new(
+            ClientSessionType::Pull,
+            address,
+            app_name,
+            stream_name,
+            self.channel_event_producer.clone(),
+            statistics,
+        );
+        supervisor.set_policy(self.reconnect_policy.clone());
+        let finished_sender = self.finished_sender.clone();
+        tokio::spawn(async move {
+            supervisor.run(stop_receiver, result_sender).await;
+            let _ = finished_sender.send(id);
+        });
+
+        Ok(())
+    }
 }
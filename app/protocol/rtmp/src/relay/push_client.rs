@@ -1,17 +1,27 @@
 use {
-    super::errors::ClientError,
-    crate::session::client_session::{ClientSession, ClientSessionType},
+    super::{
+        errors::ClientError,
+        supervisor::{ReconnectPolicy, RelaySupervisor},
+    },
+    crate::session::client_session::ClientSessionType,
+    std::{collections::HashMap, sync::Arc},
     streamhub::{
-        define::{StreamHubEventSender, BroadcastEvent, BroadcastEventReceiver},
+        define::{BroadcastEvent, BroadcastEventReceiver, RelayType, StreamHubEventSender},
+        statistics::StatisticRelay,
         stream::StreamIdentifier,
     },
-    tokio::net::TcpStream,
+    tokio::sync::{oneshot, Mutex},
 };
 
 pub struct PushClient {
     address: String,
     client_event_consumer: BroadcastEventReceiver,
     channel_event_producer: StreamHubEventSender,
+    /*milliseconds of the media in one aggregate message, not aggregated if None*/
+    aggregate_duration: Option<u32>,
+    /*relay id to the stop signal of the push, stopped when the stream is unpublished*/
+    relays: HashMap<String, oneshot::Sender<()>>,
+    reconnect_policy: ReconnectPolicy,
 }
 
 impl PushClient {
@@ -25,9 +35,20 @@ impl PushClient {
 
             client_event_consumer: consumer,
             channel_event_producer: producer,
+            aggregate_duration: None,
+            relays: HashMap::new(),
+            reconnect_policy: ReconnectPolicy::default(),
         }
     }
 
+    pub fn set_aggregate_duration(&mut self, duration: u32) {
+        self.aggregate_duration = Some(duration);
+    }
+
+    pub fn set_reconnect_policy(&mut self, policy: ReconnectPolicy) {
+        self.reconnect_policy = policy;
+    }
+
     pub async fn run(&mut self) -> Result<(), ClientError> {
         log::info!("push client run...");
 
@@ -39,7 +60,7 @@ impl PushClient {
                     if let StreamIdentifier::Rtmp {
                         app_name,
                         stream_name,
-                    } = identifier
+                    } = &identifier
                     {
                         log::info!(
                             "publish app_name: {} stream_name: {} address: {}",
@@ -47,23 +68,42 @@ impl PushClient {
                             stream_name.clone(),
                             self.address.clone()
                         );
-                        let stream = TcpStream::connect(self.address.clone()).await?;
 
-                        let mut client_session = ClientSession::new(
-                            stream,
+                        let id = format!("{}/{}/{}", self.address, app_name, stream_name);
+                        let statistics = Arc::new(Mutex::new(StatisticRelay::new(
+                            id.clone(),
+                            RelayType::Push,
+                            identifier.clone(),
+                            self.address.clone(),
+                        )));
+
+                        let mut supervisor = RelaySupervisor::new(
                             ClientSessionType::Push,
                             self.address.clone(),
-                            app_name,
-                            stream_name,
+                            app_name.clone(),
+                            stream_name.clone(),
                             self.channel_event_producer.clone(),
-                            0,
+                            statistics,
                         );
+                        supervisor.set_aggregate_duration(self.aggregate_duration);
+                        supervisor.set_policy(self.reconnect_policy.clone());
 
-                        tokio::spawn(async move {
-                            if let Err(err) = client_session.run().await {
-                                log::error!("client_session as push client run error: {}", err);
-                            }
-                        });
+                        /*the former push of the same stream is stopped*/
+                        let (stop_sender, stop_receiver) = oneshot::channel();
+                        self.relays.insert(id, stop_sender);
+                        tokio::spawn(supervisor.run(stop_receiver, None));
+                    }
+                }
+                BroadcastEvent::UnPublish {
+                    identifier:
+                        StreamIdentifier::Rtmp {
+                            app_name,
+                            stream_name,
+                        },
+                } => {
+                    let id = format!("{}/{}/{}", self.address, app_name, stream_name);
+                    if let Some(stop_sender) = self.relays.remove(&id) {
+                        let _ = stop_sender.send(());
                     }
                 }
 
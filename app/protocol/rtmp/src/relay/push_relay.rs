@@ -0,0 +1,140 @@
+use {
+    super::{
+        errors::ClientError,
+        relay_error, remove_finished_relay, send_result,
+        supervisor::{ReconnectPolicy, RelaySupervisor},
+    },
+    crate::{session::client_session::ClientSessionType, utils::RtmpUrlParser},
+    std::collections::HashMap,
+    streamhub::{
+        define::{
+            BroadcastEvent, BroadcastEventExecuteResultSender, BroadcastEventReceiver,
+            RelayStatistics, StreamHubEventSender,
+        },
+        errors::StreamHubError,
+        stream::StreamIdentifier,
+    },
+    tokio::sync::{mpsc, oneshot},
+};
+
+/*Pushes the local streams to the urls given by the api, each push is
+identified by the relay id and can be stopped at runtime.*/
+pub struct PushRelayClient {
+    client_event_consumer: BroadcastEventReceiver,
+    channel_event_producer: StreamHubEventSender,
+    /*relay id to the stop signal of the push*/
+    relays: HashMap<String, oneshot::Sender<()>>,
+    /*the ids of the relays whose supervisors have exited*/
+    finished_sender: mpsc::UnboundedSender<String>,
+    finished_receiver: mpsc::UnboundedReceiver<String>,
+    reconnect_policy: ReconnectPolicy,
+}
+
+impl PushRelayClient {
+    pub fn new(consumer: BroadcastEventReceiver, producer: StreamHubEventSender) -> Self {
+        let (finished_sender, finished_receiver) = mpsc::unbounded_channel();
+        Self {
+            client_event_consumer: consumer,
+            channel_event_producer: producer,
+            relays: HashMap::new(),
+            finished_sender,
+            finished_receiver,
+            reconnect_policy: ReconnectPolicy::default(),
+        }
+    }
+
+    pub fn set_reconnect_policy(&mut self, policy: ReconnectPolicy) {
+        self.reconnect_policy = policy;
+    }
+
+    pub async fn run(&mut self) -> Result<(), ClientError> {
+        log::info!("push relay client run...");
+
+        loop {
+            let event = tokio::select! {
+                event = self.client_event_consumer.recv() => event?,
+                Some(id) = self.finished_receiver.recv() => {
+                    remove_finished_relay(&mut self.relays, &id);
+                    continue;
+                }
+            };
+
+            match event {
+                BroadcastEvent::StartPush {
+                    id,
+                    identifier,
+                    url,
+                    statistics,
+                    result_sender,
+                } => {
+                    if let Err(err) =
+                        self.start_push(id, identifier, url, statistics, &result_sender)
+                    {
+                        send_result(result_sender, Err(err)).await;
+                    }
+                }
+                BroadcastEvent::StopPush { id, result_sender } => {
+                    /*the push may have finished by itself*/
+                    if let Some(stop_sender) = self.relays.remove(&id) {
+                        let _ = stop_sender.send(());
+                    }
+                    send_result(result_sender, Ok(())).await;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn start_push(
+        &mut self,
+        id: String,
+        identifier: StreamIdentifier,
+        url: String,
+        statistics: RelayStatistics,
+        result_sender: &BroadcastEventExecuteResultSender,
+    ) -> Result<(), StreamHubError> {
+        let (app_name, stream_name) = match identifier {
+            StreamIdentifier::Rtmp {
+                app_name,
+                stream_name,
+            } => (app_name, stream_name),
+            _ => return Err(relay_error(String::from("only rtmp streams can be pushed"))),
+        };
+
+        let mut url_parser = RtmpUrlParser::new(url.clone());
+        if !url.starts_with("rtmp://") || url_parser.parse_url().is_err() {
+            return Err(relay_error(format!("not a valid rtmp url: {}", url)));
+        }
+        url_parser.append_port(String::from("1935"));
+
+        log::info!(
+            "start push relay id: {} app_name: {} stream_name: {} url: {}",
+            id,
+            app_name,
+            stream_name,
+            url
+        );
+
+        let (stop_sender, stop_receiver) = oneshot::channel();
+        self.relays.insert(id.clone(), stop_sender);
+
+        let mut supervisor = RelaySupervisor::new(
+            ClientSessionType::Push,
+            url_parser.host_with_port,
+            url_parser.app_name,
+            url_parser.stream_name_with_query,
+            self.channel_event_producer.clone(),
+            statistics,
+        );
+        supervisor.set_local_stream(app_name, stream_name);
+        supervisor.set_policy(self.reconnect_policy.clone());
+        let result_sender = Some(result_sender.clone());
+        let finished_sender = self.finished_sender.clone();
+        tokio::spawn(async move {
+            supervisor.run(stop_receiver, result_sender).await;
+            let _ = finished_sender.send(id);
+        });
+
+        Ok(())
+    }
+}
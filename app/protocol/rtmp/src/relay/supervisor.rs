@@ -0,0 +1,281 @@
+use {
+    super::{relay_error, send_result},
+    crate::session::client_session::{ClientSession, ClientSessionType},
+    rand::Rng,
+    std::time::{Duration, Instant},
+    streamhub::{
+        define::{
+            BroadcastEventExecuteResultSender, RelayStatistics, StreamHubEvent,
+            StreamHubEventSender,
+        },
+        statistics::RelayState,
+    },
+    tokio::{net::TcpStream, sync::oneshot, time},
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/*How a relay reconnects after its session drops, the delay is doubled on
+each retry until the max delay.*/
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    /*the relay fails after the retries in a row, it retries forever if None*/
+    pub max_retries: Option<u32>,
+    /*from 0 to 1, how much the delays are randomized*/
+    pub jitter: f64,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            max_retries: Some(10),
+            jitter: 0.2,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /*the delay before the retry, retries are counted from 1, it never exceeds the
+    max delay even with the jitter*/
+    pub fn delay(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1).min(31);
+        let delay = self
+            .initial_delay
+            .saturating_mul(1u32 << exponent)
+            .min(self.max_delay);
+
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return delay;
+        }
+        let factor = rand::thread_rng().gen_range(1.0 - jitter..=1.0 + jitter);
+        delay.mul_f64(factor).min(self.max_delay)
+    }
+}
+
+/*Runs the session of a relay and reconnects it when it drops, the state
+changes are reported to the stream hub.*/
+pub struct RelaySupervisor {
+    client_type: ClientSessionType,
+    /*host with port*/
+    address: String,
+    /*the app and the stream(with query) on the remote server*/
+    app_name: String,
+    stream_name: String,
+    /*the local stream which is pushed*/
+    local_stream: Option<(String, String)>,
+    producer: StreamHubEventSender,
+    statistics: RelayStatistics,
+    policy: ReconnectPolicy,
+    aggregate_duration: Option<u32>,
+}
+
+impl RelaySupervisor {
+    pub fn new(
+        client_type: ClientSessionType,
+        address: String,
+        app_name: String,
+        stream_name: String,
+        producer: StreamHubEventSender,
+        statistics: RelayStatistics,
+    ) -> Self {
+        Self {
+            client_type,
+            address,
+            app_name,
+            stream_name,
+            local_stream: None,
+            producer,
+            statistics,
+            policy: ReconnectPolicy::default(),
+            aggregate_duration: None,
+        }
+    }
+
+    pub fn set_local_stream(&mut self, app_name: String, stream_name: String) {
+        self.local_stream = Some((app_name, stream_name));
+    }
+
+    pub fn set_policy(&mut self, policy: ReconnectPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn set_aggregate_duration(&mut self, duration: Option<u32>) {
+        self.aggregate_duration = duration;
+    }
+
+    async fn set_state(&self, state: RelayState, retries: u32, error: Option<String>) {
+        {
+            let mut statistics = self.statistics.lock().await;
+            statistics.state = state;
+            statistics.retries = retries;
+            if error.is_some() {
+                statistics.error = error;
+            }
+        }
+
+        let state_event = StreamHubEvent::RelayStateChange {
+            statistics: self.statistics.clone(),
+        };
+        if let Err(err) = self.producer.send(state_event) {
+            log::error!("send relay state err: {}", err);
+        }
+    }
+
+    async fn connect(&self) -> Result<TcpStream, String> {
+        match time::timeout(CONNECT_TIMEOUT, TcpStream::connect(self.address.clone())).await {
+            Ok(Ok(stream)) => Ok(stream),
+            Ok(Err(err)) => Err(format!("connect {} error: {}", self.address, err)),
+            Err(_) => Err(format!("connect {} timeout", self.address)),
+        }
+    }
+
+    fn new_session(&self, stream: TcpStream) -> ClientSession {
+        let mut client_session = ClientSession::new(
+            stream,
+            self.client_type.clone(),
+            self.address.clone(),
+            self.app_name.clone(),
+            self.stream_name.clone(),
+            self.producer.clone(),
+            0,
+        );
+        if let Some((app_name, stream_name)) = &self.local_stream {
+            client_session.subscribe(app_name.clone(), stream_name.clone());
+        }
+        if let Some(duration) = self.aggregate_duration {
+            client_session.set_aggregate_duration(duration);
+        }
+        client_session.set_relay_statistics(self.statistics.clone());
+        client_session
+    }
+
+    /*The result of the first connection is sent to the api caller, and the
+    relay is not retried if it fails. The relay stops once the stop signal is
+    sent or its sender is dropped.*/
+    pub async fn run(
+        self,
+        mut stop_receiver: oneshot::Receiver<()>,
+        mut result_sender: Option<BroadcastEventExecuteResultSender>,
+    ) {
+        let mut retries = 0;
+
+        loop {
+            self.set_state(RelayState::Connecting, retries, None).await;
+
+            let connect_result = tokio::select! {
+                result = self.connect() => result,
+                _ = &mut stop_receiver => break,
+            };
+
+            let error = match connect_result {
+                Ok(stream) => {
+                    if let Some(sender) = result_sender.take() {
+                        send_result(sender, Ok(())).await;
+                    }
+
+                    let start_time = Instant::now();
+                    let mut client_session = self.new_session(stream);
+                    let result = tokio::select! {
+                        result = client_session.run() => Some(result),
+                        _ = &mut stop_receiver => None,
+                    };
+
+                    if !matches!(result, Some(Err(_))) {
+                        if let Err(err) = client_session.flush().await {
+                            log::error!("relay {} flush error: {}", self.address, err);
+                        }
+                    }
+                    if let Err(err) = client_session.unsubscribe().await {
+                        log::error!("relay {} unsubscribe error: {}", self.address, err);
+                    }
+                    if let Err(err) = client_session.unpublish().await {
+                        log::error!("relay {} unpublish error: {}", self.address, err);
+                    }
+
+                    /*a session which lasted long enough starts the retries over*/
+                    if start_time.elapsed() >= self.policy.max_delay {
+                        retries = 0;
+                    }
+
+                    /*a session which finishes without an error is not reconnected*/
+                    match result {
+                        Some(Err(err)) => err.to_string(),
+                        Some(Ok(())) | None => break,
+                    }
+                }
+                Err(err) => {
+                    if let Some(sender) = result_sender.take() {
+                        self.set_state(RelayState::Failed, retries, Some(err.clone()))
+                            .await;
+                        send_result(sender, Err(relay_error(err))).await;
+                        return;
+                    }
+                    err
+                }
+            };
+
+            retries += 1;
+            if matches!(self.policy.max_retries, Some(max_retries) if retries > max_retries) {
+                log::error!("relay {} failed: {}", self.address, error);
+                self.set_state(RelayState::Failed, retries - 1, Some(error))
+                    .await;
+                return;
+            }
+
+            let delay = self.policy.delay(retries);
+            log::warn!(
+                "relay {} dropped: {}, retry {} in {:?}",
+                self.address,
+                error,
+                retries,
+                delay
+            );
+            self.set_state(RelayState::BackingOff, retries, Some(error))
+                .await;
+
+            tokio::select! {
+                _ = time::sleep(delay) => {}
+                _ = &mut stop_receiver => break,
+            }
+        }
+
+        log::info!("relay {} stopped", self.address);
+        self.set_state(RelayState::Stopped, retries, None).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::ReconnectPolicy;
+    use std::time::Duration;
+
+    #[test]
+    fn test_reconnect_delay() {
+        let mut policy = ReconnectPolicy {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(5),
+            max_retries: Some(10),
+            jitter: 0.0,
+        };
+
+        let delays: Vec<u64> = (1..=6)
+            .map(|retry| policy.delay(retry).as_millis() as u64)
+            .collect();
+        assert_eq!(delays, vec![500, 1000, 2000, 4000, 5000, 5000]);
+        assert_eq!(policy.delay(100), Duration::from_secs(5));
+
+        policy.jitter = 0.5;
+        for _ in 0..100 {
+            let delay = policy.delay(2);
+            assert!(delay >= Duration::from_millis(500) && delay <= Duration::from_millis(1500));
+            let delay = policy.delay(10);
+            assert!(delay >= Duration::from_millis(2500) && delay <= Duration::from_secs(5));
+        }
+    }
+}
@@ -1,16 +1,32 @@
 use streamhub::define::StreamHubEventSender;
 
 use super::session::server_session;
+use super::session::watchdog::SessionTimeouts;
+use bytesio::bytesio::TlsIO;
 use commonlib::auth::Auth;
 use std::net::SocketAddr;
-use tokio::io::Error;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{Error, ErrorKind};
 use tokio::net::TcpListener;
+use tokio::time::timeout;
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+
+/*a client which connects and sends nothing would hold the task forever*/
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
 
 pub struct RtmpServer {
     address: String,
     event_producer: StreamHubEventSender,
     gop_num: usize,
     auth: Option<Auth>,
+    /*the sessions are over TLS(RTMPS) if set*/
+    tls_acceptor: Option<TlsAcceptor>,
+    timeouts: SessionTimeouts,
 }
 
 impl RtmpServer {
@@ -25,25 +41,94 @@ impl RtmpServer {
             event_producer,
             gop_num,
             auth,
+            tls_acceptor: None,
+            timeouts: SessionTimeouts::default(),
         }
     }
 
+    pub fn set_session_timeouts(&mut self, timeouts: SessionTimeouts) {
+        self.timeouts = timeouts;
+    }
+
+    /*load the PEM files of the certificate chain and the private key*/
+    pub fn enable_tls(&mut self, cert_path: &str, key_path: &str) -> Result<(), Error> {
+        let invalid_data = |path: &str, err: String| {
+            Error::new(ErrorKind::InvalidData, format!("load {path} error: {err}"))
+        };
+
+        let certs = CertificateDer::pem_file_iter(cert_path)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|err| invalid_data(cert_path, err.to_string()))?;
+        let key = PrivateKeyDer::from_pem_file(key_path)
+            .map_err(|err| invalid_data(key_path, err.to_string()))?;
+
+        let config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+            .map_err(|err| invalid_data(cert_path, err.to_string()))?;
+
+        self.tls_acceptor = Some(TlsAcceptor::from(Arc::new(config)));
+        Ok(())
+    }
+
     pub async fn run(&mut self) -> Result<(), Error> {
         let socket_addr: &SocketAddr = &self.address.parse().unwrap();
         let listener = TcpListener::bind(socket_addr).await?;
 
-        log::info!("Rtmp server listening on tcp://{}", socket_addr);
+        if self.tls_acceptor.is_some() {
+            log::info!("Rtmps server listening on tcp://{}", socket_addr);
+        } else {
+            log::info!("Rtmp server listening on tcp://{}", socket_addr);
+        }
         loop {
-            let (tcp_stream, _) = listener.accept().await?;
+            let (tcp_stream, remote_addr) = listener.accept().await?;
             //tcp_stream.set_keepalive(Some(Duration::from_secs(30)))?;
 
-            let mut session = server_session::ServerSession::new(
-                tcp_stream,
-                self.event_producer.clone(),
-                self.gop_num,
-                self.auth.clone(),
-            );
+            let tls_acceptor = self.tls_acceptor.clone();
+            let event_producer = self.event_producer.clone();
+            let gop_num = self.gop_num;
+            let auth = self.auth.clone();
+            let timeouts = self.timeouts.clone();
+
             tokio::spawn(async move {
+                /*the TLS handshake is done in the task of the session not to block the
+                other connections*/
+                let mut session = match tls_acceptor {
+                    Some(acceptor) => {
+                        match timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(tcp_stream)).await {
+                            Ok(Ok(tls_stream)) => {
+                                log::info!("server session: {} over tls", remote_addr);
+                                server_session::ServerSession::new_with_io(
+                                    Box::new(TlsIO::new(tls_stream)),
+                                    Some(remote_addr),
+                                    event_producer,
+                                    gop_num,
+                                    auth,
+                                )
+                            }
+                            Ok(Err(err)) => {
+                                log::error!(
+                                    "tls handshake error: {}, remote: {}",
+                                    err,
+                                    remote_addr
+                                );
+                                return;
+                            }
+                            Err(_) => {
+                                log::error!("tls handshake timed out, remote: {}", remote_addr);
+                                return;
+                            }
+                        }
+                    }
+                    None => server_session::ServerSession::new(
+                        tcp_stream,
+                        event_producer,
+                        gop_num,
+                        auth,
+                    ),
+                };
+                session.set_timeouts(timeouts);
+
                 if let Err(err) = session.run().await {
                     log::info!(
                         "session run error: session_type: {}, app_name: {}, stream_name: {}, err: {}",
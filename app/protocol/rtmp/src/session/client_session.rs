@@ -29,7 +29,10 @@ use {
     std::sync::Arc,
     std::time::Duration,
     //crate::utils::print::print,
-    streamhub::define::StreamHubEventSender,
+    streamhub::{
+        define::{RelayStatistics, StreamHubEventSender},
+        statistics::RelayState,
+    },
     tokio::{net::TcpStream, sync::Mutex},
     xflv::amf0::Amf0ValueType,
 };
@@ -84,6 +87,8 @@ pub struct ClientSession {
     sub_stream_name: Option<String>,
     /*configure how many gops will be cached.*/
     gop_num: usize,
+    /*the pulled stream is published to the stream hub*/
+    is_publishing: bool,
 }
 
 impl ClientSession {
@@ -130,6 +135,7 @@ impl ClientSession {
             sub_app_name: None,
             sub_stream_name: None,
             gop_num,
+            is_publishing: false,
         }
     }
     
@@ -137,6 +143,15 @@ impl ClientSession {
         self.timeout = Some(timeout)
     }
 
+    /*the push client sends the media in aggregate messages of the duration(milliseconds)*/
+    pub fn set_aggregate_duration(&mut self, duration: u32) {
+        self.common.set_aggregate_duration(duration)
+    }
+
+    pub fn set_relay_statistics(&mut self, statistics: RelayStatistics) {
+        self.common.set_relay_statistics(statistics)
+    }
+
     pub async fn run(&mut self) -> Result<(), SessionError> {
         loop {
             match self.state {
@@ -261,6 +276,10 @@ impl ClientSession {
                 log::info!("[C <- S] on_stream_is_recorded...");
                 self.on_stream_is_recorded(stream_id)?;
             }
+            RtmpMessageData::PingRequest { timestamp } => {
+                log::trace!("[C <- S] on_ping_request...");
+                self.send_ping_response(*timestamp).await?;
+            }
             RtmpMessageData::AudioData { data } => {
                 self.common.on_audio_data(data, timestamp).await?
             }
@@ -270,6 +289,9 @@ impl ClientSession {
             RtmpMessageData::AmfData { raw_data } => {
                 self.common.on_meta_data(raw_data, timestamp).await?;
             }
+            RtmpMessageData::AggregateData { messages } => {
+                self.common.on_aggregate_data(messages).await?;
+            }
 
             _ => {}
         }
@@ -459,6 +481,13 @@ impl ClientSession {
         Ok(())
     }
 
+    pub async fn send_ping_response(&mut self, timestamp: u32) -> Result<(), SessionError> {
+        let mut eventmessages = EventMessagesWriter::new(AsyncBytesWriter::new(self.io.clone()));
+        eventmessages.write_ping_response(timestamp).await?;
+
+        Ok(())
+    }
+
     pub async fn on_result_connect(&mut self) -> Result<(), SessionError> {
         let mut controlmessage =
             ProtocolControlMessagesWriter::new(AsyncBytesWriter::new(self.io.clone()));
@@ -520,6 +549,14 @@ impl ClientSession {
         obj: &IndexMap<String, Amf0ValueType>,
     ) -> Result<(), SessionError> {
         if let Some(Amf0ValueType::UTF8String(code_info)) = obj.get("code") {
+            if let Some(Amf0ValueType::UTF8String(level)) = obj.get("level") {
+                if level == "error" {
+                    return Err(SessionError {
+                        value: SessionErrorValue::ErrorStatus(code_info.clone()),
+                    });
+                }
+            }
+
             match &code_info[..] {
                 "NetStream.Publish.Start" => {
                     self.state = ClientSessionState::StartPublish;
@@ -538,6 +575,7 @@ impl ClientSession {
                             )
                             .await?;
                     }
+                    self.common.set_relay_state(RelayState::Active).await;
                 }
                 "NetStream.Publish.Reset" => {}
                 "NetStream.Play.Start" => {
@@ -548,7 +586,9 @@ impl ClientSession {
                             self.stream_name.clone(),
                             self.gop_num,
                         )
-                        .await?
+                        .await?;
+                    self.is_publishing = true;
+                    self.common.set_relay_state(RelayState::Active).await;
                 }
                 _ => {}
             }
@@ -561,4 +601,36 @@ impl ClientSession {
         self.sub_app_name = Some(app_name);
         self.sub_stream_name = Some(stream_name);
     }
+
+    /*send the media which is batched in an aggregate message before the push is stopped*/
+    pub async fn flush(&mut self) -> Result<(), SessionError> {
+        self.common.flush_aggregate_message().await
+    }
+
+    /*stop taking the local stream after the push is stopped*/
+    pub async fn unsubscribe(&mut self) -> Result<(), SessionError> {
+        if !matches!(self.state, ClientSessionState::StartPublish) {
+            return Ok(());
+        }
+
+        let (app_name, stream_name) = match (&self.sub_app_name, &self.sub_stream_name) {
+            (Some(app_name), Some(stream_name)) => (app_name.clone(), stream_name.clone()),
+            _ => (self.app_name.clone(), self.stream_name.clone()),
+        };
+        self.common
+            .unsubscribe_from_stream_hub(app_name, stream_name)
+            .await
+    }
+
+    /*remove the pulled stream from the stream hub after the pull is stopped*/
+    pub async fn unpublish(&mut self) -> Result<(), SessionError> {
+        if !self.is_publishing {
+            return Ok(());
+        }
+
+        self.is_publishing = false;
+        self.common
+            .unpublish_to_stream_hub(self.app_name.clone(), self.stream_name.clone())
+            .await
+    }
 }
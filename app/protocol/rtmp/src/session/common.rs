@@ -14,7 +14,10 @@ use {
             packetizer::ChunkPacketizer,
             ChunkInfo,
         },
-        messages::define::msg_type_id,
+        messages::{
+            aggregate::AggregateWriter,
+            define::{msg_type_id, RtmpMessageData},
+        },
     },
     async_trait::async_trait,
     bytes::BytesMut,
@@ -23,11 +26,11 @@ use {
     streamhub::{
         define::{
             FrameData, FrameDataReceiver, FrameDataSender, InformationSender, NotifyInfo,
-            PublishType, PublisherInfo, StreamHubEvent, StreamHubEventSender, SubscribeType,
-            SubscriberInfo, TStreamHandler,
+            PublishType, PublisherInfo, RelayStatistics, SessionTimeoutReason, StreamHubEvent,
+            StreamHubEventSender, SubscribeType, SubscriberInfo, TStreamHandler,
         },
         errors::{StreamHubError, StreamHubErrorValue},
-        statistics::StatisticsStream,
+        statistics::{RelayState, StatisticsStream},
         stream::StreamIdentifier,
         utils::Uuid,
     },
@@ -55,6 +58,12 @@ pub struct Common {
     pub stream_handler: Arc<RtmpStreamHandler>,
     /* now used for subscriber session */
     statistic_data_sender: Option<StatisticDataSender>,
+    /*the audio and video are sent in aggregate messages if set*/
+    aggregate_writer: Option<AggregateWriter>,
+    /*the status of the relay which the client session works for*/
+    relay_statistics: Option<RelayStatistics>,
+    /*the publisher waits as a hot standby of the stream*/
+    pub is_backup: bool,
 }
 
 impl Common {
@@ -80,17 +89,73 @@ impl Common {
             request_url: String::default(),
             stream_handler: Arc::new(RtmpStreamHandler::new()),
             statistic_data_sender: None,
+            aggregate_writer: None,
+            relay_statistics: None,
+            is_backup: false,
             //cache: None,
         }
     }
+
+    /*batch the audio and video of the duration(milliseconds) into one message*/
+    pub fn set_aggregate_duration(&mut self, duration: u32) {
+        self.aggregate_writer = Some(AggregateWriter::new(duration));
+    }
+
+    pub fn set_relay_statistics(&mut self, statistics: RelayStatistics) {
+        self.relay_statistics = Some(statistics);
+    }
+
+    pub async fn set_relay_state(&self, state: RelayState) {
+        if let Some(statistics) = &self.relay_statistics {
+            statistics.lock().await.state = state;
+
+            let state_event = StreamHubEvent::RelayStateChange {
+                statistics: statistics.clone(),
+            };
+            if let Err(err) = self.event_producer.send(state_event) {
+                log::error!("send relay state err: {}", err);
+            }
+        }
+    }
+
+    async fn add_relay_send_bytes(&self, data_size: usize) {
+        if let Some(statistics) = &self.relay_statistics {
+            statistics.lock().await.send_bytes += data_size;
+        }
+    }
+
+    async fn add_relay_recv_bytes(&self, data_size: usize) {
+        if let Some(statistics) = &self.relay_statistics {
+            statistics.lock().await.recv_bytes += data_size;
+        }
+    }
+
     pub async fn send_channel_data(&mut self) -> Result<(), SessionError> {
         let mut retry_times = 0;
         loop {
-            if let Some(data) = self.data_receiver.recv().await {
+            /*the batched media is sent once no more comes within the aggregate duration*/
+            let flush_interval = self
+                .aggregate_writer
+                .as_ref()
+                .and_then(AggregateWriter::flush_interval);
+            let data = match flush_interval {
+                Some(interval) => tokio::select! {
+                    data = self.data_receiver.recv() => Some(data),
+                    _ = tokio::time::sleep(interval) => None,
+                },
+                None => Some(self.data_receiver.recv().await),
+            };
+            let Some(data) = data else {
+                self.flush_aggregate_message().await?;
+                continue;
+            };
+
+            if let Some(data) = data {
                 match data {
                     FrameData::Audio { timestamp, data } => {
                         let data_size = data.len();
                         self.send_audio(data, timestamp).await?;
+                        self.add_relay_send_bytes(data_size).await;
 
                         if let Some(sender) = &self.statistic_data_sender {
                             let statistic_audio_data = StatisticData::Audio {
@@ -107,6 +172,7 @@ impl Common {
                     FrameData::Video { timestamp, data } => {
                         let data_size = data.len();
                         self.send_video(data, timestamp).await?;
+                        self.add_relay_send_bytes(data_size).await;
 
                         if let Some(sender) = &self.statistic_data_sender {
                             let statistic_video_data = StatisticData::Video {
@@ -122,11 +188,17 @@ impl Common {
                         }
                     }
                     FrameData::MetaData { timestamp, data } => {
+                        let data_size = data.len();
                         self.send_metadata(data, timestamp).await?;
+                        self.add_relay_send_bytes(data_size).await;
                     }
+                    /*the timestamps of the publisher which takes over are rebased by the
+                    hub, the players go on with its headers and gop*/
                     _ => {}
                 }
             } else {
+                /*the stream is unpublished, the batched media is still sent*/
+                self.flush_aggregate_message().await?;
                 retry_times += 1;
                 log::debug!(
                     "send_channel_data: no data receives ,retry {} times!",
@@ -143,6 +215,12 @@ impl Common {
     }
 
     pub async fn send_audio(&mut self, data: BytesMut, timestamp: u32) -> Result<(), SessionError> {
+        if self.aggregate_writer.is_some() {
+            return self
+                .send_aggregate_message(msg_type_id::AUDIO, data, timestamp)
+                .await;
+        }
+
         let mut chunk_info = ChunkInfo::new(
             csid_type::AUDIO,
             chunk_type::TYPE_0,
@@ -161,6 +239,12 @@ impl Common {
     }
 
     pub async fn send_video(&mut self, data: BytesMut, timestamp: u32) -> Result<(), SessionError> {
+        if self.aggregate_writer.is_some() {
+            return self
+                .send_aggregate_message(msg_type_id::VIDEO, data, timestamp)
+                .await;
+        }
+
         let mut chunk_info = ChunkInfo::new(
             csid_type::VIDEO,
             chunk_type::TYPE_0,
@@ -183,6 +267,9 @@ impl Common {
         data: BytesMut,
         timestamp: u32,
     ) -> Result<(), SessionError> {
+        /*the metadata is not batched, the media before it is sent first*/
+        self.flush_aggregate_message().await?;
+
         let mut chunk_info = ChunkInfo::new(
             csid_type::DATA_AMF0_AMF3,
             chunk_type::TYPE_0,
@@ -200,11 +287,54 @@ impl Common {
         Ok(())
     }
 
+    async fn send_aggregate_message(
+        &mut self,
+        msg_type_id: u8,
+        data: BytesMut,
+        timestamp: u32,
+    ) -> Result<(), SessionError> {
+        if let Some(aggregate_writer) = &mut self.aggregate_writer {
+            aggregate_writer.write_message(msg_type_id, timestamp, &data)?;
+            if !aggregate_writer.is_full() {
+                return Ok(());
+            }
+        }
+
+        self.flush_aggregate_message().await
+    }
+
+    pub async fn flush_aggregate_message(&mut self) -> Result<(), SessionError> {
+        let Some((timestamp, data)) = self
+            .aggregate_writer
+            .as_mut()
+            .and_then(|aggregate_writer| aggregate_writer.extract_message())
+        else {
+            return Ok(());
+        };
+
+        let mut chunk_info = ChunkInfo::new(
+            csid_type::VIDEO,
+            chunk_type::TYPE_0,
+            timestamp,
+            data.len() as u32,
+            msg_type_id::AGGREGATE,
+            0,
+            data,
+        );
+
+        if let Some(packetizer) = &mut self.packetizer {
+            packetizer.write_chunk(&mut chunk_info).await?;
+        }
+
+        Ok(())
+    }
+
     pub async fn on_video_data(
         &mut self,
         data: &mut BytesMut,
         timestamp: &u32,
     ) -> Result<(), SessionError> {
+        self.add_relay_recv_bytes(data.len()).await;
         let channel_data = FrameData::Video {
             timestamp: *timestamp,
             data: data.clone(),
@@ -232,6 +362,7 @@ impl Common {
         data: &mut BytesMut,
         timestamp: &u32,
     ) -> Result<(), SessionError> {
+        self.add_relay_recv_bytes(data.len()).await;
         let channel_data = FrameData::Audio {
             timestamp: *timestamp,
             data: data.clone(),
@@ -278,6 +409,25 @@ impl Common {
         Ok(())
     }
 
+    /*the sub-messages are handled as the messages received one by one*/
+    pub async fn on_aggregate_data(
+        &mut self,
+        messages: &mut [(u32, RtmpMessageData)],
+    ) -> Result<(), SessionError> {
+        for (timestamp, message) in messages.iter_mut() {
+            match message {
+                RtmpMessageData::AudioData { data } => self.on_audio_data(data, timestamp).await?,
+                RtmpMessageData::VideoData { data } => self.on_video_data(data, timestamp).await?,
+                RtmpMessageData::AmfData { raw_data } => {
+                    self.on_meta_data(raw_data, timestamp).await?
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
     fn get_subscriber_info(&mut self) -> SubscriberInfo {
         let remote_addr = if let Some(addr) = self.remote_addr {
             addr.to_string()
@@ -323,6 +473,7 @@ impl Common {
                 request_url: self.request_url.clone(),
                 remote_addr,
             },
+            backup: self.is_backup,
         }
     }
 
@@ -490,6 +641,30 @@ impl Common {
         }
         Ok(())
     }
+
+    /*report the stalled session to the stream hub before it is closed*/
+    pub fn report_session_timeout(
+        &mut self,
+        app_name: String,
+        stream_name: String,
+        reason: SessionTimeoutReason,
+    ) -> Result<(), SessionError> {
+        let event = StreamHubEvent::SessionTimeout {
+            identifier: StreamIdentifier::Rtmp {
+                app_name,
+                stream_name,
+            },
+            info: self.get_publisher_info().notify_info,
+            reason,
+        };
+
+        if self.event_producer.send(event).is_err() {
+            return Err(SessionError {
+                value: SessionErrorValue::StreamHubEventSendErr,
+            });
+        }
+        Ok(())
+    }
 }
 
 #[derive(Default)]
@@ -13,7 +13,7 @@ use {
     commonlib::errors::AuthError,
     failure::{Backtrace, Fail},
     std::fmt,
-    streamhub::errors::StreamHubError,
+    streamhub::{define::SessionTimeoutReason, errors::StreamHubError},
     tokio::sync::oneshot::error::RecvError,
     xflv::amf0::errors::Amf0WriteError,
 };
@@ -77,9 +77,13 @@ pub enum SessionErrorValue {
     NoAppName,
     #[fail(display = "no media data can be received now.")]
     NoMediaDataReceived,
+    #[fail(display = "session timeout: {:?}", _0)]
+    SessionTimeout(SessionTimeoutReason),
 
     #[fail(display = "session is finished.")]
     Finish,
+    #[fail(display = "error status from the remote server: {}", _0)]
+    ErrorStatus(String),
     #[fail(display = "Auth err: {}", _0)]
     AuthError(#[cause] AuthError),
 }
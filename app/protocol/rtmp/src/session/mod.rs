@@ -4,3 +4,4 @@ pub mod errors;
 pub mod common;
 pub mod client_session;
 pub mod server_session;
+pub mod watchdog;
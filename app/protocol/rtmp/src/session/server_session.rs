@@ -8,6 +8,7 @@ use {
         define,
         define::SessionType,
         errors::{SessionError, SessionErrorValue},
+        watchdog::{SessionTimeouts, SessionWatchdog},
     },
     crate::{
         chunk::{
@@ -27,12 +28,16 @@ use {
     bytesio::{
         bytes_writer::AsyncBytesWriter,
         bytesio::{TNetIO, TcpIO},
+        bytesio_errors::{BytesIOError, BytesIOErrorValue},
     },
     commonlib::auth::Auth,
     indexmap::IndexMap,
-    std::{sync::Arc, time::Duration},
-    streamhub::define::StreamHubEventSender,
-    tokio::{net::TcpStream, sync::Mutex},
+    std::{net::SocketAddr, sync::Arc},
+    streamhub::{
+        define::{SessionTimeoutReason, StreamHubEventSender},
+        errors::{StreamHubError, StreamHubErrorValue},
+    },
+    tokio::{net::TcpStream, sync::Mutex, time::Instant},
     xflv::amf0::Amf0ValueType,
 };
 
@@ -61,6 +66,10 @@ pub struct ServerSession {
     /*configure how many gops will be cached.*/
     gop_num: usize,
     auth: Option<Auth>,
+    /*closes the stalled session and pings the silent client*/
+    watchdog: SessionWatchdog,
+    /*the timestamps of the ping requests are counted from the start of the session*/
+    start_time: Instant,
 }
 
 impl ServerSession {
@@ -77,8 +86,24 @@ impl ServerSession {
             None
         };
 
-        let tcp_io: Box<dyn TNetIO + Send + Sync> = Box::new(TcpIO::new(stream));
-        let net_io = Arc::new(Mutex::new(tcp_io));
+        Self::new_with_io(
+            Box::new(TcpIO::new(stream)),
+            remote_addr,
+            event_producer,
+            gop_num,
+            auth,
+        )
+    }
+
+    /*a session over another transport, e.g. TLS*/
+    pub fn new_with_io(
+        io: Box<dyn TNetIO + Send + Sync>,
+        remote_addr: Option<SocketAddr>,
+        event_producer: StreamHubEventSender,
+        gop_num: usize,
+        auth: Option<Auth>,
+    ) -> Self {
+        let net_io = Arc::new(Mutex::new(io));
 
         Self {
             app_name: String::from(""),
@@ -100,9 +125,15 @@ impl ServerSession {
             connect_properties: ConnectProperties::default(),
             gop_num,
             auth,
+            watchdog: SessionWatchdog::new(SessionTimeouts::default(), Instant::now()),
+            start_time: Instant::now(),
         }
     }
 
+    pub fn set_timeouts(&mut self, timeouts: SessionTimeouts) {
+        self.watchdog = SessionWatchdog::new(timeouts, Instant::now());
+    }
+
     pub async fn run(&mut self) -> Result<(), SessionError> {
         loop {
             match self.state {
@@ -128,7 +159,12 @@ impl ServerSession {
         let mut bytes_len = 0;
 
         while bytes_len < handshake::define::RTMP_HANDSHAKE_SIZE {
-            self.bytesio_data = self.io.lock().await.read().await?;
+            self.bytesio_data = self
+                .io
+                .lock()
+                .await
+                .read_timeout(self.watchdog.idle_timeout())
+                .await?;
             bytes_len += self.bytesio_data.len();
             self.handshaker.extend_data(&self.bytesio_data[..]);
         }
@@ -152,16 +188,18 @@ impl ServerSession {
 
     async fn read_parse_chunks(&mut self) -> Result<(), SessionError> {
         if !self.has_remaing_data {
-            match self
-                .io
-                .lock()
-                .await
-                .read_timeout(Duration::from_secs(2))
-                .await
-            {
+            let duration = self.watchdog.next_check(Instant::now());
+            let result = self.io.lock().await.read_timeout(duration).await;
+            match result {
                 Ok(data) => {
+                    self.watchdog.on_received(Instant::now());
                     self.bytesio_data = data;
                 }
+                Err(BytesIOError {
+                    value: BytesIOErrorValue::TimeoutError(_),
+                }) => {
+                    return self.check_timeouts().await;
+                }
                 Err(err) => {
                     self.common
                         .unpublish_to_stream_hub(self.app_name.clone(), self.stream_name.clone())
@@ -204,9 +242,50 @@ impl ServerSession {
                 }
             }
         }
+
+        self.check_timeouts().await
+    }
+
+    async fn check_timeouts(&mut self) -> Result<(), SessionError> {
+        let now = Instant::now();
+        if let Some(reason) = self.watchdog.check(now) {
+            return Err(self.close_stalled_session(reason).await);
+        }
+        if self.watchdog.ping_due(now) {
+            self.send_ping_request().await?;
+        }
         Ok(())
     }
 
+    /*a half-open connection or a stalled publisher must not keep the stream alive*/
+    async fn close_stalled_session(&mut self, reason: SessionTimeoutReason) -> SessionError {
+        log::warn!(
+            "close the stalled session, app_name: {}, stream_name: {}, reason: {:?}",
+            self.app_name,
+            self.stream_name,
+            reason
+        );
+
+        if let Err(err) = self.common.report_session_timeout(
+            self.app_name.clone(),
+            self.stream_name.clone(),
+            reason,
+        ) {
+            log::error!("report session timeout error: {}", err);
+        }
+        if let Err(err) = self
+            .common
+            .unpublish_to_stream_hub(self.app_name.clone(), self.stream_name.clone())
+            .await
+        {
+            return err;
+        }
+
+        SessionError {
+            value: SessionErrorValue::SessionTimeout(reason),
+        }
+    }
+
     async fn play(&mut self) -> Result<(), SessionError> {
         match self.common.send_channel_data().await {
             Ok(_) => {}
@@ -229,6 +308,21 @@ impl ServerSession {
         Ok(())
     }
 
+    pub async fn send_ping_request(&mut self) -> Result<(), SessionError> {
+        let timestamp = self.start_time.elapsed().as_millis() as u32;
+        let mut event_messages = EventMessagesWriter::new(AsyncBytesWriter::new(self.io.clone()));
+        event_messages.write_ping_request(timestamp).await?;
+
+        Ok(())
+    }
+
+    pub async fn send_ping_response(&mut self, timestamp: u32) -> Result<(), SessionError> {
+        let mut event_messages = EventMessagesWriter::new(AsyncBytesWriter::new(self.io.clone()));
+        event_messages.write_ping_response(timestamp).await?;
+
+        Ok(())
+    }
+
     pub async fn process_messages(
         &mut self,
         rtmp_msg: &mut RtmpMessageData,
@@ -255,14 +349,37 @@ impl ServerSession {
                 self.on_set_chunk_size(*chunk_size as usize)?;
             }
             RtmpMessageData::AudioData { data } => {
+                self.watchdog.on_audio(Instant::now());
                 self.common.on_audio_data(data, timestamp).await?;
             }
             RtmpMessageData::VideoData { data } => {
+                self.watchdog.on_video(data, Instant::now());
                 self.common.on_video_data(data, timestamp).await?;
             }
             RtmpMessageData::AmfData { raw_data } => {
                 self.common.on_meta_data(raw_data, timestamp).await?;
             }
+            RtmpMessageData::AggregateData { messages } => {
+                let now = Instant::now();
+                for (_, message) in messages.iter() {
+                    match message {
+                        RtmpMessageData::AudioData { .. } => self.watchdog.on_audio(now),
+                        RtmpMessageData::VideoData { data } => self.watchdog.on_video(data, now),
+                        _ => {}
+                    }
+                }
+                self.common.on_aggregate_data(messages).await?;
+            }
+            RtmpMessageData::PingRequest {
+                timestamp: ping_timestamp,
+            } => {
+                self.send_ping_response(*ping_timestamp).await?;
+            }
+            RtmpMessageData::PingResponse {
+                timestamp: ping_timestamp,
+            } => {
+                log::trace!("[ S<-C ] [ping response] timestamp: {}", ping_timestamp);
+            }
 
             _ => {}
         }
@@ -410,6 +527,14 @@ impl ServerSession {
         }
     }
 
+    /*the commands are replied in AMF3 if the client connects with objectEncoding 3*/
+    fn object_encoding(&self) -> f64 {
+        match self.connect_properties.object_encoding {
+            Some(encoding) if encoding == define::OBJENCODING_AMF3 => define::OBJENCODING_AMF3,
+            _ => define::OBJENCODING_AMF0,
+        }
+    }
+
     async fn on_connect(
         &mut self,
         transaction_id: &f64,
@@ -452,7 +577,8 @@ impl ServerSession {
             }
         };
 
-        let mut netconnection = NetConnection::new(Arc::clone(&self.io));
+        let mut netconnection =
+            NetConnection::new_with_encoding(Arc::clone(&self.io), self.object_encoding());
         log::info!("[ S->C ] [set connect_response]",);
         netconnection
             .write_connect_response(
@@ -470,7 +596,8 @@ impl ServerSession {
     }
 
     pub async fn on_create_stream(&mut self, transaction_id: &f64) -> Result<(), SessionError> {
-        let mut netconnection = NetConnection::new(Arc::clone(&self.io));
+        let mut netconnection =
+            NetConnection::new_with_encoding(Arc::clone(&self.io), self.object_encoding());
         netconnection
             .write_create_stream_response(transaction_id, &define::STREAM_ID)
             .await?;
@@ -492,7 +619,8 @@ impl ServerSession {
             .unpublish_to_stream_hub(self.app_name.clone(), self.stream_name.clone())
             .await?;
 
-        let mut netstream = NetStreamWriter::new(Arc::clone(&self.io));
+        let mut netstream =
+            NetStreamWriter::new_with_encoding(Arc::clone(&self.io), self.object_encoding());
         netstream
             .write_on_status(
                 transaction_id,
@@ -589,7 +717,8 @@ impl ServerSession {
             reset.is_some()
         );
 
-        let mut netstream = NetStreamWriter::new(Arc::clone(&self.io));
+        let mut netstream =
+            NetStreamWriter::new_with_encoding(Arc::clone(&self.io), self.object_encoding());
         netstream
             .write_on_status(transaction_id, "status", "NetStream.Play.Reset", "reset")
             .await?;
@@ -720,6 +849,12 @@ impl ServerSession {
 
         /*Now it can update the request url*/
         self.common.request_url = self.get_request_url(stream_name_with_query);
+        /*e.g. rtmp://host/app/stream?role=backup*/
+        self.common.is_backup = self
+            .query
+            .as_ref()
+            .and_then(|query| RtmpUrlParser::get_query_value(query, "role"))
+            .is_some_and(|role| role == "backup");
 
         let _ = match other_values.remove(0) {
             Amf0ValueType::UTF8String(val) => val,
@@ -753,24 +888,50 @@ impl ServerSession {
         let mut event_messages = EventMessagesWriter::new(AsyncBytesWriter::new(self.io.clone()));
         event_messages.write_stream_begin(*stream_id).await?;
 
-        let mut netstream = NetStreamWriter::new(Arc::clone(&self.io));
+        let mut netstream =
+            NetStreamWriter::new_with_encoding(Arc::clone(&self.io), self.object_encoding());
+
+        /*the stream is being published by another client and the newcomer is rejected*/
+        if let Err(err) = self
+            .common
+            .publish_to_stream_hub(
+                self.app_name.clone(),
+                self.stream_name.clone(),
+                self.gop_num,
+            )
+            .await
+        {
+            if let SessionErrorValue::ChannelError(StreamHubError {
+                value: StreamHubErrorValue::Exists,
+            }) = &err.value
+            {
+                netstream
+                    .write_on_status(
+                        transaction_id,
+                        "error",
+                        "NetStream.Publish.BadName",
+                        "the stream is already being published",
+                    )
+                    .await?;
+                log::info!(
+                    "[ S->C ] [NetStream.Publish.BadName]  app_name: {}, stream_name: {}",
+                    self.app_name,
+                    self.stream_name
+                );
+            }
+            return Err(err);
+        }
+
         netstream
             .write_on_status(transaction_id, "status", "NetStream.Publish.Start", "")
             .await?;
+        self.watchdog.on_publish(Instant::now());
         log::info!(
             "[ S->C ] [NetStream.Publish.Start]  app_name: {}, stream_name: {}",
             self.app_name,
             self.stream_name
         );
 
-        self.common
-            .publish_to_stream_hub(
-                self.app_name.clone(),
-                self.stream_name.clone(),
-                self.gop_num,
-            )
-            .await?;
-
         Ok(())
     }
 }
@@ -0,0 +1,204 @@
+use {
+    bytes::BytesMut, std::time::Duration, streamhub::define::SessionTimeoutReason,
+    tokio::time::Instant, xflv::define::frame_type,
+};
+
+/*How long a server session may stall before it is closed, the durations of
+the media are counted from the start of the publishing.*/
+#[derive(Debug, Clone)]
+pub struct SessionTimeouts {
+    /*nothing is received from the client*/
+    pub idle: Duration,
+    /*a ping request is sent to the client which sends nothing for the interval*/
+    pub ping_interval: Option<Duration>,
+    /*the publisher sends no audio or video*/
+    pub no_media: Option<Duration>,
+    /*the publisher sends no video keyframe*/
+    pub no_keyframe: Option<Duration>,
+}
+
+impl Default for SessionTimeouts {
+    fn default() -> Self {
+        Self {
+            idle: Duration::from_secs(2),
+            ping_interval: None,
+            no_media: None,
+            no_keyframe: None,
+        }
+    }
+}
+
+pub struct SessionWatchdog {
+    timeouts: SessionTimeouts,
+    last_received: Instant,
+    last_ping: Instant,
+    /*set once the session starts publishing*/
+    last_media: Option<Instant>,
+    last_keyframe: Option<Instant>,
+}
+
+impl SessionWatchdog {
+    pub fn new(timeouts: SessionTimeouts, now: Instant) -> Self {
+        Self {
+            timeouts,
+            last_received: now,
+            last_ping: now,
+            last_media: None,
+            last_keyframe: None,
+        }
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        self.timeouts.idle
+    }
+
+    pub fn on_received(&mut self, now: Instant) {
+        self.last_received = now;
+    }
+
+    pub fn on_publish(&mut self, now: Instant) {
+        self.last_media = Some(now);
+        self.last_keyframe = Some(now);
+    }
+
+    pub fn on_audio(&mut self, now: Instant) {
+        if self.last_media.is_some() {
+            self.last_media = Some(now);
+        }
+    }
+
+    pub fn on_video(&mut self, data: &BytesMut, now: Instant) {
+        if self.last_media.is_some() {
+            self.last_media = Some(now);
+            /*the high bit is the ex header flag of the enhanced RTMP*/
+            if data
+                .first()
+                .is_some_and(|flags| (flags >> 4) & 0x07 == frame_type::KEY_FRAME)
+            {
+                self.last_keyframe = Some(now);
+            }
+        }
+    }
+
+    pub fn check(&self, now: Instant) -> Option<SessionTimeoutReason> {
+        if now.duration_since(self.last_received) >= self.timeouts.idle {
+            return Some(SessionTimeoutReason::Idle);
+        }
+        if Self::expired(self.last_media, self.timeouts.no_media, now) {
+            return Some(SessionTimeoutReason::NoMedia);
+        }
+        if Self::expired(self.last_keyframe, self.timeouts.no_keyframe, now) {
+            return Some(SessionTimeoutReason::NoKeyframe);
+        }
+        None
+    }
+
+    fn expired(last: Option<Instant>, timeout: Option<Duration>, now: Instant) -> bool {
+        match (last, timeout) {
+            (Some(last), Some(timeout)) => now.duration_since(last) >= timeout,
+            _ => false,
+        }
+    }
+
+    /*a ping request is due once per interval while the client sends nothing*/
+    pub fn ping_due(&mut self, now: Instant) -> bool {
+        if let Some(interval) = self.timeouts.ping_interval {
+            let last = self.last_received.max(self.last_ping);
+            if now.duration_since(last) >= interval {
+                self.last_ping = now;
+                return true;
+            }
+        }
+        false
+    }
+
+    /*how long to wait for the data before the timeouts are checked again*/
+    pub fn next_check(&self, now: Instant) -> Duration {
+        let mut deadline = self.last_received + self.timeouts.idle;
+        if let Some(interval) = self.timeouts.ping_interval {
+            deadline = deadline.min(self.last_received.max(self.last_ping) + interval);
+        }
+        if let (Some(last), Some(timeout)) = (self.last_media, self.timeouts.no_media) {
+            deadline = deadline.min(last + timeout);
+        }
+        if let (Some(last), Some(timeout)) = (self.last_keyframe, self.timeouts.no_keyframe) {
+            deadline = deadline.min(last + timeout);
+        }
+        deadline.saturating_duration_since(now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{SessionTimeouts, SessionWatchdog},
+        bytes::BytesMut,
+        std::time::Duration,
+        streamhub::define::SessionTimeoutReason,
+        tokio::time::Instant,
+    };
+
+    fn timeouts() -> SessionTimeouts {
+        SessionTimeouts {
+            idle: Duration::from_secs(10),
+            ping_interval: Some(Duration::from_secs(3)),
+            no_media: Some(Duration::from_secs(5)),
+            no_keyframe: Some(Duration::from_secs(8)),
+        }
+    }
+
+    #[test]
+    fn test_idle_and_ping() {
+        let start = Instant::now();
+        let mut watchdog = SessionWatchdog::new(timeouts(), start);
+
+        assert!(!watchdog.ping_due(start + Duration::from_secs(2)));
+        assert!(watchdog.ping_due(start + Duration::from_secs(3)));
+        /*only one ping per interval*/
+        assert!(!watchdog.ping_due(start + Duration::from_secs(4)));
+        assert_eq!(
+            watchdog.next_check(start + Duration::from_secs(4)),
+            Duration::from_secs(2)
+        );
+
+        /*the pings do not keep a silent client alive*/
+        assert_eq!(watchdog.check(start + Duration::from_secs(9)), None);
+        assert_eq!(
+            watchdog.check(start + Duration::from_secs(10)),
+            Some(SessionTimeoutReason::Idle)
+        );
+
+        watchdog.on_received(start + Duration::from_secs(9));
+        assert_eq!(watchdog.check(start + Duration::from_secs(10)), None);
+    }
+
+    #[test]
+    fn test_media_timeouts() {
+        let start = Instant::now();
+        let mut watchdog = SessionWatchdog::new(timeouts(), start);
+        let inter_frame = BytesMut::from(&[0x27, 0x01][..]);
+        let key_frame = BytesMut::from(&[0x17, 0x01][..]);
+
+        /*the media timeouts start with the publishing*/
+        watchdog.on_video(&inter_frame, start + Duration::from_secs(1));
+        watchdog.on_received(start + Duration::from_secs(6));
+        assert_eq!(watchdog.check(start + Duration::from_secs(6)), None);
+
+        watchdog.on_publish(start + Duration::from_secs(6));
+        watchdog.on_received(start + Duration::from_secs(11));
+        assert_eq!(
+            watchdog.check(start + Duration::from_secs(11)),
+            Some(SessionTimeoutReason::NoMedia)
+        );
+
+        watchdog.on_video(&inter_frame, start + Duration::from_secs(13));
+        watchdog.on_received(start + Duration::from_secs(14));
+        assert_eq!(
+            watchdog.check(start + Duration::from_secs(14)),
+            Some(SessionTimeoutReason::NoKeyframe)
+        );
+
+        watchdog.on_video(&key_frame, start + Duration::from_secs(14));
+        assert_eq!(watchdog.check(start + Duration::from_secs(14)), None);
+    }
+}
@@ -31,6 +31,14 @@ impl EventMessagesReader {
                 self.read_stream_is_recorded()
             }
 
+            define::RTMP_EVENT_PING => {
+                self.read_ping_request()
+            }
+
+            define::RTMP_EVENT_PONG => {
+                self.read_ping_response()
+            }
+
             _ => {
                 Err(errors::EventMessagesError {
                     value: errors::EventMessagesErrorValue::UnknowEventMessageType,
@@ -69,4 +77,47 @@ impl EventMessagesReader {
             stream_id,
         })
     }
+
+    pub fn read_ping_request(
+        &mut self,
+    ) -> Result<message_define::RtmpMessageData, errors::EventMessagesError> {
+        let timestamp = self.reader.read_u32::<BigEndian>()?;
+
+        Ok(message_define::RtmpMessageData::PingRequest { timestamp })
+    }
+
+    pub fn read_ping_response(
+        &mut self,
+    ) -> Result<message_define::RtmpMessageData, errors::EventMessagesError> {
+        let timestamp = self.reader.read_u32::<BigEndian>()?;
+
+        Ok(message_define::RtmpMessageData::PingResponse { timestamp })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::EventMessagesReader,
+        crate::messages::define::RtmpMessageData,
+        bytes::BytesMut,
+        bytesio::bytes_reader::BytesReader,
+    };
+
+    #[test]
+    fn test_parse_ping() {
+        let data: [u8; 6] = [0, 6, 0, 0, 0x03, 0xe8];
+        let mut reader = EventMessagesReader::new(BytesReader::new(BytesMut::from(&data[..])));
+        assert!(matches!(
+            reader.parse_event(),
+            Ok(RtmpMessageData::PingRequest { timestamp: 1000 })
+        ));
+
+        let data: [u8; 6] = [0, 7, 0, 0, 0x03, 0xe8];
+        let mut reader = EventMessagesReader::new(BytesReader::new(BytesMut::from(&data[..])));
+        assert!(matches!(
+            reader.parse_event(),
+            Ok(RtmpMessageData::PingResponse { timestamp: 1000 })
+        ));
+    }
 }
@@ -30,6 +30,7 @@ impl RtmpUrlParser {
 
     /*
      example;rtmp://domain.name.cn:1935/app_name/stream_name?auth_key=test_Key
+     (or rtmps://)
      host_with_port: domain.name.cn:1935
      host: domain.name.cn
      port: 1935
@@ -39,8 +40,11 @@ impl RtmpUrlParser {
      query: auth_key=test_Key
     */
     pub fn parse_url(&mut self) -> Result<(), RtmpUrlParseError> {
-        if let Some(idx) = self.url.find("rtmp://") {
-            let remove_header_left = &self.url[idx + 7..];
+        let header = ["rtmp://", "rtmps://"]
+            .iter()
+            .find_map(|scheme| Some((self.url.find(scheme)?, scheme.len())));
+        if let Some((idx, scheme_len)) = header {
+            let remove_header_left = &self.url[idx + scheme_len..];
             let url_parts: Vec<&str> = remove_header_left.split('/').collect();
             if url_parts.len() != 3 {
                 return Err(RtmpUrlParseError {
@@ -95,6 +99,16 @@ impl RtmpUrlParser {
         (stream_name, query)
     }
 
+    /*the value of a key in the query, e.g. backup of role in role=backup&token=abc*/
+    pub fn get_query_value(query: &str, key: &str) -> Option<String> {
+        query
+            .split('&')
+            .find_map(|pair| match pair.split_once('=') {
+                Some((k, v)) if k == key => Some(v.to_string()),
+                _ => None,
+            })
+    }
+
     pub fn append_port(&mut self, port: String) {
         if !self.host_with_port.contains(':') {
             self.host_with_port = format!("{}:{}", self.host_with_port, port);
@@ -146,4 +160,33 @@ mod tests {
             println!(" query: {}", parser.query.unwrap());
         }
     }
+
+    #[test]
+    fn test_rtmps_url_parser() {
+        let mut parser = RtmpUrlParser::new(String::from(
+            "rtmps://domain.name.cn:443/app_name/stream_name?auth_key=test_Key",
+        ));
+
+        parser.parse_url().unwrap();
+
+        assert_eq!(parser.host, "domain.name.cn");
+        assert_eq!(parser.port, Some(String::from("443")));
+        assert_eq!(parser.app_name, "app_name");
+        assert_eq!(parser.stream_name, "stream_name");
+        assert_eq!(parser.query, Some(String::from("auth_key=test_Key")));
+    }
+
+    #[test]
+    fn test_get_query_value() {
+        let query = "token=abc&role=backup";
+        assert_eq!(
+            RtmpUrlParser::get_query_value(query, "role"),
+            Some(String::from("backup"))
+        );
+        assert_eq!(
+            RtmpUrlParser::get_query_value(query, "token"),
+            Some(String::from("abc"))
+        );
+        assert_eq!(RtmpUrlParser::get_query_value(query, "auth"), None);
+    }
 }
@@ -5,10 +5,13 @@ module streamer::streamer {
     use std::string::{Self, String};
     use sui::clock::Clock;
     use sui::event;
+    use sui::dynamic_field as df;
 
     const ENoSuchLiveStream: u64 = 1;
     const ELiveStreamUrlAlreadyExists: u64 = 2;
     const ENoSuchVodStream: u64 = 1;
+    const ENoVodStreamToConcat: u64 = 3;
+    const EVodStreamDeleted: u64 = 4;
 
     public struct LiveStreamInfo has copy, store, drop {
         name: String,
@@ -25,6 +28,22 @@ module streamer::streamer {
         m3u8_content: String,
     }
 
+    // The fields of the published structs can not be changed by a package upgrade,
+    // so the edit state of a VOD stream is kept in a dynamic field of the streams
+    public struct VodStreamKey has copy, store, drop {
+        index: u64,
+    }
+
+    public struct VodStreamState has copy, store, drop {
+        // the VOD streams it is edited from, empty for a recorded one.
+        // an edit is added as a new VOD stream, the sources are kept as they are
+        sources: vector<u64>,
+        // an unlisted one is kept but not shown in the listed streams
+        listed: bool,
+        // the content of a deleted one is cleared, its index is kept for the provenance
+        deleted: bool,
+    }
+
     public struct AdminCap has key {
         id: UID,
     }
@@ -44,6 +63,20 @@ module streamer::streamer {
         data: AllStreamsInfo, 
     }
 
+    public struct ListedVodStream has copy, drop {
+        index: u64,
+        info: VodStreamInfo,
+    }
+
+    public struct ListedStreamsInfo has copy, drop {
+        live_streams: VecMap<String, LiveStreamInfo>,
+        vod_streams: vector<ListedVodStream>,
+    }
+
+    public struct ListedStreamsEvent has copy, drop {
+        data: ListedStreamsInfo,
+    }
+
     public struct SingleLiveStreamsEvent has copy, drop {
         data: LiveStreamInfo, 
     }
@@ -52,6 +85,21 @@ module streamer::streamer {
         data: VodStreamInfo, 
     }
 
+    public struct VodStreamStateEvent has copy, drop {
+        index: u64,
+        data: VodStreamState,
+    }
+
+    public struct VodStreamEditedEvent has copy, drop {
+        index: u64,
+        sources: vector<u64>,
+    }
+
+    public struct VodStreamDeletedEvent has copy, drop {
+        index: u64,
+        m3u8_content: String,
+    }
+
     public struct STREAMER has drop {}
 
     fun init(otw: STREAMER, ctx: &mut TxContext) {
@@ -111,6 +159,48 @@ module streamer::streamer {
         streams.vod_streams.push_back(stream);
     } 
 
+    fun add_edited_vod_stream(streams: &mut Streams, clock: &Clock, name: String, desc: String,
+                            m3u8_content: String, sources: vector<u64>) {
+        let stream = VodStreamInfo {
+            name,
+            desc,
+            upload_at: clock.timestamp_ms(),
+            m3u8_content,
+        };
+        streams.vod_streams.push_back(stream);
+
+        let index = streams.vod_streams.length() - 1;
+        df::add(&mut streams.id, VodStreamKey {index}, VodStreamState {sources, listed: true, deleted: false});
+        event::emit(VodStreamEditedEvent {index, sources});
+    }
+
+    // the trimmed playlist is made of a segment range of the source
+    public fun trim_vod_stream(_: &AdminCap, streams: &mut Streams, clock: &Clock, 
+                            index: u64, m3u8_content: String, _ctx: &mut TxContext) {
+        assert_vod_stream_exists(streams, index);
+
+        let s = streams.vod_streams.borrow(index);
+        let (name, desc) = (s.name, s.desc);
+        add_edited_vod_stream(streams, clock, name, desc, m3u8_content, vector[index]);
+    }
+
+    // the playlist joins the sources in order with discontinuities
+    public fun concat_vod_streams(_: &AdminCap, streams: &mut Streams, clock: &Clock, 
+                            indexes: vector<u64>, name: String, desc: String, m3u8_content: String, _ctx: &mut TxContext) {
+        assert!(!indexes.is_empty(), ENoVodStreamToConcat);
+        indexes.do_ref!(|index| assert_vod_stream_exists(streams, *index));
+
+        add_edited_vod_stream(streams, clock, name, desc, m3u8_content, indexes);
+    }
+
+    public fun update_vod_stream_info(_: &AdminCap, streams: &mut Streams, clock: &Clock, 
+                            index: u64, name: String, desc: String, _ctx: &mut TxContext) {
+        assert_vod_stream_exists(streams, index);
+
+        let m3u8_content = streams.vod_streams.borrow(index).m3u8_content;
+        add_edited_vod_stream(streams, clock, name, desc, m3u8_content, vector[index]);
+    }
+
     // move to VOD stream when live stream ends
     public fun move_live_stream_to_vod_stream(admin: &AdminCap, streams: &mut Streams, clock: &Clock, 
                             url: String, full_m3u8_content: String, _ctx: &mut TxContext) {        
@@ -119,6 +209,59 @@ module streamer::streamer {
         add_vod_stream(admin, streams, clock, v.name, v.desc, full_m3u8_content, _ctx);
     } 
 
+    public fun set_vod_stream_listed(_: &AdminCap, streams: &mut Streams, index: u64, listed: bool, _ctx: &mut TxContext) {
+        assert_vod_stream_exists(streams, index);
+
+        vod_stream_state_mut(streams, index).listed = listed;
+    }
+
+    // only the playlist is removed, the recorded blobs are permanent on walrus
+    public fun delete_vod_stream(_: &AdminCap, streams: &mut Streams, index: u64, _ctx: &mut TxContext) {
+        assert_vod_stream_exists(streams, index);
+
+        let s = streams.vod_streams.borrow_mut(index);
+        let m3u8_content = s.m3u8_content;
+        s.m3u8_content = string::utf8(b"");
+
+        let state = vod_stream_state_mut(streams, index);
+        state.listed = false;
+        state.deleted = true;
+
+        event::emit(VodStreamDeletedEvent {index, m3u8_content});
+    }
+
+    fun assert_vod_stream_exists(streams: &Streams, index: u64) {
+        assert!(index < streams.vod_streams.length(), ENoSuchVodStream);
+        assert!(!vod_stream_state(streams, index).deleted, EVodStreamDeleted);
+    }
+
+    // the VOD streams without the state are the listed recordings
+    fun vod_stream_state(streams: &Streams, index: u64): VodStreamState {
+        let key = VodStreamKey {index};
+        if (df::exists_(&streams.id, key)) {
+            *df::borrow(&streams.id, key)
+        } else {
+            VodStreamState {sources: vector::empty(), listed: true, deleted: false}
+        }
+    }
+
+    fun vod_stream_state_mut(streams: &mut Streams, index: u64): &mut VodStreamState {
+        let key = VodStreamKey {index};
+        if (!df::exists_(&streams.id, key)) {
+            let state = vod_stream_state(streams, index);
+            df::add(&mut streams.id, key, state);
+        };
+        df::borrow_mut(&mut streams.id, key)
+    }
+
+    public fun get_vod_stream_state(streams: &mut Streams, index: u64, _ctx: &mut TxContext): VodStreamState {
+        assert_vod_stream_exists(streams, index);
+
+        let info = vod_stream_state(streams, index);
+        event::emit(VodStreamStateEvent {index, data: info});
+        info
+    }
+
     public fun get_all_streams(streams: &mut Streams, _ctx: &mut TxContext): AllStreamsInfo {
         let info = AllStreamsInfo {
             live_streams: streams.live_streams,
@@ -129,6 +272,27 @@ module streamer::streamer {
         info
     }
 
+    // the VOD streams which are neither unlisted nor deleted, with their indexes
+    public fun get_listed_streams(streams: &mut Streams, _ctx: &mut TxContext): ListedStreamsInfo {
+        let mut vod_streams = vector::empty();
+        let mut index = 0;
+        while (index < streams.vod_streams.length()) {
+            if (vod_stream_state(streams, index).listed) {
+                let info = *streams.vod_streams.borrow(index);
+                vod_streams.push_back(ListedVodStream {index, info});
+            };
+            index = index + 1;
+        };
+
+        let info = ListedStreamsInfo {
+            live_streams: streams.live_streams,
+            vod_streams,
+        };
+
+        event::emit(ListedStreamsEvent {data: info});
+        info
+    }
+
     public fun get_live_stream(streams: &mut Streams, url: String, _ctx: &mut TxContext): LiveStreamInfo  {
         let s = streams.live_streams.try_get(&url);
         assert!(s.is_some(), ENoSuchLiveStream);
@@ -146,7 +310,7 @@ module streamer::streamer {
     } 
 
     public fun get_vod_stream(streams: &mut Streams, index: u64, _ctx: &mut TxContext): VodStreamInfo  {
-        assert!(index < streams.vod_streams.length(), ENoSuchVodStream);
+        assert_vod_stream_exists(streams, index);
         
         let s = streams.vod_streams.borrow(index);
 
@@ -14,7 +14,8 @@ import toast, { Toaster } from 'react-hot-toast';
 
 import './index.css'
 
-const GET_ALL_STREAMS_TARGET = '0x631274a289104633260905535e8a26903fd44026fe313ea1c96e55ff83cef5fc::streamer::get_all_streams';
+// the unlisted and the deleted VOD streams are left out
+const GET_LISTED_STREAMS_TARGET = '0x631274a289104633260905535e8a26903fd44026fe313ea1c96e55ff83cef5fc::streamer::get_listed_streams';
 const StreamerObjectId = '0xfac88744d3c6b359d21fad3aa20f0aa81cca9fdaee25b10d2ffac62a989f8785';
 const BackendPrefix = 'http://127.0.0.1:8000';
 
@@ -54,7 +55,7 @@ function App() {
 
     try {        
       txb.moveCall({
-        target: GET_ALL_STREAMS_TARGET,
+        target: GET_LISTED_STREAMS_TARGET,
         arguments: [
           txb.object(StreamerObjectId),
         ],
@@ -90,9 +91,10 @@ function App() {
 
             len = result.vod_streams?.length;
             for (let i = 0; i < len; ++i) {
-              let vs = result.vod_streams[i];
+              let vs = result.vod_streams[i]['info'];
               let s: Stream = {
                 url: "",
+                index: String(result.vod_streams[i]['index']),
                 name: vs['name'],
 
                 description: vs['desc'],
@@ -243,12 +245,6 @@ function App() {
 
   const sortedLiveStreams = liveStreams.sort((s1 , s2) => { return Number(s2.startAt) - Number(s1.startAt); });
 
-  let idx = 0;
-  pastStreams.forEach((item) => {
-    item.index = idx.toString();
-    ++idx;
-  });
-
   const sortedPastStreams = pastStreams.reverse();
 
   return (